@@ -0,0 +1,173 @@
+//! Lock round-trip costs: the uncontended paths within one process, and a
+//! ping-pong where the parent and the 32-bit child take turns incrementing
+//! `number`.
+//!
+//! Run with `cargo bench --bench lock`. The ping-pong reports time per
+//! handoff, i.e. per increment, each of which passes the lock to the other
+//! process.
+
+use criterion::{Criterion, criterion_group, criterion_main};
+use sharedmem_multiarch::shared::DEFAULT_SPIN_ROUNDS;
+use sharedmem_multiarch::{ChildExecutable, OwnedSharedData, SharedData};
+use std::hint::black_box;
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn uncontended(c: &mut Criterion) {
+    let shared_data = OwnedSharedData::create().unwrap();
+
+    c.bench_function("lock_unlock_uncontended", |b| {
+        b.iter(|| {
+            shared_data.lock().unwrap();
+            shared_data.unlock();
+        })
+    });
+
+    c.bench_function("try_lock_success", |b| {
+        b.iter(|| {
+            assert!(black_box(shared_data.try_lock()));
+            shared_data.unlock();
+        })
+    });
+
+    shared_data.lock().unwrap();
+    c.bench_function("try_lock_failure", |b| {
+        b.iter(|| assert!(!black_box(shared_data.try_lock())))
+    });
+    shared_data.unlock();
+}
+
+/// Lock attempts while another thread keeps storing to `number`, which only
+/// stay cheap if `number` and the lock word are on separate cache lines.
+fn number_hammered(c: &mut Criterion) {
+    let owned = OwnedSharedData::create().unwrap();
+    // The mapping itself cannot cross threads, but the data in it can
+    let shared_data: &SharedData = &owned;
+    let stop = AtomicBool::new(false);
+
+    std::thread::scope(|scope| {
+        scope.spawn(|| {
+            let mut n = 0;
+            while !stop.load(Ordering::Relaxed) {
+                shared_data.set_number(n);
+                n += 1;
+            }
+        });
+
+        // Failing keeps unlock's wake syscall from drowning out the cache
+        // traffic being measured.
+        shared_data.lock().unwrap();
+        c.bench_function("try_lock_failure_while_number_hammered", |b| {
+            b.iter(|| assert!(!black_box(shared_data.try_lock())))
+        });
+        shared_data.unlock();
+        stop.store(true, Ordering::Relaxed);
+    });
+}
+
+/// Lock round-trips against a thread that keeps taking the lock itself,
+/// holding it either for a few stores or for 50µs, with and without
+/// spinning before the futex wait. Spinning can only pay off with a core
+/// per thread; on one, the holder is not running while the waiter spins.
+fn spin_vs_futex(c: &mut Criterion) {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data: &SharedData = &owned;
+    let mut group = c.benchmark_group("spin_vs_futex");
+
+    for (hold_name, hold) in [
+        ("short_hold", None),
+        ("long_hold", Some(Duration::from_micros(50))),
+    ] {
+        for (spin_name, rounds) in [("futex_only", 0), ("spin_then_futex", DEFAULT_SPIN_ROUNDS)] {
+            SharedData::set_spin_rounds(rounds);
+            let stop = AtomicBool::new(false);
+            std::thread::scope(|scope| {
+                scope.spawn(|| {
+                    while !stop.load(Ordering::Relaxed) {
+                        let mut guard = shared_data.lock_guard().unwrap();
+                        guard.update(|n| n + 1);
+                        if let Some(hold) = hold {
+                            let until = Instant::now() + hold;
+                            while Instant::now() < until {
+                                std::hint::spin_loop();
+                            }
+                        }
+                    }
+                });
+
+                group.bench_function(format!("{}/{}", hold_name, spin_name), |b| {
+                    b.iter(|| {
+                        shared_data
+                            .lock_timeout_guard(TIMEOUT)
+                            .unwrap()
+                            .update(|n| n + 1);
+                    })
+                });
+                stop.store(true, Ordering::Relaxed);
+            });
+        }
+    }
+    SharedData::set_spin_rounds(DEFAULT_SPIN_ROUNDS);
+    group.finish();
+}
+
+fn ping_pong(c: &mut Criterion) {
+    let child_binary = include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded"));
+    let child_exe = ChildExecutable::extract(child_binary).unwrap();
+
+    c.bench_function("ping_pong_handoff", |b| {
+        b.iter_custom(|handoffs| {
+            let shared_data = OwnedSharedData::create().unwrap();
+            // Odd numbers are the child's turn, so -1 keeps it waiting
+            shared_data.set_number(-1);
+            let mut child = Command::new(&child_exe)
+                .arg("--ping-pong")
+                .arg(shared_data.os_id())
+                .arg(handoffs.to_string())
+                .stdout(Stdio::null())
+                .spawn()
+                .unwrap();
+
+            // Leave the child's startup out of the measurement
+            let ready_by = Instant::now() + TIMEOUT;
+            while shared_data.read_status().0 != 1 {
+                assert!(Instant::now() < ready_by, "child never became ready");
+                std::thread::sleep(Duration::from_millis(1));
+            }
+
+            let handoffs = handoffs as i64;
+            let start = Instant::now();
+            shared_data.set_number(0);
+            shared_data.notify_change();
+            loop {
+                let number = shared_data
+                    .wait_until(|n| n % 2 == 0 || n >= handoffs, TIMEOUT)
+                    .unwrap();
+                if number >= handoffs {
+                    break;
+                }
+                shared_data
+                    .lock_timeout_guard(TIMEOUT)
+                    .unwrap()
+                    .update(|n| n + 1);
+                shared_data.notify_change();
+            }
+            let elapsed = start.elapsed();
+
+            assert!(child.wait().unwrap().success());
+            elapsed
+        })
+    });
+}
+
+criterion_group!(
+    benches,
+    uncontended,
+    number_hammered,
+    spin_vs_futex,
+    ping_pong
+);
+criterion_main!(benches);
@@ -1,42 +1,292 @@
+use std::collections::hash_map::DefaultHasher;
 use std::env;
-use std::path::Path;
+use std::hash::{Hash, Hasher};
+#[cfg(unix)]
+use std::os::unix::fs::PermissionsExt;
+use std::path::{Path, PathBuf};
 use std::process::Command;
 
+#[path = "build_support/child_build_error.rs"]
+mod child_build_error;
+
+const DEFAULT_CHILD_TARGET: &str = "i686-unknown-linux-gnu";
+
+/// Everything the child binary is built from. The shared modules are pulled
+/// in with `#[path]`, so they count as child sources too.
+const CHILD_INPUTS: &[&str] = &[
+    "child_process/src",
+    "child_process/Cargo.toml",
+    "child_process/Cargo.lock",
+    "src/eventlog.rs",
+    "src/expr.rs",
+    "src/layout.rs",
+    "src/memfd.rs",
+    "src/mpmc.rs",
+    "src/raw_sync.rs",
+    "src/record.rs",
+    "src/ring.rs",
+    "src/sharedvec.rs",
+    "src/stack.rs",
+    "src/sysv.rs",
+    "src/timeouts.rs",
+];
+
 fn main() {
-    println!("cargo:rerun-if-changed=child_process");
+    for input in CHILD_INPUTS {
+        // A missing path would make cargo rerun us on every build
+        if Path::new(input).exists() {
+            println!("cargo:rerun-if-changed={}", input);
+        }
+    }
+    println!("cargo:rerun-if-env-changed=SHAREDMEM_CHILD_TARGET");
+    println!("cargo:rerun-if-env-changed=SHAREDMEM_CHILD_BIN");
+    println!("cargo:rerun-if-env-changed=SHAREDMEM_CHILD_OUT");
 
     let out_dir = env::var("OUT_DIR").unwrap();
-    let target_dir = Path::new(&out_dir).join("child_build");
+    let dest = Path::new(&out_dir).join("child_process_embedded");
+
+    embed_child(&out_dir, &dest);
+    copy_for_inspection(&out_dir, &dest);
+    #[cfg(feature = "compress-child")]
+    compress_child(&dest);
+}
+
+/// Writes `child_process_embedded.zst` next to the child for the parent to
+/// embed instead, and passes the child's length and checksum on so the
+/// parent can tell it decompressed exactly what was built.
+#[cfg(feature = "compress-child")]
+fn compress_child(embedded: &Path) {
+    let binary = std::fs::read(embedded).unwrap();
+    let compressed = zstd::encode_all(binary.as_slice(), 19).unwrap();
+    std::fs::write(embedded.with_extension("zst"), &compressed).unwrap();
+
+    println!("cargo:rustc-env=SHAREDMEM_CHILD_LEN={}", binary.len());
+    println!(
+        "cargo:rustc-env=SHAREDMEM_CHILD_CHECKSUM={}",
+        fnv1a(&binary)
+    );
+    println!(
+        "Child process compressed from {} to {} bytes",
+        binary.len(),
+        compressed.len()
+    );
+}
+
+/// 64-bit FNV-1a; must match `extract::checksum`.
+#[cfg(feature = "compress-child")]
+fn fnv1a(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Puts the child executable at `dest`, building it unless a prebuilt one
+/// was given or the cached build is still current.
+fn embed_child(out_dir: &str, dest: &Path) {
+    // A prebuilt child skips the cross build entirely, for machines without
+    // the 32-bit toolchain.
+    if let Ok(prebuilt) = env::var("SHAREDMEM_CHILD_BIN") {
+        embed_prebuilt(Path::new(&prebuilt), dest);
+        return;
+    }
+
+    // SHAREDMEM_CHILD_TARGET picks the child's target triple; "none" means
+    // there is nothing to build, so a prebuilt child is required. macOS has
+    // no 32-bit target any more, so there the child matches the parent;
+    // Windows still has one, built with the same toolchain flavour.
+    let target_os = env::var("CARGO_CFG_TARGET_OS").unwrap_or_default();
+    let target_env = env::var("CARGO_CFG_TARGET_ENV").unwrap_or_default();
+    let default_target = match (target_os.as_str(), target_env.as_str()) {
+        ("macos", _) => env::var("TARGET").unwrap(),
+        ("windows", "msvc") => "i686-pc-windows-msvc".to_string(),
+        ("windows", _) => "i686-pc-windows-gnu".to_string(),
+        _ => DEFAULT_CHILD_TARGET.to_string(),
+    };
+    let child_target = env::var("SHAREDMEM_CHILD_TARGET").unwrap_or(default_target);
+
+    if child_target == "none" {
+        panic!(
+            "SHAREDMEM_CHILD_TARGET=none requires SHAREDMEM_CHILD_BIN to point at a prebuilt child"
+        );
+    }
+
+    // So `--check` can tell whether the child is what this build was for
+    println!("cargo:rustc-env=SHAREDMEM_CHILD_BUILT_FOR={}", child_target);
+
+    if !target_installed(&child_target) {
+        println!(
+            "cargo:warning=Rust target {} is not installed; run: rustup target add {}",
+            child_target, child_target
+        );
+    }
+
+    // The build script also reruns for reasons that have nothing to do with
+    // the child (e.g. a profile change), so only rebuild when its inputs did.
+    // To check by hand, run `touch child_process/src/main.rs && cargo build -vv`
+    // and look for "reusing cached build"; after editing that file the same
+    // command prints "Child process built successfully" instead.
+    let hash_file = Path::new(out_dir).join("child_process_embedded.hash");
+    // The child is instrumented whenever the parent is.
+    let tracing = env::var_os("CARGO_FEATURE_TRACING").is_some();
+    let input_hash = format!("{:016x}", hash_inputs(&child_target, tracing));
+    if dest.exists() && std::fs::read_to_string(&hash_file).ok().as_deref() == Some(&input_hash) {
+        println!("Child process unchanged, reusing cached build");
+        return;
+    }
+
+    let target_dir = Path::new(out_dir).join("child_build");
 
     std::fs::create_dir_all(&target_dir).unwrap();
 
     // Build the 32-bit child process
-    let output = Command::new("cargo")
-        .args(&[
-            "build",
-            "--release",
-            "--target", "i686-unknown-linux-gnu",
-            "--manifest-path", "child_process/Cargo.toml",
-            "--target-dir", target_dir.to_str().unwrap(),
-        ])
-        .output()
-        .expect("Failed to build child process. Make sure you have: rustup target add i686-unknown-linux-gnu");
+    let mut build = Command::new("cargo");
+    build.args([
+        "build",
+        "--release",
+        "--target",
+        &child_target,
+        "--manifest-path",
+        "child_process/Cargo.toml",
+        "--target-dir",
+        target_dir.to_str().unwrap(),
+    ]);
+    if tracing {
+        build.args(["--features", "tracing"]);
+    }
+    let output = build.output().unwrap_or_else(|_| {
+        panic!(
+            "Failed to build child process. Make sure you have: rustup target add {}",
+            child_target
+        )
+    });
 
     if !output.status.success() {
-        panic!(
-            "Failed to build child process: {}",
-            String::from_utf8_lossy(&output.stderr)
-        );
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        // A known cause gets a short panic with the fix; the full output
+        // only stays useful for the rest
+        if let Some(diagnosis) = child_build_error::diagnose(&stderr) {
+            for warning in diagnosis.warnings(&child_target) {
+                println!("{}", warning);
+            }
+            panic!("{}", diagnosis.message(&child_target));
+        }
+        panic!("Failed to build child process: {}", stderr);
     }
 
     // Copy the built executable
+    let exe_name = if child_target.contains("windows") {
+        "child_process.exe"
+    } else {
+        "child_process"
+    };
     let source = target_dir
-        .join("i686-unknown-linux-gnu")
+        .join(&child_target)
         .join("release")
-        .join("child_process");
+        .join(exe_name);
 
-    let dest = Path::new(&out_dir).join("child_process_embedded");
-    std::fs::copy(&source, &dest).unwrap();
+    std::fs::copy(&source, dest).unwrap();
+    std::fs::write(&hash_file, input_hash).unwrap();
 
     println!("Child process built successfully");
 }
+
+/// Copies the embedded child to `SHAREDMEM_CHILD_OUT`, or next to the
+/// profile directories as `target/child_process_release`, so it can be run
+/// by hand against a segment created some other way. Failing to do so only
+/// warns: the build itself does not need the copy.
+fn copy_for_inspection(out_dir: &str, embedded: &Path) {
+    let copy = match env::var_os("SHAREDMEM_CHILD_OUT") {
+        Some(path) => PathBuf::from(path),
+        // OUT_DIR is <target dir>/<profile>/build/<package>-<hash>/out
+        None => match Path::new(out_dir).ancestors().nth(4) {
+            Some(target_dir) => {
+                let mut name = String::from("child_process_release");
+                if env::var("CARGO_CFG_TARGET_OS").as_deref() == Ok("windows") {
+                    name.push_str(".exe");
+                }
+                target_dir.join(name)
+            }
+            None => return,
+        },
+    };
+
+    if let Err(e) = std::fs::copy(embedded, &copy) {
+        println!(
+            "cargo:warning=Could not copy the child process to {}: {}",
+            copy.display(),
+            e
+        );
+    }
+}
+
+/// Hashes the target triple, the feature set, plus the path and contents of every child
+/// input, walking directories in sorted order so the result is stable.
+fn hash_inputs(target: &str, tracing: bool) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    target.hash(&mut hasher);
+    tracing.hash(&mut hasher);
+
+    let mut pending: Vec<PathBuf> = CHILD_INPUTS.iter().map(PathBuf::from).collect();
+    let mut files = Vec::new();
+    while let Some(path) = pending.pop() {
+        if path.is_dir() {
+            pending.extend(
+                std::fs::read_dir(&path)
+                    .unwrap()
+                    .map(|entry| entry.unwrap().path()),
+            );
+        } else if path.is_file() {
+            files.push(path);
+        }
+    }
+    files.sort();
+
+    for file in files {
+        file.hash(&mut hasher);
+        std::fs::read(&file).unwrap().hash(&mut hasher);
+    }
+    hasher.finish()
+}
+
+/// Copies a user-supplied child executable into place, refusing anything
+/// that could not be exec'd at runtime.
+fn embed_prebuilt(prebuilt: &Path, dest: &Path) {
+    println!("cargo:rerun-if-changed={}", prebuilt.display());
+
+    let metadata = std::fs::metadata(prebuilt).unwrap_or_else(|e| {
+        panic!(
+            "SHAREDMEM_CHILD_BIN={} cannot be read ({}); point it at a built child_process executable",
+            prebuilt.display(),
+            e
+        )
+    });
+    #[cfg(unix)]
+    let executable = metadata.permissions().mode() & 0o111 != 0;
+    // Windows decides by extension, which the parent adds when extracting
+    #[cfg(not(unix))]
+    let executable = true;
+    if !metadata.is_file() || !executable {
+        panic!(
+            "SHAREDMEM_CHILD_BIN={} is not an executable file; try: chmod +x {}",
+            prebuilt.display(),
+            prebuilt.display()
+        );
+    }
+
+    std::fs::copy(prebuilt, dest).unwrap();
+    println!("Using prebuilt child process from {}", prebuilt.display());
+}
+
+/// Asks rustup whether `target` is installed. Toolchains not managed by
+/// rustup are assumed to know what they are doing.
+fn target_installed(target: &str) -> bool {
+    match Command::new("rustup")
+        .args(["target", "list", "--installed"])
+        .output()
+    {
+        Ok(output) if output.status.success() => String::from_utf8_lossy(&output.stdout)
+            .lines()
+            .any(|line| line.trim() == target),
+        _ => true,
+    }
+}
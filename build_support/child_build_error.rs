@@ -0,0 +1,104 @@
+//! Recognizing why the 32-bit child failed to build, so `build.rs` can say
+//! what to install instead of dumping cargo's stderr.
+//!
+//! Included by `build.rs` and by `tests/build_errors.rs` with `#[path]`,
+//! so it only depends on `std`.
+
+#![allow(dead_code)]
+
+/// A known cause of a failed child build.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Diagnosis {
+    /// The linker found no 32-bit C runtime: crt files, libc or libgcc.
+    MissingLibc,
+    /// The Rust standard library for the child's target is not installed.
+    MissingStd,
+}
+
+/// Linker complaints that mean the 32-bit libc or libgcc is not there,
+/// from GNU ld, gold, lld and mold. Not ld's "skipping incompatible": it
+/// is only a warning, printed on links that go on to find the right
+/// library and fail, if at all, for some other reason.
+const MISSING_LIBC: &[&str] = &[
+    "cannot find crt1.o",
+    "cannot find crti.o",
+    "cannot find crtn.o",
+    "cannot open crt1.o",
+    "cannot open crti.o",
+    "cannot open crtn.o",
+    "cannot find Scrt1.o",
+    "cannot open Scrt1.o",
+    "cannot find -lc",
+    "unable to find library -lc",
+    "cannot find -lgcc",
+    "unable to find library -lgcc",
+    "cannot find -lgcc_s",
+    "unable to find library -lgcc_s",
+    "gnu/stubs-32.h",
+];
+
+const MISSING_STD: &[&str] = &["can't find crate for `std`", "can't find crate for `core`"];
+
+/// What went wrong, judging by the stderr of the child's `cargo build`, or
+/// `None` if it is nothing we know how to help with.
+pub fn diagnose(stderr: &str) -> Option<Diagnosis> {
+    // Checked first: without std nothing gets as far as linking
+    if MISSING_STD.iter().any(|sign| stderr.contains(sign)) {
+        return Some(Diagnosis::MissingStd);
+    }
+    if MISSING_LIBC.iter().any(|sign| stderr.contains(sign)) {
+        return Some(Diagnosis::MissingLibc);
+    }
+    None
+}
+
+impl Diagnosis {
+    /// `cargo:warning` lines explaining the fix, one instruction per line.
+    pub fn warnings(&self, target: &str) -> Vec<String> {
+        let mut lines = vec![self.summary(target)];
+        lines.extend(self.fixes(target));
+        lines.push(
+            "Or skip the cross build: set SHAREDMEM_CHILD_BIN to a prebuilt child".to_string(),
+        );
+        lines
+            .into_iter()
+            .map(|line| format!("cargo:warning={}", line))
+            .collect()
+    }
+
+    /// The message to panic with: the cause and every fix in a few lines.
+    pub fn message(&self, target: &str) -> String {
+        let mut message = format!("Failed to build child process: {}", self.summary(target));
+        for fix in self.fixes(target) {
+            message.push_str("\n  ");
+            message.push_str(&fix);
+        }
+        message.push_str("\n  or set SHAREDMEM_CHILD_BIN to a prebuilt child_process executable");
+        message
+    }
+
+    fn summary(&self, target: &str) -> String {
+        match self {
+            Diagnosis::MissingLibc => format!(
+                "the 32-bit C library needed to link for {} is not installed",
+                target
+            ),
+            Diagnosis::MissingStd => format!("the Rust target {} is not installed", target),
+        }
+    }
+
+    fn fixes(&self, target: &str) -> Vec<String> {
+        match self {
+            Diagnosis::MissingLibc if target.ends_with("-linux-musl") => vec![
+                // musl targets link their own libc, so it is the toolchain
+                "install a C toolchain that can produce 32-bit code, e.g. gcc-multilib".to_string(),
+            ],
+            Diagnosis::MissingLibc => vec![
+                "Debian/Ubuntu: sudo apt install gcc-multilib".to_string(),
+                "Fedora: sudo dnf install glibc-devel.i686 libgcc.i686".to_string(),
+                "Arch: sudo pacman -S lib32-glibc lib32-gcc-libs (from [multilib])".to_string(),
+            ],
+            Diagnosis::MissingStd => vec![format!("run: rustup target add {}", target)],
+        }
+    }
+}
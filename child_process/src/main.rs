@@ -1,36 +1,298 @@
-use linux_futex::{Futex, Shared, TimedWaitError};
-use shared_memory::ShmemConf;
+use raw_sync::{RawSync, TimedWaitError};
+use shared_memory::{Shmem, ShmemConf};
 use std::env;
-use std::sync::atomic::{AtomicI64, Ordering};
-use std::time::Duration;
+use std::error::Error;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::process::ExitCode;
+use std::sync::atomic::{
+    AtomicBool, AtomicI32, AtomicI64, AtomicU8, AtomicU32, AtomicU64, Ordering,
+};
+use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+#[path = "../../src/eventlog.rs"]
+mod eventlog;
+#[path = "../../src/expr.rs"]
+mod expr;
+#[path = "../../src/layout.rs"]
+mod layout;
+#[cfg(target_os = "linux")]
+#[path = "../../src/memfd.rs"]
+mod memfd;
+#[path = "../../src/mpmc.rs"]
+mod mpmc;
+#[path = "../../src/raw_sync.rs"]
+mod raw_sync;
+#[path = "../../src/record.rs"]
+mod record;
+#[path = "../../src/ring.rs"]
+mod ring;
+#[path = "../../src/sharedvec.rs"]
+mod sharedvec;
+#[path = "../../src/stack.rs"]
+mod stack;
+#[cfg(unix)]
+#[path = "../../src/sysv.rs"]
+mod sysv;
+#[path = "../../src/timeouts.rs"]
+mod timeouts;
 
 /// The data structure shared between the parent and child processes
 /// Must match exactly with the parent's SharedData structure
 #[repr(C)]
 struct SharedData {
-    pub futex: Futex<Shared>,
+    pub header: layout::Header, // Magic, layout version and struct size written by the parent
+    pub futex: layout::CacheAligned<RawSync>, // Own cache line, away from number
+    pub owner_pid: AtomicI32,   // PID of the lock holder, 0 when unlocked
+    pub poisoned: AtomicBool,   // Set when a holder panicked with the lock held
+    pub should_stop: AtomicBool, // Set by the parent's request_stop
+    pub owner_tid: AtomicI32,   // Thread holding the lock, if the parent tracks deadlocks
+    pub waiter_tids: [AtomicI32; layout::TRACKED_WAITERS], // Threads sleeping on the lock
+    pub number: layout::CacheAligned<AtomicI64>,
+    pub number_generation: AtomicU64, // Bumped by the parent's set_number
+    pub initial_number: AtomicI64,    // Where the parent's reset puts number back
+    pub words: [AtomicI64; layout::PROTECTED_WORDS], // Also protected by futex
+    pub float_bits: AtomicU64,        // The parent's get_float/set_float, as f64::to_bits
+    pub turn: RawSync,                // Index of the child allowed to work next
+    pub change_seq: RawSync,          // Bumped whenever number changes
+    pub phase: RawSync,               // Start-up handshake, a layout::Phase
+    pub attached_children: RawSync,   // Children reported in during the handshake
+    pub published: RwSharedData,      // Read-mostly copy of the result
+    pub lock_acquisitions: AtomicU64, // Lock statistics shared with the parent
+    pub lock_contended: AtomicU64,
+    pub total_wait_nanos: AtomicU64,
+    pub locked_at_nanos: AtomicU64, // When the holder took futex, on the holder's own clock
+    pub total_hold_nanos: AtomicU64,
+    pub max_hold_nanos: AtomicU64,
+    pub next_ticket: AtomicU32, // Fair ticket lock, unused by the child
+    pub now_serving: RawSync,
+    pub abandoned_tickets: AtomicU32,
+    pub last_heartbeat_nanos: AtomicU64, // Wall-clock nanos of the last beat, see beat()
+    pub child_compute_nanos: AtomicU64,  // Time our update took, see record_compute()
+    pub status: AtomicI32,               // Result code left for the parent, see set_status()
+    pub status_message: [AtomicU8; layout::STATUS_MESSAGE_LEN],
+    pub ready: AtomicU8, // Set to READY_SENTINEL last, once everything else is written
+    #[cfg(sharedmem_layout_drift)]
+    pub drift: [AtomicU64; 8], // Deliberately not in the parent, to prove the size check fires
+}
+
+/// Reader/writer locked number, must match the parent's RwSharedData
+#[repr(C)]
+struct RwSharedData {
+    pub readers: RawSync,  // Number of readers holding the lock
+    pub writer: RawSync,   // 1 while a writer waits for or holds the lock
+    pub upgrader: RawSync, // 1 while an upgradable reader or a writer holds the lock
     pub number: AtomicI64,
 }
 
+/// Everything that can go wrong in the child, mirrored from the parent's SharedMemError
+/// Each variant exits with a distinct code so the parent can tell them apart
+#[derive(Debug)]
+enum SharedMemError {
+    Timeout,
+    Interrupted,
+    /// The holder died with the lock held; it was reset but data may be inconsistent
+    RecoveredFromDeadOwner {
+        owner_pid: i32,
+    },
+    LayoutMismatch(layout::LayoutMismatch),
+    NotInitialized,
+    OpenFailed(shared_memory::ShmemError),
+    /// The named segment still could not be opened after retrying for `waited`
+    OpenTimedOut {
+        waited: Duration,
+        last: shared_memory::ShmemError,
+    },
+    Stopped,
+    /// The mapping is shorter than SharedData
+    SizeMismatch {
+        expected: usize,
+        actual: usize,
+    },
+    EndianMismatch, // The parent stores integers in the other byte order
+}
+
+impl SharedMemError {
+    /// Must match the parent's SharedMemError::exit_code
+    fn exit_code(&self) -> u8 {
+        match self {
+            SharedMemError::Timeout => 10,
+            SharedMemError::Interrupted => 11,
+            SharedMemError::RecoveredFromDeadOwner { .. } => 12,
+            SharedMemError::LayoutMismatch(_) => 13,
+            SharedMemError::NotInitialized => 14,
+            SharedMemError::OpenFailed(_) | SharedMemError::OpenTimedOut { .. } => 15,
+            SharedMemError::Stopped => 16,
+            SharedMemError::SizeMismatch { .. } => 17,
+            SharedMemError::EndianMismatch => 18,
+        }
+    }
+}
+
+impl std::fmt::Display for SharedMemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SharedMemError::Timeout => write!(f, "timed out"),
+            SharedMemError::Interrupted => write!(f, "interrupted by a signal"),
+            SharedMemError::RecoveredFromDeadOwner { owner_pid } => write!(
+                f,
+                "lock owner {} died while holding the lock; shared data may be inconsistent",
+                owner_pid
+            ),
+            SharedMemError::LayoutMismatch(mismatch) => write!(f, "{}", mismatch),
+            SharedMemError::NotInitialized => {
+                write!(f, "shared memory was never marked ready by the parent")
+            }
+            SharedMemError::OpenFailed(e) => write!(f, "failed to open shared memory: {}", e),
+            SharedMemError::OpenTimedOut { waited, last } => write!(
+                f,
+                "shared memory did not appear within {:?}: {}",
+                waited, last
+            ),
+            SharedMemError::Stopped => write!(f, "stopped by the parent"),
+            SharedMemError::SizeMismatch { expected, actual } => write!(
+                f,
+                "shared memory region is {} bytes, expected at least {}",
+                actual, expected
+            ),
+            SharedMemError::EndianMismatch => {
+                write!(f, "shared memory was written with the other byte order")
+            }
+        }
+    }
+}
+
+impl std::error::Error for SharedMemError {}
+
+/// Why try_unlock refused, mirrored from the parent's UnlockError
+#[derive(Debug)]
+enum UnlockError {
+    NotLocked,
+    WrongOwner { owner_pid: i32 },
+}
+
+impl std::fmt::Display for UnlockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnlockError::NotLocked => write!(f, "unlock called but the lock is not held"),
+            UnlockError::WrongOwner { owner_pid } => write!(
+                f,
+                "unlock called by {} but the lock is held by {}",
+                std::process::id(),
+                owner_pid
+            ),
+        }
+    }
+}
+
+// The payload must look the same from the 32-bit side as from the parent
+const _: () = assert!(std::mem::size_of::<AtomicI64>() == layout::PAYLOAD_SIZE);
+const _: () = assert!(std::mem::align_of::<AtomicI64>() == layout::PAYLOAD_ALIGN);
+
+// The futex and number must stay on separate cache lines exactly as in the parent
+const _: () = assert!(
+    std::mem::align_of::<SharedData>() == layout::SHARED_DATA_ALIGN,
+    "the child's SharedData alignment no longer matches the parent's"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SharedData, number) - std::mem::offset_of!(SharedData, futex)
+        >= layout::CACHE_LINE
+);
+#[cfg(target_os = "linux")]
+const _: () = assert!(
+    std::mem::size_of::<SharedData>() == layout::SHARED_DATA_SIZE,
+    "the child's SharedData size no longer matches the parent's"
+);
+
 impl SharedData {
+    /// Wait for the parent to finish initializing the region, then hand it out
+    /// Only the ready flag is read until it holds the sentinel (Acquire pairs with the parent's Release)
+    /// A zero-filled segment the parent never initialized times out here rather than reading as 0
+    pub unsafe fn open<'a>(ptr: *const SharedData, timeout: Duration) -> Option<&'a SharedData> {
+        let ready = unsafe { &(*ptr).ready };
+        let deadline = Instant::now() + timeout;
+
+        while ready.load(Ordering::Acquire) != layout::READY_SENTINEL {
+            if Instant::now() >= deadline {
+                return None;
+            }
+            std::thread::yield_now();
+        }
+        Some(unsafe { &*ptr })
+    }
+
     /// Get the current value of the shared number
     pub fn get_number(&self) -> i64 {
         self.number.load(Ordering::SeqCst)
     }
 
-    /// Set the shared number to a new value
-    pub fn set_number(&self, value: i64) {
-        self.number.store(value, Ordering::SeqCst);
+    /// Whether the parent called request_stop
+    pub fn stop_requested(&self) -> bool {
+        self.should_stop.load(Ordering::Acquire)
+    }
+
+    /// Block until `pred` holds for number, waking whenever someone calls notify_change
+    pub fn wait_until<F: Fn(i64) -> bool>(
+        &self,
+        pred: F,
+        timeout: Duration,
+    ) -> Result<i64, SharedMemError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            // Read the sequence first so a change in between makes the wait return immediately
+            let seq = self.change_seq.value.load(Ordering::Acquire);
+            let number = self.get_number();
+            if pred(number) {
+                return Ok(number);
+            }
+            if self.stop_requested() {
+                return Err(SharedMemError::Stopped);
+            }
+            sleep_while(&self.change_seq, seq, deadline)?;
+        }
+    }
+
+    /// Tell the parent we are still making progress, see the parent's peer_alive
+    pub fn beat(&self) {
+        let now = SystemTime::now()
+            .duration_since(UNIX_EPOCH)
+            .map_or(0, |since| since.as_nanos() as u64);
+        self.last_heartbeat_nanos.store(now, Ordering::Relaxed);
+    }
+
+    /// Tell the parent how long our update took, before releasing the lock
+    pub fn record_compute(&self, took: Duration) {
+        // At least 1, as 0 means nobody has reported yet
+        let nanos = (took.as_nanos().min(u64::MAX as u128) as u64).max(1);
+        self.child_compute_nanos.store(nanos, Ordering::Release);
+    }
+
+    /// Leave a result code and message for the parent to read after we exit
+    /// The message is cut at the last whole character that fits
+    pub fn set_status(&self, code: i32, message: &str) {
+        let mut len = message.len().min(layout::STATUS_MESSAGE_LEN);
+        while !message.is_char_boundary(len) {
+            len -= 1;
+        }
+        let bytes = &message.as_bytes()[..len];
+        for (i, slot) in self.status_message.iter().enumerate() {
+            slot.store(bytes.get(i).copied().unwrap_or(0), Ordering::Relaxed);
+        }
+        self.status.store(code, Ordering::Release);
     }
 
     /// Acquire the futex lock with a timeout
-    pub fn lock_timeout(&self, timeout: Duration) -> Result<(), TimedWaitError> {
+    pub fn lock_timeout(&self, timeout: Duration) -> Result<(), SharedMemError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("lock_timeout", pid = std::process::id()).entered();
         let start = std::time::Instant::now();
+        let mut contended = false;
 
         loop {
             // Check timeout
             if start.elapsed() >= timeout {
-                return Err(TimedWaitError::TimedOut);
+                return Err(self.timed_out());
             }
 
             // Try to change futex value from 0 (unlocked) to 1 (locked)
@@ -39,103 +301,930 @@ impl SharedData {
                 .value
                 .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
             {
-                Ok(_) => return Ok(()), // Successfully acquired lock
-                Err(_) => {
+                Ok(_) => {
+                    // Successfully acquired lock, record ourselves as owner
+                    self.owner_pid
+                        .store(std::process::id() as i32, Ordering::Relaxed);
+                    self.locked_at_nanos
+                        .store(monotonic_nanos(), Ordering::Relaxed);
+                    let _acquisition = self.record_acquisition(start, contended);
+                    #[cfg(feature = "tracing")]
+                    tracing::debug!(
+                        acquisition = _acquisition,
+                        waited_us = start.elapsed().as_micros() as u64,
+                        "lock acquired"
+                    );
+                    return Ok(());
+                }
+                Err(_observed) => {
+                    #[cfg(feature = "tracing")]
+                    if !contended {
+                        tracing::debug!(observed = _observed, "lock contended");
+                    }
+                    contended = true;
                     // Lock is contended, wait for it to be released with remaining timeout
                     let remaining = timeout.saturating_sub(start.elapsed());
                     if remaining.is_zero() {
-                        return Err(TimedWaitError::TimedOut);
+                        return Err(self.timed_out());
+                    }
+                    match self.futex.wait_for(1, remaining) {
+                        Ok(())
+                        | Err(TimedWaitError::WrongValue)
+                        | Err(TimedWaitError::TimedOut) => {}
+                        // Like the parent, a signal only means waiting out what is left
+                        Err(TimedWaitError::Interrupted) => {}
                     }
-                    let _ = self.futex.wait_for(1, remaining)?;
                 }
             }
         }
     }
 
+    /// Wait until every child before us has passed the turn on
+    pub fn wait_for_turn(&self, index: u32, timeout: Duration) -> Result<(), SharedMemError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let turn = self.turn.value.load(Ordering::Acquire);
+            if turn == index {
+                return Ok(());
+            }
+            if self.stop_requested() {
+                return Err(SharedMemError::Stopped);
+            }
+            if Instant::now() >= deadline {
+                return Err(SharedMemError::Timeout);
+            }
+
+            // Sleep on our own bit so only the handoff meant for us wakes us
+            match self.turn.wait_bitset_until(turn, turn_bit(index), deadline) {
+                Ok(()) | Err(TimedWaitError::WrongValue) | Err(TimedWaitError::TimedOut) => {}
+                Err(TimedWaitError::Interrupted) => return Err(SharedMemError::Interrupted),
+            }
+        }
+    }
+
+    /// Give the turn to the next child, waking only that child
+    pub fn pass_turn(&self) {
+        let next = self.turn.value.fetch_add(1, Ordering::Release) + 1;
+        self.turn.wake_bitset(i32::MAX, turn_bit(next));
+    }
+
+    /// Tell everyone waiting on number that it changed
+    pub fn notify_change(&self) {
+        self.change_seq.value.fetch_add(1, Ordering::Release);
+        self.change_seq.wake(i32::MAX); // All waiters re-check their condition
+    }
+
+    /// Report in to the parent's start-up handshake and wait until it lets us run
+    /// Returns at once if the parent announced no handshake
+    pub fn join_handshake(&self, timeout: Duration) -> Result<(), SharedMemError> {
+        if self.phase.value.load(Ordering::Acquire) == layout::Phase::Created as u32 {
+            return Ok(());
+        }
+        // Only the first child to report in moves the phase on
+        if self
+            .phase
+            .value
+            .compare_exchange(
+                layout::Phase::ParentReady as u32,
+                layout::Phase::ChildAttached as u32,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            )
+            .is_ok()
+        {
+            self.phase.wake(i32::MAX);
+        }
+        // Counted only now, so a parent that sees us also sees the phase
+        self.attached_children.value.fetch_add(1, Ordering::Release);
+        self.attached_children.wake(i32::MAX);
+
+        let deadline = Instant::now() + timeout;
+        loop {
+            let phase = self.phase.value.load(Ordering::Acquire);
+            if phase == layout::Phase::Running as u32 {
+                return Ok(());
+            }
+            sleep_while(&self.phase, phase, deadline)?;
+        }
+    }
+
+    /// Update the lock statistics the same way the parent does
+    /// Returns which acquisition this was, counted across all processes
+    fn record_acquisition(&self, start: Instant, contended: bool) -> u64 {
+        let acquisition = self.lock_acquisitions.fetch_add(1, Ordering::Relaxed);
+        if contended {
+            self.lock_contended.fetch_add(1, Ordering::Relaxed);
+            let waited = start.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+            self.total_wait_nanos.fetch_add(waited, Ordering::Relaxed);
+        }
+        acquisition
+    }
+
+    /// Add the hold ending now to the hold-time statistics, as the parent does
+    fn record_release(&self) {
+        let held = monotonic_nanos().saturating_sub(self.locked_at_nanos.load(Ordering::Relaxed));
+        self.total_hold_nanos.fetch_add(held, Ordering::Relaxed);
+        self.max_hold_nanos.fetch_max(held, Ordering::Relaxed);
+    }
+
+    /// Decide whether a timeout was caused by a dead owner, resetting the lock if so
+    fn timed_out(&self) -> SharedMemError {
+        let owner_pid = self.owner_pid.load(Ordering::Relaxed);
+        if owner_pid == 0 || process_alive(owner_pid) {
+            #[cfg(feature = "tracing")]
+            tracing::debug!("lock timed out");
+            return SharedMemError::Timeout;
+        }
+
+        // Only reset if nobody else has taken over the lock in the meantime
+        if self
+            .owner_pid
+            .compare_exchange(owner_pid, 0, Ordering::Relaxed, Ordering::Relaxed)
+            .is_err()
+        {
+            return SharedMemError::Timeout;
+        }
+        self.futex.value.store(0, Ordering::Release);
+        self.futex.wake(1);
+        #[cfg(feature = "tracing")]
+        tracing::warn!(owner_pid, "lock recovered from dead owner");
+        SharedMemError::RecoveredFromDeadOwner { owner_pid }
+    }
+
+    /// Acquire the futex lock with a timeout, returning a guard that
+    /// releases it when dropped
+    pub fn lock_timeout_guard(
+        &self,
+        timeout: Duration,
+    ) -> Result<SharedDataGuard<'_>, SharedMemError> {
+        self.lock_timeout(timeout)?;
+        Ok(SharedDataGuard {
+            data: self,
+            _not_send: PhantomData,
+        })
+    }
+
     /// Release the futex lock and wake up waiting processes
+    /// Releasing a lock we don't hold panics in debug builds and is ignored otherwise
     pub fn unlock(&self) {
-        self.futex.value.store(0, Ordering::Release);
+        if let Err(e) = self.try_unlock() {
+            #[cfg(debug_assertions)]
+            panic!("Child: unlock: {}", e);
+            #[cfg(not(debug_assertions))]
+            let _ = e;
+        }
+    }
+
+    /// Release the futex lock only if this process holds it
+    pub fn try_unlock(&self) -> Result<(), UnlockError> {
+        // The lock being released is the latest acquisition
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "unlock",
+            pid = std::process::id(),
+            observed = self.futex.value.load(Ordering::Relaxed),
+            acquisition = self
+                .lock_acquisitions
+                .load(Ordering::Relaxed)
+                .wrapping_sub(1)
+        )
+        .entered();
+        if self.futex.value.load(Ordering::Relaxed) == 0 {
+            return Err(UnlockError::NotLocked);
+        }
+        let owner_pid = self.owner_pid.load(Ordering::Relaxed);
+        if owner_pid != std::process::id() as i32 {
+            return Err(UnlockError::WrongOwner { owner_pid });
+        }
+
+        self.record_release();
+        self.owner_pid.store(0, Ordering::Relaxed);
+        if self
+            .futex
+            .value
+            .compare_exchange(1, 0, Ordering::Release, Ordering::Relaxed)
+            .is_err()
+        {
+            return Err(UnlockError::NotLocked);
+        }
         self.futex.wake(1); // Wake up one waiting process
+        #[cfg(feature = "tracing")]
+        tracing::debug!("lock released");
+        Ok(())
+    }
+}
+
+impl RwSharedData {
+    /// Take a shared read lock; never blocks other readers, only writers
+    pub fn read_lock_timeout(&self, timeout: Duration) -> Result<RwReadGuard<'_>, SharedMemError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            // Writers have priority: don't join while one is waiting
+            if self.writer.value.load(Ordering::SeqCst) != 0 {
+                sleep_while(&self.writer, 1, deadline)?;
+                continue;
+            }
+
+            self.readers.value.fetch_add(1, Ordering::SeqCst);
+            if self.writer.value.load(Ordering::SeqCst) == 0 {
+                return Ok(RwReadGuard { data: self });
+            }
+
+            // A writer arrived in the meantime, back off and let it go first
+            self.read_unlock();
+        }
+    }
+
+    /// Drop our read lock, waking the writer if we were the last reader
+    fn read_unlock(&self) {
+        // A parent upgrading its read lock waits for the count to drop to 1, a writer for 0
+        if self.readers.value.fetch_sub(1, Ordering::Release) <= 2 {
+            self.readers.wake(1);
+        }
+    }
+}
+
+/// Shared access to the published number, released on drop
+#[must_use = "if unused the lock will immediately unlock"]
+struct RwReadGuard<'a> {
+    data: &'a RwSharedData,
+}
+
+impl Deref for RwReadGuard<'_> {
+    type Target = i64;
+
+    fn deref(&self) -> &i64 {
+        // SAFETY: no writer can hold the lock while we are a reader
+        unsafe { &*self.data.number.as_ptr() }
+    }
+}
+
+impl Drop for RwReadGuard<'_> {
+    fn drop(&mut self) {
+        self.data.read_unlock();
+    }
+}
+
+/// Sleep while the futex still holds `expected`, up to the deadline
+fn sleep_while(futex: &RawSync, expected: u32, deadline: Instant) -> Result<(), SharedMemError> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(SharedMemError::Timeout);
+    }
+    match futex.wait_for(expected, remaining) {
+        Ok(()) | Err(TimedWaitError::WrongValue) | Err(TimedWaitError::TimedOut) => Ok(()),
+        Err(TimedWaitError::Interrupted) => Err(SharedMemError::Interrupted),
+    }
+}
+
+/// Futex bitset for a turn index; must match the parent's turn_bit
+fn turn_bit(index: u32) -> u32 {
+    1 << (index % 32)
+}
+
+/// Monotonic nanoseconds since this process first asked, for lock hold times
+fn monotonic_nanos() -> u64 {
+    static EPOCH: std::sync::OnceLock<Instant> = std::sync::OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+/// Check whether a process exists (EPERM still means it does)
+#[cfg(unix)]
+fn process_alive(pid: i32) -> bool {
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    std::io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+/// Check whether a process exists and has not exited (access denied still means it does)
+#[cfg(windows)]
+fn process_alive(pid: i32) -> bool {
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, ERROR_INVALID_PARAMETER, GetLastError, STILL_ACTIVE,
+    };
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid as u32);
+        if process.is_null() {
+            return GetLastError() != ERROR_INVALID_PARAMETER;
+        }
+        let mut exit_code = 0;
+        let queried = GetExitCodeProcess(process, &mut exit_code);
+        CloseHandle(process);
+        queried == 0 || exit_code == STILL_ACTIVE as u32
+    }
+}
+
+/// Holds the futex lock and releases it on drop, even during a panic
+/// Must match the parent's SharedDataGuard semantics
+#[must_use = "if unused the lock will immediately unlock"]
+struct SharedDataGuard<'a> {
+    data: &'a SharedData,
+    _not_send: PhantomData<*const ()>, // The lock belongs to this thread
+}
+
+impl SharedDataGuard<'_> {
+    /// The number, Relaxed as the lock orders it; no &mut i64, which would race with get_number
+    pub fn get(&self) -> i64 {
+        self.data.number.load(Ordering::Relaxed)
+    }
+
+    pub fn set(&mut self, value: i64) {
+        self.data.number.store(value, Ordering::Relaxed);
+    }
+
+    pub fn update(&mut self, f: impl FnOnce(i64) -> i64) {
+        let value = f(self.get());
+        self.set(value);
+    }
+}
+
+impl Drop for SharedDataGuard<'_> {
+    fn drop(&mut self) {
+        // Tell the parent the number may be half-updated
+        if std::thread::panicking() {
+            self.data.poisoned.store(true, Ordering::Release);
+        }
+        self.data.unlock();
+    }
+}
+
+/// What a child does to the number when the parent does not say
+const DEFAULT_OP: &str = "(n + 25) * 2";
+
+/// How long a child waits for its turn or the lock when the parent does not say
+const DEFAULT_TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Names a marker file; if it does not exist yet, the child creates it and exits mid-update
+const CRASH_ONCE_VAR: &str = "SHAREDMEM_CHILD_CRASH_ONCE";
+
+/// Fault injection: when set, the child finishes its work but then never exits
+const HANG_VAR: &str = "SHAREDMEM_CHILD_HANG";
+
+/// Milliseconds to keep retrying a named segment that cannot be opened yet, e.g. because
+/// the child was spawned before the parent finished creating it
+const OPEN_TIMEOUT_VAR: &str = "SHAREDMEM_CHILD_OPEN_TIMEOUT_MS";
+
+/// How long to retry opening when OPEN_TIMEOUT_VAR is not set
+const DEFAULT_OPEN_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// First and longest pause between attempts to open a named segment
+const OPEN_MIN_BACKOFF: Duration = Duration::from_millis(1);
+const OPEN_MAX_BACKOFF: Duration = Duration::from_millis(100);
+
+fn main() -> ExitCode {
+    match run() {
+        Ok(()) => ExitCode::SUCCESS,
+        Err(e) => {
+            eprintln!("Child: {}", e);
+            // Shared memory failures get their own exit codes for the parent to interpret
+            let code = e
+                .downcast_ref::<SharedMemError>()
+                .map_or(1, SharedMemError::exit_code);
+            ExitCode::from(code)
+        }
     }
 }
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
+fn run() -> Result<(), Box<dyn Error>> {
+    #[cfg(feature = "tracing")]
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .init();
+
     println!("=== 32-bit Child Process Started ===");
     println!("Child Process ID: {}", std::process::id());
 
-    // Get shared memory OS ID from command line argument
+    // Get shared memory OS ID (and optionally our position among the children)
     let args: Vec<String> = env::args().collect();
-    if args.len() != 2 {
-        return Err("Usage: child_process <shared_memory_os_id>".into());
+
+    // The ring buffer example uses the child as a producer instead
+    if args.len() == 4 && args[1] == "--ring" {
+        return run_ring_producer(&args[2], args[3].parse()?);
+    }
+
+    // The work stack example uses the child to drain what the parent pushed
+    if args.len() == 4 && args[1] == "--stack" {
+        return run_stack_consumer(&args[2], args[3].parse()?);
+    }
+
+    // The shared vec test has the child fill a vector for the parent to read
+    if args.len() == 4 && args[1] == "--fill-vec" {
+        return run_vec_filler(&args[2], args[3].parse()?);
+    }
+
+    // The MPMC example runs several children as producers alongside the parent's
+    if args.len() == 5 && args[1] == "--mpmc" {
+        return run_mpmc_producer(&args[2], args[3].parse()?, args[4].parse()?);
+    }
+
+    // The record example has the child bump one counter while the parent bumps another
+    if args.len() == 4 && args[1] == "--record" {
+        return run_record_requests(&args[2], args[3].parse()?);
     }
 
+    // The event log example has the child log its lock handoffs next to the parent's
+    if args.len() == 4 && args[1] == "--events" {
+        return run_event_logger(&args[2], args[3].parse()?);
+    }
+
+    // A long-lived child serving requests until the parent asks it to stop
+    if args.len() == 3 && args[1] == "--serve" {
+        return run_server(&args[2]);
+    }
+
+    // The lock benchmark uses the child as the other end of a ping-pong
+    if args.len() == 4 && args[1] == "--ping-pong" {
+        return run_ping_pong(&args[2], args[3].parse()?);
+    }
+
+    // Without an operation or timeout from the parent, fall back to the demo's defaults
+    // (a timeout from the parent is already scaled, a default is scaled here)
+    let (index, count, op, timeout): (u32, u32, &str, Duration) = match args.len() {
+        2 => (0, 1, DEFAULT_OP, timeouts::scaled(DEFAULT_TIMEOUT)),
+        4 => (
+            args[2].parse()?,
+            args[3].parse()?,
+            DEFAULT_OP,
+            timeouts::scaled(DEFAULT_TIMEOUT),
+        ),
+        6 => (
+            args[2].parse()?,
+            args[3].parse()?,
+            &args[4],
+            Duration::from_millis(args[5].parse()?),
+        ),
+        _ => {
+            return Err(
+                "Usage: child_process <shared_memory_os_id> [<index> <count> [<op> <timeout_ms>]]"
+                    .into(),
+            );
+        }
+    };
+    let op = expr::Expr::parse(op).map_err(|e| format!("Invalid operation {:?}: {}", op, e))?;
+
     let os_id = &args[1];
+    println!("Child: I am child {} of {}", index + 1, count);
     println!("Child: Opening shared memory with OS ID: {}", os_id);
 
-    // Open the existing shared memory using the OS ID
-    let shmem = ShmemConf::new().os_id(os_id).open()?;
-    println!("Child: Successfully opened shared memory");
+    let mapped = attach(os_id)?;
+    let shared_data = mapped.get();
 
-    // Get a pointer to the shared data
-    let shared_data_ptr = shmem.as_ptr() as *const SharedData;
-    let shared_data = unsafe { &*shared_data_ptr };
+    shared_data.beat();
+
+    // Touch nothing else until the parent has finished setting up; it
+    // waits up to a full timeout for the other children to report in
+    println!("Child: Waiting for the parent to start us...");
+    if let Err(e) = shared_data.join_handshake(timeout * 2) {
+        eprintln!("Child: The parent never started us");
+        return Err(e.into());
+    }
 
     // Verify we can read the shared data
     let initial_number = shared_data.get_number();
     println!("Child: Can see initial number: {}", initial_number);
 
-    if initial_number != 100 {
-        eprintln!(
-            "Child: Warning - expected initial number 100, got {}",
-            initial_number
-        );
+    // Wait for the children before us; each of them may take up to a full timeout
+    println!("Child: Waiting for turn {}...", index);
+    if let Err(e) = shared_data.wait_for_turn(index, timeout * (index + 1)) {
+        eprintln!("Child: Failed waiting for turn {}", index);
+        return Err(e.into());
     }
 
     // Attempt to acquire the lock (will block until parent releases it)
     println!("Child: Attempting to acquire lock...");
 
-    // Use a timeout to avoid hanging indefinitely
-    const TIMEOUT_SECONDS: u64 = 10;
-    let timeout = Duration::from_secs(TIMEOUT_SECONDS);
-
-    match shared_data.lock_timeout(timeout) {
-        Ok(_) => {
+    let mut guard = match shared_data.lock_timeout_guard(timeout) {
+        Ok(guard) => {
             println!("Child: Lock acquired successfully!");
-        }
-        Err(TimedWaitError::TimedOut) => {
-            return Err(format!(
-                "Child: Timeout waiting for lock after {} seconds",
-                TIMEOUT_SECONDS
-            )
-            .into());
-        }
-        Err(TimedWaitError::Interrupted) => {
-            return Err("Child: Lock attempt was interrupted".into());
+            guard
         }
         Err(e) => {
-            return Err(format!("Child: Failed to acquire lock: {:?}", e).into());
+            eprintln!("Child: Failed to acquire lock within {:?}", timeout);
+            return Err(e.into());
         }
+    };
+
+    // The parent may have given up on us while we waited
+    if shared_data.stop_requested() {
+        return Err(SharedMemError::Stopped.into());
     }
 
+    // Timed from here to the release, simulated work included
+    let compute_started = Instant::now();
+
     // Read the current number
-    let current_number = shared_data.get_number();
+    let current_number = guard.get();
     println!("Child: Current number: {}", current_number);
 
-    // Child does math: add 25 and multiply by 2
-    let new_number = (current_number + 25) * 2;
-    shared_data.set_number(new_number);
+    // Child does the math it was given, (n + 25) * 2 unless told otherwise
+    let new_number = op
+        .eval(current_number)
+        .ok_or_else(|| format!("Child: {} overflows for n = {}", op, current_number))?;
+    guard.set(new_number);
 
-    println!("Child: Applied operation ((n + 25) * 2)");
+    // Fault injection for the parent's --max-retries: the first child to get
+    // here creates the marker file and dies holding the lock
+    if let Some(marker) = env::var_os(CRASH_ONCE_VAR)
+        && std::fs::File::create_new(&marker).is_ok()
+    {
+        eprintln!(
+            "Child: Crashing with the lock held, as {} asked",
+            CRASH_ONCE_VAR
+        );
+        std::process::exit(1);
+    }
+
+    println!("Child: Applied operation ({})", op);
     println!("Child: New number: {}", new_number);
 
-    // Simulate some work
+    // Simulate some work, beating so the parent can tell we have not hung
+    shared_data.beat();
     std::thread::sleep(std::time::Duration::from_millis(500));
+    shared_data.beat();
+    shared_data.record_compute(compute_started.elapsed());
 
     // Release the lock
-    shared_data.unlock();
+    drop(guard);
     println!("Child: Lock released");
 
+    // Wake anyone waiting for number to change
+    shared_data.notify_change();
+
+    // Let the next child (if any) take its turn
+    shared_data.pass_turn();
+
+    // Read the published value; the parent holds a read lock too, which must not block us
+    match shared_data.published.read_lock_timeout(timeout) {
+        Ok(published) => println!("Child: Published value under read lock: {}", *published),
+        Err(e) => {
+            eprintln!("Child: Failed to read-lock published value");
+            return Err(e.into());
+        }
+    }
+
+    shared_data.set_status(
+        0,
+        &format!("child {} of {} produced {}", index + 1, count, new_number),
+    );
+
     println!("=== Child process finished successfully ===");
 
+    if env::var_os(HANG_VAR).is_some() {
+        println!("Child: Hanging instead of exiting, as {} asked", HANG_VAR);
+        loop {
+            std::thread::sleep(Duration::from_secs(3600));
+        }
+    }
+
+    Ok(())
+}
+
+/// The parent's region: a named segment, an inherited memfd or a System V segment
+enum Region {
+    Named(Shmem),
+    #[cfg(target_os = "linux")]
+    Memfd(memfd::MemfdMapping),
+    #[cfg(unix)]
+    Sysv(sysv::SysvMapping),
+}
+
+impl Region {
+    fn as_ptr(&self) -> *mut u8 {
+        match self {
+            Region::Named(shmem) => shmem.as_ptr(),
+            #[cfg(target_os = "linux")]
+            Region::Memfd(memfd) => memfd.as_ptr(),
+            #[cfg(unix)]
+            Region::Sysv(sysv) => sysv.as_ptr(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Region::Named(shmem) => shmem.len(),
+            #[cfg(target_os = "linux")]
+            Region::Memfd(memfd) => memfd.len(),
+            #[cfg(unix)]
+            Region::Sysv(sysv) => sysv.len(),
+        }
+    }
+}
+
+/// Map the region named by `os_id`, which is an `fd:<n>` handle if the parent made it anonymous
+/// and a `sysv:<shmid>` one if it made a System V segment
+fn open_region(os_id: &str) -> Result<Region, SharedMemError> {
+    #[cfg(unix)]
+    if let Some(id) = sysv::parse_handle(os_id) {
+        return sysv::SysvMapping::attach(id)
+            .map(Region::Sysv)
+            .map_err(|e| {
+                SharedMemError::OpenFailed(shared_memory::ShmemError::MapOpenFailed(
+                    e.raw_os_error().unwrap_or(0) as u32,
+                ))
+            });
+    }
+
+    #[cfg(target_os = "linux")]
+    if let Some(fd) = memfd::parse_handle(os_id) {
+        use std::os::fd::{FromRawFd, OwnedFd};
+
+        // The parent left this fd open across exec for us, and nothing else here uses it
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        return memfd::MemfdMapping::from_fd(fd)
+            .map(Region::Memfd)
+            .map_err(|e| {
+                SharedMemError::OpenFailed(shared_memory::ShmemError::MapOpenFailed(
+                    e.raw_os_error().unwrap_or(0) as u32,
+                ))
+            });
+    }
+    open_named(os_id, open_timeout()).map(Region::Named)
+}
+
+/// Open the named segment `os_id`, retrying with backoff for up to `timeout` while it
+/// does not exist yet or is not sized yet; both show up as MapOpenFailed
+fn open_named(os_id: &str, timeout: Duration) -> Result<Shmem, SharedMemError> {
+    let deadline = Instant::now() + timeout;
+    let mut backoff = OPEN_MIN_BACKOFF;
+    loop {
+        match ShmemConf::new().os_id(os_id).open() {
+            Ok(shmem) => return Ok(shmem),
+            Err(last @ shared_memory::ShmemError::MapOpenFailed(_)) => {
+                let remaining = deadline.saturating_duration_since(Instant::now());
+                if remaining.is_zero() {
+                    return Err(SharedMemError::OpenTimedOut {
+                        waited: timeout,
+                        last,
+                    });
+                }
+                std::thread::sleep(backoff.min(remaining));
+                backoff = (backoff * 2).min(OPEN_MAX_BACKOFF);
+            }
+            Err(e) => return Err(SharedMemError::OpenFailed(e)),
+        }
+    }
+}
+
+/// The retry budget for opening from OPEN_TIMEOUT_VAR, or the default if unset or invalid,
+/// scaled like every other timeout
+fn open_timeout() -> Duration {
+    let Ok(millis) = env::var(OPEN_TIMEOUT_VAR) else {
+        return timeouts::scaled(DEFAULT_OPEN_TIMEOUT);
+    };
+    match millis.parse() {
+        Ok(millis) => timeouts::scaled(Duration::from_millis(millis)),
+        Err(e) => {
+            let fallback = timeouts::scaled(DEFAULT_OPEN_TIMEOUT);
+            eprintln!(
+                "Child: Ignoring {}={:?} ({}), retrying for {:?}",
+                OPEN_TIMEOUT_VAR, millis, e, fallback
+            );
+            fallback
+        }
+    }
+}
+
+/// The parent's region, checked by attach to hold a SharedData
+/// References from get borrow the mapping, so none can outlive it
+struct MappedSharedData {
+    region: Region,
+}
+
+impl MappedSharedData {
+    fn get(&self) -> &SharedData {
+        // The region stays mapped for as long as self, and attach checked its size and header
+        unsafe { &*(self.region.as_ptr() as *const SharedData) }
+    }
+}
+
+/// Open the parent's region and check it before any field other than ready is trusted
+fn attach(os_id: &str) -> Result<MappedSharedData, SharedMemError> {
+    // Open the existing shared memory using the OS ID
+    let shmem = open_region(os_id)?;
+    println!("Child: Successfully opened shared memory");
+
+    // Even the ready flag lies past the end of a region that is too short
+    let expected = std::mem::size_of::<SharedData>();
+    if shmem.len() < expected {
+        return Err(SharedMemError::SizeMismatch {
+            expected,
+            actual: shmem.len(),
+        });
+    }
+
+    // Get the shared data once the parent has finished initializing it
+    let shared_data_ptr = shmem.as_ptr() as *const SharedData;
+    let shared_data =
+        unsafe { SharedData::open(shared_data_ptr, timeouts::scaled(Duration::from_secs(5))) }
+            .ok_or(SharedMemError::NotInitialized)?;
+
+    // Make sure the parent was built with the same byte order and layout before trusting any other field
+    if !shared_data.header.same_byte_order() {
+        return Err(SharedMemError::EndianMismatch);
+    }
+    shared_data
+        .header
+        .check::<SharedData>()
+        .map_err(SharedMemError::LayoutMismatch)?;
+
+    Ok(MappedSharedData { region: shmem })
+}
+
+/// Take every odd step of the lock benchmark's ping-pong until number reaches `handoffs`
+/// The parent takes the even steps, so each increment hands the lock to the other side
+fn run_ping_pong(os_id: &str, handoffs: i64) -> Result<(), Box<dyn Error>> {
+    let mapped = attach(os_id)?;
+    let shared_data = mapped.get();
+    let timeout = timeouts::scaled(Duration::from_secs(10));
+
+    // Tell the parent to start its clock
+    shared_data.set_status(1, "ready");
+    loop {
+        let number = shared_data.wait_until(|n| n % 2 == 1 || n >= handoffs, timeout)?;
+        if number >= handoffs {
+            return Ok(());
+        }
+        shared_data.lock_timeout_guard(timeout)?.update(|n| n + 1);
+        shared_data.notify_change();
+    }
+}
+
+/// Push `count` values into the ring buffer example's shared region
+fn run_ring_producer(os_id: &str, count: i64) -> Result<(), Box<dyn Error>> {
+    println!("Child: Producing {} values into ring {}", count, os_id);
+
+    let shmem = ShmemConf::new().os_id(os_id).open()?;
+    let ring = unsafe { &*(shmem.as_ptr() as *const ring::SharedRing<{ layout::RING_CAPACITY }>) };
+
+    for i in 1..=count {
+        // Block while the parent catches up rather than dropping values
+        if let Err(e) = ring.push_blocking(i * i, timeouts::scaled(Duration::from_secs(10))) {
+            return Err(format!("Child: Failed to push value {}: {:?}", i, e).into());
+        }
+    }
+
+    println!("Child: Finished producing");
+    Ok(())
+}
+
+/// Enqueue `first..first + count` into the MPMC example's queue, competing with other producers
+fn run_mpmc_producer(os_id: &str, first: i64, count: i64) -> Result<(), Box<dyn Error>> {
+    println!(
+        "Child: Enqueueing {}..{} into queue {}",
+        first,
+        first + count,
+        os_id
+    );
+
+    let shmem = ShmemConf::new().os_id(os_id).open()?;
+    let queue =
+        unsafe { &*(shmem.as_ptr() as *const mpmc::SharedMpmcQueue<{ layout::MPMC_CAPACITY }>) };
+
+    for value in first..first + count {
+        queue
+            .enqueue_timeout(value, timeouts::scaled(Duration::from_secs(10)))
+            .map_err(|e| format!("Child: Failed to enqueue {}: {:?}", value, e))?;
+    }
+
+    println!("Child: Enqueued all {} values", count);
+    Ok(())
+}
+
+/// How long the server sleeps between heartbeats while no request comes in
+const SERVE_IDLE: Duration = Duration::from_millis(500);
+
+/// Answer every odd number the parent leaves by adding one, until it asks us to stop
+/// Stopping is only checked between requests, so a request is never left half done
+fn run_server(os_id: &str) -> Result<(), Box<dyn Error>> {
+    let mapped = attach(os_id)?;
+    let shared_data = mapped.get();
+    println!("Child: Serving requests in {}", os_id);
+
+    let mut served = 0;
+    while !shared_data.stop_requested() {
+        shared_data.beat();
+        match shared_data.wait_until(|n| n % 2 != 0, SERVE_IDLE) {
+            Ok(_) => {}
+            Err(SharedMemError::Timeout) => continue,
+            Err(SharedMemError::Stopped) => break,
+            Err(e) => return Err(e.into()),
+        }
+
+        let mut guard = shared_data.lock_timeout_guard(timeouts::scaled(DEFAULT_TIMEOUT))?;
+        if shared_data.stop_requested() {
+            break;
+        }
+        guard.update(|n| n + 1);
+        drop(guard);
+        shared_data.notify_change();
+        served += 1;
+    }
+
+    println!(
+        "Child: Stop requested after serving {} requests, exiting",
+        served
+    );
+    Ok(())
+}
+
+/// The event log example's log, which follows SharedData in the region
+type EventLog = eventlog::SharedEventLog<{ layout::EVENT_LOG_CAPACITY }>;
+
+/// Take the lock `count` times for the event log example, logging each step
+fn run_event_logger(os_id: &str, count: u32) -> Result<(), Box<dyn Error>> {
+    println!("Child: Logging {} lock handoffs in {}", count, os_id);
+
+    let mapped = attach(os_id)?;
+    let shared_data = mapped.get();
+    let offset = std::mem::size_of::<SharedData>();
+    if mapped.region.len() < offset + std::mem::size_of::<EventLog>() {
+        return Err("Child: No room for the event log after the shared data".into());
+    }
+    let log = unsafe { &*(mapped.region.as_ptr().add(offset) as *const EventLog) };
+
+    for _ in 0..count {
+        let mut guard = shared_data.lock_timeout_guard(timeouts::scaled(DEFAULT_TIMEOUT))?;
+        log.record(eventlog::LOCK_ACQUIRED);
+        guard.update(|n| n + 1);
+        log.record(eventlog::NUMBER_SET);
+        log.record(eventlog::LOCK_RELEASED);
+        drop(guard);
+        std::thread::yield_now();
+    }
+    Ok(())
+}
+
+/// Count `count` requests in the record example's shared record, leaving its other fields to the parent
+fn run_record_requests(os_id: &str, count: u64) -> Result<(), Box<dyn Error>> {
+    println!("Child: Counting {} requests in record {}", count, os_id);
+
+    let shmem = ShmemConf::new().os_id(os_id).open()?;
+    let record = unsafe { &*(shmem.as_ptr() as *const record::SharedRecord) };
+
+    let errors_before = record.errors();
+    for _ in 0..count {
+        record.add_requests(1);
+        std::thread::yield_now();
+    }
+    record.set_last_value(count as i64);
+
+    println!(
+        "Child: Counted {} requests; errors meanwhile went from {} to {}",
+        count,
+        errors_before,
+        record.errors()
+    );
+    Ok(())
+}
+
+/// Push 0, 10, 20, ... into the shared vec, `count` values in all
+fn run_vec_filler(os_id: &str, count: i64) -> Result<(), Box<dyn Error>> {
+    println!("Child: Pushing {} values into vec {}", count, os_id);
+
+    let shmem = ShmemConf::new().os_id(os_id).open()?;
+    let vec =
+        unsafe { &*(shmem.as_ptr() as *const sharedvec::SharedVec<i64, { layout::VEC_CAPACITY }>) };
+
+    // One lock for the lot, so the parent never sees the vec half filled
+    let mut guard = vec.lock();
+    for i in 0..count {
+        guard
+            .push(i * 10)
+            .map_err(|value| format!("Child: Vec full before pushing {}", value))?;
+    }
+    println!("Child: Vec now holds {:?}", &*guard);
+    Ok(())
+}
+
+/// Pop all `count` values the work stack example pushed, checking they come off newest first
+fn run_stack_consumer(os_id: &str, count: i64) -> Result<(), Box<dyn Error>> {
+    println!("Child: Popping {} values from stack {}", count, os_id);
+
+    let shmem = ShmemConf::new().os_id(os_id).open()?;
+    let stack =
+        unsafe { &*(shmem.as_ptr() as *const stack::SharedStack<{ layout::STACK_CAPACITY }>) };
+
+    for expected in (1..=count).rev() {
+        let value = stack
+            .pop_blocking(timeouts::scaled(Duration::from_secs(10)))
+            .map_err(|e| format!("Child: Failed to pop value {}: {:?}", expected, e))?;
+        if value != expected {
+            return Err(format!("Child: Expected {} got {}", expected, value).into());
+        }
+    }
+
+    // Everything pushed has been taken, so the stack must now report empty
+    if let Some(value) = stack.pop() {
+        return Err(format!("Child: Stack should be empty but held {}", value).into());
+    }
+
+    println!("Child: Popped all {} values newest first", count);
     Ok(())
 }
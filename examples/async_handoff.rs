@@ -0,0 +1,66 @@
+//! Awaits the lock handoff from the 32-bit child on a tokio runtime while
+//! another task keeps running on the same runtime.
+//!
+//! Run with `cargo run --example async_handoff --features async`.
+
+use sharedmem_multiarch::{ChildExecutable, OwnedSharedData};
+use std::process::Command;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
+
+#[tokio::main(flavor = "current_thread")]
+async fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let shared_data = OwnedSharedData::create()?;
+    let initial = shared_data.get_number();
+
+    let child_binary = include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded"));
+    let child_exe = ChildExecutable::extract(child_binary)?;
+
+    // Hold the lock while the child starts so it has to wait for us
+    let guard = shared_data.lock_async().await?;
+    let mut child = Command::new(&child_exe).arg(shared_data.os_id()).spawn()?;
+    tokio::time::sleep(Duration::from_millis(200)).await;
+    drop(guard);
+    println!("Parent: Lock released, awaiting the child's handoff");
+
+    // With a single runtime thread, this only ticks if waiting for the lock
+    // leaves the thread free
+    let ticks = Arc::new(AtomicU32::new(0));
+    let done = Arc::new(AtomicBool::new(false));
+    let ticker = tokio::spawn({
+        let ticks = ticks.clone();
+        let done = done.clone();
+        async move {
+            while !done.load(Ordering::Relaxed) {
+                ticks.fetch_add(1, Ordering::Relaxed);
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        }
+    });
+
+    // We may win the lock back before the child gets it, so keep awaiting
+    // until its result is there
+    let number = loop {
+        let guard = shared_data.lock_async().await?;
+        if guard.get() != initial {
+            break guard.get();
+        }
+        drop(guard);
+        tokio::time::sleep(Duration::from_millis(10)).await;
+    };
+    done.store(true, Ordering::Relaxed);
+    ticker.await?;
+
+    println!(
+        "Parent: Got the lock back with number {} -> {}, the other task ticked {} times meanwhile",
+        initial,
+        number,
+        ticks.load(Ordering::Relaxed)
+    );
+
+    if !child.wait()?.success() {
+        return Err("Child process failed".into());
+    }
+    Ok(())
+}
@@ -0,0 +1,88 @@
+//! Keeps a shared event log after the `SharedData` in one region. The
+//! 64-bit parent and the 32-bit child both take the lock over and over,
+//! logging each acquisition, update and release, and the parent then dumps
+//! the merged timeline and checks no two holders ever overlapped in it.
+//!
+//! Run with `cargo run --example event_log [handoffs]`. Both sides log
+//! three events per handoff, and only the last `EVENT_LOG_CAPACITY` stay.
+
+use sharedmem_multiarch::eventlog::{LOCK_ACQUIRED, LOCK_RELEASED, NUMBER_SET, SharedEventLog};
+use sharedmem_multiarch::layout::EVENT_LOG_CAPACITY;
+use sharedmem_multiarch::{ChildExecutable, SharedData, SharedRegion};
+use std::process::Command;
+use std::time::Duration;
+
+type EventLog = SharedEventLog<EVENT_LOG_CAPACITY>;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let handoffs: u32 = match std::env::args().nth(1) {
+        Some(arg) => arg.parse()?,
+        None => 10,
+    };
+
+    let shared_data = SharedRegion::builder()
+        .size(std::mem::size_of::<SharedData>() + std::mem::size_of::<EventLog>())
+        .build()?;
+    let (extra, _) = shared_data.extra_space();
+    let log_ptr = extra as *mut EventLog;
+    unsafe {
+        std::ptr::write(log_ptr, EventLog::new());
+    }
+    let log = unsafe { &*log_ptr };
+    println!("Parent: Region created with OS ID: {}", shared_data.os_id());
+
+    let child_binary = include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded"));
+    let child_exe = ChildExecutable::extract(child_binary)?;
+    let mut child = Command::new(&child_exe)
+        .arg("--events")
+        .arg(shared_data.os_id())
+        .arg(handoffs.to_string())
+        .spawn()?;
+
+    for _ in 0..handoffs {
+        let mut guard = shared_data.lock_timeout_guard(Duration::from_secs(5))?;
+        log.record(LOCK_ACQUIRED);
+        guard.update(|n| n + 1);
+        log.record(NUMBER_SET);
+        log.record(LOCK_RELEASED);
+        drop(guard);
+        std::thread::yield_now();
+    }
+    if !child.wait()?.success() {
+        return Err("Child process failed".into());
+    }
+
+    let events = log.dump();
+    println!(
+        "Parent: {} events recorded, the last {} of them:",
+        log.recorded(),
+        events.len()
+    );
+    for event in &events {
+        println!("  {}", event);
+    }
+
+    // Every event but the first few belongs to some holder's acquire, set,
+    // release triple; the log may have wrapped in the middle of one
+    let start = events
+        .iter()
+        .position(|event| event.code == LOCK_ACQUIRED)
+        .ok_or("no acquisition left in the log")?;
+    for triple in events[start..].chunks(3) {
+        let codes: Vec<u32> = triple.iter().map(|event| event.code).collect();
+        let holder = triple[0].pid;
+        if codes[..] != [LOCK_ACQUIRED, NUMBER_SET, LOCK_RELEASED][..codes.len()]
+            || triple.iter().any(|event| event.pid != holder)
+        {
+            return Err(format!("Parent: Overlapping holders in {:?}", triple).into());
+        }
+    }
+    if events
+        .windows(2)
+        .any(|pair| pair[0].timestamp_nanos > pair[1].timestamp_nanos)
+    {
+        return Err("Parent: Timestamps go backwards".into());
+    }
+    println!("Parent: Every holder acquired, set and released before the next one came in");
+    Ok(())
+}
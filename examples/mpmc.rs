@@ -0,0 +1,100 @@
+//! Several producers and consumers sharing one bounded queue: two 32-bit
+//! children and a parent thread enqueue disjoint ranges while three parent
+//! threads dequeue, and every value must come out exactly once.
+//!
+//! Run with `cargo run --example mpmc [count]`, where `count` is how many
+//! values each producer enqueues.
+
+use shared_memory::ShmemConf;
+use sharedmem_multiarch::ChildExecutable;
+use sharedmem_multiarch::layout::MPMC_CAPACITY;
+use sharedmem_multiarch::mpmc::SharedMpmcQueue;
+use std::process::Command;
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::Duration;
+
+const CHILD_PRODUCERS: i64 = 2;
+const CONSUMERS: usize = 3;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let count: i64 = match std::env::args().nth(1) {
+        Some(arg) => arg.parse()?,
+        None => 10_000,
+    };
+    let total = (CHILD_PRODUCERS + 1) * count;
+
+    let shmem = ShmemConf::new()
+        .size(std::mem::size_of::<SharedMpmcQueue<MPMC_CAPACITY>>())
+        .create()?;
+    let queue_ptr = shmem.as_ptr() as *mut SharedMpmcQueue<MPMC_CAPACITY>;
+    unsafe {
+        std::ptr::write(queue_ptr, SharedMpmcQueue::new());
+    }
+    let queue = unsafe { &*queue_ptr };
+    println!(
+        "Parent: Queue of {} slots created with OS ID: {}",
+        MPMC_CAPACITY,
+        shmem.get_os_id()
+    );
+
+    let child_binary = include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded"));
+    let child_exe = ChildExecutable::extract(child_binary)?;
+    let mut children = Vec::new();
+    for producer in 1..=CHILD_PRODUCERS {
+        children.push(
+            Command::new(&child_exe)
+                .arg("--mpmc")
+                .arg(shmem.get_os_id())
+                .arg((producer * count).to_string())
+                .arg(count.to_string())
+                .spawn()?,
+        );
+    }
+
+    // Consumers stop once `total` values have been taken between them
+    let taken = AtomicI64::new(0);
+    let mut seen = std::thread::scope(|scope| {
+        scope.spawn(|| {
+            for value in 0..count {
+                queue.enqueue_timeout(value, TIMEOUT).unwrap();
+            }
+        });
+        let consumers: Vec<_> = (0..CONSUMERS)
+            .map(|_| {
+                scope.spawn(|| {
+                    let mut values = Vec::new();
+                    while taken.fetch_add(1, Ordering::Relaxed) < total {
+                        values.push(queue.dequeue_timeout(TIMEOUT).unwrap());
+                    }
+                    values
+                })
+            })
+            .collect();
+        consumers
+            .into_iter()
+            .flat_map(|consumer| consumer.join().unwrap())
+            .collect::<Vec<_>>()
+    });
+
+    for mut child in children {
+        if !child.wait()?.success() {
+            return Err("Child process failed".into());
+        }
+    }
+
+    seen.sort_unstable();
+    if seen != (0..total).collect::<Vec<_>>() {
+        return Err(format!("Parent: Values lost or duplicated among {}", seen.len()).into());
+    }
+    if !queue.is_empty() || queue.consumers_waiting.load(Ordering::Relaxed) != 0 {
+        return Err("Parent: The queue should be empty with nobody waiting".into());
+    }
+    println!(
+        "Parent: {} producers and {} consumers passed {} values, each exactly once",
+        CHILD_PRODUCERS + 1,
+        CONSUMERS,
+        total
+    );
+    Ok(())
+}
@@ -0,0 +1,58 @@
+//! Two processes finding the same region by a fixed name instead of passing
+//! a generated OS ID on the command line.
+//!
+//! Run with `cargo run --example named_region`. Without arguments it creates
+//! the region and starts a second copy of itself with `attach`, which opens
+//! the region by name and bumps the number. `attach` can also be run by hand
+//! from another terminal while the first one waits.
+
+use sharedmem_multiarch::{OpenMode, SharedRegion};
+use std::process::Command;
+use std::time::Duration;
+
+const REGION_NAME: &str = "/sharedmem-multiarch-named-example";
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    match std::env::args().nth(1).as_deref() {
+        None => create(),
+        Some("attach") => attach(),
+        Some(other) => Err(format!("Unknown argument {:?}, expected attach", other).into()),
+    }
+}
+
+fn create() -> Result<(), Box<dyn std::error::Error>> {
+    let shared_data = SharedRegion::builder()
+        .os_id(REGION_NAME)
+        .mode(OpenMode::Create)
+        .build()?;
+    println!("Creator: Region {} created", shared_data.os_id());
+
+    let initial = shared_data.get_number();
+    let mut child = Command::new(std::env::current_exe()?)
+        .arg("attach")
+        .spawn()?;
+
+    let number = shared_data.wait_until(|n| n != initial, Duration::from_secs(10))?;
+    println!("Creator: Saw the number change {} -> {}", initial, number);
+
+    if !child.wait()?.success() {
+        return Err("Attaching process failed".into());
+    }
+    Ok(())
+}
+
+fn attach() -> Result<(), Box<dyn std::error::Error>> {
+    let shared_data = SharedRegion::builder()
+        .os_id(REGION_NAME)
+        .mode(OpenMode::Open)
+        .build()?;
+    println!("Attacher: Opened region {} by name", shared_data.os_id());
+
+    {
+        let mut guard = shared_data.lock_timeout_guard(Duration::from_secs(5))?;
+        guard.update(|n| n + 1);
+    }
+    shared_data.notify_change();
+    println!("Attacher: Incremented the number");
+    Ok(())
+}
@@ -0,0 +1,59 @@
+//! Shares a record of named counters between the 64-bit parent and the
+//! 32-bit child. The child counts requests while the parent counts errors
+//! at the same time, and neither field picks up the other's updates.
+//!
+//! Run with `cargo run --example record [count]`.
+
+use shared_memory::ShmemConf;
+use sharedmem_multiarch::ChildExecutable;
+use sharedmem_multiarch::record::{RecordSnapshot, SharedRecord};
+use std::process::Command;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let count: u64 = match std::env::args().nth(1) {
+        Some(arg) => arg.parse()?,
+        None => 10_000,
+    };
+
+    let shmem = ShmemConf::new()
+        .size(std::mem::size_of::<SharedRecord>())
+        .create()?;
+    let record_ptr = shmem.as_ptr() as *mut SharedRecord;
+    unsafe {
+        std::ptr::write(record_ptr, SharedRecord::new());
+    }
+    let record = unsafe { &*record_ptr };
+    println!("Parent: Record created with OS ID: {}", shmem.get_os_id());
+
+    let child_binary = include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded"));
+    let child_exe = ChildExecutable::extract(child_binary)?;
+    let mut child = Command::new(&child_exe)
+        .arg("--record")
+        .arg(shmem.get_os_id())
+        .arg(count.to_string())
+        .spawn()?;
+
+    // Count errors for as long as the child counts requests
+    let mut errors = 0;
+    while child.try_wait()?.is_none() {
+        record.add_errors(1);
+        errors += 1;
+        std::thread::yield_now();
+    }
+    if !child.wait()?.success() {
+        return Err("Child process failed".into());
+    }
+
+    let expected = RecordSnapshot {
+        requests: count,
+        errors,
+        last_value: count as i64,
+    };
+    let snapshot = record.snapshot();
+    println!("Parent: Record holds {:?}", snapshot);
+    if snapshot != expected {
+        return Err(format!("Parent: Expected {:?}", expected).into());
+    }
+    println!("Parent: Every field holds exactly what its own writer put there");
+    Ok(())
+}
@@ -0,0 +1,66 @@
+//! Streams values from the 32-bit child to the 64-bit parent through a
+//! shared-memory ring buffer. The child produces, the parent consumes.
+//!
+//! Run with `cargo run --example ring [count]`.
+
+use shared_memory::ShmemConf;
+use sharedmem_multiarch::ChildExecutable;
+use sharedmem_multiarch::layout::RING_CAPACITY;
+use sharedmem_multiarch::ring::SharedRing;
+use std::process::Command;
+use std::time::Duration;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let count: i64 = match std::env::args().nth(1) {
+        Some(arg) => arg.parse()?,
+        None => 32,
+    };
+
+    let shmem = ShmemConf::new()
+        .size(std::mem::size_of::<SharedRing<RING_CAPACITY>>())
+        .create()?;
+    let ring_ptr = shmem.as_ptr() as *mut SharedRing<RING_CAPACITY>;
+    unsafe {
+        std::ptr::write(ring_ptr, SharedRing::new());
+    }
+    let ring = unsafe { &*ring_ptr };
+
+    println!(
+        "Parent: Ring of {} slots created with OS ID: {}",
+        RING_CAPACITY,
+        shmem.get_os_id()
+    );
+
+    let child_binary = include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded"));
+    let child_exe = ChildExecutable::extract(child_binary)?;
+
+    let mut child = Command::new(&child_exe)
+        .arg("--ring")
+        .arg(shmem.get_os_id())
+        .arg(count.to_string())
+        .spawn()?;
+
+    // The ring is much smaller than `count`, so this exercises both the
+    // empty (we wait) and full (the child waits) paths.
+    let mut sum = 0;
+    for i in 1..=count {
+        let value = ring
+            .pop_blocking(Duration::from_secs(10))
+            .map_err(|e| format!("Parent: Failed to pop value {}: {:?}", i, e))?;
+        if value != i * i {
+            return Err(format!("Parent: Expected {} got {}", i * i, value).into());
+        }
+        sum += value;
+    }
+
+    let exit_status = child.wait()?;
+    if !exit_status.success() {
+        return Err("Child process failed".into());
+    }
+
+    println!(
+        "Parent: Received {} values in order, sum of squares = {}",
+        count, sum
+    );
+    Ok(())
+}
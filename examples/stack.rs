@@ -0,0 +1,65 @@
+//! Hands work from the 64-bit parent to the 32-bit child through a shared
+//! LIFO stack. The parent fills the stack, the child drains it and checks
+//! the values come off newest first.
+//!
+//! Run with `cargo run --example stack [count]`, with `count` at most the
+//! stack's capacity.
+
+use shared_memory::ShmemConf;
+use sharedmem_multiarch::ChildExecutable;
+use sharedmem_multiarch::layout::STACK_CAPACITY;
+use sharedmem_multiarch::stack::{Full, SharedStack};
+use std::process::Command;
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let count: i64 = match std::env::args().nth(1) {
+        Some(arg) => arg.parse()?,
+        None => STACK_CAPACITY as i64,
+    };
+    if !(1..=STACK_CAPACITY as i64).contains(&count) {
+        return Err(format!("count must be between 1 and {}", STACK_CAPACITY).into());
+    }
+
+    let shmem = ShmemConf::new()
+        .size(std::mem::size_of::<SharedStack<STACK_CAPACITY>>())
+        .create()?;
+    let stack_ptr = shmem.as_ptr() as *mut SharedStack<STACK_CAPACITY>;
+    unsafe {
+        std::ptr::write(stack_ptr, SharedStack::new());
+    }
+    let stack = unsafe { &*stack_ptr };
+
+    if stack.pop().is_some() {
+        return Err("Parent: A new stack should be empty".into());
+    }
+    for i in 1..=count {
+        stack
+            .push(i)
+            .map_err(|Full(value)| format!("Parent: Stack full at {}", value))?;
+    }
+    if count == STACK_CAPACITY as i64 && stack.push(0) != Err(Full(0)) {
+        return Err("Parent: A full stack should refuse a push".into());
+    }
+    println!(
+        "Parent: Pushed {} values onto stack {}",
+        count,
+        shmem.get_os_id()
+    );
+
+    let child_binary = include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded"));
+    let child_exe = ChildExecutable::extract(child_binary)?;
+    let exit_status = Command::new(&child_exe)
+        .arg("--stack")
+        .arg(shmem.get_os_id())
+        .arg(count.to_string())
+        .status()?;
+    if !exit_status.success() {
+        return Err("Child process failed".into());
+    }
+
+    if !stack.is_empty() {
+        return Err("Parent: The child left values on the stack".into());
+    }
+    println!("Parent: The child took every value, newest first");
+    Ok(())
+}
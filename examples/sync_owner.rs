@@ -0,0 +1,46 @@
+//! One half of a pair of unrelated programs sharing a region by name; the
+//! other half is `sync_peer`. Neither starts the other, and neither involves
+//! the 32-bit child.
+//!
+//! Run `cargo run --example sync_owner [rounds]` in one terminal, then
+//! `cargo run --example sync_peer [rounds]` in another with the same number
+//! of rounds. The two take turns incrementing the number: the owner on even
+//! values, the peer on odd ones.
+
+use sharedmem_multiarch::{SharedData, create_region};
+use std::time::Duration;
+
+const REGION_NAME: &str = "/sharedmem-multiarch-sync-example";
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let rounds: i64 = match std::env::args().nth(1) {
+        Some(arg) => arg.parse()?,
+        None => 5,
+    };
+
+    let shared_data = create_region(REGION_NAME, std::mem::size_of::<SharedData>())?;
+    shared_data.set_number(0);
+    println!(
+        "Owner: Created region {}, waiting for sync_peer",
+        shared_data.os_id()
+    );
+
+    for round in 0..rounds {
+        shared_data.wait_until(|n| n == 2 * round, TIMEOUT)?;
+        shared_data.lock_timeout_guard(TIMEOUT)?.update(|n| n + 1);
+        shared_data.notify_change();
+        println!(
+            "Owner: Round {}, passed {} to the peer",
+            round + 1,
+            2 * round + 1
+        );
+    }
+
+    let number = shared_data.wait_until(|n| n == 2 * rounds, TIMEOUT)?;
+    println!(
+        "Owner: Done after {} rounds, the number is {}",
+        rounds, number
+    );
+    Ok(())
+}
@@ -0,0 +1,26 @@
+//! The other half of `sync_owner`: attaches to its region by name and takes
+//! the odd turns. Start `sync_owner` first; see there for how to run both.
+
+use sharedmem_multiarch::open_region;
+use std::time::Duration;
+
+const REGION_NAME: &str = "/sharedmem-multiarch-sync-example";
+const TIMEOUT: Duration = Duration::from_secs(60);
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let rounds: i64 = match std::env::args().nth(1) {
+        Some(arg) => arg.parse()?,
+        None => 5,
+    };
+
+    let shared_data = open_region(REGION_NAME)?;
+    println!("Peer: Opened region {}", shared_data.os_id());
+
+    for round in 0..rounds {
+        shared_data.wait_until(|n| n == 2 * round + 1, TIMEOUT)?;
+        shared_data.lock_timeout_guard(TIMEOUT)?.update(|n| n + 1);
+        shared_data.notify_change();
+        println!("Peer: Round {}, passed {} back", round + 1, 2 * round + 2);
+    }
+    Ok(())
+}
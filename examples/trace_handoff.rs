@@ -0,0 +1,138 @@
+//! Captures the lock spans of the parent and one child and checks that the
+//! lock was handed parent -> child -> parent.
+//!
+//! Run with `cargo run --example trace_handoff --features tracing`.
+//!
+//! Timestamps from two processes are not a reliable order, so this relies
+//! on the `acquisition` field instead: it comes from the shared acquisition
+//! counter, which every process bumps while holding the lock.
+
+use sharedmem_multiarch::{ChildExecutable, OwnedSharedData};
+use std::io::Write;
+use std::process::{Command, Stdio};
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Collects everything the subscriber writes.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// One "lock acquired" or "lock released" line.
+#[derive(Debug, PartialEq, Eq)]
+struct LockEvent {
+    acquisition: u64,
+    pid: u32,
+    acquired: bool,
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    let captured = Captured::default();
+    let writer = captured.clone();
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_writer(move || writer.clone())
+        .init();
+
+    let shared_data = OwnedSharedData::create()?;
+
+    let child_binary = include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded"));
+    let child_exe = ChildExecutable::extract(child_binary)?;
+
+    // Hold the lock while the child starts so it has to wait for us.
+    let guard = shared_data.lock_timeout_guard(Duration::from_secs(5))?;
+    let child = Command::new(&child_exe)
+        .arg(shared_data.os_id())
+        .arg("0")
+        .arg("1")
+        .stdout(Stdio::piped())
+        .spawn()?;
+    let child_pid = child.id();
+    std::thread::sleep(Duration::from_millis(200));
+    drop(guard);
+
+    let output = child.wait_with_output()?;
+    if !output.status.success() {
+        return Err("Child process failed".into());
+    }
+    drop(shared_data.lock_timeout_guard(Duration::from_secs(5))?);
+
+    let parent_log = String::from_utf8(captured.0.lock().unwrap().clone())?;
+    let child_log = String::from_utf8(output.stdout)?;
+    let mut events: Vec<LockEvent> = parent_log
+        .lines()
+        .chain(child_log.lines())
+        .filter_map(parse_event)
+        .collect();
+    events.sort_by_key(|event| (event.acquisition, !event.acquired));
+
+    let parent_pid = std::process::id();
+    let expected = [
+        (0, parent_pid, true),
+        (0, parent_pid, false),
+        (1, child_pid, true),
+        (1, child_pid, false),
+        (2, parent_pid, true),
+        (2, parent_pid, false),
+    ]
+    .map(|(acquisition, pid, acquired)| LockEvent {
+        acquisition,
+        pid,
+        acquired,
+    });
+    if events != expected {
+        return Err(format!("Unexpected lock order: {:#?}", events).into());
+    }
+
+    for event in &events {
+        println!(
+            "#{} {} by {}",
+            event.acquisition,
+            if event.acquired {
+                "acquired"
+            } else {
+                "released"
+            },
+            if event.pid == parent_pid {
+                "parent"
+            } else {
+                "child"
+            }
+        );
+    }
+    println!("Lock was handed parent -> child -> parent as expected");
+    Ok(())
+}
+
+/// Picks the pid and acquisition out of a line such as
+/// `... lock_timeout{pid=7}: ...: lock acquired acquisition=0 waited_us=2` or
+/// `... unlock{pid=7 observed=1 acquisition=0}: ...: lock released`.
+fn parse_event(line: &str) -> Option<LockEvent> {
+    let acquired = line.contains("lock acquired");
+    if !acquired && !line.contains("lock released") {
+        return None;
+    }
+    Some(LockEvent {
+        acquisition: field(line, "acquisition=")?,
+        pid: field(line, "pid=")? as u32,
+        acquired,
+    })
+}
+
+fn field(line: &str, name: &str) -> Option<u64> {
+    let rest = &line[line.find(name)? + name.len()..];
+    let end = rest
+        .find(|c: char| !c.is_ascii_digit())
+        .unwrap_or(rest.len());
+    rest[..end].parse().ok()
+}
@@ -0,0 +1,43 @@
+//! Pinning processes to a CPU, so handoff timings are not blurred by the
+//! scheduler moving either side between cores.
+//!
+//! Only Linux has `sched_setaffinity`; elsewhere pinning does nothing and
+//! reports success, so callers need no platform checks of their own.
+
+use std::io;
+
+/// Restricts the process `pid` to run only on `cpu`. Pass
+/// `std::process::id()` to pin the calling process.
+#[cfg(target_os = "linux")]
+pub fn pin_to_cpu(pid: u32, cpu: usize) -> io::Result<()> {
+    if cpu >= libc::CPU_SETSIZE as usize {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidInput,
+            format!(
+                "CPU {} is beyond the {} a mask holds",
+                cpu,
+                libc::CPU_SETSIZE
+            ),
+        ));
+    }
+    // SAFETY: the set is plain data, zeroed before use, and `cpu` is in
+    // range for it.
+    unsafe {
+        let mut set: libc::cpu_set_t = std::mem::zeroed();
+        libc::CPU_SET(cpu, &mut set);
+        if libc::sched_setaffinity(
+            pid as libc::pid_t,
+            std::mem::size_of::<libc::cpu_set_t>(),
+            &set,
+        ) != 0
+        {
+            return Err(io::Error::last_os_error());
+        }
+    }
+    Ok(())
+}
+
+#[cfg(not(target_os = "linux"))]
+pub fn pin_to_cpu(_pid: u32, _cpu: usize) -> io::Result<()> {
+    Ok(())
+}
@@ -0,0 +1,101 @@
+//! Finding segments left in `/dev/shm` by processes that crashed before
+//! they could unlink them, for cleanup tools.
+//!
+//! Segments are told apart by their first bytes: only one that starts with
+//! our `layout::MAGIC` is reported as ours, and only such a segment can be
+//! unlinked through this module, so entries of unrelated programs that
+//! happen to match a prefix are left alone.
+
+use crate::layout::MAGIC;
+use std::io::{self, Read};
+use std::path::{Path, PathBuf};
+
+/// Where Linux keeps POSIX shared memory segments.
+pub const SHM_DIR: &str = "/dev/shm";
+
+/// Prefix of the OS IDs `shared_memory` generates when none is given, which
+/// is how the demo and `OwnedSharedData::create` name their segments.
+pub const DEFAULT_PREFIX: &str = "shmem_";
+
+/// A `/dev/shm` entry as `list_segments` found it.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SegmentInfo {
+    /// The OS ID, with the leading `/` that `open_region` expects.
+    pub name: String,
+    /// Size in bytes.
+    pub size: u64,
+    /// Whether it starts with a header carrying our magic.
+    pub has_magic: bool,
+    /// The header's `LAYOUT_VERSION`, if it has our magic.
+    pub layout_version: Option<u16>,
+}
+
+impl SegmentInfo {
+    /// Unlinks the segment, as its creator would have on drop. Whoever
+    /// still has it mapped keeps it until they unmap it.
+    ///
+    /// Refuses with `InvalidInput` unless the segment still starts with our
+    /// magic, which is checked again here in case the name was reused
+    /// since it was listed. A segment in use looks the same as an orphaned
+    /// one, so make sure its creator is gone first, e.g. from its
+    /// `owner_pid` or heartbeat.
+    pub fn unlink(&self) -> io::Result<()> {
+        let path = entry_path(&self.name);
+        if read_header(&path)?.is_none() {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                format!("{} is not a sharedmem-multiarch segment", self.name),
+            ));
+        }
+        std::fs::remove_file(path)
+    }
+}
+
+/// The segments in `/dev/shm` whose names start with `prefix` (with or
+/// without the leading `/`), sorted by name. Entries that cannot be read
+/// are listed without magic; a missing `/dev/shm` lists nothing.
+pub fn list_segments(prefix: &str) -> Vec<SegmentInfo> {
+    let prefix = prefix.trim_start_matches('/');
+    let Ok(entries) = std::fs::read_dir(SHM_DIR) else {
+        return Vec::new();
+    };
+    let mut segments: Vec<SegmentInfo> = entries
+        .filter_map(|entry| {
+            let entry = entry.ok()?;
+            let file_name = entry.file_name().into_string().ok()?;
+            if !file_name.starts_with(prefix) {
+                return None;
+            }
+            let metadata = entry.metadata().ok().filter(|m| m.is_file())?;
+            let layout_version = read_header(&entry.path()).ok().flatten();
+            Some(SegmentInfo {
+                name: format!("/{}", file_name),
+                size: metadata.len(),
+                has_magic: layout_version.is_some(),
+                layout_version,
+            })
+        })
+        .collect();
+    segments.sort_by(|a, b| a.name.cmp(&b.name));
+    segments
+}
+
+fn entry_path(name: &str) -> PathBuf {
+    Path::new(SHM_DIR).join(name.trim_start_matches('/'))
+}
+
+/// The layout version in the header at the start of `path`, or `None` if
+/// it is too short or does not start with our magic.
+fn read_header(path: &Path) -> io::Result<Option<u16>> {
+    let mut bytes = [0u8; 6];
+    match std::fs::File::open(path)?.read_exact(&mut bytes) {
+        Ok(()) => {}
+        Err(e) if e.kind() == io::ErrorKind::UnexpectedEof => return Ok(None),
+        Err(e) => return Err(e),
+    }
+    // Native order, as the creator wrote it; a segment from a peer with
+    // the other byte order is not one we could have left behind.
+    let magic = u32::from_ne_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]);
+    let version = u16::from_ne_bytes([bytes[4], bytes[5]]);
+    Ok((magic == MAGIC).then_some(version))
+}
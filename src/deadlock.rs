@@ -0,0 +1,59 @@
+//! Diagnosing processes and threads that wait on each other's locks.
+//!
+//! Two holders each timing out on the lock the other holds look, from
+//! either side, like nothing more than a slow peer. With
+//! `SharedData::set_deadlock_tracking` on, every region records the thread
+//! holding its lock and the threads sleeping on it, and `detect_deadlock`
+//! turns those records into a wait-for graph and looks for a cycle. It only
+//! reports; breaking the cycle, e.g. by letting one side time out and back
+//! off, is up to the caller.
+
+use crate::shared::SharedData;
+use std::collections::{BTreeMap, BTreeSet};
+use std::sync::atomic::Ordering;
+
+/// Looks for threads waiting on each other in a ring through the locks of
+/// `regions`, returning their IDs in waiting order: each waits for a lock
+/// the next one holds, and the last for one the first holds. A thread
+/// waiting for a lock it already holds comes back alone.
+///
+/// The records are read one field at a time while the locks keep changing
+/// hands, so a cycle found is only certain if it is still there when looked
+/// at again; a real deadlock does not go away by itself.
+pub fn detect_deadlock(regions: &[&SharedData]) -> Option<Vec<i32>> {
+    // A thread sleeps on one lock at a time, so it has at most one edge
+    let mut waits_for = BTreeMap::new();
+    for data in regions {
+        let owner = data.owner_tid.load(Ordering::Relaxed);
+        if owner == 0 {
+            continue;
+        }
+        for slot in &data.waiter_tids {
+            let waiter = slot.load(Ordering::Relaxed);
+            if waiter != 0 {
+                waits_for.insert(waiter, owner);
+            }
+        }
+    }
+
+    let mut cleared = BTreeSet::new();
+    for &start in waits_for.keys() {
+        let mut path = Vec::new();
+        let mut tid = start;
+        loop {
+            if let Some(pos) = path.iter().position(|&seen| seen == tid) {
+                return Some(path.split_off(pos));
+            }
+            if cleared.contains(&tid) {
+                break;
+            }
+            path.push(tid);
+            match waits_for.get(&tid) {
+                Some(&owner) => tid = owner,
+                None => break,
+            }
+        }
+        cleared.extend(path);
+    }
+    None
+}
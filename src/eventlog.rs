@@ -0,0 +1,162 @@
+//! A log of events in shared memory that every attached process appends
+//! to, for piecing together after the fact what happened in which order.
+//!
+//! Like `ring`, this file is included by the child with `#[path]`, so it
+//! only depends on `std`.
+
+#![allow(dead_code)]
+
+use std::sync::atomic::{AtomicI32, AtomicU32, AtomicU64, Ordering, fence};
+use std::time::{SystemTime, UNIX_EPOCH};
+
+/// Codes for the events the lock and `number` go through. Any other code
+/// may be recorded too; these are only the ones with a name.
+pub const LOCK_ACQUIRED: u32 = 1;
+pub const LOCK_RELEASED: u32 = 2;
+pub const NUMBER_SET: u32 = 3;
+
+/// The name of a known event code, for dumps.
+pub fn event_name(code: u32) -> Option<&'static str> {
+    match code {
+        LOCK_ACQUIRED => Some("lock-acquired"),
+        LOCK_RELEASED => Some("lock-released"),
+        NUMBER_SET => Some("number-set"),
+        _ => None,
+    }
+}
+
+/// The last `N` events recorded by any process, oldest overwritten first.
+///
+/// Recording never waits: a writer claims the next position with one
+/// `fetch_add` on `next` and owns the slot it lands on, `N` positions
+/// apart wrapping to the same slot. Each slot is its own little seqlock:
+/// its `position` is zeroed while the writer fills it in and set to the
+/// claimed position plus one afterwards, so a dump skips slots caught
+/// mid-write instead of returning a mix of two events. Two writers can
+/// only share a slot if one is a whole lap of `N` events behind, which a
+/// log sized for its traffic never sees.
+///
+/// Positions are `u64`, which is 8-aligned in the 32-bit child as well,
+/// and never wrap in practice.
+#[repr(C)]
+pub struct SharedEventLog<const N: usize> {
+    next: AtomicU64,
+    slots: [Slot; N],
+}
+
+/// 24 bytes on both sides.
+#[repr(C)]
+struct Slot {
+    /// The claimed position plus one once written, 0 while being written.
+    position: AtomicU64,
+    timestamp_nanos: AtomicU64,
+    pid: AtomicI32,
+    code: AtomicU32,
+}
+
+#[cfg(target_os = "linux")]
+const _: () = assert!(std::mem::size_of::<SharedEventLog<4>>() == 8 + 4 * 24);
+
+/// One entry of `SharedEventLog::dump`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Event {
+    /// Where it landed in the log, counting from 0 across all processes.
+    pub position: u64,
+    /// Wall-clock time it was recorded, in nanoseconds since the Unix epoch.
+    pub timestamp_nanos: u64,
+    pub pid: i32,
+    pub code: u32,
+}
+
+impl<const N: usize> SharedEventLog<N> {
+    pub fn new() -> Self {
+        const { assert!(N > 0) };
+        Self {
+            next: AtomicU64::new(0),
+            slots: std::array::from_fn(|_| Slot {
+                position: AtomicU64::new(0),
+                timestamp_nanos: AtomicU64::new(0),
+                pid: AtomicI32::new(0),
+                code: AtomicU32::new(0),
+            }),
+        }
+    }
+
+    /// Appends an event with `code` for this process, stamped with the
+    /// current time, returning its position.
+    ///
+    /// Positions give the order events were recorded in across processes.
+    /// The time is taken after the position is claimed, so of two events
+    /// recorded at once the later position may carry the earlier time;
+    /// events recorded under a lock come out in the same order by both.
+    pub fn record(&self, code: u32) -> u64 {
+        let position = self.next.fetch_add(1, Ordering::Relaxed);
+        let slot = &self.slots[(position % N as u64) as usize];
+
+        slot.position.store(0, Ordering::Relaxed);
+        // Pairs with the fence in `dump`: a reader that sees any of the
+        // stores below also sees the slot marked as being written.
+        fence(Ordering::Release);
+        slot.timestamp_nanos.store(unix_nanos(), Ordering::Relaxed);
+        slot.pid.store(std::process::id() as i32, Ordering::Relaxed);
+        slot.code.store(code, Ordering::Relaxed);
+        slot.position.store(position + 1, Ordering::Release);
+        position
+    }
+
+    /// Number of events recorded so far, including those since overwritten.
+    pub fn recorded(&self) -> u64 {
+        self.next.load(Ordering::Relaxed)
+    }
+
+    /// The events still in the log, oldest first. Slots being written
+    /// while this runs are left out.
+    pub fn dump(&self) -> Vec<Event> {
+        let mut events: Vec<Event> = self
+            .slots
+            .iter()
+            .filter_map(|slot| {
+                let before = slot.position.load(Ordering::Acquire);
+                let event = Event {
+                    position: before.checked_sub(1)?,
+                    timestamp_nanos: slot.timestamp_nanos.load(Ordering::Relaxed),
+                    pid: slot.pid.load(Ordering::Relaxed),
+                    code: slot.code.load(Ordering::Relaxed),
+                };
+                fence(Ordering::Acquire);
+                (slot.position.load(Ordering::Relaxed) == before).then_some(event)
+            })
+            .collect();
+        events.sort_by_key(|event| event.position);
+        events
+    }
+}
+
+impl<const N: usize> Default for SharedEventLog<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+impl std::fmt::Display for Event {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "#{} {}.{:09} pid {} ",
+            self.position,
+            self.timestamp_nanos / 1_000_000_000,
+            self.timestamp_nanos % 1_000_000_000,
+            self.pid
+        )?;
+        match event_name(self.code) {
+            Some(name) => write!(f, "{}", name),
+            None => write!(f, "event {}", self.code),
+        }
+    }
+}
+
+fn unix_nanos() -> u64 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64)
+}
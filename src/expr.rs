@@ -0,0 +1,206 @@
+//! Integer arithmetic on the shared number, such as `(n + 25) * 2`, so the
+//! demo's operations can be chosen on the command line.
+//!
+//! The child crate includes this file with `#[path]` and receives the
+//! expression as text, so both sides parse it the same way. Supported are
+//! integer literals, `n`, `+ - * / %`, unary minus and parentheses, with
+//! the usual precedence.
+
+#![allow(dead_code)]
+
+/// A parsed expression in `n`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Expr {
+    source: String,
+    root: Node,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Node {
+    Number(i64),
+    Var,
+    Neg(Box<Node>),
+    Binary(Box<Node>, Op, Box<Node>),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Op {
+    Add,
+    Sub,
+    Mul,
+    Div,
+    Rem,
+}
+
+/// Why `Expr::parse` rejected its input, with the byte offset it got to.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct ExprError {
+    pub position: usize,
+    pub message: &'static str,
+}
+
+impl std::fmt::Display for ExprError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} at offset {}", self.message, self.position)
+    }
+}
+
+impl std::error::Error for ExprError {}
+
+impl Expr {
+    pub fn parse(source: &str) -> Result<Self, ExprError> {
+        let mut parser = Parser {
+            bytes: source.as_bytes(),
+            pos: 0,
+        };
+        let root = parser.sum()?;
+        parser.skip_spaces();
+        if parser.pos != parser.bytes.len() {
+            return Err(parser.error("unexpected character"));
+        }
+        Ok(Expr {
+            source: source.to_string(),
+            root,
+        })
+    }
+
+    /// Evaluates the expression for `n`, or `None` on overflow or division
+    /// by zero.
+    pub fn eval(&self, n: i64) -> Option<i64> {
+        eval(&self.root, n)
+    }
+
+    /// The text the expression was parsed from, e.g. to hand it to a child.
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+}
+
+impl std::fmt::Display for Expr {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(&self.source)
+    }
+}
+
+impl std::str::FromStr for Expr {
+    type Err = ExprError;
+
+    fn from_str(source: &str) -> Result<Self, ExprError> {
+        Expr::parse(source)
+    }
+}
+
+fn eval(node: &Node, n: i64) -> Option<i64> {
+    match node {
+        Node::Number(value) => Some(*value),
+        Node::Var => Some(n),
+        Node::Neg(inner) => eval(inner, n)?.checked_neg(),
+        Node::Binary(lhs, op, rhs) => {
+            let (lhs, rhs) = (eval(lhs, n)?, eval(rhs, n)?);
+            match op {
+                Op::Add => lhs.checked_add(rhs),
+                Op::Sub => lhs.checked_sub(rhs),
+                Op::Mul => lhs.checked_mul(rhs),
+                Op::Div => lhs.checked_div(rhs),
+                Op::Rem => lhs.checked_rem(rhs),
+            }
+        }
+    }
+}
+
+/// Recursive descent, one method per precedence level.
+struct Parser<'a> {
+    bytes: &'a [u8],
+    pos: usize,
+}
+
+impl Parser<'_> {
+    fn sum(&mut self) -> Result<Node, ExprError> {
+        let mut node = self.product()?;
+        loop {
+            let op = match self.peek() {
+                Some(b'+') => Op::Add,
+                Some(b'-') => Op::Sub,
+                _ => return Ok(node),
+            };
+            self.pos += 1;
+            node = Node::Binary(Box::new(node), op, Box::new(self.product()?));
+        }
+    }
+
+    fn product(&mut self) -> Result<Node, ExprError> {
+        let mut node = self.unary()?;
+        loop {
+            let op = match self.peek() {
+                Some(b'*') => Op::Mul,
+                Some(b'/') => Op::Div,
+                Some(b'%') => Op::Rem,
+                _ => return Ok(node),
+            };
+            self.pos += 1;
+            node = Node::Binary(Box::new(node), op, Box::new(self.unary()?));
+        }
+    }
+
+    fn unary(&mut self) -> Result<Node, ExprError> {
+        if self.peek() == Some(b'-') {
+            self.pos += 1;
+            return Ok(Node::Neg(Box::new(self.unary()?)));
+        }
+        self.atom()
+    }
+
+    fn atom(&mut self) -> Result<Node, ExprError> {
+        match self.peek() {
+            Some(b'n') => {
+                self.pos += 1;
+                Ok(Node::Var)
+            }
+            Some(b'(') => {
+                self.pos += 1;
+                let node = self.sum()?;
+                if self.peek() != Some(b')') {
+                    return Err(self.error("expected ')'"));
+                }
+                self.pos += 1;
+                Ok(node)
+            }
+            Some(b'0'..=b'9') => {
+                let start = self.pos;
+                while self.bytes.get(self.pos).is_some_and(u8::is_ascii_digit) {
+                    self.pos += 1;
+                }
+                let digits = std::str::from_utf8(&self.bytes[start..self.pos]).unwrap();
+                digits.parse().map(Node::Number).map_err(|_| ExprError {
+                    position: start,
+                    message: "number too large",
+                })
+            }
+            Some(_) => Err(self.error("expected a number, 'n' or '('")),
+            None => Err(self.error("unexpected end of expression")),
+        }
+    }
+
+    /// The next non-space byte, without consuming it.
+    fn peek(&mut self) -> Option<u8> {
+        self.skip_spaces();
+        self.bytes.get(self.pos).copied()
+    }
+
+    fn skip_spaces(&mut self) {
+        while self
+            .bytes
+            .get(self.pos)
+            .is_some_and(u8::is_ascii_whitespace)
+        {
+            self.pos += 1;
+        }
+    }
+
+    fn error(&self, message: &'static str) -> ExprError {
+        ExprError {
+            position: self.pos,
+            message,
+        }
+    }
+}
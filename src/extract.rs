@@ -0,0 +1,316 @@
+//! Putting the embedded child binary somewhere it can be executed.
+//!
+//! The obvious place, the temp directory, is often mounted `noexec`. On Linux
+//! the binary lives in an anonymous `memfd` instead, which never touches
+//! disk and is sealed against changes once written; elsewhere, or if that
+//! fails, a list of directories is tried in turn and each copy is checked
+//! for exec permission before it is used.
+
+use std::ffi::OsStr;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+
+/// A runnable copy of the child binary, usable wherever `Command::new`
+/// takes a program. Dropping it removes the copy.
+#[derive(Debug)]
+pub struct ChildExecutable {
+    path: PathBuf,
+    _backing: Backing,
+}
+
+/// Only held for its `Drop`.
+#[allow(dead_code)]
+#[derive(Debug)]
+enum Backing {
+    /// Kept open so `/proc/self/fd/<n>` stays valid; spawned children
+    /// inherit it and exec through that path, which is what `fexecve` does
+    /// under the hood.
+    #[cfg(target_os = "linux")]
+    Memfd(std::os::fd::OwnedFd),
+    File(tempfile::TempPath),
+}
+
+impl ChildExecutable {
+    /// Extracts `binary` into a `memfd` where available, falling back to
+    /// the first of `candidate_dirs()` that allows executing it.
+    pub fn extract(binary: &[u8]) -> io::Result<Self> {
+        #[cfg(target_os = "linux")]
+        match Self::extract_memfd(binary) {
+            Ok(executable) => return Ok(executable),
+            Err(_e) => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(error = %_e, "memfd extraction failed, using a file");
+            }
+        }
+        Self::extract_to(binary, &candidate_dirs())
+    }
+
+    /// Extracts `binary` into the first of `dirs` that allows executing it.
+    ///
+    /// If none does, the error lists every directory tried and why it was
+    /// rejected.
+    pub fn extract_to(binary: &[u8], dirs: &[PathBuf]) -> io::Result<Self> {
+        let mut failures = Vec::new();
+        for dir in dirs {
+            match extract_file(binary, dir) {
+                Ok(path) => {
+                    return Ok(ChildExecutable {
+                        path: path.to_path_buf(),
+                        _backing: Backing::File(path),
+                    });
+                }
+                Err(e) => failures.push(format!("{}: {}", dir.display(), e)),
+            }
+        }
+        Err(io::Error::new(
+            io::ErrorKind::PermissionDenied,
+            format!(
+                "no directory allows executing the child binary, they may be \
+                 mounted noexec; set TMPDIR to one that is not (tried {})",
+                failures.join("; ")
+            ),
+        ))
+    }
+
+    #[cfg(target_os = "linux")]
+    fn extract_memfd(binary: &[u8]) -> io::Result<Self> {
+        use std::os::fd::{AsRawFd, FromRawFd, OwnedFd};
+
+        // Deliberately without MFD_CLOEXEC: children must inherit the fd to
+        // exec through /proc/self/fd.
+        let fd = unsafe { libc::memfd_create(c"child_process".as_ptr(), libc::MFD_ALLOW_SEALING) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let writable = unsafe { OwnedFd::from_raw_fd(fd) };
+        std::fs::File::from(writable.try_clone()?).write_all(binary)?;
+
+        // Freeze the contents, so nobody who gets hold of the fd can swap
+        // the binary between here and exec. Without sealing support this
+        // fails and `extract` falls back to a file.
+        let seals =
+            libc::F_SEAL_WRITE | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_SEAL;
+        if unsafe { libc::fcntl(writable.as_raw_fd(), libc::F_ADD_SEALS, seals) } < 0 {
+            return Err(io::Error::last_os_error());
+        }
+
+        // Exec refuses files that are open for writing, so swap the fd for
+        // a read-only one before handing it out.
+        let writable_path = format!("/proc/self/fd/{}", writable.as_raw_fd());
+        let readonly = OwnedFd::from(std::fs::File::open(&writable_path)?);
+        unsafe {
+            if libc::fcntl(readonly.as_raw_fd(), libc::F_SETFD, 0) < 0 {
+                return Err(io::Error::last_os_error());
+            }
+        }
+        drop(writable);
+
+        let path = PathBuf::from(format!("/proc/self/fd/{}", readonly.as_raw_fd()));
+        check_executable(&path)?;
+        Ok(ChildExecutable {
+            path,
+            _backing: Backing::Memfd(readonly),
+        })
+    }
+
+    /// Where the binary can be executed from. For a `memfd` this path is
+    /// only meaningful to this process and the children it spawns.
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+}
+
+/// Decompresses a child embedded by the `compress-child` feature, checking
+/// the result is `expected_len` bytes with `expected_checksum` so a corrupt
+/// copy is never written out and run.
+#[cfg(feature = "compress-child")]
+pub fn decompress_child(
+    compressed: &[u8],
+    expected_len: usize,
+    expected_checksum: u64,
+) -> io::Result<Vec<u8>> {
+    let binary = zstd::decode_all(compressed)?;
+    if binary.len() != expected_len || checksum(&binary) != expected_checksum {
+        return Err(io::Error::new(
+            io::ErrorKind::InvalidData,
+            format!(
+                "decompressed child is {} bytes with checksum {:016x}, expected {} bytes with {:016x}",
+                binary.len(),
+                checksum(&binary),
+                expected_len,
+                expected_checksum
+            ),
+        ));
+    }
+    Ok(binary)
+}
+
+/// 64-bit FNV-1a, which build.rs also computes over the child it embeds.
+#[cfg(feature = "compress-child")]
+pub fn checksum(bytes: &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf2_9ce4_8422_2325, |hash, &byte| {
+        (hash ^ byte as u64).wrapping_mul(0x0100_0000_01b3)
+    })
+}
+
+/// Directories `extract` falls back to, in order: the temp directory
+/// (`$TMPDIR` on Unix), `$XDG_RUNTIME_DIR`, then the directory holding the
+/// current executable.
+pub fn candidate_dirs() -> Vec<PathBuf> {
+    let mut dirs = vec![std::env::temp_dir()];
+    if let Some(runtime) = std::env::var_os("XDG_RUNTIME_DIR").filter(|dir| !dir.is_empty()) {
+        dirs.push(PathBuf::from(runtime));
+    }
+    if let Some(exe_dir) = std::env::current_exe()
+        .ok()
+        .and_then(|exe| exe.parent().map(Path::to_path_buf))
+    {
+        dirs.push(exe_dir);
+    }
+    dirs.dedup();
+    dirs
+}
+
+fn extract_file(binary: &[u8], dir: &Path) -> io::Result<tempfile::TempPath> {
+    // Windows only runs files ending in `.exe`
+    let mut temp_file = tempfile::Builder::new()
+        .prefix("child_process")
+        .suffix(std::env::consts::EXE_SUFFIX)
+        .tempfile_in(dir)?;
+    temp_file.write_all(binary)?;
+    temp_file.flush()?;
+
+    #[cfg(unix)]
+    {
+        use std::os::unix::fs::PermissionsExt;
+        let mut perms = temp_file.as_file().metadata()?.permissions();
+        perms.set_mode(0o755);
+        temp_file.as_file().set_permissions(perms)?;
+    }
+
+    let path = temp_file.into_temp_path();
+    check_executable(&path)?;
+    Ok(path)
+}
+
+/// Fails if `path` cannot be executed, which on a `noexec` mount is the
+/// case even with the exec bits set.
+#[cfg(unix)]
+pub fn check_executable(path: &Path) -> io::Result<()> {
+    use std::os::unix::ffi::OsStrExt;
+
+    let path = std::ffi::CString::new(path.as_os_str().as_bytes())?;
+    if unsafe { libc::access(path.as_ptr(), libc::X_OK) } != 0 {
+        return Err(io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+#[cfg(not(unix))]
+pub fn check_executable(_path: &Path) -> io::Result<()> {
+    Ok(())
+}
+
+/// The machine an executable was built for, as its header tells it.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct BinaryArch {
+    /// `"ELF"`, `"PE"` or `"Mach-O"`.
+    pub format: &'static str,
+    /// 32 or 64.
+    pub bits: u32,
+    /// Named the way `std::env::consts::ARCH` names it, e.g. `"x86"`.
+    pub arch: &'static str,
+}
+
+impl BinaryArch {
+    /// What a child built for the target `triple` should look like, if its
+    /// architecture is one `binary_arch` knows.
+    pub fn for_triple(triple: &str) -> Option<Self> {
+        let (arch, bits) = match triple.split('-').next()? {
+            "i386" | "i586" | "i686" => ("x86", 32),
+            "x86_64" => ("x86_64", 64),
+            "aarch64" => ("aarch64", 64),
+            _ => return None,
+        };
+        let format = if triple.contains("-windows") {
+            "PE"
+        } else if triple.contains("-apple-") {
+            "Mach-O"
+        } else {
+            "ELF"
+        };
+        Some(BinaryArch { format, bits, arch })
+    }
+}
+
+impl std::fmt::Display for BinaryArch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} {}-bit {}", self.format, self.bits, self.arch)
+    }
+}
+
+/// Reads what `binary` was built for from its ELF, PE or Mach-O header, or
+/// `None` if it is none of those or for a machine not listed here.
+pub fn binary_arch(binary: &[u8]) -> Option<BinaryArch> {
+    let u16_at = |at: usize| Some(u16::from_le_bytes(binary.get(at..at + 2)?.try_into().ok()?));
+    let u32_at = |at: usize| Some(u32::from_le_bytes(binary.get(at..at + 4)?.try_into().ok()?));
+
+    if binary.starts_with(b"\x7fELF") {
+        let bits = match binary.get(4)? {
+            1 => 32,
+            2 => 64,
+            _ => return None,
+        };
+        // Little-endian only, as every machine below is
+        let arch = match u16_at(18)? {
+            3 => "x86",
+            62 => "x86_64",
+            183 => "aarch64",
+            _ => return None,
+        };
+        return Some(BinaryArch {
+            format: "ELF",
+            bits,
+            arch,
+        });
+    }
+    if binary.starts_with(b"MZ") {
+        let pe = u32_at(0x3c)? as usize;
+        if binary.get(pe..pe + 4)? != b"PE\0\0" {
+            return None;
+        }
+        let (arch, bits) = match u16_at(pe + 4)? {
+            0x014c => ("x86", 32),
+            0x8664 => ("x86_64", 64),
+            0xaa64 => ("aarch64", 64),
+            _ => return None,
+        };
+        return Some(BinaryArch {
+            format: "PE",
+            bits,
+            arch,
+        });
+    }
+    let bits = match u32_at(0)? {
+        0xfeed_face => 32,
+        0xfeed_facf => 64,
+        _ => return None,
+    };
+    let arch = match u32_at(4)? {
+        7 => "x86",
+        0x0100_0007 => "x86_64",
+        0x0100_000c => "aarch64",
+        _ => return None,
+    };
+    Some(BinaryArch {
+        format: "Mach-O",
+        bits,
+        arch,
+    })
+}
+
+impl AsRef<OsStr> for ChildExecutable {
+    fn as_ref(&self) -> &OsStr {
+        self.path.as_os_str()
+    }
+}
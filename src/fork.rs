@@ -0,0 +1,135 @@
+//! A `SharedData` shared with a forked copy of this process, for Rust-only
+//! setups on one architecture that do not need the 32-bit child.
+//!
+//! The region is an anonymous `MAP_SHARED` mapping made before `fork`, so
+//! the child inherits it at the same address: no name, no descriptor, no
+//! child binary to extract and exec.
+
+use crate::shared::SharedData;
+use std::ops::Deref;
+use std::os::unix::process::ExitStatusExt;
+use std::process::ExitStatus;
+
+/// Exit status of a forked child whose closure panicked, as for a Rust
+/// program that panics in `main`.
+pub const PANIC_EXIT_CODE: i32 = 101;
+
+/// Maps a fresh `SharedData`, forks, and runs `f` on it in the child,
+/// which exits with status 0 when `f` returns, or `PANIC_EXIT_CODE` if it
+/// panics. The parent gets the child's handle straight away and reaches
+/// the same `SharedData` through it.
+///
+/// A child that returns exits with `_exit`: it skips destructors and does
+/// not flush buffered output, both of which belong to the parent.
+///
+/// # Safety
+///
+/// Only the calling thread exists in the child. If other threads held
+/// locks at the moment of the fork (the allocator's, stdout's), those stay
+/// locked there, and the child deadlocks on its first try to take one.
+/// Unless the caller is the only thread in its process, `f` must therefore
+/// stick to async-signal-safe work, such as the `SharedData` operations
+/// and `libc::_exit`, and must not allocate, print or panic; see
+/// `nix::unistd::fork` for the same contract.
+pub unsafe fn fork_with_shared<F: FnOnce(&SharedData)>(f: F) -> std::io::Result<ForkedChild> {
+    let len = std::mem::size_of::<SharedData>();
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ | libc::PROT_WRITE,
+            libc::MAP_SHARED | libc::MAP_ANONYMOUS,
+            -1,
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(std::io::Error::last_os_error());
+    }
+    // SAFETY: a fresh page-aligned mapping of the right size that nobody
+    // else can see yet.
+    unsafe { SharedData::init_raw(ptr.cast()) };
+
+    let pid = unsafe { libc::fork() };
+    if pid < 0 {
+        let error = std::io::Error::last_os_error();
+        unsafe { libc::munmap(ptr, len) };
+        return Err(error);
+    }
+    if pid == 0 {
+        // SAFETY: initialized above, and mapped until this process exits.
+        let data = unsafe { &*(ptr as *const SharedData) };
+        let code = match std::panic::catch_unwind(std::panic::AssertUnwindSafe(|| f(data))) {
+            Ok(()) => 0,
+            Err(_) => PANIC_EXIT_CODE,
+        };
+        unsafe { libc::_exit(code) };
+    }
+    Ok(ForkedChild {
+        ptr,
+        len,
+        pid,
+        status: None,
+    })
+}
+
+/// The parent's side of `fork_with_shared`: the child process and the
+/// region shared with it, which it dereferences to.
+///
+/// Dropping it asks the child to stop through `request_stop`, waits for it
+/// if `wait` has not, then unmaps the region, so a child that ignores the
+/// request keeps the drop waiting.
+pub struct ForkedChild {
+    ptr: *mut libc::c_void,
+    len: usize,
+    pid: libc::pid_t,
+    status: Option<ExitStatus>,
+}
+
+impl ForkedChild {
+    pub fn pid(&self) -> u32 {
+        self.pid as u32
+    }
+
+    /// Waits for the child to exit and returns how it did. Later calls
+    /// return the same status.
+    pub fn wait(&mut self) -> std::io::Result<ExitStatus> {
+        if let Some(status) = self.status {
+            return Ok(status);
+        }
+        let mut raw = 0;
+        loop {
+            if unsafe { libc::waitpid(self.pid, &mut raw, 0) } >= 0 {
+                break;
+            }
+            let error = std::io::Error::last_os_error();
+            if error.kind() != std::io::ErrorKind::Interrupted {
+                return Err(error);
+            }
+        }
+        let status = ExitStatus::from_raw(raw);
+        self.status = Some(status);
+        Ok(status)
+    }
+}
+
+impl Deref for ForkedChild {
+    type Target = SharedData;
+
+    fn deref(&self) -> &SharedData {
+        // SAFETY: initialized before the fork and mapped for as long as
+        // `self`.
+        unsafe { &*(self.ptr as *const SharedData) }
+    }
+}
+
+impl Drop for ForkedChild {
+    fn drop(&mut self) {
+        if self.status.is_none() {
+            self.request_stop();
+            // Nothing to report it to; the child is reaped either way
+            let _ = self.wait();
+        }
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
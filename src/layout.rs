@@ -0,0 +1,234 @@
+//! Layout constants shared by the parent and the 32-bit child.
+//!
+//! The child crate includes this file with `#[path]`, so both sides compile
+//! their shared-memory structs against the same numbers. Not every crate
+//! that includes it uses every item.
+
+#![allow(dead_code)]
+
+/// Size in bytes of the payload stored next to the futex.
+pub const PAYLOAD_SIZE: usize = 8;
+
+/// Alignment in bytes of the payload stored next to the futex.
+///
+/// `AtomicI64` is 8-aligned on every target, whereas a plain `i64` is only
+/// 4-aligned on i686, so payloads must be chosen with this in mind.
+pub const PAYLOAD_ALIGN: usize = 8;
+
+/// Cache line size assumed when keeping hot fields apart. 64 bytes holds
+/// for every x86 CPU either side can run on.
+pub const CACHE_LINE: usize = 64;
+
+/// Gives a field a cache line of its own, so stores to its neighbours do
+/// not invalidate it for other processes. Dereferences to the inner value.
+#[repr(C, align(64))]
+pub struct CacheAligned<T>(pub T);
+
+impl<T> CacheAligned<T> {
+    pub const fn new(value: T) -> Self {
+        Self(value)
+    }
+}
+
+impl<T> std::ops::Deref for CacheAligned<T> {
+    type Target = T;
+
+    fn deref(&self) -> &T {
+        &self.0
+    }
+}
+
+const _: () = assert!(std::mem::align_of::<CacheAligned<u8>>() == CACHE_LINE);
+
+/// Number of extra `i64` words guarded by the same lock as `number`, for
+/// updates that must change several values together.
+pub const PROTECTED_WORDS: usize = 4;
+
+/// Number of threads waiting for the lock that `SharedData` can record
+/// for deadlock detection. Further waiters simply go unrecorded.
+pub const TRACKED_WAITERS: usize = 4;
+
+/// Capacity in bytes of the status message a process can leave behind with
+/// `set_status`. Longer messages are truncated.
+pub const STATUS_MESSAGE_LEN: usize = 64;
+
+/// Identifies a region as one of ours; "SHMA" in ASCII.
+pub const MAGIC: u32 = 0x5348_4D41;
+
+/// Version of the `SharedData` layout. Bump it whenever a field is added,
+/// removed, reordered or resized on either side.
+pub const LAYOUT_VERSION: u16 = 22;
+
+/// Value of `SharedData::ready` once the creator has finished. A fresh
+/// segment is zero-filled, and a lone set bit is easier to get by accident
+/// than this pattern.
+pub const READY_SENTINEL: u8 = 0xA5;
+
+/// Where the start-up handshake in `SharedData::phase` stands. The parent
+/// moves it to `ParentReady` before spawning, each child reports itself
+/// and the first one moves it to `ChildAttached`, and the parent moves it
+/// to `Running` once it is done setting up. A region nobody announced a
+/// handshake on stays at `Created`, and children go ahead without one.
+#[repr(u32)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord)]
+pub enum Phase {
+    Created = 0,
+    ParentReady = 1,
+    ChildAttached = 2,
+    Running = 3,
+}
+
+impl Phase {
+    /// The phase stored as `raw`, or `None` for a value no side writes.
+    pub fn from_raw(raw: u32) -> Option<Phase> {
+        match raw {
+            0 => Some(Phase::Created),
+            1 => Some(Phase::ParentReady),
+            2 => Some(Phase::ChildAttached),
+            3 => Some(Phase::Running),
+            _ => None,
+        }
+    }
+}
+
+/// Stored natively by the creator. A peer with the other byte order reads
+/// it reversed, and since every byte differs any reordering shows.
+pub const BYTE_ORDER_MARK: u64 = 0x0102_0304_0506_0708;
+
+/// First bytes of the shared region, written once by the parent before
+/// any child is spawned and checked by the child before it reads anything
+/// else.
+#[repr(C)]
+pub struct Header {
+    pub magic: u32,
+    pub version: u16,
+    pub reserved: u16,
+    /// `BYTE_ORDER_MARK` as the creator sees it. At offset 8 on every
+    /// target, even where `u64` is only 4-aligned.
+    pub byte_order: u64,
+    /// `size_of` and `align_of` of the struct this header starts, as the
+    /// creator's build sees them. Both sides assert these at compile time,
+    /// but only against their own copy of the numbers; comparing at run
+    /// time also catches a parent and child built from different sources
+    /// that both forgot to bump `LAYOUT_VERSION`.
+    pub struct_size: u32,
+    pub struct_align: u32,
+}
+
+impl Header {
+    /// A header for a region holding a `T`.
+    pub const fn new<T>() -> Self {
+        Self {
+            magic: MAGIC,
+            version: LAYOUT_VERSION,
+            reserved: 0,
+            byte_order: BYTE_ORDER_MARK,
+            struct_size: std::mem::size_of::<T>() as u32,
+            struct_align: std::mem::align_of::<T>() as u32,
+        }
+    }
+
+    /// Whether the creator stores integers in the same byte order as we
+    /// do. Check this before `check`: with the order flipped the magic is
+    /// flipped too, which would be misreported as a layout mismatch.
+    pub fn same_byte_order(&self) -> bool {
+        self.byte_order == BYTE_ORDER_MARK
+    }
+
+    /// Confirms the region was written by a peer built with this layout,
+    /// and whose `T` has the same size and alignment as ours.
+    pub fn check<T>(&self) -> Result<(), LayoutMismatch> {
+        let expected = Self::new::<T>();
+        if self.magic != expected.magic
+            || self.version != expected.version
+            || self.struct_size != expected.struct_size
+            || self.struct_align != expected.struct_align
+        {
+            return Err(LayoutMismatch {
+                expected_magic: expected.magic,
+                found_magic: self.magic,
+                expected_version: expected.version,
+                found_version: self.version,
+                expected_size: expected.struct_size,
+                found_size: self.struct_size,
+                expected_align: expected.struct_align,
+                found_align: self.struct_align,
+            });
+        }
+        Ok(())
+    }
+}
+
+/// What `Header::check` found instead of the expected magic and version.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LayoutMismatch {
+    pub expected_magic: u32,
+    pub found_magic: u32,
+    pub expected_version: u16,
+    pub found_version: u16,
+    pub expected_size: u32,
+    pub found_size: u32,
+    pub expected_align: u32,
+    pub found_align: u32,
+}
+
+impl std::fmt::Display for LayoutMismatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.found_magic != self.expected_magic {
+            write!(
+                f,
+                "shared memory magic mismatch: expected {:#010x} got {:#010x}",
+                self.expected_magic, self.found_magic
+            )
+        } else if self.found_version != self.expected_version {
+            write!(
+                f,
+                "shared memory layout mismatch: expected v{} got v{}",
+                self.expected_version, self.found_version
+            )
+        } else {
+            write!(
+                f,
+                "shared memory struct mismatch: expected {} bytes aligned to {}, got {} aligned to {}",
+                self.expected_size, self.expected_align, self.found_size, self.found_align
+            )
+        }
+    }
+}
+
+impl std::error::Error for LayoutMismatch {}
+
+/// Capacity of the ring buffer used by the producer/consumer example.
+pub const RING_CAPACITY: usize = 8;
+
+/// Capacity of the stack used by the work-stack example.
+pub const STACK_CAPACITY: usize = 16;
+
+/// Capacity of the vector the child fills in the shared vec test.
+pub const VEC_CAPACITY: usize = 16;
+
+/// Capacity of the queue used by the MPMC example; a power of two.
+pub const MPMC_CAPACITY: usize = 8;
+
+/// Capacity of the event log used by the event log example.
+pub const EVENT_LOG_CAPACITY: usize = 64;
+
+/// Size in bytes of `SharedData` on Linux, where `RawSync` is a bare futex
+/// word on both sides: `futex` and `number` each fill a 64-byte line and
+/// the whole struct is rounded up to a multiple of 64. Asserted in both
+/// crates so a padding change on one side cannot go unnoticed.
+///
+/// The size is the same for the 64-bit parent and the 32-bit child because
+/// no field depends on the pointer width: there is no `usize`, pointer or
+/// `AtomicUsize`, only fixed-width atomics, and `AtomicI64`/`AtomicU64` are
+/// 8-aligned even on i686, where a plain `u64` is only 4-aligned and would
+/// shift every field after it.
+///
+/// To see the check fire, build the child with a field too many:
+/// `RUSTFLAGS="--cfg sharedmem_layout_drift" cargo build --manifest-path
+/// child_process/Cargo.toml`.
+pub const SHARED_DATA_SIZE: usize = 512;
+
+/// Alignment of `SharedData` on both sides, set by its cache-aligned
+/// fields.
+pub const SHARED_DATA_ALIGN: usize = CACHE_LINE;
@@ -0,0 +1,55 @@
+//! Futex-synchronized data shared between a 64-bit process and a 32-bit
+//! child through a single shared memory segment.
+//!
+//! Start with `SharedRegion::builder()` to create or attach to a region; the
+//! resulting `OwnedSharedData` dereferences to the `SharedData` living in it.
+//! Two separate programs can also share one by name with `create_region` and
+//! `open_region`, without either spawning the other; see the `sync_owner`
+//! and `sync_peer` examples. Peers that may start in either order can all
+//! call `SharedRegion::create_or_open` instead, and a creator that wants
+//! the region to start out other than the defaults sets it up with
+//! `SharedRegion::create_with`.
+
+pub mod affinity;
+#[cfg(target_os = "linux")]
+pub mod cleanup;
+pub mod deadlock;
+pub mod eventlog;
+pub mod expr;
+pub mod extract;
+#[cfg(unix)]
+pub mod fork;
+pub mod layout;
+pub mod lock;
+#[cfg(target_os = "linux")]
+pub mod memfd;
+#[cfg(feature = "metrics")]
+pub mod metrics;
+pub mod mpmc;
+pub mod raw_sync;
+#[cfg(unix)]
+pub mod readonly;
+pub mod record;
+pub mod ring;
+pub mod segments;
+pub mod shared;
+pub mod sharedvec;
+pub mod stack;
+#[cfg(unix)]
+pub mod sysv;
+pub mod timeouts;
+
+#[cfg(target_os = "linux")]
+pub use cleanup::{SegmentInfo, list_segments};
+pub use deadlock::detect_deadlock;
+pub use extract::ChildExecutable;
+#[cfg(unix)]
+pub use fork::{ForkedChild, fork_with_shared};
+pub use lock::SharedLock;
+#[cfg(unix)]
+pub use readonly::{OwnedSharedDataReadonly, SharedDataReadonly};
+pub use segments::SegmentSet;
+pub use shared::{
+    OpenMode, OwnedSharedData, PoisonError, SharedData, SharedMemError, SharedRegion,
+    SharedRegionBuilder, SharedView, UnlockError, create_region, open_region,
+};
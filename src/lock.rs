@@ -0,0 +1,57 @@
+//! The lock protocol as a trait, so code that only needs to take and
+//! release a lock can be written once for the futex lock in `SharedData`
+//! and for locks of the caller's own, e.g. a spinlock in the bytes from
+//! `OwnedSharedData::user_region`.
+//!
+//! `SharedData` itself is not generic over its lock: the 32-bit child
+//! mirrors its layout field by field and takes the lock with the same
+//! futex protocol, so swapping the lock in one build would silently break
+//! the other. An alternative lock therefore lives next to `SharedData`, not
+//! inside it, and guards data of its own.
+//!
+//! An implementation shared between processes has to follow the same
+//! rules as the fields of `SharedData`: `#[repr(C)]`, fixed-width atomics
+//! only (no `usize`, pointers or `Instant`s), and nothing that needs a
+//! constructor to run in every process that maps it. Its state must also
+//! mean "unlocked" when zeroed, as a fresh segment is, or be initialized by
+//! the creator before anyone else attaches. Peers built from other sources,
+//! like the child, must agree on it byte for byte.
+
+use crate::shared::{LockState, SharedData, SharedMemError};
+use std::time::Duration;
+
+/// A mutual-exclusion lock that can live in shared memory.
+pub trait SharedLock {
+    /// Takes the lock, giving up with `SharedMemError::Timeout` after
+    /// `timeout`. Implementations may return other errors, as the futex
+    /// lock does for a holder that died.
+    fn lock_timeout(&self, timeout: Duration) -> Result<(), SharedMemError>;
+
+    /// Releases the lock, which the caller must hold.
+    fn unlock(&self);
+
+    /// Takes the lock if it is free right now, without waiting.
+    fn try_lock(&self) -> bool;
+
+    /// Who holds the lock. Implementations that do not record their owner
+    /// report `owner_pid: 0` while locked.
+    fn lock_state(&self) -> LockState;
+}
+
+impl SharedLock for SharedData {
+    fn lock_timeout(&self, timeout: Duration) -> Result<(), SharedMemError> {
+        SharedData::lock_timeout(self, timeout)
+    }
+
+    fn unlock(&self) {
+        SharedData::unlock(self)
+    }
+
+    fn try_lock(&self) -> bool {
+        SharedData::try_lock(self)
+    }
+
+    fn lock_state(&self) -> LockState {
+        SharedData::lock_state(self)
+    }
+}
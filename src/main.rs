@@ -1,105 +1,908 @@
-mod shared;
+use clap::{CommandFactory, Parser};
+#[cfg(unix)]
+use sharedmem_multiarch::OpenMode;
+use sharedmem_multiarch::affinity;
+use sharedmem_multiarch::expr::Expr;
+use sharedmem_multiarch::extract::{self, BinaryArch};
+use sharedmem_multiarch::layout::LAYOUT_VERSION;
+use sharedmem_multiarch::shared::{LockState, SharedDataGuard};
+use sharedmem_multiarch::timeouts;
+use sharedmem_multiarch::{
+    ChildExecutable, OwnedSharedData, SegmentSet, SharedData, SharedMemError, SharedRegion,
+    SharedRegionBuilder,
+};
+use std::borrow::Cow;
+use std::ffi::OsStr;
+use std::path::PathBuf;
+use std::process::{Child, Command, ExitStatus, Stdio};
+use std::thread::JoinHandle;
+use std::time::{Duration, Instant};
 
-use shared::SharedData;
-use shared_memory::ShmemConf;
-use std::fs;
-use std::io::Write;
-use std::os::unix::fs::PermissionsExt;
-use std::process::Command;
-use tempfile::NamedTempFile;
+/// Hands a shared number from this 64-bit process through one or more
+/// 32-bit children and back.
+#[derive(Parser)]
+struct Args {
+    /// Value the shared number starts at
+    #[arg(long, default_value_t = 100, allow_negative_numbers = true)]
+    initial: i64,
+    /// Seconds to wait for any one lock before giving up, e.g. 2.5. This
+    /// and every other timeout are multiplied by $SHAREDMEM_TIMEOUT_SCALE
+    #[arg(long, default_value = "5", value_parser = parse_seconds)]
+    lock_timeout: Duration,
+    /// Number of 32-bit children, which take turns applying --child-op
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    child_count: u32,
+    /// What each child does to the number n
+    #[arg(long, default_value = "(n + 25) * 2")]
+    child_op: Expr,
+    /// What the parent does to the number n once the children are done
+    #[arg(long, default_value = "n * 3 + 50")]
+    parent_op: Expr,
+    /// Share an unnamed memfd the children inherit instead of a named
+    /// segment, so no OS ID shows up on their command lines (Linux only)
+    #[arg(long)]
+    anonymous: bool,
+    /// Share a System V segment instead of a POSIX one, as is done anyway
+    /// when POSIX shared memory is unavailable (Unix only)
+    #[arg(long, conflicts_with = "anonymous")]
+    sysv: bool,
+    /// Pin every child to this CPU, for steadier timings (Linux only)
+    #[arg(long, env = "SHAREDMEM_CHILD_CPU")]
+    child_cpu: Option<usize>,
+    /// Pin the parent to this CPU (Linux only)
+    #[arg(long, env = "SHAREDMEM_PARENT_CPU")]
+    parent_cpu: Option<usize>,
+    /// Times to respawn the children after one fails, starting over from
+    /// the number they were handed
+    #[arg(long, default_value_t = 0)]
+    max_retries: u32,
+    /// Run each child under this command, split on whitespace, e.g.
+    /// "gdb --args" or "bwrap --dev-bind / / --". The child's path and
+    /// arguments follow it. A sandbox must still reach the region: bind
+    /// /dev/shm for a named segment; with --anonymous, or for the child
+    /// binary itself on Linux, keep inherited fds and mount /proc
+    #[arg(long, env = "SHAREDMEM_CHILD_WRAPPER")]
+    child_wrapper: Option<String>,
+    /// Seconds to wait for all children to exit once they are running,
+    /// after which any left are terminated and the run fails
+    #[arg(long, default_value = "30", value_parser = parse_seconds)]
+    child_wait_timeout: Duration,
+    /// Give each child a segment of its own, started at --initial plus the
+    /// child's index, instead of passing one number through all of them
+    #[arg(long)]
+    fan_out: bool,
+    /// Seconds the parent may wait for a lock before dumping its state
+    /// (futex value, owner and whether it is alive, number) to stderr
+    #[arg(long, value_parser = parse_seconds)]
+    watchdog_grace: Option<Duration>,
+    /// Run this child executable instead of extracting the embedded one,
+    /// to try changes to the child without rebuilding the parent
+    #[arg(long, env = "SHAREDMEM_CHILD_RUNTIME")]
+    child_runtime: Option<PathBuf>,
+    /// Collect the children's stdout and stderr instead of letting them
+    /// stream to the terminal, echo them once each child exits, and end a
+    /// failed run with the tail of what every failing child wrote
+    #[arg(long)]
+    capture_child_output: bool,
+    /// Only check that a region can be created and the child extracts, is
+    /// executable and was built for the expected target, print a report
+    /// and exit, without spawning anything
+    #[arg(long)]
+    check: bool,
+    /// Print the summary at the end as one line of JSON instead
+    #[cfg(feature = "json")]
+    #[arg(long, conflicts_with = "fan_out")]
+    json: bool,
+}
 
-fn main() -> Result<(), Box<dyn std::error::Error>> {
-    println!("=== 64-bit Parent Process Started ===");
-    println!("Process ID: {}", std::process::id());
+/// How recent a heartbeat must be for a child still running at the wait
+/// deadline to be given more time, up to a second `--child-wait-timeout`.
+const HEARTBEAT_GRACE: Duration = Duration::from_secs(1);
+
+/// Lines of each stream a failing child's output is cut down to in the
+/// error `--capture-child-output` ends the run with.
+const OUTPUT_TAIL_LINES: usize = 10;
 
-    let shmem = ShmemConf::new()
-        .size(std::mem::size_of::<SharedData>())
-        .create()?;
+/// How long a child gets to exit after SIGTERM before it is killed.
+#[cfg(unix)]
+const TERMINATE_GRACE: Duration = Duration::from_secs(1);
+
+fn parse_seconds(arg: &str) -> Result<Duration, String> {
+    let seconds: f64 = arg.parse().map_err(|e| format!("{}", e))?;
+    Duration::try_from_secs_f64(seconds).map_err(|e| format!("{}", e))
+}
+
+fn main() -> Result<(), Box<dyn std::error::Error>> {
+    #[cfg(feature = "tracing")]
+    tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .init();
 
-    println!("Shared memory created with OS ID: {}", shmem.get_os_id());
+    let mut args = match Args::try_parse() {
+        Ok(args) => args,
+        // --help and --version end up here as well, and exit successfully
+        Err(e) if !e.use_stderr() => e.exit(),
+        Err(e) => {
+            let _ = e.print();
+            eprintln!("\n{}", Args::command().render_usage());
+            std::process::exit(2);
+        }
+    };
+    // Scaled once here, so the children are handed the scaled lock timeout
+    args.lock_timeout = timeouts::scaled(args.lock_timeout);
+    args.child_wait_timeout = timeouts::scaled(args.child_wait_timeout);
+    args.watchdog_grace = args.watchdog_grace.map(timeouts::scaled);
+    if args.check {
+        return run_check(&args);
+    }
+    if args.fan_out {
+        let region = start_parent(&args)?;
+        return run_fan_out(&args, &region);
+    }
 
-    let shared_data_ptr = shmem.as_ptr() as *mut SharedData;
+    let started = Instant::now();
+    let child_count = args.child_count;
+    let timeout = args.lock_timeout;
 
-    unsafe {
-        std::ptr::write(shared_data_ptr, SharedData::new());
+    // Work out the expected results up front, so an operation that
+    // overflows is reported before anything is spawned
+    let mut expected_child_result = args.initial;
+    for _ in 0..child_count {
+        expected_child_result = args
+            .child_op
+            .eval(expected_child_result)
+            .ok_or_else(|| format!("--child-op {} overflows", args.child_op))?;
+    }
+    if args.parent_op.eval(expected_child_result).is_none() {
+        return Err(format!("--parent-op {} overflows", args.parent_op).into());
     }
 
-    let shared_data = unsafe { &*shared_data_ptr };
+    let shared_data = start_parent(&args)?.build()?;
 
+    println!("Shared memory created with OS ID: {}", shared_data.os_id());
+
+    shared_data.set_initial_number(args.initial);
+    *shared_data.published.write_lock_timeout(timeout)? = args.initial;
     println!("Shared memory initialized");
     println!("Initial number: {}", shared_data.get_number());
 
-    match shared_data.lock_timeout(std::time::Duration::from_secs(5)) {
-        Ok(_) => println!("Parent has acquired the initial lock"),
-        Err(e) => return Err(format!("Parent failed to acquire initial lock: {:?}", e).into()),
+    // Retries start over from here, should a child die mid-handoff
+    let checkpoint = shared_data.get_number();
+    let mut attempt = 0;
+    let (child_pids, children_took) = loop {
+        let children_started = Instant::now();
+        let failures = match run_children(&shared_data, &args, expected_child_result)? {
+            Ok(pids) => break (pids, children_started.elapsed()),
+            Err(failures) => failures,
+        };
+        if attempt == args.max_retries {
+            return Err(ChildFailed(failures).into());
+        }
+        attempt += 1;
+        println!(
+            "\n=== Retrying the children, attempt {} of {} ===",
+            attempt + 1,
+            args.max_retries + 1
+        );
+
+        // Every child has been waited on, so one that died holding the lock
+        // is gone for good; a live holder means something else has it
+        if let Some(owner_pid) = shared_data.recover_dead_owner() {
+            println!("Parent: Recovered the lock from dead process {}", owner_pid);
+        }
+        if let LockState::Locked { owner_pid } = shared_data.lock_state() {
+            return Err(format!("Cannot retry, the lock is held by {}", owner_pid).into());
+        }
+        shared_data.clear_poison();
+        shared_data.reset_turns();
+        shared_data.set_number(checkpoint);
+        println!("Parent: Number reset to checkpoint {}", checkpoint);
+    };
+
+    println!("\n=== Parent performing final operations ===");
+
+    println!("Parent: Acquiring lock for final operations...");
+    let mut guard = match parent_lock(&shared_data, &args) {
+        Ok(guard) => {
+            println!("Parent: Lock acquired!");
+            guard
+        }
+        Err(e) => return Err(format!("Parent: Failed to acquire final lock: {}", e).into()),
+    };
+
+    let current_number = guard.get();
+    println!("Parent: Number after child processing: {}", current_number);
+
+    let new_number = args.parent_op.eval(current_number).ok_or_else(|| {
+        format!(
+            "Parent: {} overflows for n = {}",
+            args.parent_op, current_number
+        )
+    })?;
+    guard.set(new_number);
+
+    println!("Parent: Applied operation ({})", args.parent_op);
+    println!("Parent: Final result: {}", new_number);
+
+    drop(guard);
+    println!("Parent: Lock released");
+
+    match shared_data.published.write_lock_timeout(timeout) {
+        Ok(mut published) => *published = new_number,
+        Err(e) => return Err(format!("Parent: Failed to publish final result: {}", e).into()),
     }
+    println!("Parent: Published final result for readers");
 
-    let child_binary = include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded"));
-    let mut temp_file = NamedTempFile::new()?;
-    temp_file.write_all(child_binary)?;
-    temp_file.flush()?;
+    // The children are let go just before the initial lock is released,
+    // so the first of them usually, but not always, had to wait for it.
+    let stats = shared_data.stats();
+    println!(
+        "Parent: Lock taken {} times, {} contended, {:?} spent waiting",
+        stats.acquisitions, stats.contended, stats.total_wait
+    );
+    println!(
+        "Parent: Lock held {:?} in total, {:?} at most",
+        stats.total_hold, stats.max_hold
+    );
 
-    let mut perms = temp_file.as_file().metadata()?.permissions();
-    perms.set_mode(0o755);
-    temp_file.as_file().set_permissions(perms)?;
+    #[cfg(feature = "json")]
+    if args.json {
+        let summary = serde_json::json!({
+            "initial": args.initial,
+            "child_count": child_count,
+            "child_op": args.child_op.as_str(),
+            "child_result": expected_child_result,
+            "parent_op": args.parent_op.as_str(),
+            "parent_result": new_number,
+            "child_pids": child_pids,
+            "attempts": attempt + 1,
+            "timings_ms": {
+                "children": children_took.as_secs_f64() * 1000.0,
+                "lock_wait": stats.total_wait.as_secs_f64() * 1000.0,
+                "child_compute": shared_data
+                    .child_compute_time()
+                    .map(|took| took.as_secs_f64() * 1000.0),
+                "total": started.elapsed().as_secs_f64() * 1000.0,
+            },
+        });
+        println!("{}", summary);
+        return Ok(());
+    }
+    // Only the JSON summary reports these
+    #[cfg(not(feature = "json"))]
+    let _ = (child_pids, children_took, started);
 
-    let temp_path = temp_file.into_temp_path();
+    println!(
+        "\n=== Parent process completed successfully ===\n\
+         Summary:\n\
+         - Initial value: {}\n\
+         - Child operation x{}: ({}) = {}\n\
+         - Parent operation: ({}) = {}\n\
+         - Architecture demo: ✓ 64-bit parent, 32-bit child\n\
+         - Synchronization: ✓ Futex-based locking\n\
+         - Memory sharing: ✓ Zero-copy inter-process communication",
+        args.initial, child_count, args.child_op, expected_child_result, args.parent_op, new_number
+    );
 
-    println!("\n=== Spawning 32-bit child process ===");
+    Ok(())
+}
 
-    let mut child = Command::new(&temp_path).arg(shmem.get_os_id()).spawn()?;
+/// Waits for `child` to exit until `deadline`, or later while it keeps
+/// beating. A child still running then is terminated, and `Err` carries how
+/// it ended.
+fn wait_child(
+    child: &mut Child,
+    shared_data: &SharedData,
+    deadline: Instant,
+) -> std::io::Result<Result<ExitStatus, ExitStatus>> {
+    let hard_deadline = deadline + deadline.saturating_duration_since(Instant::now());
+    loop {
+        if let Some(exit_status) = child.try_wait()? {
+            return Ok(Ok(exit_status));
+        }
+        let now = Instant::now();
+        if now >= hard_deadline
+            || (now >= deadline && !shared_data.peer_alive(timeouts::scaled(HEARTBEAT_GRACE)))
+        {
+            return terminate(child).map(Err);
+        }
+        std::thread::sleep(Duration::from_millis(10));
+    }
+}
 
-    println!("Child process spawned with PID: {}", child.id());
+/// Asks `child` to exit with SIGTERM, then kills it if it has not within
+/// `TERMINATE_GRACE`. Windows has no SIGTERM, so there it is killed at once.
+fn terminate(child: &mut Child) -> std::io::Result<ExitStatus> {
+    #[cfg(unix)]
+    {
+        unsafe { libc::kill(child.id() as libc::pid_t, libc::SIGTERM) };
+        let killed_by = Instant::now() + timeouts::scaled(TERMINATE_GRACE);
+        while Instant::now() < killed_by {
+            if let Some(exit_status) = child.try_wait()? {
+                return Ok(exit_status);
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        }
+    }
+    child.kill()?;
+    child.wait()
+}
 
-    std::thread::sleep(std::time::Duration::from_millis(500));
+/// The child binary build.rs embedded, decompressed and checked first with
+/// the `compress-child` feature.
+#[cfg(not(feature = "compress-child"))]
+fn embedded_child() -> std::io::Result<Cow<'static, [u8]>> {
+    Ok(Cow::Borrowed(include_bytes!(concat!(
+        env!("OUT_DIR"),
+        "/child_process_embedded"
+    ))))
+}
 
-    println!("\n=== Parent releasing lock ===");
+#[cfg(feature = "compress-child")]
+fn embedded_child() -> std::io::Result<Cow<'static, [u8]>> {
+    let compressed = include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded.zst"));
+    sharedmem_multiarch::extract::decompress_child(
+        compressed,
+        env!("SHAREDMEM_CHILD_LEN").parse().unwrap(),
+        env!("SHAREDMEM_CHILD_CHECKSUM").parse().unwrap(),
+    )
+    .map(Cow::Owned)
+}
 
-    shared_data.unlock();
-    println!("Parent: Lock released, child should now acquire it");
+/// The child executable the parent spawns.
+enum ChildProgram {
+    Embedded(ChildExecutable),
+    /// Given with `--child-runtime`, and run where it is.
+    Runtime(PathBuf),
+}
 
-    println!("\n=== Parent waiting for child to complete ===");
-    let exit_status = child.wait()?;
-    println!("Child process completed with status: {}", exit_status);
+impl AsRef<OsStr> for ChildProgram {
+    fn as_ref(&self) -> &OsStr {
+        match self {
+            ChildProgram::Embedded(child_exe) => child_exe.as_ref(),
+            ChildProgram::Runtime(path) => path.as_os_str(),
+        }
+    }
+}
 
-    if !exit_status.success() {
-        return Err("Child process failed".into());
+/// The child `--child-runtime` names, or else the embedded one, extracted
+/// afresh.
+fn child_program(args: &Args) -> std::io::Result<ChildProgram> {
+    match &args.child_runtime {
+        Some(path) => {
+            println!("Using the child at {}", path.display());
+            Ok(ChildProgram::Runtime(path.clone()))
+        }
+        None => Ok(ChildProgram::Embedded(ChildExecutable::extract(
+            &embedded_child()?,
+        )?)),
     }
+}
 
-    println!("\n=== Parent performing final operations ===");
+/// A command running `child_exe`, under `wrapper` if one was given.
+fn child_command(child_exe: &ChildProgram, wrapper: Option<&str>) -> Command {
+    let mut words = wrapper.into_iter().flat_map(str::split_whitespace);
+    match words.next() {
+        Some(program) => {
+            let mut command = Command::new(program);
+            command.args(words).arg(child_exe);
+            command
+        }
+        None => Command::new(child_exe),
+    }
+}
 
-    println!("Parent: Acquiring lock for final operations...");
-    match shared_data.lock_timeout(std::time::Duration::from_secs(5)) {
-        Ok(_) => println!("Parent: Lock acquired!"),
-        Err(e) => return Err(format!("Parent: Failed to acquire final lock: {:?}", e).into()),
+/// Takes the lock for the parent, with a watchdog if `--watchdog-grace`
+/// asks for one.
+fn parent_lock<'a>(
+    shared_data: &'a SharedData,
+    args: &Args,
+) -> Result<SharedDataGuard<'a>, SharedMemError> {
+    match args.watchdog_grace {
+        Some(grace) => shared_data.lock_timeout_guard_watched(args.lock_timeout, grace, |dump| {
+            eprintln!("Parent: Watchdog: {}", dump)
+        }),
+        None => shared_data.lock_timeout_guard(args.lock_timeout),
     }
+}
 
-    let current_number = shared_data.get_number();
-    println!("Parent: Number after child processing: {}", current_number);
+/// Announces the parent, pins it if asked to and returns the settings its
+/// regions are created with.
+fn start_parent(args: &Args) -> Result<SharedRegionBuilder, Box<dyn std::error::Error>> {
+    println!("=== 64-bit Parent Process Started ===");
+    println!("Process ID: {}", std::process::id());
+    if let Some(cpu) = args.parent_cpu {
+        affinity::pin_to_cpu(std::process::id(), cpu)
+            .map_err(|e| format!("Failed to pin the parent to CPU {}: {}", cpu, e))?;
+        println!("Parent pinned to CPU {}", cpu);
+    }
 
-    let new_number = current_number * 3 + 50;
-    shared_data.set_number(new_number);
+    if args.anonymous && cfg!(not(target_os = "linux")) {
+        return Err("--anonymous needs Linux memfds".into());
+    }
+    if args.sysv && cfg!(not(unix)) {
+        return Err("--sysv needs System V shared memory".into());
+    }
+    let region = SharedRegion::builder();
+    #[cfg(target_os = "linux")]
+    let region = if args.anonymous {
+        region.mode(OpenMode::Anonymous)
+    } else {
+        region
+    };
+    #[cfg(unix)]
+    let region = if args.sysv {
+        region.mode(OpenMode::SysV)
+    } else {
+        region
+    };
+    Ok(region)
+}
 
-    println!("Parent: Applied operation (n * 3 + 50)");
-    println!("Parent: Final result: {}", new_number);
+/// Runs the `--check` preflight: creates a region the way a run would,
+/// then extracts the child and reads its header without starting it. Every
+/// check is reported, and the preflight fails if any of them did.
+fn run_check(args: &Args) -> Result<(), Box<dyn std::error::Error>> {
+    let region = start_parent(args);
+    println!("\n=== Preflight check ===");
+    let mut failed = 0;
+    let mut report = |what: &str, result: Result<String, String>| match result {
+        Ok(detail) => println!("{}: ok, {}", what, detail),
+        Err(e) => {
+            println!("{}: FAILED, {}", what, e);
+            failed += 1;
+        }
+    };
 
-    shared_data.unlock();
-    println!("Parent: Lock released");
+    report(
+        "Shared memory",
+        region
+            .and_then(|region| Ok(region.build()?))
+            .map(|shared_data| {
+                format!(
+                    "created {} with layout version {}",
+                    shared_data.os_id(),
+                    LAYOUT_VERSION
+                )
+            })
+            .map_err(|e| e.to_string()),
+    );
+    match child_program(args) {
+        Ok(child_exe) => {
+            report("Child binary", check_child_executable(&child_exe));
+            report("Child target", check_child_target(&child_exe));
+        }
+        Err(e) => {
+            report("Child binary", Err(format!("cannot be extracted: {}", e)));
+            report("Child target", Err("no child to check".to_string()));
+        }
+    }
+
+    if failed > 0 {
+        return Err(format!(
+            "Preflight check failed: {} of 3 checks did not pass",
+            failed
+        )
+        .into());
+    }
+    println!("Preflight check passed, no child was spawned");
+    Ok(())
+}
 
+fn check_child_executable(child_exe: &ChildProgram) -> Result<String, String> {
+    let path = match child_exe {
+        ChildProgram::Embedded(child_exe) => child_exe.path(),
+        ChildProgram::Runtime(path) => path.as_path(),
+    };
+    if !path.is_file() {
+        return Err(format!("{} is not a file", path.display()));
+    }
+    extract::check_executable(path)
+        .map_err(|e| format!("{} cannot be executed: {}", path.display(), e))?;
+    Ok(format!("{} is executable", path.display()))
+}
+
+/// Compares what the child's header says it was built for with the target
+/// build.rs built it for, or with the usual child target when it was
+/// prebuilt or given with `--child-runtime`.
+fn check_child_target(child_exe: &ChildProgram) -> Result<String, String> {
+    let (binary, built_for) = match child_exe {
+        ChildProgram::Embedded(_) => (
+            embedded_child().map_err(|e| e.to_string())?,
+            option_env!("SHAREDMEM_CHILD_BUILT_FOR"),
+        ),
+        ChildProgram::Runtime(path) => (
+            Cow::Owned(std::fs::read(path).map_err(|e| e.to_string())?),
+            None,
+        ),
+    };
+    let arch = extract::binary_arch(&binary)
+        .ok_or("not an executable for any architecture the parent knows")?;
+    let (expected, source) = match built_for.and_then(BinaryArch::for_triple) {
+        Some(expected) => (expected, format!("built for {}", built_for.unwrap())),
+        None => (default_child_arch(), "the usual child target".to_string()),
+    };
+    if arch != expected {
+        return Err(format!("{}, expected {} ({})", arch, expected, source));
+    }
+    Ok(format!("{}, as {}", arch, source))
+}
+
+/// What build.rs builds the child for by default: the parent's own
+/// architecture on macOS, 32-bit x86 everywhere else.
+fn default_child_arch() -> BinaryArch {
+    if cfg!(target_os = "macos") {
+        BinaryArch {
+            format: "Mach-O",
+            bits: usize::BITS,
+            arch: std::env::consts::ARCH,
+        }
+    } else if cfg!(windows) {
+        BinaryArch {
+            format: "PE",
+            bits: 32,
+            arch: "x86",
+        }
+    } else {
+        BinaryArch {
+            format: "ELF",
+            bits: 32,
+            arch: "x86",
+        }
+    }
+}
+
+/// Gives every child a region of its own, starting at `--initial` plus the
+/// child's index, lets them all work at once and checks each region's
+/// result on its own.
+fn run_fan_out(
+    args: &Args,
+    region: &SharedRegionBuilder,
+) -> Result<(), Box<dyn std::error::Error>> {
+    let child_count = args.child_count;
+    let expected = (0..child_count)
+        .map(|index| {
+            args.initial
+                .checked_add(index.into())
+                .and_then(|n| args.child_op.eval(n))
+                .ok_or_else(|| {
+                    format!(
+                        "--child-op {} overflows for child {}",
+                        args.child_op,
+                        index + 1
+                    )
+                })
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let segments = SegmentSet::build(child_count as usize, region)?;
     println!(
-        "\n=== Parent process completed successfully ===\n\
-         Summary:\n\
-         - Initial value: 100\n\
-         - Child operation: ((n + 25) * 2) = {}\n\
-         - Parent operation: (n * 3 + 50) = {}\n\
-         - Architecture demo: ✓ 64-bit parent, 32-bit child\n\
-         - Synchronization: ✓ Futex-based locking\n\
-         - Memory sharing: ✓ Zero-copy inter-process communication",
-        (100 + 25) * 2,
-        new_number
+        "\n=== Fanning out to {} child(ren), one segment each ===",
+        child_count
     );
+    for (index, segment) in segments.iter().enumerate() {
+        segment.set_initial_number(args.initial + index as i64);
+        println!(
+            "Segment {} created with OS ID {}, starting at {}",
+            index + 1,
+            segment.os_id(),
+            segment.get_number()
+        );
+    }
+
+    let child_exe = child_program(args)?;
+    let mut children = Vec::new();
+    for (index, os_id) in segments.os_ids().into_iter().enumerate() {
+        // Alone on its segment, every child is the first and only one
+        let child = child_command(&child_exe, args.child_wrapper.as_deref())
+            .arg(os_id)
+            .arg("0")
+            .arg("1")
+            .arg(args.child_op.as_str())
+            .arg(args.lock_timeout.as_millis().to_string())
+            .spawn()
+            .map_err(|e| format!("Failed to spawn child {}: {}", index + 1, e))?;
+        println!(
+            "Child {} spawned with PID {} for segment {}",
+            index + 1,
+            child.id(),
+            index + 1
+        );
+        if let Some(cpu) = args.child_cpu {
+            affinity::pin_to_cpu(child.id(), cpu)
+                .map_err(|e| format!("Failed to pin child {} to CPU {}: {}", index + 1, cpu, e))?;
+        }
+        children.push(child);
+    }
 
-    let _ = fs::remove_file(&temp_path);
+    println!("\n=== Parent collecting per-segment results ===");
+    let deadline = Instant::now() + args.child_wait_timeout;
+    let mut failed = Vec::new();
+    for (index, (mut child, segment)) in children.into_iter().zip(&segments).enumerate() {
+        let exit_status = match wait_child(&mut child, segment, deadline)? {
+            Ok(exit_status) => exit_status,
+            Err(exit_status) => {
+                eprintln!(
+                    "Child {} did not exit within {:?} and was terminated ({})",
+                    index + 1,
+                    args.child_wait_timeout,
+                    exit_status
+                );
+                failed.push((index + 1).to_string());
+                continue;
+            }
+        };
+        let number = segment.get_number();
+        println!(
+            "Parent: Segment {} result: {} (child exited with {})",
+            index + 1,
+            number,
+            exit_status
+        );
+        if !exit_status.success() || number != expected[index] {
+            eprintln!(
+                "Parent: Segment {} should hold {}",
+                index + 1,
+                expected[index]
+            );
+            failed.push((index + 1).to_string());
+        }
+    }
+    if !failed.is_empty() {
+        return Err(format!("Segment(s) {} did not end as expected", failed.join(", ")).into());
+    }
 
+    println!(
+        "\n=== All {} segment(s) hold their expected results ===",
+        child_count
+    );
     Ok(())
 }
+
+/// Spawns the children, lets them take their turns and waits for them all,
+/// returning their PIDs if every one succeeded, and otherwise what the
+/// failing ones wrote if it was captured. Extracts the embedded child
+/// afresh, so a retry does not depend on a copy that may have gone bad.
+fn run_children(
+    shared_data: &OwnedSharedData,
+    args: &Args,
+    expected_child_result: i64,
+) -> Result<Result<Vec<u32>, Vec<String>>, Box<dyn std::error::Error>> {
+    let child_count = args.child_count;
+    let timeout = args.lock_timeout;
+
+    let initial_guard = match parent_lock(shared_data, args) {
+        Ok(guard) => {
+            println!("Parent has acquired the initial lock");
+            guard
+        }
+        Err(e) => return Err(format!("Parent failed to acquire initial lock: {}", e).into()),
+    };
+
+    let child_exe = child_program(args)?;
+    // Children spawned from here on report in and wait for start_running
+    shared_data.begin_handshake();
+
+    println!(
+        "\n=== Spawning {} 32-bit child process(es) ===",
+        child_count
+    );
+
+    let mut children = Vec::new();
+    let mut pids = Vec::new();
+    for index in 0..child_count {
+        let mut command = child_command(&child_exe, args.child_wrapper.as_deref());
+        command
+            .arg(shared_data.os_id())
+            .arg(index.to_string())
+            .arg(child_count.to_string())
+            .arg(args.child_op.as_str())
+            .arg(timeout.as_millis().to_string());
+        if args.capture_child_output {
+            command.stdout(Stdio::piped()).stderr(Stdio::piped());
+        }
+        let mut child = command
+            .spawn()
+            .map_err(|e| format!("Failed to spawn child {}: {}", index + 1, e))?;
+        let captured = args
+            .capture_child_output
+            .then(|| CapturedOutput::start(&mut child));
+        println!(
+            "Child {} of {} spawned with PID: {}",
+            index + 1,
+            child_count,
+            child.id()
+        );
+        // The child cannot get far before this: it waits for start_running
+        if let Some(cpu) = args.child_cpu {
+            affinity::pin_to_cpu(child.id(), cpu)
+                .map_err(|e| format!("Failed to pin child {} to CPU {}: {}", index + 1, cpu, e))?;
+            println!("Child {} pinned to CPU {}", index + 1, cpu);
+        }
+        pids.push(child.id());
+        children.push((child, captured));
+    }
+
+    // A child that fails to report in is reported when it is waited on
+    match shared_data.wait_attached(child_count, timeout) {
+        Ok(()) => println!("Parent: All {} child(ren) attached", child_count),
+        Err(e) => eprintln!("Parent: Not every child attached: {}", e),
+    }
+    shared_data.start_running();
+
+    println!("\n=== Parent releasing lock ===");
+
+    drop(initial_guard);
+    println!("Parent: Lock released, children should now acquire it in turn");
+
+    // Readers share the published value, so children can read it while we do
+    let published_guard = match shared_data.published.read_lock_timeout(timeout) {
+        Ok(guard) => guard,
+        Err(e) => {
+            return Err(format!("Parent failed to read-lock published value: {}", e).into());
+        }
+    };
+    println!(
+        "Parent: Holding a read lock on the published value ({})",
+        *published_guard
+    );
+
+    // Sleep until the last child's result shows up instead of polling;
+    // each child may wait out a full timeout for its turn and its lock
+    match shared_data.wait_until(|n| n == expected_child_result, timeout * 2 * child_count) {
+        Ok(n) => println!("Parent: Observed children's result {} via wait_until", n),
+        Err(e) => eprintln!("Parent: Children's result did not appear: {}", e),
+    }
+
+    println!("\n=== Parent waiting for children to complete ===");
+    let deadline = Instant::now() + args.child_wait_timeout;
+    let mut all_succeeded = true;
+    let mut hung = Vec::new();
+    let mut failures = Vec::new();
+    for (index, (mut child, captured)) in children.into_iter().enumerate() {
+        let (exit_status, terminated) = match wait_child(&mut child, shared_data, deadline)? {
+            Ok(exit_status) => (exit_status, false),
+            Err(exit_status) => (exit_status, true),
+        };
+        if let Some(captured) = captured {
+            let output = captured.finish();
+            output.echo();
+            if terminated || !exit_status.success() {
+                failures.push(output.describe(index + 1, exit_status));
+            }
+        }
+        if terminated {
+            eprintln!(
+                "Child {} did not exit within {:?} and was terminated ({})",
+                index + 1,
+                args.child_wait_timeout,
+                exit_status
+            );
+            hung.push((index + 1).to_string());
+            continue;
+        }
+        println!(
+            "Child {} process completed with status: {}",
+            index + 1,
+            exit_status
+        );
+        if let Some(reason) = exit_status
+            .code()
+            .and_then(SharedMemError::describe_exit_code)
+        {
+            eprintln!("Child {} failed: {}", index + 1, reason);
+        }
+        all_succeeded &= exit_status.success();
+    }
+
+    drop(published_guard);
+
+    if !hung.is_empty() {
+        return Err(format!(
+            "{} {} did not exit within {:?}",
+            if hung.len() == 1 { "Child" } else { "Children" },
+            hung.join(", "),
+            args.child_wait_timeout
+        )
+        .into());
+    }
+
+    let (status, message) = shared_data.read_status();
+    println!("Last child status: {} ({})", status, message);
+    if let Some(took) = shared_data.child_compute_time() {
+        println!("Last child spent {:?} on its update", took);
+    }
+
+    Ok(if all_succeeded {
+        Ok(pids)
+    } else {
+        Err(failures)
+    })
+}
+
+/// A child's stdout and stderr, each read on a thread of its own so the
+/// child never blocks on a full pipe while the parent waits for it.
+struct CapturedOutput {
+    stdout: JoinHandle<Vec<u8>>,
+    stderr: JoinHandle<Vec<u8>>,
+}
+
+impl CapturedOutput {
+    /// Takes over `child`'s stdout and stderr, which must be piped.
+    fn start(child: &mut Child) -> Self {
+        fn drain(mut pipe: impl std::io::Read + Send + 'static) -> JoinHandle<Vec<u8>> {
+            std::thread::spawn(move || {
+                let mut bytes = Vec::new();
+                // Whatever arrived before a read error is still worth keeping
+                let _ = pipe.read_to_end(&mut bytes);
+                bytes
+            })
+        }
+        CapturedOutput {
+            stdout: drain(child.stdout.take().expect("stdout is piped")),
+            stderr: drain(child.stderr.take().expect("stderr is piped")),
+        }
+    }
+
+    /// Everything the child wrote. Call it once the child has exited, when
+    /// both pipes are about to reach their end.
+    fn finish(self) -> ChildOutput {
+        ChildOutput {
+            stdout: String::from_utf8_lossy(&self.stdout.join().unwrap_or_default()).into_owned(),
+            stderr: String::from_utf8_lossy(&self.stderr.join().unwrap_or_default()).into_owned(),
+        }
+    }
+}
+
+struct ChildOutput {
+    stdout: String,
+    stderr: String,
+}
+
+impl ChildOutput {
+    /// Passes the output on to our own stdout and stderr, late but whole.
+    fn echo(&self) {
+        print!("{}", self.stdout);
+        eprint!("{}", self.stderr);
+    }
+
+    /// The child and how it ended, followed by the last lines it wrote.
+    fn describe(&self, index: usize, exit_status: ExitStatus) -> String {
+        let mut description = format!("child {} ended with {}", index, exit_status);
+        for (name, text) in [("stdout", &self.stdout), ("stderr", &self.stderr)] {
+            let lines: Vec<&str> = text.lines().collect();
+            if lines.is_empty() {
+                continue;
+            }
+            description.push_str(&format!("\n  last {}:", name));
+            for line in &lines[lines.len().saturating_sub(OUTPUT_TAIL_LINES)..] {
+                description.push_str(&format!("\n    {}", line));
+            }
+        }
+        description
+    }
+}
+
+/// The error a run ends with once the children have failed for good,
+/// carrying what the failing children wrote if it was captured. `main`
+/// reports errors with `Debug`, which would escape the line breaks in that
+/// output, so `Debug` shows the same text as `Display`.
+struct ChildFailed(Vec<String>);
+
+impl std::fmt::Display for ChildFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "Child process failed")?;
+        for failure in &self.0 {
+            write!(f, "\n{}", failure)?;
+        }
+        Ok(())
+    }
+}
+
+impl std::fmt::Debug for ChildFailed {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        std::fmt::Display::fmt(self, f)
+    }
+}
+
+impl std::error::Error for ChildFailed {}
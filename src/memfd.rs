@@ -0,0 +1,115 @@
+//! Shared regions backed by an anonymous `memfd` instead of a named segment.
+//!
+//! A named segment's OS ID travels on the child's command line, where any
+//! user can read it from `/proc/<pid>/cmdline` and open the segment. A memfd
+//! has no name: the parent creates it without `MFD_CLOEXEC`, every child it
+//! spawns inherits the descriptor, and only the descriptor number is passed
+//! on, as a handle of the form `fd:<n>`. Nothing appears under `/dev/shm`.
+//!
+//! The child crate includes this file with `#[path]` to map the handle it
+//! was given.
+
+#![allow(dead_code)]
+
+use std::io;
+use std::os::fd::{AsRawFd, FromRawFd, OwnedFd, RawFd};
+
+/// Prefix that marks an OS ID as an inherited descriptor.
+pub const HANDLE_PREFIX: &str = "fd:";
+
+/// The descriptor number in a handle such as `fd:3`, or `None` for the OS ID
+/// of a named segment.
+pub fn parse_handle(os_id: &str) -> Option<RawFd> {
+    os_id.strip_prefix(HANDLE_PREFIX)?.parse().ok()
+}
+
+/// A memfd mapped shared into this process. Dropping it unmaps the region
+/// and closes the descriptor; the memory goes away once no process has
+/// either left.
+#[derive(Debug)]
+pub struct MemfdMapping {
+    fd: OwnedFd,
+    ptr: *mut u8,
+    len: usize,
+}
+
+impl MemfdMapping {
+    /// Creates a zeroed memfd of `len` bytes that children inherit.
+    pub fn create(len: usize) -> io::Result<Self> {
+        // Deliberately without MFD_CLOEXEC, so spawned children keep the fd.
+        let fd = unsafe { libc::memfd_create(c"sharedmem".as_ptr(), 0) };
+        if fd < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        if unsafe { libc::ftruncate(fd.as_raw_fd(), len as libc::off_t) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        Self::map(fd, len)
+    }
+
+    /// Maps an inherited descriptor over its full size.
+    pub fn from_fd(fd: OwnedFd) -> io::Result<Self> {
+        let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+        if unsafe { libc::fstat(fd.as_raw_fd(), stat.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let len = unsafe { stat.assume_init() }.st_size as usize;
+        Self::map(fd, len)
+    }
+
+    fn map(fd: OwnedFd, len: usize) -> io::Result<Self> {
+        if len == 0 {
+            return Ok(MemfdMapping {
+                fd,
+                ptr: std::ptr::null_mut(),
+                len,
+            });
+        }
+        let ptr = unsafe {
+            libc::mmap(
+                std::ptr::null_mut(),
+                len,
+                libc::PROT_READ | libc::PROT_WRITE,
+                libc::MAP_SHARED,
+                fd.as_raw_fd(),
+                0,
+            )
+        };
+        if ptr == libc::MAP_FAILED {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(MemfdMapping {
+            fd,
+            ptr: ptr as *mut u8,
+            len,
+        })
+    }
+
+    /// Start of the mapping, page aligned. Null if the memfd is empty.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The handle children open this region with, e.g. `fd:3`.
+    pub fn handle(&self) -> String {
+        format!("{}{}", HANDLE_PREFIX, self.fd.as_raw_fd())
+    }
+}
+
+impl Drop for MemfdMapping {
+    fn drop(&mut self) {
+        if !self.ptr.is_null() {
+            unsafe { libc::munmap(self.ptr as *mut libc::c_void, self.len) };
+        }
+        // `fd` is closed next.
+    }
+}
@@ -0,0 +1,92 @@
+//! The lock counters as Prometheus exposition text, for a monitoring
+//! sidecar that maps the segment (read-only, say) and serves what it reads
+//! on its own `/metrics` endpoint.
+//!
+//! Only compiled with the `metrics` feature. The format is plain enough to
+//! write by hand, so this needs no dependencies.
+
+use crate::shared::{LockStats, SharedData};
+use std::fmt::Write;
+use std::time::Duration;
+
+impl SharedData {
+    /// The lock counters from `stats` in Prometheus text format, every
+    /// sample labelled `segment="<segment>"`. `SharedData` does not know
+    /// its own name, so the caller passes one, typically `os_id`.
+    ///
+    /// Durations are in seconds, as Prometheus expects.
+    pub fn prometheus_metrics(&self, segment: &str) -> String {
+        format_stats(&self.stats(), segment)
+    }
+}
+
+#[cfg(unix)]
+impl crate::readonly::SharedDataReadonly {
+    /// `SharedData::prometheus_metrics` for an observer.
+    pub fn prometheus_metrics(&self, segment: &str) -> String {
+        format_stats(&self.stats(), segment)
+    }
+}
+
+fn format_stats(stats: &LockStats, segment: &str) -> String {
+    let label = escape_label(segment);
+    let mut out = String::new();
+    let mut metric = |name: &str, kind: &str, help: &str, value: String| {
+        // Writing to a `String` cannot fail.
+        let _ = writeln!(out, "# HELP {} {}", name, help);
+        let _ = writeln!(out, "# TYPE {} {}", name, kind);
+        let _ = writeln!(out, "{}{{segment=\"{}\"}} {}", name, label, value);
+    };
+
+    metric(
+        "sharedmem_lock_acquisitions_total",
+        "counter",
+        "Times the lock was taken, by any process.",
+        stats.acquisitions.to_string(),
+    );
+    metric(
+        "sharedmem_lock_contended_total",
+        "counter",
+        "Acquisitions that found the lock already held.",
+        stats.contended.to_string(),
+    );
+    metric(
+        "sharedmem_lock_wait_seconds_total",
+        "counter",
+        "Time spent waiting for the lock across all acquisitions.",
+        seconds(stats.total_wait),
+    );
+    metric(
+        "sharedmem_lock_hold_seconds_total",
+        "counter",
+        "Time the lock was held, over every release so far.",
+        seconds(stats.total_hold),
+    );
+    metric(
+        "sharedmem_lock_hold_max_seconds",
+        "gauge",
+        "Longest single hold released so far.",
+        seconds(stats.max_hold),
+    );
+    out
+}
+
+/// `duration` in seconds, without losing nanoseconds to float rounding for
+/// any realistic total.
+fn seconds(duration: Duration) -> String {
+    format!("{}.{:09}", duration.as_secs(), duration.subsec_nanos())
+}
+
+/// `value` as a label value: backslash, double quote and newline escaped.
+fn escape_label(value: &str) -> String {
+    let mut escaped = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            '\\' => escaped.push_str("\\\\"),
+            '"' => escaped.push_str("\\\""),
+            '\n' => escaped.push_str("\\n"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
@@ -0,0 +1,215 @@
+//! Bounded multi-producer multi-consumer queue living in shared memory, for
+//! spreading work over the parent and several children in both directions.
+//!
+//! Like `ring`, this file is included by the child with `#[path]`, so it
+//! only depends on `std`, `raw_sync` and `ring`.
+
+#![allow(dead_code)]
+
+use crate::raw_sync::{RawSync, TimedWaitError};
+pub use crate::ring::Full;
+use crate::ring::wait_for_change;
+use std::sync::atomic::{AtomicI64, AtomicU32, Ordering, fence};
+use std::time::{Duration, Instant};
+
+/// A bounded queue of `i64` for any number of producers and consumers in
+/// any number of processes, after Dmitry Vyukov's bounded MPMC queue.
+///
+/// Every slot carries a sequence number saying whose turn it is: equal to
+/// a position in `enqueue_pos`, the slot is free for the producer that
+/// claims that position; one past it, it holds a value for the consumer
+/// that claims the same position in `dequeue_pos`. Producers and consumers
+/// each claim positions with one compare-and-swap and never wait for one
+/// another except when the queue is full or empty. Positions are `u32`,
+/// not `usize`, which is narrower in the 32-bit child, and `N` must be a
+/// power of two so they can wrap.
+///
+/// Blocked consumers sleep on `enqueued`, blocked producers on `dequeued`,
+/// each bumped by every operation of the other side. The bump is followed
+/// by a wake only while someone is registered in `consumers_waiting` or
+/// `producers_waiting`, so the uncontended path makes no syscall. A waiter
+/// that gives up deregisters on its way out, whether it timed out or was
+/// interrupted, and otherwise leaves no trace.
+#[repr(C)]
+pub struct SharedMpmcQueue<const N: usize> {
+    enqueue_pos: AtomicU32,
+    dequeue_pos: AtomicU32,
+    /// Bumped by every enqueue.
+    pub enqueued: RawSync,
+    /// Bumped by every dequeue.
+    pub dequeued: RawSync,
+    /// Consumers currently registered to sleep on `enqueued`.
+    pub consumers_waiting: AtomicU32,
+    /// Producers currently registered to sleep on `dequeued`.
+    pub producers_waiting: AtomicU32,
+    slots: [Slot; N],
+}
+
+/// 16 bytes on both sides, as `AtomicI64` is 8-aligned even on i686.
+#[repr(C)]
+struct Slot {
+    sequence: AtomicU32,
+    value: AtomicI64,
+}
+
+#[cfg(target_os = "linux")]
+const _: () = assert!(std::mem::size_of::<SharedMpmcQueue<4>>() == 24 + 4 * 16);
+
+impl<const N: usize> SharedMpmcQueue<N> {
+    pub fn new() -> Self {
+        const { assert!(N.is_power_of_two() && N <= 1 << 30) };
+        Self {
+            enqueue_pos: AtomicU32::new(0),
+            dequeue_pos: AtomicU32::new(0),
+            enqueued: RawSync::new(0),
+            dequeued: RawSync::new(0),
+            consumers_waiting: AtomicU32::new(0),
+            producers_waiting: AtomicU32::new(0),
+            slots: std::array::from_fn(|i| Slot {
+                sequence: AtomicU32::new(i as u32),
+                value: AtomicI64::new(0),
+            }),
+        }
+    }
+
+    /// Number of values enqueued and not yet dequeued. Operations in
+    /// flight may already have moved on.
+    pub fn len(&self) -> usize {
+        let dequeue_pos = self.dequeue_pos.load(Ordering::Acquire);
+        let enqueue_pos = self.enqueue_pos.load(Ordering::Acquire);
+        (enqueue_pos.wrapping_sub(dequeue_pos) as usize).min(N)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends `value`, or hands it back if the queue is full.
+    pub fn try_enqueue(&self, value: i64) -> Result<(), Full> {
+        let mut pos = self.enqueue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos as usize & (N - 1)];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            match sequence.wrapping_sub(pos) as i32 {
+                0 => match self.enqueue_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        slot.value.store(value, Ordering::Relaxed);
+                        slot.sequence.store(pos.wrapping_add(1), Ordering::Release);
+                        Self::signal(&self.enqueued, &self.consumers_waiting);
+                        return Ok(());
+                    }
+                    Err(current) => pos = current,
+                },
+                // The slot still holds the value from one lap ago
+                lag if lag < 0 => return Err(Full(value)),
+                // Another producer claimed `pos` first
+                _ => pos = self.enqueue_pos.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Takes the oldest value, if any.
+    pub fn try_dequeue(&self) -> Option<i64> {
+        let mut pos = self.dequeue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos as usize & (N - 1)];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            match sequence.wrapping_sub(pos.wrapping_add(1)) as i32 {
+                0 => match self.dequeue_pos.compare_exchange_weak(
+                    pos,
+                    pos.wrapping_add(1),
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let value = slot.value.load(Ordering::Relaxed);
+                        // Free the slot for the producer one lap ahead
+                        slot.sequence
+                            .store(pos.wrapping_add(N as u32), Ordering::Release);
+                        Self::signal(&self.dequeued, &self.producers_waiting);
+                        return Some(value);
+                    }
+                    Err(current) => pos = current,
+                },
+                // Nothing has been enqueued at `pos` yet
+                lag if lag < 0 => return None,
+                // Another consumer claimed `pos` first
+                _ => pos = self.dequeue_pos.load(Ordering::Relaxed),
+            }
+        }
+    }
+
+    /// Like `try_enqueue`, but sleeps until a dequeue frees a slot. On
+    /// error `value` was not enqueued.
+    pub fn enqueue_timeout(&self, value: i64, timeout: Duration) -> Result<(), TimedWaitError> {
+        if self.try_enqueue(value).is_ok() {
+            return Ok(());
+        }
+        let deadline = Instant::now() + timeout;
+        let _registered = Registration::new(&self.producers_waiting);
+        loop {
+            let dequeued = self.dequeued.value.load(Ordering::Acquire);
+            if self.try_enqueue(value).is_ok() {
+                return Ok(());
+            }
+            wait_for_change(&self.dequeued, dequeued, deadline)?;
+        }
+    }
+
+    /// Like `try_dequeue`, but sleeps until something is enqueued.
+    pub fn dequeue_timeout(&self, timeout: Duration) -> Result<i64, TimedWaitError> {
+        if let Some(value) = self.try_dequeue() {
+            return Ok(value);
+        }
+        let deadline = Instant::now() + timeout;
+        let _registered = Registration::new(&self.consumers_waiting);
+        loop {
+            let enqueued = self.enqueued.value.load(Ordering::Acquire);
+            if let Some(value) = self.try_dequeue() {
+                return Ok(value);
+            }
+            wait_for_change(&self.enqueued, enqueued, deadline)?;
+        }
+    }
+
+    /// Bumps `futex` and wakes its sleepers, if any have registered in
+    /// `waiting`.
+    fn signal(futex: &RawSync, waiting: &AtomicU32) {
+        futex.value.fetch_add(1, Ordering::Release);
+        // Pairs with the fence in `Registration::new`: either the waiter
+        // sees this operation when it retries, or we see the waiter here.
+        fence(Ordering::SeqCst);
+        if waiting.load(Ordering::Relaxed) != 0 {
+            futex.wake(i32::MAX);
+        }
+    }
+}
+
+impl<const N: usize> Default for SharedMpmcQueue<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// A waiter counted in `consumers_waiting` or `producers_waiting` for as
+/// long as it lives, so leaving early by `?` deregisters as well.
+struct Registration<'a>(&'a AtomicU32);
+
+impl<'a> Registration<'a> {
+    fn new(waiting: &'a AtomicU32) -> Self {
+        waiting.fetch_add(1, Ordering::Relaxed);
+        fence(Ordering::SeqCst);
+        Registration(waiting)
+    }
+}
+
+impl Drop for Registration<'_> {
+    fn drop(&mut self) {
+        self.0.fetch_sub(1, Ordering::Relaxed);
+    }
+}
@@ -0,0 +1,359 @@
+//! The wait/wake primitive every shared-memory lock in this crate is built on.
+//!
+//! On Linux `RawSync` is simply a process-shared `linux_futex::Futex`. macOS
+//! has no public futex, so there it is a `value` word plus a
+//! `PTHREAD_PROCESS_SHARED` mutex and condition variable living next to it in
+//! the shared region. Windows has no process-shared futex either; there the
+//! region holds the word and a name for a kernel event object. Every backend
+//! exposes the same methods and error types. Like
+//! `layout`, this file is included by the child with `#[path]`, which does
+//! not use every item.
+
+#![allow(dead_code, unused_imports)]
+
+#[cfg(target_os = "linux")]
+pub use linux_futex::{TimedWaitError, WaitError};
+
+#[cfg(target_os = "linux")]
+pub type RawSync = linux_futex::Futex<linux_futex::Shared>;
+
+#[cfg(target_os = "macos")]
+pub use self::macos::{RawSync, TimedWaitError, WaitError};
+
+#[cfg(windows)]
+pub use self::windows::{RawSync, TimedWaitError, WaitError};
+
+#[cfg(target_os = "macos")]
+mod macos {
+    use std::cell::UnsafeCell;
+    use std::sync::atomic::{AtomicU32, Ordering};
+    use std::time::{Duration, Instant, SystemTime, UNIX_EPOCH};
+
+    /// Mirrors `linux_futex::WaitError`.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum WaitError {
+        WrongValue,
+        Interrupted,
+    }
+
+    /// Mirrors `linux_futex::TimedWaitError`.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum TimedWaitError {
+        WrongValue,
+        Interrupted,
+        TimedOut,
+    }
+
+    const UNINIT: u32 = 0;
+    const INITIALIZING: u32 = 1;
+    const READY: u32 = 2;
+
+    /// Futex emulation for a shared mapping.
+    ///
+    /// Waiters check `value` while holding `mutex`, and wakers take `mutex`
+    /// before signalling, so a wake can't slip in between the check and the
+    /// sleep. Bitsets are not emulated: `wake_bitset` wakes everyone, which
+    /// callers already tolerate as spurious wakeups.
+    ///
+    /// The pthread objects are initialized on first use rather than in
+    /// `new`, because a process-shared mutex must be initialized at the
+    /// address it is used from and `new` values are moved into place.
+    #[repr(C)]
+    pub struct RawSync {
+        pub value: AtomicU32,
+        init: AtomicU32,
+        mutex: UnsafeCell<libc::pthread_mutex_t>,
+        cond: UnsafeCell<libc::pthread_cond_t>,
+    }
+
+    // SAFETY: the pthread objects are only touched through the pthread API,
+    // which synchronizes across threads and (being process-shared) processes.
+    unsafe impl Sync for RawSync {}
+    unsafe impl Send for RawSync {}
+
+    impl RawSync {
+        pub const fn new(value: u32) -> Self {
+            Self {
+                value: AtomicU32::new(value),
+                init: AtomicU32::new(UNINIT),
+                mutex: UnsafeCell::new(libc::PTHREAD_MUTEX_INITIALIZER),
+                cond: UnsafeCell::new(libc::PTHREAD_COND_INITIALIZER),
+            }
+        }
+
+        pub fn wait(&self, expected_value: u32) -> Result<(), WaitError> {
+            match self.wait_until_inner(expected_value, None) {
+                Ok(()) => Ok(()),
+                Err(TimedWaitError::WrongValue) => Err(WaitError::WrongValue),
+                Err(_) => Err(WaitError::Interrupted),
+            }
+        }
+
+        pub fn wait_for(
+            &self,
+            expected_value: u32,
+            timeout: Duration,
+        ) -> Result<(), TimedWaitError> {
+            self.wait_until_inner(expected_value, Some(Instant::now() + timeout))
+        }
+
+        pub fn wait_bitset(&self, expected_value: u32, _bitset: u32) -> Result<(), WaitError> {
+            self.wait(expected_value)
+        }
+
+        pub fn wait_bitset_until(
+            &self,
+            expected_value: u32,
+            _bitset: u32,
+            deadline: Instant,
+        ) -> Result<(), TimedWaitError> {
+            self.wait_until_inner(expected_value, Some(deadline))
+        }
+
+        /// Wakes one waiter if `n == 1`, otherwise all of them. Unlike the
+        /// futex version the number woken is unknown, so this returns 0.
+        pub fn wake(&self, n: i32) -> i32 {
+            self.ensure_init();
+            unsafe {
+                libc::pthread_mutex_lock(self.mutex.get());
+                if n == 1 {
+                    libc::pthread_cond_signal(self.cond.get());
+                } else {
+                    libc::pthread_cond_broadcast(self.cond.get());
+                }
+                libc::pthread_mutex_unlock(self.mutex.get());
+            }
+            0
+        }
+
+        pub fn wake_bitset(&self, _n: i32, _bitset: u32) -> i32 {
+            self.wake(i32::MAX)
+        }
+
+        fn wait_until_inner(
+            &self,
+            expected_value: u32,
+            deadline: Option<Instant>,
+        ) -> Result<(), TimedWaitError> {
+            self.ensure_init();
+            unsafe {
+                libc::pthread_mutex_lock(self.mutex.get());
+                if self.value.load(Ordering::SeqCst) != expected_value {
+                    libc::pthread_mutex_unlock(self.mutex.get());
+                    return Err(TimedWaitError::WrongValue);
+                }
+                let rc = match deadline {
+                    None => libc::pthread_cond_wait(self.cond.get(), self.mutex.get()),
+                    Some(deadline) => {
+                        let abstime = realtime_deadline(deadline);
+                        libc::pthread_cond_timedwait(self.cond.get(), self.mutex.get(), &abstime)
+                    }
+                };
+                libc::pthread_mutex_unlock(self.mutex.get());
+                match rc {
+                    libc::ETIMEDOUT => Err(TimedWaitError::TimedOut),
+                    _ => Ok(()),
+                }
+            }
+        }
+
+        /// Initializes the pthread objects exactly once, whichever process
+        /// gets here first.
+        fn ensure_init(&self) {
+            if self.init.load(Ordering::Acquire) == READY {
+                return;
+            }
+            if self
+                .init
+                .compare_exchange(UNINIT, INITIALIZING, Ordering::Acquire, Ordering::Acquire)
+                .is_err()
+            {
+                while self.init.load(Ordering::Acquire) != READY {
+                    std::thread::yield_now();
+                }
+                return;
+            }
+
+            unsafe {
+                let mut mutex_attr: libc::pthread_mutexattr_t = std::mem::zeroed();
+                libc::pthread_mutexattr_init(&mut mutex_attr);
+                libc::pthread_mutexattr_setpshared(&mut mutex_attr, libc::PTHREAD_PROCESS_SHARED);
+                libc::pthread_mutex_init(self.mutex.get(), &mutex_attr);
+                libc::pthread_mutexattr_destroy(&mut mutex_attr);
+
+                let mut cond_attr: libc::pthread_condattr_t = std::mem::zeroed();
+                libc::pthread_condattr_init(&mut cond_attr);
+                libc::pthread_condattr_setpshared(&mut cond_attr, libc::PTHREAD_PROCESS_SHARED);
+                libc::pthread_cond_init(self.cond.get(), &cond_attr);
+                libc::pthread_condattr_destroy(&mut cond_attr);
+            }
+            self.init.store(READY, Ordering::Release);
+        }
+    }
+
+    /// `pthread_cond_timedwait` wants a wall-clock deadline.
+    fn realtime_deadline(deadline: Instant) -> libc::timespec {
+        let remaining = deadline.saturating_duration_since(Instant::now());
+        let wall = (SystemTime::now() + remaining)
+            .duration_since(UNIX_EPOCH)
+            .unwrap_or_default();
+        libc::timespec {
+            tv_sec: wall.as_secs() as libc::time_t,
+            tv_nsec: wall.subsec_nanos() as libc::c_long,
+        }
+    }
+}
+
+#[cfg(windows)]
+mod windows {
+    use std::collections::HashMap;
+    use std::sync::atomic::{AtomicU32, AtomicU64, Ordering};
+    use std::sync::{Mutex, OnceLock};
+    use std::time::{Duration, Instant};
+    use windows_sys::Win32::Foundation::{HANDLE, WAIT_TIMEOUT};
+    use windows_sys::Win32::System::Threading::{CreateEventW, SetEvent, WaitForSingleObject};
+
+    /// Mirrors `linux_futex::WaitError`.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum WaitError {
+        WrongValue,
+        Interrupted,
+    }
+
+    /// Mirrors `linux_futex::TimedWaitError`.
+    #[derive(Clone, Copy, PartialEq, Eq, Debug)]
+    pub enum TimedWaitError {
+        WrongValue,
+        Interrupted,
+        TimedOut,
+    }
+
+    /// Longest a waiter sleeps before returning as if woken. An auto-reset
+    /// event releases one waiter per `SetEvent`, so when several wait at
+    /// once the others fall back to this.
+    const MAX_SLICE: Duration = Duration::from_millis(10);
+
+    /// Futex emulation for a shared mapping.
+    ///
+    /// `id` names an auto-reset event (`Local\sharedmem-sync-<id>`) that
+    /// every process opens for itself; it is picked by whichever process
+    /// touches the word first. A waiter opens the event before checking
+    /// `value`, and the event stays signalled until someone waits on it, so
+    /// a wake between the check and the sleep is not lost. Bitsets are not
+    /// emulated and every return may be spurious, which callers tolerate.
+    #[repr(C)]
+    pub struct RawSync {
+        pub value: AtomicU32,
+        id: AtomicU64,
+    }
+
+    impl RawSync {
+        pub const fn new(value: u32) -> Self {
+            Self {
+                value: AtomicU32::new(value),
+                id: AtomicU64::new(0),
+            }
+        }
+
+        pub fn wait(&self, expected_value: u32) -> Result<(), WaitError> {
+            match self.wait_until_inner(expected_value, None) {
+                Ok(()) => Ok(()),
+                Err(TimedWaitError::WrongValue) => Err(WaitError::WrongValue),
+                Err(_) => Err(WaitError::Interrupted),
+            }
+        }
+
+        pub fn wait_for(
+            &self,
+            expected_value: u32,
+            timeout: Duration,
+        ) -> Result<(), TimedWaitError> {
+            self.wait_until_inner(expected_value, Some(Instant::now() + timeout))
+        }
+
+        pub fn wait_bitset(&self, expected_value: u32, _bitset: u32) -> Result<(), WaitError> {
+            self.wait(expected_value)
+        }
+
+        pub fn wait_bitset_until(
+            &self,
+            expected_value: u32,
+            _bitset: u32,
+            deadline: Instant,
+        ) -> Result<(), TimedWaitError> {
+            self.wait_until_inner(expected_value, Some(deadline))
+        }
+
+        /// Wakes at least one waiter. The number woken is unknown, so this
+        /// returns 0.
+        pub fn wake(&self, _n: i32) -> i32 {
+            unsafe { SetEvent(self.event() as HANDLE) };
+            0
+        }
+
+        pub fn wake_bitset(&self, n: i32, _bitset: u32) -> i32 {
+            self.wake(n)
+        }
+
+        fn wait_until_inner(
+            &self,
+            expected_value: u32,
+            deadline: Option<Instant>,
+        ) -> Result<(), TimedWaitError> {
+            let event = self.event();
+            if self.value.load(Ordering::SeqCst) != expected_value {
+                return Err(TimedWaitError::WrongValue);
+            }
+
+            let remaining = match deadline {
+                Some(deadline) => deadline.saturating_duration_since(Instant::now()),
+                None => MAX_SLICE,
+            };
+            if remaining.is_zero() {
+                return Err(TimedWaitError::TimedOut);
+            }
+            let slice = remaining.min(MAX_SLICE);
+            match unsafe { WaitForSingleObject(event as HANDLE, slice.as_millis() as u32) } {
+                WAIT_TIMEOUT if slice == remaining && deadline.is_some() => {
+                    Err(TimedWaitError::TimedOut)
+                }
+                _ => Ok(()),
+            }
+        }
+
+        /// This process's handle to the event, created or opened on first
+        /// use and kept for the life of the process.
+        fn event(&self) -> usize {
+            static HANDLES: OnceLock<Mutex<HashMap<u64, usize>>> = OnceLock::new();
+
+            let id = self.id();
+            let mut handles = HANDLES.get_or_init(Default::default).lock().unwrap();
+            *handles.entry(id).or_insert_with(|| {
+                let name: Vec<u16> = format!("Local\\sharedmem-sync-{:016x}", id)
+                    .encode_utf16()
+                    .chain(Some(0))
+                    .collect();
+                // Opens the existing event if another process created it.
+                unsafe { CreateEventW(std::ptr::null(), 0, 0, name.as_ptr()) as usize }
+            })
+        }
+
+        fn id(&self) -> u64 {
+            static NEXT: AtomicU32 = AtomicU32::new(1);
+
+            let id = self.id.load(Ordering::Acquire);
+            if id != 0 {
+                return id;
+            }
+            let mine =
+                (std::process::id() as u64) << 32 | NEXT.fetch_add(1, Ordering::Relaxed) as u64;
+            match self
+                .id
+                .compare_exchange(0, mine, Ordering::AcqRel, Ordering::Acquire)
+            {
+                Ok(_) => mine,
+                Err(theirs) => theirs,
+            }
+        }
+    }
+}
@@ -0,0 +1,139 @@
+//! Read-only access to a region, for observers that watch the number but
+//! must never write it or take the lock.
+//!
+//! The segment is mapped with `PROT_READ` only, so even a stray write
+//! faults; and `SharedDataReadonly` exposes nothing but loads, so such a
+//! write cannot be expressed in safe code in the first place. Atomic loads
+//! from read-only memory are fine for the lock-free sizes used here; only
+//! read-modify-write operations would need the page to be writable.
+
+use crate::shared::{LockState, LockStats, SharedData, SharedMemError, SharedRegion, check_opened};
+use std::ops::Deref;
+
+/// The observer's view of a `SharedData`: loads only.
+#[repr(transparent)]
+pub struct SharedDataReadonly(SharedData);
+
+impl SharedDataReadonly {
+    pub fn get_number(&self) -> i64 {
+        self.0.get_number()
+    }
+
+    pub fn lock_state(&self) -> LockState {
+        self.0.lock_state()
+    }
+
+    pub fn raw_futex_value(&self) -> i32 {
+        self.0.raw_futex_value()
+    }
+
+    pub fn is_locked(&self) -> bool {
+        self.0.is_locked()
+    }
+
+    pub fn stats(&self) -> LockStats {
+        self.0.stats()
+    }
+}
+
+/// A region mapped read-only by `SharedRegion::open_readonly`. Dropping it
+/// only unmaps; the segment stays with its creator.
+pub struct OwnedSharedDataReadonly {
+    ptr: *mut libc::c_void,
+    len: usize,
+}
+
+impl SharedRegion {
+    /// Maps the region `os_id` read-only, once its creator has initialized
+    /// it, and checks its header. On Linux `os_id` may also be the `fd:<n>`
+    /// handle of an inherited anonymous region.
+    pub fn open_readonly(os_id: &str) -> Result<OwnedSharedDataReadonly, SharedMemError> {
+        let fd = open_fd(os_id).map_err(map_open_failed)?;
+        // SAFETY: `fd` is open. The mapping outlives it, so it is closed
+        // however this ends.
+        let mapped = unsafe { map_readonly(fd) };
+        unsafe { libc::close(fd) };
+        let mapped = mapped.map_err(map_open_failed)?;
+
+        if mapped.len < std::mem::size_of::<SharedData>() {
+            return Err(SharedMemError::SizeMismatch {
+                expected: std::mem::size_of::<SharedData>(),
+                actual: mapped.len,
+            });
+        }
+        // SAFETY: the mapping is large enough and lives in `mapped`.
+        unsafe { check_opened(mapped.ptr as *const u8) }?;
+        Ok(mapped)
+    }
+}
+
+/// A descriptor for `os_id` that this function's caller must close.
+fn open_fd(os_id: &str) -> std::io::Result<libc::c_int> {
+    #[cfg(target_os = "linux")]
+    if let Some(fd) = crate::memfd::parse_handle(os_id) {
+        // Duplicated so the inherited descriptor stays open for others.
+        let fd = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 0) };
+        if fd < 0 {
+            return Err(std::io::Error::last_os_error());
+        }
+        return Ok(fd);
+    }
+    let name = std::ffi::CString::new(os_id)?;
+    let fd = unsafe { libc::shm_open(name.as_ptr(), libc::O_RDONLY, 0) };
+    if fd < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(fd)
+}
+
+/// Maps all of `fd` with `PROT_READ` only.
+///
+/// # Safety
+///
+/// `fd` must be an open descriptor.
+unsafe fn map_readonly(fd: libc::c_int) -> std::io::Result<OwnedSharedDataReadonly> {
+    let mut stat = std::mem::MaybeUninit::<libc::stat>::uninit();
+    if unsafe { libc::fstat(fd, stat.as_mut_ptr()) } != 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    let len = unsafe { stat.assume_init() }.st_size as usize;
+    if len == 0 {
+        return Err(std::io::Error::from_raw_os_error(libc::EINVAL));
+    }
+    let ptr = unsafe {
+        libc::mmap(
+            std::ptr::null_mut(),
+            len,
+            libc::PROT_READ,
+            libc::MAP_SHARED,
+            fd,
+            0,
+        )
+    };
+    if ptr == libc::MAP_FAILED {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(OwnedSharedDataReadonly { ptr, len })
+}
+
+fn map_open_failed(e: std::io::Error) -> SharedMemError {
+    SharedMemError::OpenFailed(shared_memory::ShmemError::MapOpenFailed(
+        e.raw_os_error().unwrap_or(0) as u32,
+    ))
+}
+
+impl Deref for OwnedSharedDataReadonly {
+    type Target = SharedDataReadonly;
+
+    fn deref(&self) -> &SharedDataReadonly {
+        // SAFETY: checked in `open_readonly` and mapped for as long as
+        // `self`; the cast is sound thanks to `repr(transparent)`.
+        unsafe { &*(self.ptr as *const SharedDataReadonly) }
+    }
+}
+
+impl Drop for OwnedSharedDataReadonly {
+    fn drop(&mut self) {
+        unsafe { libc::munmap(self.ptr, self.len) };
+    }
+}
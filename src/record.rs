@@ -0,0 +1,94 @@
+//! A record of named counters living in shared memory, for when a single
+//! `number` is not enough.
+//!
+//! Like `ring`, this file is included by the child with `#[path]`, so it
+//! only depends on `std`.
+
+#![allow(dead_code)]
+
+use std::sync::atomic::{AtomicI64, AtomicU64, Ordering};
+
+/// Counters any process may update at any time, without a lock.
+///
+/// Every field is a fixed-width atomic, which is 8-aligned in the 32-bit
+/// child too, so both sides agree on the layout. Each field is its own
+/// atomic and publishes nothing else, so updates use `Relaxed`: bumping
+/// `requests` never delays or reorders a read of `errors`.
+#[repr(C)]
+pub struct SharedRecord {
+    requests: AtomicU64,
+    errors: AtomicU64,
+    last_value: AtomicI64,
+}
+
+#[cfg(target_os = "linux")]
+const _: () = assert!(std::mem::size_of::<SharedRecord>() == 3 * 8);
+
+/// The fields of a `SharedRecord`, read one after another. Updates racing
+/// with `snapshot` may show up in some fields and not yet in others.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct RecordSnapshot {
+    pub requests: u64,
+    pub errors: u64,
+    pub last_value: i64,
+}
+
+impl SharedRecord {
+    pub const fn new() -> Self {
+        Self {
+            requests: AtomicU64::new(0),
+            errors: AtomicU64::new(0),
+            last_value: AtomicI64::new(0),
+        }
+    }
+
+    pub fn requests(&self) -> u64 {
+        self.requests.load(Ordering::Relaxed)
+    }
+
+    pub fn set_requests(&self, value: u64) {
+        self.requests.store(value, Ordering::Relaxed);
+    }
+
+    /// Adds `n` to `requests`, returning the new count.
+    pub fn add_requests(&self, n: u64) -> u64 {
+        self.requests
+            .fetch_add(n, Ordering::Relaxed)
+            .wrapping_add(n)
+    }
+
+    pub fn errors(&self) -> u64 {
+        self.errors.load(Ordering::Relaxed)
+    }
+
+    pub fn set_errors(&self, value: u64) {
+        self.errors.store(value, Ordering::Relaxed);
+    }
+
+    /// Adds `n` to `errors`, returning the new count.
+    pub fn add_errors(&self, n: u64) -> u64 {
+        self.errors.fetch_add(n, Ordering::Relaxed).wrapping_add(n)
+    }
+
+    pub fn last_value(&self) -> i64 {
+        self.last_value.load(Ordering::Relaxed)
+    }
+
+    pub fn set_last_value(&self, value: i64) {
+        self.last_value.store(value, Ordering::Relaxed);
+    }
+
+    pub fn snapshot(&self) -> RecordSnapshot {
+        RecordSnapshot {
+            requests: self.requests(),
+            errors: self.errors(),
+            last_value: self.last_value(),
+        }
+    }
+}
+
+impl Default for SharedRecord {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,135 @@
+//! Single-producer single-consumer ring buffer living in shared memory.
+//!
+//! Like `layout`, this file is included by the child with `#[path]`, so it
+//! only depends on `std` and `raw_sync`.
+
+#![allow(dead_code)]
+
+use crate::raw_sync::{RawSync, TimedWaitError};
+use std::sync::atomic::{AtomicI64, Ordering};
+use std::time::{Duration, Instant};
+
+/// Returned by `SharedRing::push` when there is no free slot; carries the
+/// rejected value back to the caller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Full(pub i64);
+
+/// A bounded queue of `i64` for exactly one producer and one consumer.
+///
+/// `head` and `tail` are free-running `u32` counters (not `usize`, which is
+/// narrower in the 32-bit child) that double as futex words: a consumer
+/// finding the ring empty sleeps on `tail`, a producer finding it full
+/// sleeps on `head`. `N` must be a power of two so the counters can wrap.
+#[repr(C)]
+pub struct SharedRing<const N: usize> {
+    /// Index of the next slot to pop; only the consumer writes it.
+    pub head: RawSync,
+    /// Index of the next slot to push; only the producer writes it.
+    pub tail: RawSync,
+    slots: [AtomicI64; N],
+}
+
+#[cfg(target_os = "linux")]
+const _: () = assert!(std::mem::size_of::<SharedRing<4>>() == 8 + 4 * 8);
+
+impl<const N: usize> SharedRing<N> {
+    pub fn new() -> Self {
+        const { assert!(N.is_power_of_two() && N <= 1 << 31) };
+        Self {
+            head: RawSync::new(0),
+            tail: RawSync::new(0),
+            slots: std::array::from_fn(|_| AtomicI64::new(0)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        let tail = self.tail.value.load(Ordering::Acquire);
+        let head = self.head.value.load(Ordering::Acquire);
+        tail.wrapping_sub(head) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends `value`, or hands it back if the ring is full.
+    pub fn push(&self, value: i64) -> Result<(), Full> {
+        let tail = self.tail.value.load(Ordering::Relaxed);
+        let head = self.head.value.load(Ordering::Acquire);
+        if tail.wrapping_sub(head) as usize == N {
+            return Err(Full(value));
+        }
+
+        self.slots[tail as usize % N].store(value, Ordering::Relaxed);
+        self.tail
+            .value
+            .store(tail.wrapping_add(1), Ordering::Release);
+        self.tail.wake(1);
+        Ok(())
+    }
+
+    /// Removes the oldest value, if any.
+    pub fn pop(&self) -> Option<i64> {
+        let head = self.head.value.load(Ordering::Relaxed);
+        let tail = self.tail.value.load(Ordering::Acquire);
+        if head == tail {
+            return None;
+        }
+
+        let value = self.slots[head as usize % N].load(Ordering::Relaxed);
+        self.head
+            .value
+            .store(head.wrapping_add(1), Ordering::Release);
+        self.head.wake(1);
+        Some(value)
+    }
+
+    /// Like `push`, but sleeps until the consumer frees a slot.
+    pub fn push_blocking(&self, value: i64, timeout: Duration) -> Result<(), TimedWaitError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            // Read `head` before trying so a pop in between wakes us at once.
+            let head = self.head.value.load(Ordering::Acquire);
+            if self.push(value).is_ok() {
+                return Ok(());
+            }
+            wait_for_change(&self.head, head, deadline)?;
+        }
+    }
+
+    /// Like `pop`, but sleeps until the producer pushes a value.
+    pub fn pop_blocking(&self, timeout: Duration) -> Result<i64, TimedWaitError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let tail = self.tail.value.load(Ordering::Acquire);
+            if let Some(value) = self.pop() {
+                return Ok(value);
+            }
+            wait_for_change(&self.tail, tail, deadline)?;
+        }
+    }
+}
+
+impl<const N: usize> Default for SharedRing<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// Sleeps while `futex` still holds `expected`, giving up at `deadline`.
+pub(crate) fn wait_for_change(
+    futex: &RawSync,
+    expected: u32,
+    deadline: Instant,
+) -> Result<(), TimedWaitError> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(TimedWaitError::TimedOut);
+    }
+    match futex.wait_for(expected, remaining) {
+        Ok(()) | Err(TimedWaitError::WrongValue) | Err(TimedWaitError::TimedOut) => Ok(()),
+        Err(TimedWaitError::Interrupted) => Err(TimedWaitError::Interrupted),
+    }
+}
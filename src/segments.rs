@@ -0,0 +1,72 @@
+//! Several independent regions held together, for fan-out work where each
+//! child gets a region of its own instead of sharing one.
+
+use crate::shared::{OwnedSharedData, SharedMemError, SharedRegionBuilder};
+use std::ops::Index;
+
+/// A fixed number of regions, each with its own lock, number and OS ID.
+/// Dropping the set drops every region in it, so the segments this process
+/// created are removed together.
+pub struct SegmentSet {
+    segments: Vec<OwnedSharedData>,
+}
+
+impl SegmentSet {
+    /// Creates `count` regions with randomly generated OS IDs.
+    pub fn create(count: usize) -> Result<Self, SharedMemError> {
+        Self::build(count, &crate::SharedRegion::builder())
+    }
+
+    /// Creates `count` regions from the same settings. The builder should
+    /// not fix an OS ID: only the first region could be created under it.
+    pub fn build(count: usize, builder: &SharedRegionBuilder) -> Result<Self, SharedMemError> {
+        let segments = (0..count)
+            .map(|_| builder.clone().build())
+            .collect::<Result<_, _>>()?;
+        Ok(SegmentSet { segments })
+    }
+
+    pub fn len(&self) -> usize {
+        self.segments.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.segments.is_empty()
+    }
+
+    pub fn get(&self, index: usize) -> Option<&OwnedSharedData> {
+        self.segments.get(index)
+    }
+
+    pub fn iter(&self) -> std::slice::Iter<'_, OwnedSharedData> {
+        self.segments.iter()
+    }
+
+    /// The OS ID of every region, in order, to hand one to each child.
+    pub fn os_ids(&self) -> Vec<&str> {
+        self.iter().map(OwnedSharedData::os_id).collect()
+    }
+
+    /// The number in every region, in order. Each is read on its own, so
+    /// regions still being worked on may already have moved on.
+    pub fn numbers(&self) -> Vec<i64> {
+        self.iter().map(|segment| segment.get_number()).collect()
+    }
+}
+
+impl Index<usize> for SegmentSet {
+    type Output = OwnedSharedData;
+
+    fn index(&self, index: usize) -> &OwnedSharedData {
+        &self.segments[index]
+    }
+}
+
+impl<'a> IntoIterator for &'a SegmentSet {
+    type Item = &'a OwnedSharedData;
+    type IntoIter = std::slice::Iter<'a, OwnedSharedData>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
@@ -1,79 +1,2915 @@
-use linux_futex::{Futex, Shared, TimedWaitError, WaitError};
-use std::sync::atomic::{AtomicI64, Ordering};
-use std::time::Duration;
+use crate::layout::{
+    CACHE_LINE, CacheAligned, Header, LayoutMismatch, PAYLOAD_ALIGN, PAYLOAD_SIZE, PROTECTED_WORDS,
+    Phase, READY_SENTINEL, STATUS_MESSAGE_LEN, TRACKED_WAITERS,
+};
+use crate::raw_sync::{RawSync, TimedWaitError, WaitError};
+use shared_memory::{Shmem, ShmemConf};
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::ops::{Deref, DerefMut};
+use std::sync::OnceLock;
+use std::sync::atomic::{
+    AtomicBool, AtomicI32, AtomicI64, AtomicU8, AtomicU32, AtomicU64, Ordering, fence,
+};
+use std::time::{Duration, Instant};
 
 #[repr(C)]
 pub struct SharedData {
-    pub futex: Futex<Shared>,
-    pub number: AtomicI64,
+    /// Magic and layout version, checked by the child before anything else.
+    pub header: Header,
+    /// On its own cache line, as is `number`, so a process storing to
+    /// `number` does not slow down one spinning or sleeping on the lock.
+    pub futex: CacheAligned<FutexWord>,
+    /// PID of the process holding the lock, or 0 when unlocked. Lets a
+    /// waiter notice that the holder died without releasing it.
+    pub owner_pid: AtomicI32,
+    /// Set when a lock guard is dropped while its thread panics, since the
+    /// protected data may then be half-updated. See `lock_guard`.
+    pub poisoned: AtomicBool,
+    /// Set by `request_stop` to tell attached processes to give up. Kept
+    /// apart from `number` so that stopping leaves the result readable.
+    pub should_stop: AtomicBool,
+    /// Thread holding the lock and threads sleeping on it, 0 when unknown,
+    /// as recorded by processes that called `set_deadlock_tracking`. They
+    /// share the lock's padding, so the struct did not grow. See
+    /// `deadlock::detect_deadlock`.
+    pub owner_tid: AtomicI32,
+    pub waiter_tids: [AtomicI32; TRACKED_WAITERS],
+    pub number: CacheAligned<AtomicI64>,
+    /// Bumped by every `set_number`, so a reader can tell a rewrite of the
+    /// same value from no write at all. See `get_number_versioned`.
+    pub number_generation: AtomicU64,
+    /// What `number` started at, 100 unless set with `set_initial_number`;
+    /// `reset` goes back to it.
+    pub initial_number: AtomicI64,
+    /// More values protected by `futex`; see `with_locked`.
+    pub words: [AtomicI64; PROTECTED_WORDS],
+    /// An `f64` kept as its bit pattern, as there is no `AtomicF64`; see
+    /// `get_float`. Not protected by `futex`.
+    pub float_bits: AtomicU64,
+    /// Index of the child whose turn it is to work on `number`. Each child
+    /// waits on its own futex bit so passing the turn wakes only the next one.
+    pub turn: RawSync,
+    /// Bumped by `notify_change` so `wait_until` callers can sleep until
+    /// `number` may have changed.
+    pub change_seq: RawSync,
+    /// Start-up handshake between the parent and its children, a `Phase`;
+    /// see `begin_handshake`.
+    pub phase: RawSync,
+    /// Children that have reported in since `begin_handshake`.
+    pub attached_children: RawSync,
+    /// Read-mostly copy of the result, so observers can read it without
+    /// serializing on `futex`.
+    pub published: RwSharedData,
+    /// Lock statistics, updated by every process that takes `futex`. See
+    /// `stats`.
+    pub lock_acquisitions: AtomicU64,
+    pub lock_contended: AtomicU64,
+    pub total_wait_nanos: AtomicU64,
+    /// When the holder took `futex`, on its own process's clock: only the
+    /// holder reads it back, in `try_unlock`, to add the hold to
+    /// `total_hold_nanos` and `max_hold_nanos`.
+    pub locked_at_nanos: AtomicU64,
+    pub total_hold_nanos: AtomicU64,
+    pub max_hold_nanos: AtomicU64,
+    /// Ticket lock used by `lock_fair_timeout`: each caller takes the next
+    /// ticket and waits until `now_serving` reaches it. Both wrap around.
+    pub next_ticket: AtomicU32,
+    pub now_serving: RawSync,
+    /// Tickets whose waiter timed out, as bits keyed like `turn_bit`.
+    pub abandoned_tickets: AtomicU32,
+    /// Wall-clock time of the last `beat`, in nanoseconds since the Unix
+    /// epoch, or 0 if nobody has beaten yet. See `peer_alive`.
+    pub last_heartbeat_nanos: AtomicU64,
+    /// How long the last child to finish spent on its update with the lock
+    /// held, in nanoseconds, or 0 before any has. See `child_compute_time`.
+    pub child_compute_nanos: AtomicU64,
+    /// Result code and message left by `set_status`, typically by a child
+    /// just before it exits. The message is UTF-8, padded with zeros.
+    pub status: AtomicI32,
+    pub status_message: [AtomicU8; STATUS_MESSAGE_LEN],
+    /// Set to `READY_SENTINEL` last by `init_in_place`; nothing else may be
+    /// read until it is.
+    pub ready: AtomicU8,
+}
+
+/// Snapshot of the lock counters in `SharedData`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockStats {
+    /// Times the lock was taken, by any process.
+    pub acquisitions: u64,
+    /// Acquisitions that found the lock already held.
+    pub contended: u64,
+    /// Total time spent waiting for the lock across all acquisitions.
+    pub total_wait: Duration,
+    /// Total time the lock was held, over every release so far.
+    pub total_hold: Duration,
+    /// Longest single hold released so far.
+    pub max_hold: Duration,
+}
+
+impl LockStats {
+    /// Mean hold time per acquisition, or `None` before the first. A hold
+    /// still in progress counts as an acquisition but adds no time yet.
+    pub fn average_hold(&self) -> Option<Duration> {
+        let nanos = self
+            .total_hold
+            .as_nanos()
+            .checked_div(self.acquisitions as u128)?;
+        Some(Duration::from_nanos(nanos as u64))
+    }
+}
+
+/// Who holds the lock, as seen by `SharedData::lock_state`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum LockState {
+    Unlocked,
+    /// `owner_pid` is 0 if the holder has not recorded itself yet.
+    Locked {
+        owner_pid: i32,
+    },
+}
+
+/// What the lock looked like while a `lock_timeout_watched` caller was
+/// still waiting for it, for working out why a holder hangs.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct LockDump {
+    /// How long the caller had been waiting.
+    pub waited: Duration,
+    /// The futex word, see `SharedData::raw_futex_value`.
+    pub futex_value: i32,
+    /// The recorded holder, 0 if none has recorded itself.
+    pub owner_pid: i32,
+    /// Whether `owner_pid` still exists; `None` without a recorded holder.
+    pub owner_alive: Option<bool>,
+    pub number: i64,
+}
+
+impl std::fmt::Display for LockDump {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "lock still not acquired after {:?}: futex value {}, owner ",
+            self.waited, self.futex_value
+        )?;
+        match self.owner_alive {
+            None => write!(f, "not recorded")?,
+            Some(true) => write!(f, "{} (alive)", self.owner_pid)?,
+            Some(false) => write!(f, "{} (gone)", self.owner_pid)?,
+        }
+        write!(f, ", number {}", self.number)
+    }
+}
+
+/// How long `SharedData::open` waits for the creator to set `ready`.
+const READY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Rounds `lock` and the timed lock calls spin before sleeping on the
+/// futex, unless changed with `SharedData::set_spin_rounds`. Round `r`
+/// spins `2^r` times, so the default rounds add up to a few microseconds at
+/// most: enough to ride out a short critical section on another core
+/// without the syscall, too little to matter when the holder keeps the lock
+/// longer.
+pub const DEFAULT_SPIN_ROUNDS: u32 = 7;
+
+/// The longest single round, so large settings cannot overflow the shift.
+const MAX_SPIN_SHIFT: u32 = 16;
+
+static SPIN_ROUNDS: AtomicU32 = AtomicU32::new(DEFAULT_SPIN_ROUNDS);
+
+/// Longest single futex wait in `SharedData::lock_cancelable`, and so about
+/// the longest it takes to notice its cancel flag.
+pub const CANCEL_POLL: Duration = Duration::from_millis(10);
+
+/// Whether this process records owners and waiters in `owner_tid` and
+/// `waiter_tids`. See `SharedData::set_deadlock_tracking`.
+static DEADLOCK_TRACKING: AtomicBool = AtomicBool::new(false);
+
+/// Shortest and longest pause between attempts in `SharedData::lock_async`.
+#[cfg(feature = "async")]
+const ASYNC_MIN_BACKOFF: Duration = Duration::from_micros(50);
+#[cfg(feature = "async")]
+const ASYNC_MAX_BACKOFF: Duration = Duration::from_millis(10);
+
+/// Everything that can go wrong while attaching to or locking the shared
+/// region.
+#[derive(Debug)]
+pub enum SharedMemError {
+    /// The deadline passed before the lock (or awaited condition) was
+    /// obtained.
+    Timeout,
+    /// A signal interrupted the futex wait.
+    Interrupted,
+    /// The lock was held by a process that no longer exists. It has been
+    /// forcibly released, but the data it protected may be inconsistent.
+    RecoveredFromDeadOwner { owner_pid: i32 },
+    /// The region was written by a peer built with a different layout.
+    LayoutMismatch(LayoutMismatch),
+    /// The creator never finished initializing the region.
+    NotInitialized,
+    /// The shared memory segment could not be created or opened.
+    OpenFailed(shared_memory::ShmemError),
+    /// The owner of the region asked everyone attached to it to stop.
+    Stopped,
+    /// An existing segment is `actual` bytes, short of the `expected`
+    /// size of a `SharedData`.
+    SizeMismatch { expected: usize, actual: usize },
+    /// The region was written by a peer that stores integers in the other
+    /// byte order, so none of its values can be read as they are.
+    EndianMismatch,
+    /// Waiting for a child process failed. Only the parent, which has
+    /// children to wait for, runs into this.
+    ChildWait(std::io::Error),
+    /// The cancel flag passed to `lock_cancelable` was set before the lock
+    /// was obtained.
+    Canceled,
+}
+
+#[allow(dead_code)]
+impl SharedMemError {
+    /// Process exit code the child reports this error with, so the parent
+    /// can tell failure modes apart without parsing output.
+    pub fn exit_code(&self) -> u8 {
+        match self {
+            SharedMemError::Timeout => 10,
+            SharedMemError::Interrupted => 11,
+            SharedMemError::RecoveredFromDeadOwner { .. } => 12,
+            SharedMemError::LayoutMismatch(_) => 13,
+            SharedMemError::NotInitialized => 14,
+            SharedMemError::OpenFailed(_) => 15,
+            SharedMemError::Stopped => 16,
+            SharedMemError::SizeMismatch { .. } => 17,
+            SharedMemError::EndianMismatch => 18,
+            SharedMemError::ChildWait(_) => 19,
+            SharedMemError::Canceled => 20,
+        }
+    }
+
+    /// Inverse of `exit_code`, for describing how a child failed.
+    pub fn describe_exit_code(code: i32) -> Option<&'static str> {
+        Some(match code {
+            10 => "timed out",
+            11 => "interrupted",
+            12 => "recovered a lock from a dead owner",
+            13 => "shared memory layout mismatch",
+            14 => "shared memory not initialized",
+            15 => "failed to open shared memory",
+            16 => "stopped by the parent",
+            17 => "shared memory region too small",
+            18 => "shared memory byte order mismatch",
+            19 => "failed to wait for a child process",
+            20 => "canceled",
+            _ => return None,
+        })
+    }
+}
+
+impl std::fmt::Display for SharedMemError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            SharedMemError::Timeout => write!(f, "timed out"),
+            SharedMemError::Interrupted => write!(f, "interrupted by a signal"),
+            SharedMemError::RecoveredFromDeadOwner { owner_pid } => write!(
+                f,
+                "lock owner {} died while holding the lock; shared data may be inconsistent",
+                owner_pid
+            ),
+            SharedMemError::LayoutMismatch(mismatch) => write!(f, "{}", mismatch),
+            SharedMemError::NotInitialized => {
+                write!(f, "shared memory was never marked ready by its creator")
+            }
+            SharedMemError::OpenFailed(e) => write!(f, "failed to open shared memory: {}", e),
+            SharedMemError::Stopped => write!(f, "stopped by the owner of the shared memory"),
+            SharedMemError::SizeMismatch { expected, actual } => write!(
+                f,
+                "shared memory region is {} bytes, expected at least {}",
+                actual, expected
+            ),
+            SharedMemError::EndianMismatch => {
+                write!(f, "shared memory was written with the other byte order")
+            }
+            SharedMemError::ChildWait(e) => write!(f, "failed to wait for a child: {}", e),
+            SharedMemError::Canceled => write!(f, "canceled while waiting for the lock"),
+        }
+    }
+}
+
+impl std::error::Error for SharedMemError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            SharedMemError::OpenFailed(e) => Some(e),
+            SharedMemError::ChildWait(e) => Some(e),
+            _ => None,
+        }
+    }
+}
+
+/// Why `SharedData::try_unlock` refused to release the lock.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum UnlockError {
+    /// Nobody holds the lock, e.g. it was already released.
+    NotLocked,
+    /// Another process holds the lock (0 if it has not recorded itself yet).
+    WrongOwner { owner_pid: i32 },
+}
+
+impl std::fmt::Display for UnlockError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            UnlockError::NotLocked => write!(f, "unlock called but the lock is not held"),
+            UnlockError::WrongOwner { owner_pid } => write!(
+                f,
+                "unlock called by {} but the lock is held by {}",
+                std::process::id(),
+                owner_pid
+            ),
+        }
+    }
+}
+
+impl std::error::Error for UnlockError {}
+
+// `WrongValue` means the futex changed before we could sleep on it; like an
+// interruption, the wait ended early and the caller has to re-check.
+impl From<TimedWaitError> for SharedMemError {
+    fn from(e: TimedWaitError) -> Self {
+        match e {
+            TimedWaitError::TimedOut => SharedMemError::Timeout,
+            TimedWaitError::Interrupted | TimedWaitError::WrongValue => SharedMemError::Interrupted,
+        }
+    }
+}
+
+impl From<WaitError> for SharedMemError {
+    fn from(e: WaitError) -> Self {
+        match e {
+            WaitError::Interrupted | WaitError::WrongValue => SharedMemError::Interrupted,
+        }
+    }
+}
+
+impl From<LayoutMismatch> for SharedMemError {
+    fn from(e: LayoutMismatch) -> Self {
+        SharedMemError::LayoutMismatch(e)
+    }
+}
+
+impl From<shared_memory::ShmemError> for SharedMemError {
+    fn from(e: shared_memory::ShmemError) -> Self {
+        SharedMemError::OpenFailed(e)
+    }
+}
+
+const _: () = assert!(std::mem::size_of::<AtomicI64>() == PAYLOAD_SIZE);
+const _: () = assert!(std::mem::align_of::<AtomicI64>() == PAYLOAD_ALIGN);
+
+// The padding has to survive on both sides of the architecture boundary.
+// Only the Linux size is pinned, since `RawSync` is bigger elsewhere.
+const _: () = assert!(
+    std::mem::align_of::<SharedData>() == crate::layout::SHARED_DATA_ALIGN,
+    "SharedData alignment no longer matches layout::SHARED_DATA_ALIGN"
+);
+const _: () = assert!(
+    std::mem::offset_of!(SharedData, number) - std::mem::offset_of!(SharedData, futex)
+        >= CACHE_LINE
+);
+#[cfg(target_os = "linux")]
+const _: () = assert!(
+    std::mem::size_of::<SharedData>() == crate::layout::SHARED_DATA_SIZE,
+    "SharedData size no longer matches layout::SHARED_DATA_SIZE"
+);
+
+impl Default for SharedData {
+    fn default() -> Self {
+        Self::new()
+    }
 }
 
 #[allow(dead_code)]
 impl SharedData {
     pub fn new() -> Self {
         Self {
-            futex: Futex::new(0),
-            number: AtomicI64::new(100),
+            header: Header::new::<SharedData>(),
+            futex: CacheAligned::new(FutexWord::new(0)),
+            owner_pid: AtomicI32::new(0),
+            poisoned: AtomicBool::new(false),
+            should_stop: AtomicBool::new(false),
+            owner_tid: AtomicI32::new(0),
+            waiter_tids: [const { AtomicI32::new(0) }; TRACKED_WAITERS],
+            number: CacheAligned::new(AtomicI64::new(100)),
+            number_generation: AtomicU64::new(0),
+            initial_number: AtomicI64::new(100),
+            words: [const { AtomicI64::new(0) }; PROTECTED_WORDS],
+            float_bits: AtomicU64::new(0),
+            turn: RawSync::new(0),
+            change_seq: RawSync::new(0),
+            phase: RawSync::new(Phase::Created as u32),
+            attached_children: RawSync::new(0),
+            published: RwSharedData::new(100),
+            lock_acquisitions: AtomicU64::new(0),
+            lock_contended: AtomicU64::new(0),
+            total_wait_nanos: AtomicU64::new(0),
+            locked_at_nanos: AtomicU64::new(0),
+            total_hold_nanos: AtomicU64::new(0),
+            max_hold_nanos: AtomicU64::new(0),
+            next_ticket: AtomicU32::new(0),
+            now_serving: RawSync::new(0),
+            abandoned_tickets: AtomicU32::new(0),
+            last_heartbeat_nanos: AtomicU64::new(0),
+            child_compute_nanos: AtomicU64::new(0),
+            status: AtomicI32::new(0),
+            status_message: [const { AtomicU8::new(0) }; STATUS_MESSAGE_LEN],
+            ready: AtomicU8::new(READY_SENTINEL),
+        }
+    }
+
+    /// Initializes a `SharedData` directly in (possibly shared) memory.
+    ///
+    /// Every field is written before `ready` is set to `READY_SENTINEL`
+    /// with `Release`, so a peer that observes the sentinel with an
+    /// `Acquire` load (as `open` does) also observes the fully initialized
+    /// struct. Freshly created segments are zero-filled, which reads as "not
+    /// ready".
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must be valid for writes of `size_of::<SharedData>()` bytes and
+    /// suitably aligned, and no other process may be using the region as an
+    /// initialized `SharedData` yet.
+    pub unsafe fn init_in_place(ptr: *mut SharedData) {
+        unsafe { Self::init_raw(ptr.cast()) }
+    }
+
+    /// `init_in_place` for memory typed as what it is before this runs:
+    /// uninitialized. Each field, down to those of nested structs, is
+    /// written where it lives, so no `SharedData` is built on the stack and
+    /// copied over, no futex word is moved after it is written, and padding
+    /// keeps whatever the segment held (zeros, for a fresh one).
+    ///
+    /// # Safety
+    ///
+    /// As for `init_in_place`.
+    pub unsafe fn init_raw(ptr: *mut std::mem::MaybeUninit<SharedData>) {
+        unsafe { Self::init_raw_with(ptr, |_| {}) }
+    }
+
+    /// `init_raw`, calling `init` on the initialized struct just before
+    /// `ready` is set, so whatever it stores is published along with the
+    /// rest and no peer ever sees the defaults. `init` must leave `ready`
+    /// alone: peers waiting for the region may be reading it meanwhile,
+    /// which is also why it only gets a shared reference. Writes `init`
+    /// makes with `set_number` are not counted: `number_generation` is
+    /// back to 0 when the region becomes ready.
+    ///
+    /// # Safety
+    ///
+    /// As for `init_in_place`.
+    pub unsafe fn init_raw_with(
+        ptr: *mut std::mem::MaybeUninit<SharedData>,
+        init: impl FnOnce(&SharedData),
+    ) {
+        use std::ptr::addr_of_mut;
+
+        let ptr = ptr.cast::<SharedData>();
+        unsafe {
+            addr_of_mut!((*ptr).ready).write(AtomicU8::new(0));
+            addr_of_mut!((*ptr).header).write(Header::new::<SharedData>());
+            addr_of_mut!((*ptr).futex.0).write(FutexWord::new(0));
+            addr_of_mut!((*ptr).owner_pid).write(AtomicI32::new(0));
+            addr_of_mut!((*ptr).poisoned).write(AtomicBool::new(false));
+            addr_of_mut!((*ptr).should_stop).write(AtomicBool::new(false));
+            addr_of_mut!((*ptr).owner_tid).write(AtomicI32::new(0));
+            addr_of_mut!((*ptr).waiter_tids).write([const { AtomicI32::new(0) }; TRACKED_WAITERS]);
+            addr_of_mut!((*ptr).number.0).write(AtomicI64::new(100));
+            addr_of_mut!((*ptr).number_generation).write(AtomicU64::new(0));
+            addr_of_mut!((*ptr).initial_number).write(AtomicI64::new(100));
+            addr_of_mut!((*ptr).words).write([const { AtomicI64::new(0) }; PROTECTED_WORDS]);
+            addr_of_mut!((*ptr).float_bits).write(AtomicU64::new(0));
+            addr_of_mut!((*ptr).turn).write(RawSync::new(0));
+            addr_of_mut!((*ptr).change_seq).write(RawSync::new(0));
+            addr_of_mut!((*ptr).phase).write(RawSync::new(Phase::Created as u32));
+            addr_of_mut!((*ptr).attached_children).write(RawSync::new(0));
+            addr_of_mut!((*ptr).published.readers).write(RawSync::new(0));
+            addr_of_mut!((*ptr).published.writer).write(RawSync::new(0));
+            addr_of_mut!((*ptr).published.upgrader).write(RawSync::new(0));
+            addr_of_mut!((*ptr).published.number).write(AtomicI64::new(100));
+            addr_of_mut!((*ptr).lock_acquisitions).write(AtomicU64::new(0));
+            addr_of_mut!((*ptr).lock_contended).write(AtomicU64::new(0));
+            addr_of_mut!((*ptr).total_wait_nanos).write(AtomicU64::new(0));
+            addr_of_mut!((*ptr).locked_at_nanos).write(AtomicU64::new(0));
+            addr_of_mut!((*ptr).total_hold_nanos).write(AtomicU64::new(0));
+            addr_of_mut!((*ptr).max_hold_nanos).write(AtomicU64::new(0));
+            addr_of_mut!((*ptr).next_ticket).write(AtomicU32::new(0));
+            addr_of_mut!((*ptr).now_serving).write(RawSync::new(0));
+            addr_of_mut!((*ptr).abandoned_tickets).write(AtomicU32::new(0));
+            addr_of_mut!((*ptr).last_heartbeat_nanos).write(AtomicU64::new(0));
+            addr_of_mut!((*ptr).child_compute_nanos).write(AtomicU64::new(0));
+            addr_of_mut!((*ptr).status).write(AtomicI32::new(0));
+            addr_of_mut!((*ptr).status_message)
+                .write([const { AtomicU8::new(0) }; STATUS_MESSAGE_LEN]);
+            init(&*ptr);
+            (*ptr).number_generation.store(0, Ordering::Relaxed);
+            (*ptr).ready.store(READY_SENTINEL, Ordering::Release);
+        }
+    }
+
+    /// Waits for the creator to finish `init_in_place`, then returns the
+    /// shared data.
+    ///
+    /// # Safety
+    ///
+    /// `ptr` must point to a mapping of at least `size_of::<SharedData>()`
+    /// bytes that stays mapped for `'a`.
+    pub unsafe fn open<'a>(ptr: *const SharedData) -> Result<&'a SharedData, SharedMemError> {
+        // Only `ready` is touched until it holds the sentinel.
+        let ready = unsafe { &(*ptr).ready };
+        let deadline = Instant::now() + READY_TIMEOUT;
+
+        while ready.load(Ordering::Acquire) != READY_SENTINEL {
+            if Instant::now() >= deadline {
+                return Err(SharedMemError::NotInitialized);
+            }
+            std::thread::yield_now();
         }
+        Ok(unsafe { &*ptr })
     }
 
+    /// Reads the number with `SeqCst`, see `get_number_with` for weaker
+    /// orderings.
     pub fn get_number(&self) -> i64 {
-        self.number.load(Ordering::SeqCst)
+        self.get_number_with(Ordering::SeqCst)
     }
 
+    /// Stores `value` and bumps `number_generation`, even if `value` is
+    /// what was already there. Writes made through a lock guard are not
+    /// counted. Uses `SeqCst`, see `set_number_with` for weaker orderings.
     pub fn set_number(&self, value: i64) {
-        self.number.store(value, Ordering::SeqCst);
+        self.set_number_with(value, Ordering::SeqCst);
+    }
+
+    /// `get_number` with a chosen ordering, which panics for `Release` and
+    /// `AcqRel` like any atomic load.
+    ///
+    /// Under the lock the ordering does not matter: taking and releasing it
+    /// already orders every access made while holding it, so `Relaxed` is
+    /// enough there. Outside the lock, `Acquire` paired with a `Release` (or
+    /// stronger) store still makes everything written before that store
+    /// visible. `Relaxed` gives only the value itself, which is fine for a
+    /// counter but not for deciding that other fields are ready. `SeqCst`
+    /// is needed only when several processes must agree on the order of
+    /// stores to `number` and to other atomics, as in Dekker-style flags.
+    pub fn get_number_with(&self, order: Ordering) -> i64 {
+        self.number.load(order)
+    }
+
+    /// `set_number` with a chosen ordering for the store, which panics for
+    /// `Acquire` and `AcqRel` like any atomic store. The same rules as for
+    /// `get_number_with` apply; `number_generation` is bumped as usual.
+    pub fn set_number_with(&self, value: i64, order: Ordering) {
+        self.number.store(value, order);
+        self.bump_generation();
+    }
+
+    /// Stores `new` if the number is still `expected`, without taking the
+    /// lock. Returns the previous value on success and the actual one on
+    /// failure; only a success bumps `number_generation`.
+    ///
+    /// This only composes with writers that also bypass the lock. A lock
+    /// holder writing through its guard does not expect `number` to change
+    /// underneath it.
+    pub fn compare_and_set(&self, expected: i64, new: i64) -> Result<i64, i64> {
+        let previous =
+            self.number
+                .compare_exchange(expected, new, Ordering::SeqCst, Ordering::SeqCst)?;
+        self.bump_generation();
+        Ok(previous)
+    }
+
+    /// Adds `delta` to the number in one atomic step, without the lock,
+    /// and returns the previous value. Wraps on overflow, like every RMW
+    /// below; each bumps `number_generation`, and the caveat of
+    /// `compare_and_set` about lock holders applies to all of them.
+    pub fn fetch_add(&self, delta: i64) -> i64 {
+        let previous = self.number.fetch_add(delta, Ordering::SeqCst);
+        self.bump_generation();
+        previous
+    }
+
+    pub fn fetch_sub(&self, delta: i64) -> i64 {
+        let previous = self.number.fetch_sub(delta, Ordering::SeqCst);
+        self.bump_generation();
+        previous
+    }
+
+    /// Raises the number to `value` if it is lower, returning the previous
+    /// value.
+    pub fn fetch_max(&self, value: i64) -> i64 {
+        let previous = self.number.fetch_max(value, Ordering::SeqCst);
+        self.bump_generation();
+        previous
+    }
+
+    /// Lowers the number to `value` if it is higher, returning the previous
+    /// value.
+    pub fn fetch_min(&self, value: i64) -> i64 {
+        let previous = self.number.fetch_min(value, Ordering::SeqCst);
+        self.bump_generation();
+        previous
+    }
+
+    fn bump_generation(&self) {
+        // Orders the write to `number` before the bump, pairing with the
+        // Acquire load in `get_number_versioned`.
+        fence(Ordering::Release);
+        self.number_generation.fetch_add(1, Ordering::Relaxed);
+    }
+
+    /// The number together with a generation at least as old as it.
+    ///
+    /// A concurrent `set_number` may be caught between its two steps, in
+    /// which case the value is newer than the generation; that only makes
+    /// a later `has_changed_since` report a change the caller already saw,
+    /// never miss one.
+    pub fn get_number_versioned(&self) -> (i64, u64) {
+        let generation = self.number_generation.load(Ordering::Acquire);
+        (self.get_number(), generation)
+    }
+
+    /// Whether `set_number` was called since `generation` was read with
+    /// `get_number_versioned`, whatever values it stored.
+    pub fn has_changed_since(&self, generation: u64) -> bool {
+        self.number_generation.load(Ordering::Acquire) != generation
+    }
+
+    /// Makes `value` the number `reset` goes back to, and sets the number
+    /// to it now.
+    pub fn set_initial_number(&self, value: i64) {
+        self.initial_number.store(value, Ordering::Relaxed);
+        self.set_number(value);
+    }
+
+    /// Puts a region that has been used back the way it started, for
+    /// another run without recreating the mapping: under the lock, `number`
+    /// goes back to its initial value, `number_generation` and the lock
+    /// counters in `stats` to zero, and a `request_stop` is forgotten.
+    /// Everything else, the words and the
+    /// float included, is left alone, and waiters are woken as for any
+    /// change of the number.
+    ///
+    /// A reader holding a generation from before the reset may see the new
+    /// count reach it again and miss a change, so reset between runs, not
+    /// in the middle of one.
+    pub fn reset(&self) {
+        // Cannot fail, see `lock`
+        let _ = self.lock();
+        self.number.store(
+            self.initial_number.load(Ordering::Relaxed),
+            Ordering::Relaxed,
+        );
+        self.number_generation.store(0, Ordering::Relaxed);
+        self.lock_acquisitions.store(0, Ordering::Relaxed);
+        self.lock_contended.store(0, Ordering::Relaxed);
+        self.total_wait_nanos.store(0, Ordering::Relaxed);
+        self.total_hold_nanos.store(0, Ordering::Relaxed);
+        self.max_hold_nanos.store(0, Ordering::Relaxed);
+        // A start in the future makes this hold count as 0, so the
+        // release below does not bring the counters back to life.
+        self.locked_at_nanos.store(u64::MAX, Ordering::Relaxed);
+        self.should_stop.store(false, Ordering::Release);
+        self.unlock();
+        self.notify_change();
+    }
+
+    /// Reads the shared float, 0.0 in a fresh region.
+    ///
+    /// It is stored with `to_bits` in a `u64`, which is 8 bytes and 8-aligned
+    /// on both sides, so the layout stays the same for the 32-bit child.
+    /// Values come back bit for bit: the sign of zero survives, and so does
+    /// the payload of a NaN, as loads and stores never go through a float
+    /// register that could quiet it.
+    pub fn get_float(&self) -> f64 {
+        f64::from_bits(self.float_bits.load(Ordering::SeqCst))
+    }
+
+    pub fn set_float(&self, value: f64) {
+        self.float_bits.store(value.to_bits(), Ordering::SeqCst);
+    }
+
+    /// Adds `delta` to the shared float without taking the lock, returning
+    /// the value before. A compare-and-swap loop, as no CPU adds floats in
+    /// memory atomically, so under contention it retries until its add
+    /// lands on the value it read.
+    pub fn fetch_add_float(&self, delta: f64) -> f64 {
+        let previous = self
+            .float_bits
+            .fetch_update(Ordering::SeqCst, Ordering::SeqCst, |bits| {
+                Some((f64::from_bits(bits) + delta).to_bits())
+            })
+            .unwrap_or_else(|bits| bits);
+        f64::from_bits(previous)
     }
 
-    pub fn lock(&self) -> Result<(), WaitError> {
+    /// Blocks until the lock is taken, retrying if a signal interrupts the
+    /// wait. The `Result` is kept for symmetry with the timed variants; it
+    /// is currently always `Ok`.
+    pub fn lock(&self) -> Result<(), SharedMemError> {
         loop {
-            match self
-                .futex
-                .value
-                .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
-            {
-                Ok(_) => return Ok(()),
-                Err(_) => {
-                    let _ = self.futex.wait(1)?;
-                }
+            match self.lock_interruptible() {
+                Err(WaitError::Interrupted) => continue,
+                _ => return Ok(()),
             }
         }
     }
 
-    pub fn lock_timeout(&self, timeout: Duration) -> Result<(), TimedWaitError> {
-        let start = std::time::Instant::now();
+    /// Like `lock`, but returns `Err(WaitError::Interrupted)` when a signal
+    /// arrives while waiting, without taking the lock.
+    ///
+    /// Use this from a process that installs its own signal handlers (e.g.
+    /// to stop on SIGINT) so the handler's flag can be checked instead of
+    /// blocking on. Handlers must be installed without `SA_RESTART`, or the
+    /// kernel restarts the futex wait and the signal goes unnoticed.
+    pub fn lock_interruptible(&self) -> Result<(), WaitError> {
+        let start = Instant::now();
+        let mut contended = false;
+        let mut waiting = None;
 
         loop {
-            if start.elapsed() >= timeout {
-                return Err(TimedWaitError::TimedOut);
+            if self.futex.try_inc(1) {
+                drop(waiting);
+                self.set_owner();
+                self.record_acquisition(start, contended);
+                return Ok(());
+            }
+            contended = true;
+            if self.spin_until_free() {
+                continue;
             }
+            waiting.get_or_insert_with(|| self.track_waiter());
+            match self.futex.wait_while(1) {
+                Ok(()) | Err(WaitError::WrongValue) => {}
+                Err(WaitError::Interrupted) => return Err(WaitError::Interrupted),
+            }
+        }
+    }
 
-            match self
-                .futex
-                .value
-                .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
-            {
-                Ok(_) => return Ok(()),
-                Err(_) => {
-                    let remaining = timeout.saturating_sub(start.elapsed());
-                    if remaining.is_zero() {
-                        return Err(TimedWaitError::TimedOut);
-                    }
-                    let _ = self.futex.wait_for(1, remaining)?;
+    /// Like `lock`, but gives up after `timeout`. A signal interrupting the
+    /// wait only means waiting again for whatever time is left.
+    ///
+    /// On timeout the recorded owner is checked; if that process is gone
+    /// the lock is reset and `RecoveredFromDeadOwner` is returned so the
+    /// caller can retry knowing the data may need repair.
+    pub fn lock_timeout(&self, timeout: Duration) -> Result<(), SharedMemError> {
+        self.lock_timeout_with(timeout, || {})
+    }
+
+    /// Like `lock_timeout`, but calls `on_contended` the first time the
+    /// lock is found held, before spinning or sleeping on it, so the caller
+    /// can record the contention or decide to shed load. It runs at most
+    /// once per call, in this process, while the lock is still someone
+    /// else's; an uncontended acquisition never calls it.
+    pub fn lock_timeout_with(
+        &self,
+        timeout: Duration,
+        on_contended: impl FnMut(),
+    ) -> Result<(), SharedMemError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("lock_timeout", pid = std::process::id()).entered();
+        self.lock_until(Instant::now() + timeout, true, None, on_contended)
+    }
+
+    /// Like `lock_timeout`, but returns `Interrupted` as soon as a signal
+    /// interrupts the wait, for callers that react to signals themselves.
+    pub fn lock_timeout_no_retry(&self, timeout: Duration) -> Result<(), SharedMemError> {
+        #[cfg(feature = "tracing")]
+        let _span =
+            tracing::debug_span!("lock_timeout_no_retry", pid = std::process::id()).entered();
+        self.lock_until(Instant::now() + timeout, false, None, || {})
+    }
+
+    /// Like `lock_timeout`, but gives up at an absolute `deadline`, for
+    /// callers that already have one. A deadline in the past fails at once.
+    pub fn lock_deadline(&self, deadline: Instant) -> Result<(), SharedMemError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("lock_deadline", pid = std::process::id()).entered();
+        self.lock_until(deadline, true, None, || {})
+    }
+
+    /// Like `lock_deadline`, but also gives up with `Canceled` soon after
+    /// another thread sets `cancel`, e.g. from a Ctrl-C handler. The wait
+    /// is cut into futex waits of at most `CANCEL_POLL` so the flag is
+    /// seen without anyone having to wake the waiter. A flag already set
+    /// fails at once, even if the lock is free.
+    pub fn lock_cancelable(
+        &self,
+        deadline: Instant,
+        cancel: &AtomicBool,
+    ) -> Result<(), SharedMemError> {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!("lock_cancelable", pid = std::process::id()).entered();
+        self.lock_until(deadline, true, Some(cancel), || {})
+    }
+
+    fn lock_until(
+        &self,
+        deadline: Instant,
+        retry_interrupted: bool,
+        cancel: Option<&AtomicBool>,
+        mut on_contended: impl FnMut(),
+    ) -> Result<(), SharedMemError> {
+        let start = Instant::now();
+        let mut contended = false;
+        let mut waiting = None;
+
+        loop {
+            if cancel.is_some_and(|cancel| cancel.load(Ordering::Relaxed)) {
+                return Err(SharedMemError::Canceled);
+            }
+            if Instant::now() >= deadline {
+                return Err(self.timed_out());
+            }
+
+            if self.futex.try_inc(1) {
+                // Never both waiter and owner, or it would look like a cycle
+                drop(waiting);
+                self.set_owner();
+                let _acquisition = self.record_acquisition(start, contended);
+                #[cfg(feature = "tracing")]
+                tracing::debug!(
+                    acquisition = _acquisition,
+                    waited_us = start.elapsed().as_micros() as u64,
+                    "lock acquired"
+                );
+                return Ok(());
+            }
+            if !contended {
+                #[cfg(feature = "tracing")]
+                tracing::debug!(observed = self.futex.load(), "lock contended");
+                on_contended();
+            }
+            contended = true;
+            if self.spin_until_free() {
+                continue;
+            }
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                return Err(self.timed_out());
+            }
+            let remaining = match cancel {
+                Some(_) => remaining.min(CANCEL_POLL),
+                None => remaining,
+            };
+            waiting.get_or_insert_with(|| self.track_waiter());
+            match self.futex.wait_while_for(1, remaining) {
+                Ok(()) | Err(TimedWaitError::WrongValue) | Err(TimedWaitError::TimedOut) => {}
+                // The deadline is checked again at the top of the loop
+                Err(TimedWaitError::Interrupted) if retry_interrupted => {}
+                Err(TimedWaitError::Interrupted) => return Err(SharedMemError::Interrupted),
+            }
+        }
+    }
+
+    /// Sets how many rounds of backoff this process spins for a held lock
+    /// before sleeping on the futex; 0 goes straight to sleep. The setting
+    /// is per process, not stored in the region.
+    pub fn set_spin_rounds(rounds: u32) {
+        SPIN_ROUNDS.store(rounds, Ordering::Relaxed);
+    }
+
+    /// Turns on recording, in every region this process locks, which
+    /// thread holds the lock and which threads are sleeping on it, so
+    /// `deadlock::detect_deadlock` can look for a cycle. Off by default,
+    /// as it costs a store on every acquisition. Per process: threads of
+    /// processes that leave it off, the child among them, never show up.
+    ///
+    /// Waiters are recorded by the blocking lock calls once they go to
+    /// sleep, not by `try_lock`, the fair lock or `lock_async`.
+    pub fn set_deadlock_tracking(enabled: bool) {
+        DEADLOCK_TRACKING.store(enabled, Ordering::Relaxed);
+    }
+
+    /// Claims a free slot in `waiter_tids` for this thread, if tracking is
+    /// on and one is left. Dropping the result frees it again.
+    fn track_waiter(&self) -> TrackedWaiter<'_> {
+        if !DEADLOCK_TRACKING.load(Ordering::Relaxed) {
+            return TrackedWaiter(None);
+        }
+        let tid = current_tid();
+        TrackedWaiter(self.waiter_tids.iter().find(|slot| {
+            slot.compare_exchange(0, tid, Ordering::Relaxed, Ordering::Relaxed)
+                .is_ok()
+        }))
+    }
+
+    /// Spins with exponential backoff until the lock looks free, returning
+    /// `false` if it was still held after every round. Only loads, so the
+    /// holder's cache line is not stolen while it works.
+    fn spin_until_free(&self) -> bool {
+        for round in 0..SPIN_ROUNDS.load(Ordering::Relaxed) {
+            for _ in 0..1u32 << round.min(MAX_SPIN_SHIFT) {
+                std::hint::spin_loop();
+            }
+            if self.futex.load() == 0 {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// Takes the fair lock, granting it in the order callers arrived.
+    ///
+    /// This is a separate lock from `lock`/`lock_timeout`; data must be
+    /// protected by one or the other, not a mix. Release it with
+    /// `unlock_fair`.
+    ///
+    /// A caller that times out leaves its ticket marked as abandoned and
+    /// whoever reaches it passes it straight on. The mark shares a bit with
+    /// tickets 32 apart, so a caller more than 31 places from the front
+    /// cannot leave until it gets closer; its timeout is only honoured then.
+    pub fn lock_fair_timeout(&self, timeout: Duration) -> Result<(), SharedMemError> {
+        let deadline = Instant::now() + timeout;
+        let ticket = self.next_ticket.fetch_add(1, Ordering::SeqCst);
+        let bit = turn_bit(ticket);
+
+        loop {
+            let serving = self.now_serving.value.load(Ordering::SeqCst);
+            if serving == ticket {
+                return Ok(());
+            }
+
+            // Signals only cause a re-check: the ticket can't be handed back
+            // at an arbitrary distance from the front.
+            if Instant::now() < deadline {
+                let _ = self.now_serving.wait_bitset_until(serving, bit, deadline);
+            } else if ticket.wrapping_sub(serving) < 32 {
+                self.abandon_ticket(ticket);
+                return Err(SharedMemError::Timeout);
+            } else {
+                // Too far back to leave; our bit is woken every 32 tickets.
+                let _ = self.now_serving.wait_bitset(serving, bit);
+            }
+        }
+    }
+
+    /// Releases the fair lock, serving the next ticket that still has a
+    /// waiter.
+    pub fn unlock_fair(&self) {
+        let mut next = self.now_serving.value.load(Ordering::SeqCst);
+        loop {
+            next = next.wrapping_add(1);
+            self.now_serving.value.store(next, Ordering::SeqCst);
+            // A waiter checks `now_serving` after marking its ticket, so one
+            // of us sees the other. Whoever clears the mark owns the ticket.
+            if !self.take_abandoned(next) {
+                break;
+            }
+        }
+        self.now_serving.wake_bitset(i32::MAX, turn_bit(next));
+    }
+
+    /// Blocks until the lock is taken and returns a guard for it.
+    ///
+    /// If a previous holder panicked with the lock held, in this process or
+    /// another, the guard comes back inside a `PoisonError` instead; the
+    /// caller can still take it with `into_inner` after checking the data.
+    pub fn lock_guard(&self) -> Result<SharedDataGuard<'_>, PoisonError<'_>> {
+        while let Err(WaitError::Interrupted) = self.lock_interruptible() {}
+        let guard = SharedDataGuard::new(self);
+        if self.is_poisoned() {
+            return Err(PoisonError { guard });
+        }
+        Ok(guard)
+    }
+
+    /// Whether a lock holder panicked before releasing the lock. Only
+    /// `lock_guard` reports this by itself.
+    pub fn is_poisoned(&self) -> bool {
+        self.poisoned.load(Ordering::Acquire)
+    }
+
+    /// Marks the data as consistent again, e.g. after repairing it through
+    /// the guard from a `PoisonError`.
+    pub fn clear_poison(&self) {
+        self.poisoned.store(false, Ordering::Release);
+    }
+
+    pub fn lock_timeout_guard(
+        &self,
+        timeout: Duration,
+    ) -> Result<SharedDataGuard<'_>, SharedMemError> {
+        self.lock_timeout(timeout)?;
+        Ok(SharedDataGuard::new(self))
+    }
+
+    /// Like `lock_timeout_guard`, but if the lock is still not taken after
+    /// `grace`, a watchdog thread passes a `LockDump` to `on_stuck` while
+    /// the wait goes on. Nothing is dumped when the lock comes sooner, and
+    /// the watchdog is gone by the time this returns.
+    pub fn lock_timeout_guard_watched(
+        &self,
+        timeout: Duration,
+        grace: Duration,
+        on_stuck: impl FnOnce(LockDump) + Send,
+    ) -> Result<SharedDataGuard<'_>, SharedMemError> {
+        let start = Instant::now();
+        let (done, finished) = std::sync::mpsc::channel::<()>();
+        std::thread::scope(|scope| {
+            scope.spawn(move || {
+                // Dropping `done` disconnects us early, once the wait is over
+                if let Err(std::sync::mpsc::RecvTimeoutError::Timeout) =
+                    finished.recv_timeout(grace)
+                {
+                    on_stuck(self.dump(start.elapsed()));
                 }
+            });
+            let result = self.lock_timeout(timeout);
+            drop(done);
+            result
+        })?;
+        Ok(SharedDataGuard::new(self))
+    }
+
+    /// Waits for `child` to exit, then runs `f` with the lock held and
+    /// releases it, returning how the child ended and what `f` returned.
+    ///
+    /// Nothing is locked while waiting, so the child can still take the
+    /// lock to finish its work; and because the lock is taken only after
+    /// the child is gone, `f` sees its last write and nothing else can
+    /// change the data while `f` reads it. A child that died holding the
+    /// lock leaves it to be recovered, which is reported as
+    /// `RecoveredFromDeadOwner` instead of running `f`. Like `lock`, this
+    /// blocks for as long as another process holds the lock, and does not
+    /// check for poisoning; `f` can with `is_poisoned`.
+    pub fn wait_and_read<R>(
+        &self,
+        child: &mut std::process::Child,
+        f: impl FnOnce(&SharedData) -> R,
+    ) -> Result<(std::process::ExitStatus, R), SharedMemError> {
+        let exit_status = child.wait().map_err(SharedMemError::ChildWait)?;
+        // Reaped by now, so a lock it still held is recoverable
+        if self.owner_pid.load(Ordering::Relaxed) == child.id() as i32
+            && let Some(owner_pid) = self.recover_dead_owner()
+        {
+            return Err(SharedMemError::RecoveredFromDeadOwner { owner_pid });
+        }
+        self.lock()?;
+        let _guard = SharedDataGuard::new(self);
+        Ok((exit_status, f(self)))
+    }
+
+    /// Like `lock_guard`, but waits by sleeping on the tokio timer instead
+    /// of blocking the thread, so one runtime thread can wait on many
+    /// regions at once.
+    ///
+    /// A process releasing the lock cannot wake a timer, so the lock is
+    /// retried with a backoff from `ASYNC_MIN_BACKOFF` up to
+    /// `ASYNC_MAX_BACKOFF`, which bounds how late a handoff is noticed. As
+    /// with `lock_timeout`, a lock left behind by a dead owner is reset and
+    /// reported as `RecoveredFromDeadOwner`.
+    #[cfg(feature = "async")]
+    pub async fn lock_async(&self) -> Result<SharedDataGuard<'_>, SharedMemError> {
+        let start = Instant::now();
+        let mut backoff = ASYNC_MIN_BACKOFF;
+        let mut contended = false;
+
+        loop {
+            if self.futex.try_inc(1) {
+                self.set_owner();
+                self.record_acquisition(start, contended);
+                return Ok(SharedDataGuard::new(self));
             }
+            contended = true;
+            // Checking the owner costs a syscall, so only once waits are long
+            if backoff == ASYNC_MAX_BACKOFF
+                && let Some(owner_pid) = self.recover_dead_owner()
+            {
+                return Err(SharedMemError::RecoveredFromDeadOwner { owner_pid });
+            }
+            tokio::time::sleep(backoff).await;
+            backoff = (backoff * 2).min(ASYNC_MAX_BACKOFF);
         }
     }
 
+    /// Runs `f` with the lock held, giving it `number` and `words`
+    /// together, and releases the lock afterwards, also when `f` panics.
+    ///
+    /// The view only lives for the call, so `f` cannot smuggle references
+    /// to the protected data out through `R`.
+    pub fn with_locked<R>(
+        &self,
+        f: impl FnOnce(&mut SharedView<'_>) -> R,
+        timeout: Duration,
+    ) -> Result<R, SharedMemError> {
+        let _guard = self.lock_timeout_guard(timeout)?;
+        let mut view = SharedView {
+            number: &self.number,
+            words: &self.words,
+        };
+        Ok(f(&mut view))
+    }
+
+    /// Releases the lock taken by `lock`, `lock_timeout` or `try_lock`,
+    /// waking at most one process waiting for it. It does not wake
+    /// `wait_until` callers; see `notify_all`.
+    ///
+    /// Releasing a lock that is not held, or is held by another process,
+    /// would let two processes in at once, so it panics in debug builds and
+    /// is ignored in release builds. Use `try_unlock` to handle it.
     pub fn unlock(&self) {
-        self.futex.value.store(0, Ordering::Release);
-        self.futex.wake(1);
+        if let Err(e) = self.try_unlock() {
+            #[cfg(debug_assertions)]
+            panic!("SharedData::unlock: {}", e);
+            #[cfg(not(debug_assertions))]
+            let _ = e;
+        }
+    }
+
+    /// Releases the lock if this process holds it.
+    pub fn try_unlock(&self) -> Result<(), UnlockError> {
+        // The lock being released is the latest acquisition.
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!(
+            "unlock",
+            pid = std::process::id(),
+            observed = self.futex.load(),
+            acquisition = self
+                .lock_acquisitions
+                .load(Ordering::Relaxed)
+                .wrapping_sub(1)
+        )
+        .entered();
+        if self.futex.load() == 0 {
+            return Err(UnlockError::NotLocked);
+        }
+        let owner_pid = self.owner_pid.load(Ordering::Relaxed);
+        if owner_pid != std::process::id() as i32 {
+            return Err(UnlockError::WrongOwner { owner_pid });
+        }
+
+        self.record_release();
+        self.owner_pid.store(0, Ordering::Relaxed);
+        self.owner_tid.store(0, Ordering::Relaxed);
+        // Two threads of the owner racing to unlock: only one gets through.
+        if !self.futex.dec_and_wake(1) {
+            return Err(UnlockError::NotLocked);
+        }
+        #[cfg(feature = "tracing")]
+        tracing::debug!("lock released");
+        Ok(())
     }
 
     pub fn try_lock(&self) -> bool {
-        self.futex
-            .value
-            .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
-            .is_ok()
+        let acquired = self.futex.try_inc(1);
+        if acquired {
+            self.set_owner();
+            self.lock_acquisitions.fetch_add(1, Ordering::Relaxed);
+        }
+        acquired
+    }
+
+    /// Like `try_lock`, but retries up to `spins` times while the lock is
+    /// held, with a `spin_loop` hint in between, before giving up. Never
+    /// makes a syscall, so it suits critical sections too short to be
+    /// worth a futex wait; `spins` of 0 is plain `try_lock`. Between
+    /// attempts it only loads the lock word, leaving the holder's cache
+    /// line alone until the lock looks free.
+    pub fn try_lock_spin(&self, spins: u32) -> bool {
+        if self.try_lock() {
+            return true;
+        }
+        for _ in 0..spins {
+            std::hint::spin_loop();
+            if self.futex.load() == 0 && self.try_lock() {
+                return true;
+            }
+        }
+        false
+    }
+
+    /// A snapshot of the lock word and its recorded owner, for tools that
+    /// inspect a region before deciding whether to `force_reset_lock`.
+    pub fn lock_state(&self) -> LockState {
+        if self.futex.load() == 0 {
+            return LockState::Unlocked;
+        }
+        LockState::Locked {
+            owner_pid: self.owner_pid.load(Ordering::Relaxed),
+        }
+    }
+
+    /// The lock's futex word as it is this instant: 0 when free, 1 while
+    /// held. For tooling and logs only; it may already have changed by the
+    /// time it is returned, so never decide whether to lock based on it.
+    pub fn raw_futex_value(&self) -> i32 {
+        self.futex.load_relaxed() as i32
+    }
+
+    /// Whether the lock was held when looked at, with the same staleness as
+    /// `raw_futex_value`. Use `try_lock` to actually find out and take it.
+    pub fn is_locked(&self) -> bool {
+        self.raw_futex_value() != 0
+    }
+
+    /// Whether this process holds the lock, for error paths that are not
+    /// sure they took it and must neither unlock twice nor lock again.
+    /// Only our own acquisitions set `owner_pid` to our PID, and short of
+    /// a `force_reset_lock` only our release clears it, so unlike
+    /// `is_locked` the answer does not go stale while we look. It is per
+    /// process, though: any thread of ours holding the lock counts.
+    pub fn owned_by_me(&self) -> bool {
+        self.owner_pid.load(Ordering::Acquire) == std::process::id() as i32 && self.is_locked()
+    }
+
+    fn dump(&self, waited: Duration) -> LockDump {
+        let owner_pid = self.owner_pid.load(Ordering::Relaxed);
+        LockDump {
+            waited,
+            futex_value: self.raw_futex_value(),
+            owner_pid,
+            owner_alive: (owner_pid != 0).then(|| process_alive(owner_pid)),
+            number: self.get_number(),
+        }
+    }
+
+    /// Clears the lock whoever holds it and wakes every waiter. Meant only
+    /// for recovery tools unsticking a region whose holder is hung; normal
+    /// code releases the lock with `unlock`, and a dead holder is already
+    /// recovered by the timed lock calls.
+    ///
+    /// # Safety
+    ///
+    /// This breaks mutual exclusion: if the holder is still running it
+    /// carries on as if it had the lock while a waiter takes it too, and
+    /// both modify the protected data at once. Only call it once the holder
+    /// is known to be stuck for good, and expect the protected data to be
+    /// inconsistent.
+    pub unsafe fn force_reset_lock(&self) {
+        #[cfg(feature = "tracing")]
+        tracing::warn!(state = ?self.lock_state(), "lock forcibly reset");
+        self.owner_pid.store(0, Ordering::Relaxed);
+        self.owner_tid.store(0, Ordering::Relaxed);
+        self.futex.store_and_wake(0, i32::MAX);
+    }
+
+    /// Reads the lock counters. They are updated independently, so a
+    /// snapshot taken under contention may be off by the acquisitions in
+    /// flight.
+    pub fn stats(&self) -> LockStats {
+        LockStats {
+            acquisitions: self.lock_acquisitions.load(Ordering::Relaxed),
+            contended: self.lock_contended.load(Ordering::Relaxed),
+            total_wait: Duration::from_nanos(self.total_wait_nanos.load(Ordering::Relaxed)),
+            total_hold: Duration::from_nanos(self.total_hold_nanos.load(Ordering::Relaxed)),
+            max_hold: Duration::from_nanos(self.max_hold_nanos.load(Ordering::Relaxed)),
+        }
+    }
+
+    /// Blocks until `turn` reaches `index`, i.e. every child before this
+    /// one has called `pass_turn`.
+    pub fn wait_for_turn(&self, index: u32, timeout: Duration) -> Result<(), SharedMemError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let turn = self.turn.value.load(Ordering::Acquire);
+            if turn == index {
+                return Ok(());
+            }
+            if self.stop_requested() {
+                return Err(SharedMemError::Stopped);
+            }
+            if Instant::now() >= deadline {
+                return Err(SharedMemError::Timeout);
+            }
+
+            match self.turn.wait_bitset_until(turn, turn_bit(index), deadline) {
+                Ok(()) | Err(TimedWaitError::WrongValue) | Err(TimedWaitError::TimedOut) => {}
+                Err(TimedWaitError::Interrupted) => return Err(SharedMemError::Interrupted),
+            }
+        }
+    }
+
+    /// Hands the turn to the next child and wakes only the waiters on its bit.
+    pub fn pass_turn(&self) {
+        let next = self.turn.value.fetch_add(1, Ordering::Release) + 1;
+        self.turn.wake_bitset(i32::MAX, turn_bit(next));
+    }
+
+    /// Sends the turn back to the first child, e.g. before spawning a fresh
+    /// set of them.
+    pub fn reset_turns(&self) {
+        self.turn.value.store(0, Ordering::Release);
+        self.turn.wake_bitset(i32::MAX, u32::MAX);
+    }
+
+    /// Blocks until `pred` holds for `number`, returning the value that
+    /// satisfied it, or `Stopped` once `request_stop` has been called and
+    /// the predicate does not hold.
+    ///
+    /// Writers must call `notify_all` after updating `number` for waiters
+    /// to notice. The predicate is re-checked after every wakeup,
+    /// so spurious wakeups are harmless.
+    pub fn wait_until<F: Fn(i64) -> bool>(
+        &self,
+        pred: F,
+        timeout: Duration,
+    ) -> Result<i64, SharedMemError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            // Read the sequence before the number so a change in between
+            // makes the futex wait return immediately.
+            let seq = self.change_seq.value.load(Ordering::Acquire);
+            let number = self.get_number();
+            if pred(number) {
+                return Ok(number);
+            }
+            if self.stop_requested() {
+                return Err(SharedMemError::Stopped);
+            }
+            sleep_while(&self.change_seq, seq, deadline)?;
+        }
+    }
+
+    /// Sets `should_stop` and wakes every waiter, so attached processes
+    /// notice they should give up. `number` is left as it was.
+    pub fn request_stop(&self) {
+        self.should_stop.store(true, Ordering::Release);
+        self.futex.wake(i32::MAX);
+        self.turn.wake_bitset(i32::MAX, u32::MAX);
+        self.notify_change();
+    }
+
+    pub fn stop_requested(&self) -> bool {
+        self.should_stop.load(Ordering::Acquire)
+    }
+
+    /// Wakes every `wait_until` caller so they re-check their predicate.
+    ///
+    /// This is the broadcast counterpart to the single wake in `unlock`.
+    /// Only one waiter can take a released lock, so waking more would just
+    /// send the rest back to sleep; but any number of waiters may be
+    /// watching `number`, each for its own predicate, and a writer cannot
+    /// know which of them it just satisfied. Use this after every change to
+    /// `number` that waiters might care about, and leave the lock's wakeups
+    /// to `unlock`.
+    pub fn notify_all(&self) {
+        self.change_seq.value.fetch_add(1, Ordering::Release);
+        self.change_seq.wake(i32::MAX);
+    }
+
+    /// Same as `notify_all`.
+    pub fn notify_change(&self) {
+        self.notify_all();
+    }
+
+    /// Announces a start-up handshake, before spawning the children that
+    /// take part in it. Each of them reports itself on attaching and then
+    /// waits, touching nothing else, until `start_running`; in between the
+    /// parent can finish setting up and know every child is there, instead
+    /// of sleeping and hoping. Calling it again starts over, for a retry.
+    pub fn begin_handshake(&self) {
+        self.attached_children.value.store(0, Ordering::Relaxed);
+        self.set_phase(Phase::ParentReady);
+    }
+
+    /// Blocks until `count` children have reported in since
+    /// `begin_handshake`.
+    pub fn wait_attached(&self, count: u32, timeout: Duration) -> Result<(), SharedMemError> {
+        let deadline = Instant::now() + timeout;
+        loop {
+            let attached = self.attached_children.value.load(Ordering::Acquire);
+            if attached >= count {
+                return Ok(());
+            }
+            sleep_while(&self.attached_children, attached, deadline)?;
+        }
+    }
+
+    /// Lets the children waiting in the handshake go ahead.
+    pub fn start_running(&self) {
+        self.set_phase(Phase::Running);
+    }
+
+    /// Where the handshake stands. A value no side writes reads as
+    /// `Created`.
+    pub fn phase(&self) -> Phase {
+        Phase::from_raw(self.phase.value.load(Ordering::Acquire)).unwrap_or(Phase::Created)
+    }
+
+    fn set_phase(&self, phase: Phase) {
+        self.phase.value.store(phase as u32, Ordering::Release);
+        self.phase.wake(i32::MAX);
+    }
+
+    /// Records that this process is still making progress. Call it
+    /// periodically from the side being watched.
+    pub fn beat(&self) {
+        self.last_heartbeat_nanos
+            .store(unix_nanos(), Ordering::Relaxed);
+    }
+
+    /// Whether some process called `beat` within the last `max_staleness`,
+    /// letting a watcher give up on a hung peer well before a long lock
+    /// timeout would. False if nobody has beaten yet.
+    ///
+    /// There is a single heartbeat, so a process that beats itself only
+    /// learns that someone is alive; with several children, have just the
+    /// one being watched beat.
+    pub fn peer_alive(&self, max_staleness: Duration) -> bool {
+        let last = self.last_heartbeat_nanos.load(Ordering::Relaxed);
+        last != 0 && unix_nanos().saturating_sub(last) <= max_staleness.as_nanos() as u64
+    }
+
+    /// How long the last child to finish took over its update, measured on
+    /// the child's side from taking the lock to just before releasing it,
+    /// or `None` if no child has reported yet. With several children each
+    /// overwrites the one before.
+    pub fn child_compute_time(&self) -> Option<Duration> {
+        // Pairs with the child's release store, made before it unlocks.
+        match self.child_compute_nanos.load(Ordering::Acquire) {
+            0 => None,
+            nanos => Some(Duration::from_nanos(nanos)),
+        }
+    }
+
+    /// Leaves a result `code` and a short `message` for whoever reads the
+    /// region next, such as the parent after `Child::wait`. Messages longer
+    /// than `STATUS_MESSAGE_LEN` bytes are cut at the last character that
+    /// fits.
+    ///
+    /// Meant as a last word: two processes setting it at once can leave a
+    /// mix of both messages.
+    pub fn set_status(&self, code: i32, message: &str) {
+        let mut len = message.len().min(STATUS_MESSAGE_LEN);
+        while !message.is_char_boundary(len) {
+            len -= 1;
+        }
+        let bytes = &message.as_bytes()[..len];
+        for (i, slot) in self.status_message.iter().enumerate() {
+            slot.store(bytes.get(i).copied().unwrap_or(0), Ordering::Relaxed);
+        }
+        self.status.store(code, Ordering::Release);
+    }
+
+    /// The code and message last passed to `set_status`, or `(0, "")` if it
+    /// was never called.
+    pub fn read_status(&self) -> (i32, String) {
+        let code = self.status.load(Ordering::Acquire);
+        let bytes: Vec<u8> = self
+            .status_message
+            .iter()
+            .map(|slot| slot.load(Ordering::Relaxed))
+            .take_while(|&byte| byte != 0)
+            .collect();
+        (code, String::from_utf8_lossy(&bytes).into_owned())
+    }
+
+    fn set_owner(&self) {
+        self.owner_pid
+            .store(std::process::id() as i32, Ordering::Relaxed);
+        if DEADLOCK_TRACKING.load(Ordering::Relaxed) {
+            self.owner_tid.store(current_tid(), Ordering::Relaxed);
+        }
+        self.locked_at_nanos
+            .store(monotonic_nanos(), Ordering::Relaxed);
+    }
+
+    /// Adds the hold ending now to the hold-time counters. Called by the
+    /// holder, so `locked_at_nanos` is still its own.
+    fn record_release(&self) {
+        let held = monotonic_nanos().saturating_sub(self.locked_at_nanos.load(Ordering::Relaxed));
+        self.total_hold_nanos.fetch_add(held, Ordering::Relaxed);
+        self.max_hold_nanos.fetch_max(held, Ordering::Relaxed);
+    }
+
+    /// Updates the lock counters, returning the acquisition's position in
+    /// the order the lock was taken across all processes.
+    fn record_acquisition(&self, start: Instant, contended: bool) -> u64 {
+        let acquisition = self.lock_acquisitions.fetch_add(1, Ordering::Relaxed);
+        if contended {
+            self.lock_contended.fetch_add(1, Ordering::Relaxed);
+            let waited = start.elapsed().as_nanos().min(u64::MAX as u128) as u64;
+            self.total_wait_nanos.fetch_add(waited, Ordering::Relaxed);
+        }
+        acquisition
+    }
+
+    /// Gives up `ticket`. If it is already being served, the lock is
+    /// passed on here instead.
+    fn abandon_ticket(&self, ticket: u32) {
+        self.abandoned_tickets
+            .fetch_or(turn_bit(ticket), Ordering::SeqCst);
+        if self.now_serving.value.load(Ordering::SeqCst) == ticket && self.take_abandoned(ticket) {
+            self.unlock_fair();
+        }
+    }
+
+    /// Clears the abandoned mark for `ticket`, returning whether it was set.
+    fn take_abandoned(&self, ticket: u32) -> bool {
+        let bit = turn_bit(ticket);
+        self.abandoned_tickets.load(Ordering::SeqCst) & bit != 0
+            && self.abandoned_tickets.fetch_and(!bit, Ordering::SeqCst) & bit != 0
+    }
+
+    fn timed_out(&self) -> SharedMemError {
+        match self.recover_dead_owner() {
+            Some(owner_pid) => {
+                #[cfg(feature = "tracing")]
+                tracing::warn!(owner_pid, "lock recovered from dead owner");
+                SharedMemError::RecoveredFromDeadOwner { owner_pid }
+            }
+            None => {
+                #[cfg(feature = "tracing")]
+                tracing::debug!("lock timed out");
+                SharedMemError::Timeout
+            }
+        }
+    }
+
+    /// Releases the lock if its recorded owner no longer exists, returning
+    /// that owner's PID.
+    ///
+    /// An owner that exited but has not been reaped yet is still a zombie
+    /// and counts as alive, so the parent must `wait` on a dead child first.
+    pub fn recover_dead_owner(&self) -> Option<i32> {
+        let owner_pid = self.owner_pid.load(Ordering::Relaxed);
+        if owner_pid == 0 || process_alive(owner_pid) {
+            return None;
+        }
+
+        // Only the waiter that wins this exchange resets the lock; if the
+        // owner changed in the meantime the lock was legitimately handed on.
+        self.owner_pid
+            .compare_exchange(owner_pid, 0, Ordering::Relaxed, Ordering::Relaxed)
+            .ok()?;
+        self.owner_tid.store(0, Ordering::Relaxed);
+        self.futex.store_and_wake(0, 1);
+        Some(owner_pid)
+    }
+}
+
+/// A `SharedData` in a shared memory segment mapped by this process, as
+/// returned by `SharedRegion::builder`.
+///
+/// If this process created the segment, dropping it asks any attached
+/// children to stop before the segment is unlinked, so an early return from
+/// the parent neither leaves children waiting on a lock nobody will release
+/// nor a stale entry in /dev/shm.
+pub struct OwnedSharedData {
+    mapping: Mapping,
+}
+
+enum Mapping {
+    Named(Shmem),
+    /// An anonymous memfd, see `OpenMode::Anonymous`.
+    #[cfg(target_os = "linux")]
+    Memfd {
+        memfd: crate::memfd::MemfdMapping,
+        handle: String,
+        owner: bool,
+    },
+    /// A System V segment, see `OpenMode::SysV`.
+    #[cfg(unix)]
+    Sysv {
+        sysv: crate::sysv::SysvMapping,
+        handle: String,
+    },
+}
+
+impl Mapping {
+    fn as_ptr(&self) -> *mut u8 {
+        match self {
+            Mapping::Named(shmem) => shmem.as_ptr(),
+            #[cfg(target_os = "linux")]
+            Mapping::Memfd { memfd, .. } => memfd.as_ptr(),
+            #[cfg(unix)]
+            Mapping::Sysv { sysv, .. } => sysv.as_ptr(),
+        }
+    }
+
+    fn len(&self) -> usize {
+        match self {
+            Mapping::Named(shmem) => shmem.len(),
+            #[cfg(target_os = "linux")]
+            Mapping::Memfd { memfd, .. } => memfd.len(),
+            #[cfg(unix)]
+            Mapping::Sysv { sysv, .. } => sysv.len(),
+        }
+    }
+}
+
+impl OwnedSharedData {
+    /// Creates a region under a fresh OS ID; shorthand for
+    /// `SharedRegion::builder().build()`.
+    pub fn create() -> Result<Self, SharedMemError> {
+        SharedRegion::builder().build()
+    }
+
+    /// The ID to hand to processes that attach, or for an anonymous region
+    /// the `fd:<n>` handle of the descriptor they inherit.
+    pub fn os_id(&self) -> &str {
+        match &self.mapping {
+            Mapping::Named(shmem) => shmem.get_os_id(),
+            #[cfg(target_os = "linux")]
+            Mapping::Memfd { handle, .. } => handle,
+            #[cfg(unix)]
+            Mapping::Sysv { handle, .. } => handle,
+        }
+    }
+
+    /// The `SharedData` in the region, borrowed from `self` as with deref,
+    /// so it cannot outlive the mapping:
+    ///
+    /// ```compile_fail,E0597
+    /// use sharedmem_multiarch::{OwnedSharedData, SharedData};
+    ///
+    /// let escaped: &SharedData = {
+    ///     let owned = OwnedSharedData::create().unwrap();
+    ///     owned.get()
+    /// };
+    /// escaped.get_number();
+    /// ```
+    pub fn get(&self) -> &SharedData {
+        self
+    }
+
+    /// The bytes past `SharedData` in the region, such as those asked for
+    /// with `SharedRegionBuilder::size`, as a pointer and a length. They
+    /// start `CACHE_LINE`-aligned and stay mapped for as long as `self`;
+    /// what lives there and how it is accessed is up to the caller.
+    pub fn extra_space(&self) -> (*mut u8, usize) {
+        let offset = std::mem::size_of::<SharedData>();
+        // SAFETY: the mapping is at least `SharedData` long.
+        let ptr = unsafe { self.mapping.as_ptr().add(offset) };
+        (ptr, self.mapping.len() - offset)
+    }
+
+    /// `extra_space` as bytes, for a region built with
+    /// `SharedRegionBuilder::user_bytes`; empty if there are none.
+    ///
+    /// The slice starts right past `SharedData`, at an offset that is a
+    /// multiple of `CACHE_LINE`, so anything up to that alignment can be
+    /// placed at its start; it is at least as long as asked for, and the
+    /// same length in every process attached to the segment. Both sides
+    /// have to agree on what lives there, as nothing records it.
+    ///
+    /// Other processes may write these bytes at any time, so the slice is
+    /// only a snapshot for data that is set up once, e.g. by the creator
+    /// before anyone attaches. For fields that change while shared, place
+    /// atomics at the pointer `extra_space` returns instead, or guard the
+    /// bytes with the lock.
+    pub fn user_region(&self) -> &[u8] {
+        let (ptr, len) = self.extra_space();
+        // SAFETY: mapped for as long as `self`, see `extra_space`.
+        unsafe { std::slice::from_raw_parts(ptr, len) }
+    }
+
+    /// `user_region` for writing. `&mut self` only rules out other
+    /// borrows in this process; the caveat about other processes stands.
+    pub fn user_region_mut(&mut self) -> &mut [u8] {
+        let (ptr, len) = self.extra_space();
+        // SAFETY: as for `user_region`, and `&mut self` keeps this the only
+        // slice of it in this process.
+        unsafe { std::slice::from_raw_parts_mut(ptr, len) }
+    }
+
+    /// Whether this process created the segment (and so unlinks it).
+    pub fn is_owner(&self) -> bool {
+        match &self.mapping {
+            Mapping::Named(shmem) => shmem.is_owner(),
+            #[cfg(target_os = "linux")]
+            Mapping::Memfd { owner, .. } => *owner,
+            #[cfg(unix)]
+            Mapping::Sysv { sysv, .. } => sysv.is_owner(),
+        }
+    }
+}
+
+impl Deref for OwnedSharedData {
+    type Target = SharedData;
+
+    fn deref(&self) -> &SharedData {
+        // SAFETY: initialized (or waited for) when built, and mapped for as
+        // long as `self`.
+        unsafe { &*(self.mapping.as_ptr() as *const SharedData) }
+    }
+}
+
+impl Drop for OwnedSharedData {
+    fn drop(&mut self) {
+        // Processes that merely attached leave the region to its creator.
+        if self.is_owner() {
+            self.request_stop();
+        }
+        // `mapping` is dropped next; as the creator a named one unlinks the
+        // segment.
+    }
+}
+
+/// Entry point for creating or attaching to a shared `SharedData` region.
+pub struct SharedRegion;
+
+impl SharedRegion {
+    pub fn builder() -> SharedRegionBuilder {
+        SharedRegionBuilder {
+            os_id: None,
+            size: std::mem::size_of::<SharedData>(),
+            mode: OpenMode::Create,
+            #[cfg(unix)]
+            permissions: None,
+            #[cfg(unix)]
+            owner: None,
+        }
+    }
+
+    /// Creates the region `name`, at least `size` bytes, or attaches to it
+    /// if it already exists, so two peers can start in either order and
+    /// run the same code. Shorthand for the builder with
+    /// `OpenMode::CreateOrOpen`.
+    ///
+    /// Exactly one caller initializes `SharedData`: creating the segment is
+    /// exclusive, so only the process whose create succeeds runs
+    /// `init_in_place`, and `is_owner` tells it apart. Everyone else waits
+    /// for the ready flag it sets last, so they never see a half-built
+    /// region. The segment goes away when the owner drops its region, even
+    /// if others are still attached.
+    pub fn create_or_open(name: &str, size: usize) -> Result<OwnedSharedData, SharedMemError> {
+        Self::builder()
+            .os_id(name)
+            .size(size)
+            .mode(OpenMode::CreateOrOpen)
+            .build()
+    }
+
+    /// Creates the region `name`, at least `size` bytes, and has `init` set
+    /// up its protected state before anyone can attach: it runs once, in
+    /// the creator, before the ready flag is set, so openers only ever see
+    /// what it left. To start the number somewhere other than 100 and
+    /// have `reset` come back to it, call `set_initial_number`.
+    ///
+    /// ```no_run
+    /// use sharedmem_multiarch::SharedRegion;
+    ///
+    /// let shared_data =
+    ///     SharedRegion::create_with("/counter", 4096, |data| data.set_initial_number(7)).unwrap();
+    /// assert_eq!(shared_data.get_number(), 7);
+    /// ```
+    pub fn create_with(
+        name: &str,
+        size: usize,
+        init: impl FnOnce(&SharedData),
+    ) -> Result<OwnedSharedData, SharedMemError> {
+        Self::builder().os_id(name).size(size).build_with(init)
+    }
+
+    /// Attaches to the anonymous region behind `fd`, a descriptor inherited
+    /// from a parent that built it with `OpenMode::Anonymous` and passed
+    /// `fd:<n>` along. As with `OpenMode::Open`, this waits until the region
+    /// is ready and checks its header.
+    ///
+    /// # Safety
+    ///
+    /// `fd` must be an open descriptor that nothing else in this process
+    /// owns; the returned region closes it when dropped.
+    #[cfg(target_os = "linux")]
+    pub unsafe fn from_inherited_fd(
+        fd: std::os::fd::RawFd,
+    ) -> Result<OwnedSharedData, SharedMemError> {
+        use std::os::fd::{FromRawFd, OwnedFd};
+
+        // SAFETY: ownership is the caller's promise.
+        let fd = unsafe { OwnedFd::from_raw_fd(fd) };
+        let memfd = crate::memfd::MemfdMapping::from_fd(fd).map_err(|e| {
+            SharedMemError::OpenFailed(shared_memory::ShmemError::MapOpenFailed(
+                e.raw_os_error().unwrap_or(0) as u32,
+            ))
+        })?;
+        if memfd.len() < std::mem::size_of::<SharedData>() {
+            return Err(SharedMemError::SizeMismatch {
+                expected: std::mem::size_of::<SharedData>(),
+                actual: memfd.len(),
+            });
+        }
+        // SAFETY: the mapping is large enough and lives in `memfd`.
+        unsafe { check_opened(memfd.as_ptr()) }?;
+        Ok(OwnedSharedData {
+            mapping: Mapping::Memfd {
+                handle: memfd.handle(),
+                memfd,
+                owner: false,
+            },
+        })
+    }
+}
+
+/// Creates the region `name`, at least `size` bytes, for unrelated
+/// programs to attach to with `open_region`; nothing is spawned. Shorthand
+/// for the builder with `OpenMode::Create`. The segment goes away when the
+/// returned region is dropped.
+pub fn create_region(name: &str, size: usize) -> Result<OwnedSharedData, SharedMemError> {
+    SharedRegion::builder()
+        .os_id(name)
+        .size(size)
+        .mode(OpenMode::Create)
+        .build()
+}
+
+/// Attaches to the region `name` made by `create_region` in another
+/// program, waiting until it is ready. Shorthand for the builder with
+/// `OpenMode::Open`.
+pub fn open_region(name: &str) -> Result<OwnedSharedData, SharedMemError> {
+    SharedRegion::builder()
+        .os_id(name)
+        .mode(OpenMode::Open)
+        .build()
+}
+
+/// How `SharedRegionBuilder::build` gets hold of the segment.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum OpenMode {
+    /// Create a new segment and initialize it; fails if the ID is taken.
+    Create,
+    /// Attach to an existing segment once its creator has initialized it.
+    Open,
+    /// Create the segment, or attach to it if it already exists.
+    CreateOrOpen,
+    /// Create an unnamed memfd instead of a named segment, so nothing shows
+    /// up in /dev/shm and nobody can open it by ID. Children spawned
+    /// afterwards inherit the descriptor and attach through the `fd:<n>`
+    /// handle `os_id` returns; any `os_id` given to the builder is ignored.
+    #[cfg(target_os = "linux")]
+    Anonymous,
+    /// Create a System V segment instead of a POSIX one, for systems
+    /// without `/dev/shm`; see the `sysv` module for the tradeoffs. Children
+    /// attach through the `sysv:<shmid>` handle `os_id` returns, and any
+    /// `os_id` given to the builder is ignored. `Create` falls back to this
+    /// by itself when it has no `os_id` and creating a POSIX segment fails.
+    /// `Open` attaches to such a handle too, `open_readonly` does not.
+    #[cfg(unix)]
+    SysV,
+}
+
+/// Settings for a shared region, see `SharedRegion::builder`.
+#[derive(Debug, Clone)]
+pub struct SharedRegionBuilder {
+    os_id: Option<String>,
+    size: usize,
+    mode: OpenMode,
+    #[cfg(unix)]
+    permissions: Option<libc::mode_t>,
+    #[cfg(unix)]
+    owner: Option<(Option<libc::uid_t>, Option<libc::gid_t>)>,
+}
+
+impl SharedRegionBuilder {
+    /// Uses a fixed OS ID, so unrelated processes can find the segment by
+    /// name. On Unix it must start with `/`, e.g. `/my-app`. Without one a
+    /// random ID is generated, which makes `Open` impossible.
+    pub fn os_id(mut self, os_id: impl Into<String>) -> Self {
+        self.os_id = Some(os_id.into());
+        self
+    }
+
+    /// Size of the segment to create. Anything beyond `SharedData` is left
+    /// zeroed for the caller; smaller sizes are rounded up.
+    pub fn size(mut self, size: usize) -> Self {
+        self.size = size.max(std::mem::size_of::<SharedData>());
+        self
+    }
+
+    /// Makes the segment `bytes` longer than `SharedData`, for fields of
+    /// the caller's own reached through `OwnedSharedData::user_region`,
+    /// which documents where they start. Another way of setting `size`, so
+    /// whichever of the two is called last wins.
+    pub fn user_bytes(self, bytes: usize) -> Self {
+        self.size(std::mem::size_of::<SharedData>() + bytes)
+    }
+
+    pub fn mode(mut self, mode: OpenMode) -> Self {
+        self.mode = mode;
+        self
+    }
+
+    /// Permission bits for a segment this builder creates, e.g. `0o660` to
+    /// let the group in. Segments start out `0o600`, so nobody else can
+    /// open one before these are applied. Only named segments have them:
+    /// anonymous and System V ones always stay private to their creator's
+    /// user, and a segment that `CreateOrOpen` merely opens is left as it
+    /// is.
+    #[cfg(unix)]
+    pub fn permissions(mut self, mode: u32) -> Self {
+        self.permissions = Some(mode as libc::mode_t);
+        self
+    }
+
+    /// Hands a segment this builder creates to another user and/or group;
+    /// `None` leaves that half unchanged. Giving it away usually takes
+    /// root or, for the group, membership in it. Applies to the same
+    /// segments as `permissions`.
+    #[cfg(unix)]
+    pub fn owner(mut self, uid: Option<u32>, gid: Option<u32>) -> Self {
+        self.owner = Some((uid, gid));
+        self
+    }
+
+    /// Creates or opens the segment. A created segment is initialized with
+    /// `init_in_place`; an opened one is waited on until it is ready and
+    /// has its header checked.
+    pub fn build(self) -> Result<OwnedSharedData, SharedMemError> {
+        self.build_with(|_| {})
+    }
+
+    /// `build`, with `init` run on a created segment just before it is
+    /// marked ready, as `SharedRegion::create_with` describes. An opened
+    /// segment was set up by whoever created it, so there `init` is
+    /// dropped without being called.
+    pub fn build_with(
+        self,
+        init: impl FnOnce(&SharedData),
+    ) -> Result<OwnedSharedData, SharedMemError> {
+        #[cfg(target_os = "linux")]
+        if self.mode == OpenMode::Anonymous {
+            let memfd = crate::memfd::MemfdMapping::create(self.size).map_err(|e| {
+                SharedMemError::OpenFailed(shared_memory::ShmemError::MapCreateFailed(
+                    e.raw_os_error().unwrap_or(0) as u32,
+                ))
+            })?;
+            // SAFETY: as for a created named segment; nobody else can reach
+            // the memfd until a child is spawned.
+            unsafe { SharedData::init_raw_with(memfd.as_ptr().cast(), init) };
+            return Ok(OwnedSharedData {
+                mapping: Mapping::Memfd {
+                    handle: memfd.handle(),
+                    memfd,
+                    owner: true,
+                },
+            });
+        }
+
+        #[cfg(unix)]
+        if self.mode == OpenMode::SysV {
+            return Self::create_sysv(self.size, init);
+        }
+        #[cfg(unix)]
+        if self.mode == OpenMode::Open
+            && let Some(id) = self.os_id.as_deref().and_then(crate::sysv::parse_handle)
+        {
+            return Self::attach_sysv(id);
+        }
+
+        let mut conf = ShmemConf::new().size(self.size);
+        if let Some(os_id) = &self.os_id {
+            conf = conf.os_id(os_id);
+        }
+
+        let created = match self.mode {
+            // Without a name to keep, any segment will do
+            #[cfg(unix)]
+            OpenMode::Create if self.os_id.is_none() => match conf.clone().create() {
+                Ok(shmem) => Some(shmem),
+                Err(e) => {
+                    #[cfg(feature = "tracing")]
+                    tracing::warn!(error = %e, "POSIX shared memory failed, using System V");
+                    return Self::create_sysv(self.size, init).map_err(|_| e.into());
+                }
+            },
+            OpenMode::Create => Some(conf.clone().create()?),
+            OpenMode::Open => None,
+            #[cfg(target_os = "linux")]
+            OpenMode::Anonymous => unreachable!(),
+            #[cfg(unix)]
+            OpenMode::SysV => unreachable!(),
+            OpenMode::CreateOrOpen => match conf.clone().create() {
+                Ok(shmem) => Some(shmem),
+                Err(shared_memory::ShmemError::MappingIdExists) => None,
+                Err(e) => return Err(e.into()),
+            },
+        };
+
+        let shmem = match created {
+            Some(shmem) => {
+                // Dropping `shmem` on failure unlinks it again
+                #[cfg(unix)]
+                self.apply_permissions(shmem.get_os_id())?;
+                // SAFETY: the segment was just created at least this large
+                // and is page aligned; `ready` is still zero, so anyone who
+                // opens it by name waits for us.
+                unsafe { SharedData::init_raw_with(shmem.as_ptr().cast(), init) };
+                shmem
+            }
+            None if self.mode == OpenMode::CreateOrOpen => {
+                let shmem = Self::open_after_lost_race(&conf)?;
+                // SAFETY: the mapping is large enough and lives in `shmem`.
+                unsafe { check_opened(shmem.as_ptr()) }?;
+                shmem
+            }
+            None => {
+                let shmem = conf.open()?;
+                if shmem.len() < std::mem::size_of::<SharedData>() {
+                    return Err(SharedMemError::SizeMismatch {
+                        expected: std::mem::size_of::<SharedData>(),
+                        actual: shmem.len(),
+                    });
+                }
+                // SAFETY: the mapping is large enough and lives in `shmem`.
+                unsafe { check_opened(shmem.as_ptr()) }?;
+                shmem
+            }
+        };
+        Ok(OwnedSharedData {
+            mapping: Mapping::Named(shmem),
+        })
+    }
+
+    /// Opens a segment that another process has just created, for the
+    /// side of `CreateOrOpen` that lost the race. The winner creates the
+    /// segment empty and only then sizes it, so for a moment it cannot be
+    /// mapped, or maps shorter than `SharedData`. Both are retried until
+    /// `READY_TIMEOUT`, after which the last error is returned.
+    fn open_after_lost_race(conf: &ShmemConf) -> Result<Shmem, SharedMemError> {
+        let deadline = Instant::now() + READY_TIMEOUT;
+        loop {
+            let error = match conf.clone().open() {
+                Ok(shmem) if shmem.len() >= std::mem::size_of::<SharedData>() => {
+                    return Ok(shmem);
+                }
+                Ok(shmem) => SharedMemError::SizeMismatch {
+                    expected: std::mem::size_of::<SharedData>(),
+                    actual: shmem.len(),
+                },
+                Err(e) => e.into(),
+            };
+            if Instant::now() >= deadline {
+                return Err(error);
+            }
+            std::thread::sleep(Duration::from_millis(1));
+        }
+    }
+
+    /// Applies `permissions` and `owner` to the named segment `os_id`,
+    /// which `shared_memory` does not let us reach the descriptor of.
+    #[cfg(unix)]
+    fn apply_permissions(&self, os_id: &str) -> Result<(), SharedMemError> {
+        if self.permissions.is_none() && self.owner.is_none() {
+            return Ok(());
+        }
+        let failed = |e: std::io::Error| {
+            SharedMemError::OpenFailed(shared_memory::ShmemError::UnknownOsError(
+                e.raw_os_error().unwrap_or(0) as u32,
+            ))
+        };
+        let name = std::ffi::CString::new(os_id)
+            .map_err(|_| failed(std::io::Error::from_raw_os_error(libc::EINVAL)))?;
+        let fd = unsafe { libc::shm_open(name.as_ptr(), libc::O_RDWR, 0) };
+        if fd < 0 {
+            return Err(failed(std::io::Error::last_os_error()));
+        }
+        // Run as one block so `fd` is closed below however it ends
+        let result = (|| {
+            if let Some(mode) = self.permissions
+                && unsafe { libc::fchmod(fd, mode) } != 0
+            {
+                return Err(std::io::Error::last_os_error());
+            }
+            if let Some((uid, gid)) = self.owner {
+                // -1 leaves an ID unchanged
+                let uid = uid.unwrap_or(libc::uid_t::MAX);
+                let gid = gid.unwrap_or(libc::gid_t::MAX);
+                if unsafe { libc::fchown(fd, uid, gid) } != 0 {
+                    return Err(std::io::Error::last_os_error());
+                }
+            }
+            Ok(())
+        })();
+        unsafe { libc::close(fd) };
+        result.map_err(failed)
+    }
+
+    #[cfg(unix)]
+    fn create_sysv(
+        size: usize,
+        init: impl FnOnce(&SharedData),
+    ) -> Result<OwnedSharedData, SharedMemError> {
+        let sysv = crate::sysv::SysvMapping::create(size).map_err(|e| {
+            SharedMemError::OpenFailed(shared_memory::ShmemError::MapCreateFailed(
+                e.raw_os_error().unwrap_or(0) as u32,
+            ))
+        })?;
+        // SAFETY: as for a created named segment; nobody has been told
+        // its ID yet.
+        unsafe { SharedData::init_raw_with(sysv.as_ptr().cast(), init) };
+        Ok(OwnedSharedData {
+            mapping: Mapping::Sysv {
+                handle: sysv.handle(),
+                sysv,
+            },
+        })
+    }
+
+    #[cfg(unix)]
+    fn attach_sysv(id: libc::c_int) -> Result<OwnedSharedData, SharedMemError> {
+        let sysv = crate::sysv::SysvMapping::attach(id).map_err(|e| {
+            SharedMemError::OpenFailed(shared_memory::ShmemError::MapOpenFailed(
+                e.raw_os_error().unwrap_or(0) as u32,
+            ))
+        })?;
+        if sysv.len() < std::mem::size_of::<SharedData>() {
+            return Err(SharedMemError::SizeMismatch {
+                expected: std::mem::size_of::<SharedData>(),
+                actual: sysv.len(),
+            });
+        }
+        // SAFETY: the mapping is large enough and lives in `sysv`.
+        unsafe { check_opened(sysv.as_ptr()) }?;
+        Ok(OwnedSharedData {
+            mapping: Mapping::Sysv {
+                handle: sysv.handle(),
+                sysv,
+            },
+        })
+    }
+}
+
+/// Waits for an attached region to be ready and checks its header.
+///
+/// # Safety
+///
+/// `ptr` must point to a mapping at least as large as `SharedData`.
+pub(crate) unsafe fn check_opened(ptr: *const u8) -> Result<(), SharedMemError> {
+    let data = unsafe { SharedData::open(ptr as *const SharedData) }?;
+    if !data.header.same_byte_order() {
+        return Err(SharedMemError::EndianMismatch);
+    }
+    data.header.check::<SharedData>()?;
+    Ok(())
+}
+
+/// Futex bitset used by the child with the given turn index. Indices 32
+/// apart share a bit, which only costs them a spurious wakeup.
+fn turn_bit(index: u32) -> u32 {
+    1 << (index % 32)
+}
+
+/// The wall clock in nanoseconds since the Unix epoch. `Instant` cannot be
+/// compared across processes, so heartbeats use this instead.
+fn unix_nanos() -> u64 {
+    std::time::SystemTime::now()
+        .duration_since(std::time::UNIX_EPOCH)
+        .map_or(0, |since| since.as_nanos() as u64)
+}
+
+/// Nanoseconds since this process first asked, on a monotonic clock.
+/// Unlike `unix_nanos` it never jumps, but it only means anything within
+/// one process, which is all lock hold times need.
+fn monotonic_nanos() -> u64 {
+    static EPOCH: OnceLock<Instant> = OnceLock::new();
+    EPOCH.get_or_init(Instant::now).elapsed().as_nanos() as u64
+}
+
+#[cfg(unix)]
+fn process_alive(pid: i32) -> bool {
+    // SAFETY: signal 0 performs only the existence and permission checks.
+    if unsafe { libc::kill(pid, 0) } == 0 {
+        return true;
+    }
+    // EPERM means the process exists but belongs to someone else.
+    std::io::Error::last_os_error().raw_os_error() != Some(libc::ESRCH)
+}
+
+/// This thread's ID, unique across processes on Linux. Elsewhere it is the
+/// process ID, so the threads of one process are indistinguishable.
+fn current_tid() -> i32 {
+    #[cfg(target_os = "linux")]
+    return unsafe { libc::gettid() };
+    #[cfg(not(target_os = "linux"))]
+    return std::process::id() as i32;
+}
+
+/// A slot in `waiter_tids` held by this thread, cleared on drop.
+struct TrackedWaiter<'a>(Option<&'a AtomicI32>);
+
+impl Drop for TrackedWaiter<'_> {
+    fn drop(&mut self) {
+        if let Some(slot) = self.0 {
+            slot.store(0, Ordering::Relaxed);
+        }
+    }
+}
+
+#[cfg(windows)]
+fn process_alive(pid: i32) -> bool {
+    use windows_sys::Win32::Foundation::{
+        CloseHandle, ERROR_INVALID_PARAMETER, GetLastError, STILL_ACTIVE,
+    };
+    use windows_sys::Win32::System::Threading::{
+        GetExitCodeProcess, OpenProcess, PROCESS_QUERY_LIMITED_INFORMATION,
+    };
+
+    // SAFETY: the handle is checked before use and closed afterwards.
+    unsafe {
+        let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, 0, pid as u32);
+        if process.is_null() {
+            // Access denied still means the process exists.
+            return GetLastError() != ERROR_INVALID_PARAMETER;
+        }
+        let mut exit_code = 0;
+        let queried = GetExitCodeProcess(process, &mut exit_code);
+        CloseHandle(process);
+        queried == 0 || exit_code == STILL_ACTIVE as u32
+    }
+}
+
+/// The fields of a `SharedData` protected by its lock, as handed to the
+/// closure passed to `SharedData::with_locked`.
+///
+/// They stay atomics, as `get_number` and the other lock-free accessors
+/// may read or write them meanwhile, and a plain `&mut i64` would race
+/// with those. Taking and releasing the lock already orders every access
+/// made with it held, so `Relaxed` is enough for these.
+pub struct SharedView<'a> {
+    pub number: &'a AtomicI64,
+    pub words: &'a [AtomicI64; PROTECTED_WORDS],
+}
+
+/// A futex word holding a count, with the few operations the locks here are
+/// built from, so each picks its memory orderings in exactly one place.
+/// `try_*` take something and order later accesses after it (`Acquire`);
+/// `*_and_wake` give something back and order earlier accesses before it
+/// (`Release`).
+///
+/// The binary lock counts its holders, at most one: `try_inc(1)` takes it,
+/// `dec_and_wake(1)` releases it and `wait_while(1)` sleeps while it is
+/// taken. A counting primitive instead stores what is available and pairs
+/// `try_dec` with `inc_and_wake`. Laid out exactly like the `RawSync` it
+/// wraps, which is what the child declares.
+#[repr(transparent)]
+pub struct FutexWord(RawSync);
+
+impl FutexWord {
+    pub const fn new(value: u32) -> Self {
+        Self(RawSync::new(value))
+    }
+
+    pub fn load(&self) -> u32 {
+        self.0.value.load(Ordering::Acquire)
+    }
+
+    /// The count with no ordering at all, for logging and diagnostics that
+    /// must not be mistaken for a synchronizing read.
+    pub fn load_relaxed(&self) -> u32 {
+        self.0.value.load(Ordering::Relaxed)
+    }
+
+    /// Adds one unless the count has reached `limit`.
+    pub fn try_inc(&self, limit: u32) -> bool {
+        self.0
+            .value
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |count| {
+                (count < limit).then(|| count + 1)
+            })
+            .is_ok()
+    }
+
+    /// Subtracts one unless the count is zero.
+    pub fn try_dec(&self) -> bool {
+        self.0
+            .value
+            .fetch_update(Ordering::Acquire, Ordering::Relaxed, |count| {
+                count.checked_sub(1)
+            })
+            .is_ok()
+    }
+
+    /// Adds one and wakes up to `n` waiters.
+    pub fn inc_and_wake(&self, n: i32) {
+        self.0.value.fetch_add(1, Ordering::Release);
+        self.0.wake(n);
+    }
+
+    /// Subtracts one unless the count is already zero, then wakes up to `n`
+    /// waiters. Returns `false`, waking nobody, if it was zero.
+    pub fn dec_and_wake(&self, n: i32) -> bool {
+        let decremented = self
+            .0
+            .value
+            .fetch_update(Ordering::Release, Ordering::Relaxed, |count| {
+                count.checked_sub(1)
+            })
+            .is_ok();
+        if decremented {
+            self.0.wake(n);
+        }
+        decremented
+    }
+
+    /// Overwrites the count whatever it was, then wakes up to `n` waiters.
+    /// Only for resetting a word nobody is using correctly any more.
+    pub fn store_and_wake(&self, value: u32, n: i32) {
+        self.0.value.store(value, Ordering::Release);
+        self.0.wake(n);
+    }
+
+    /// Sleeps while the count is `value`. Returns early on a wakeup, a
+    /// signal or if the count already differs; the caller re-checks.
+    pub fn wait_while(&self, value: u32) -> Result<(), WaitError> {
+        self.0.wait(value)
+    }
+
+    /// Like `wait_while`, giving up after `timeout`.
+    pub fn wait_while_for(&self, value: u32, timeout: Duration) -> Result<(), TimedWaitError> {
+        self.0.wait_for(value, timeout)
+    }
+
+    pub fn wake(&self, n: i32) -> i32 {
+        self.0.wake(n)
+    }
+}
+
+/// Holds the futex lock on a `SharedData` and releases it when dropped,
+/// including when unwinding from a panic.
+///
+/// The protected number is read and written with `get`, `set` and
+/// `update`. The guard hands out no `&mut i64`: `number` stays a public
+/// atomic that `get_number`, `set_number` and the other lock-free
+/// accessors may touch while the lock is held, and a plain reference would
+/// race with them. It is deliberately `!Send`: the lock belongs to the
+/// process (and thread) that took it.
+#[must_use = "if unused the lock will immediately unlock"]
+pub struct SharedDataGuard<'a> {
+    data: &'a SharedData,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a> SharedDataGuard<'a> {
+    fn new(data: &'a SharedData) -> Self {
+        Self {
+            data,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// The protected number. Taking and releasing the lock already orders
+    /// the accesses made with it held, so these use `Relaxed`.
+    pub fn get(&self) -> i64 {
+        self.data.number.load(Ordering::Relaxed)
+    }
+
+    /// Stores `value` without bumping `number_generation`, unlike
+    /// `set_number`.
+    pub fn set(&mut self, value: i64) {
+        self.data.number.store(value, Ordering::Relaxed);
+    }
+
+    /// Replaces the number with `f` of it, e.g. `guard.update(|n| n + 1)`.
+    pub fn update(&mut self, f: impl FnOnce(i64) -> i64) {
+        let value = f(self.get());
+        self.set(value);
+    }
+}
+
+impl std::fmt::Debug for SharedDataGuard<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_tuple("SharedDataGuard").field(&self.get()).finish()
+    }
+}
+
+impl Drop for SharedDataGuard<'_> {
+    fn drop(&mut self) {
+        if std::thread::panicking() {
+            self.data.poisoned.store(true, Ordering::Release);
+        }
+        self.data.unlock();
+    }
+}
+
+/// Returned by `SharedData::lock_guard` when an earlier holder panicked
+/// with the lock held. The lock is taken all the same; `into_inner` hands
+/// over the guard.
+pub struct PoisonError<'a> {
+    guard: SharedDataGuard<'a>,
+}
+
+impl<'a> PoisonError<'a> {
+    pub fn into_inner(self) -> SharedDataGuard<'a> {
+        self.guard
+    }
+
+    pub fn get_ref(&self) -> &SharedDataGuard<'a> {
+        &self.guard
+    }
+}
+
+impl std::fmt::Debug for PoisonError<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("PoisonError").finish_non_exhaustive()
+    }
+}
+
+impl std::fmt::Display for PoisonError<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "a previous lock holder panicked; shared data may be inconsistent"
+        )
+    }
+}
+
+impl std::error::Error for PoisonError<'_> {}
+
+/// A futex-protected value of any plain-old-data type.
+///
+/// Every access to the value goes through the lock, so `T` does not need
+/// to be atomic. Keep in mind that the 32-bit child must see the same
+/// `#[repr(C)]` layout, so `T` should only contain fixed-width fields whose
+/// alignment does not change between targets.
+#[repr(C)]
+#[allow(dead_code)]
+pub struct SharedCell<T: Copy + 'static> {
+    pub futex: FutexWord,
+    value: UnsafeCell<T>,
+}
+
+// SAFETY: `value` is only read or written while `futex` is held.
+unsafe impl<T: Copy + Send + 'static> Sync for SharedCell<T> {}
+
+#[allow(dead_code)]
+impl<T: Copy + 'static> SharedCell<T> {
+    /// Fails to compile for a `T` that does not fit the payload slot, so
+    /// a cell the child could lay out differently never gets built:
+    ///
+    /// ```compile_fail
+    /// sharedmem_multiarch::shared::SharedCell::new([0u64; 2]);
+    /// ```
+    pub fn new(value: T) -> Self {
+        const {
+            assert!(
+                std::mem::size_of::<T>() <= PAYLOAD_SIZE
+                    && std::mem::align_of::<T>() <= PAYLOAD_ALIGN
+            )
+        };
+        Self {
+            futex: FutexWord::new(0),
+            value: UnsafeCell::new(value),
+        }
+    }
+
+    pub fn get(&self) -> Result<T, SharedMemError> {
+        self.lock()?;
+        // SAFETY: we hold the lock.
+        let value = unsafe { *self.value.get() };
+        self.unlock();
+        Ok(value)
+    }
+
+    pub fn set(&self, value: T) -> Result<(), SharedMemError> {
+        self.lock()?;
+        // SAFETY: we hold the lock.
+        unsafe { *self.value.get() = value };
+        self.unlock();
+        Ok(())
+    }
+
+    fn lock(&self) -> Result<(), SharedMemError> {
+        while !self.futex.try_inc(1) {
+            match self.futex.wait_while(1) {
+                Ok(()) | Err(WaitError::WrongValue) => {}
+                Err(WaitError::Interrupted) => return Err(SharedMemError::Interrupted),
+            }
+        }
+        Ok(())
+    }
+
+    fn unlock(&self) {
+        self.futex.dec_and_wake(1);
+    }
+}
+
+/// A number behind a reader/writer lock built from two futex words.
+///
+/// Any number of readers may hold the lock at once; a writer gets exclusive
+/// access. A writer announces itself in `writer` before waiting for the
+/// readers to drain, and new readers back off while it is set, so a steady
+/// stream of readers cannot starve writers.
+#[repr(C)]
+pub struct RwSharedData {
+    /// Number of readers currently holding the lock.
+    pub readers: RawSync,
+    /// 1 while a writer is waiting for or holding the lock, 0 otherwise.
+    pub writer: RawSync,
+    /// 1 while an upgradable reader or a writer holds the lock. Writers
+    /// take it before `writer`, so an upgrade never finds `writer` taken
+    /// by someone waiting for it to leave.
+    pub upgrader: RawSync,
+    pub number: AtomicI64,
+}
+
+#[allow(dead_code)]
+impl RwSharedData {
+    pub fn new(number: i64) -> Self {
+        Self {
+            readers: RawSync::new(0),
+            writer: RawSync::new(0),
+            upgrader: RawSync::new(0),
+            number: AtomicI64::new(number),
+        }
+    }
+
+    pub fn read_lock_timeout(&self, timeout: Duration) -> Result<RwReadGuard<'_>, SharedMemError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            if self.writer.value.load(Ordering::SeqCst) != 0 {
+                sleep_while(&self.writer, 1, deadline)?;
+                continue;
+            }
+
+            self.readers.value.fetch_add(1, Ordering::SeqCst);
+            if self.writer.value.load(Ordering::SeqCst) == 0 {
+                return Ok(RwReadGuard {
+                    data: self,
+                    _not_send: PhantomData,
+                });
+            }
+
+            // A writer showed up between the check and the increment; let it
+            // go first.
+            self.read_unlock();
+        }
+    }
+
+    /// Like `read_lock_timeout`, but the guard can later be turned into a
+    /// write guard with `upgrade` without letting a writer in between.
+    ///
+    /// Plain readers can share the lock with an upgradable reader, but only
+    /// one upgradable reader is let in at a time and writers are kept out:
+    /// two of them upgrading would each wait for the other to leave.
+    pub fn read_lock_upgradable_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<RwUpgradableGuard<'_>, SharedMemError> {
+        let deadline = Instant::now() + timeout;
+        self.lock_upgrader(deadline)?;
+        // We hold `upgrader`, so `writer` is clear and stays clear.
+        self.readers.value.fetch_add(1, Ordering::SeqCst);
+        Ok(RwUpgradableGuard {
+            data: self,
+            _not_send: PhantomData,
+        })
+    }
+
+    pub fn write_lock_timeout(
+        &self,
+        timeout: Duration,
+    ) -> Result<RwWriteGuard<'_>, SharedMemError> {
+        let deadline = Instant::now() + timeout;
+        self.lock_upgrader(deadline)?;
+        self.writer.value.store(1, Ordering::SeqCst);
+
+        if let Err(e) = self.wait_for_readers(0, Some(deadline)) {
+            self.write_unlock();
+            return Err(e);
+        }
+        Ok(RwWriteGuard {
+            data: self,
+            _not_send: PhantomData,
+        })
+    }
+
+    fn lock_upgrader(&self, deadline: Instant) -> Result<(), SharedMemError> {
+        while self
+            .upgrader
+            .value
+            .compare_exchange(0, 1, Ordering::SeqCst, Ordering::Relaxed)
+            .is_err()
+        {
+            sleep_while(&self.upgrader, 1, deadline)?;
+        }
+        Ok(())
+    }
+
+    /// Waits until at most `others` readers hold the lock, i.e. only the
+    /// caller itself if it is a reader. Without a deadline it cannot fail.
+    fn wait_for_readers(
+        &self,
+        others: u32,
+        deadline: Option<Instant>,
+    ) -> Result<(), SharedMemError> {
+        loop {
+            let readers = self.readers.value.load(Ordering::SeqCst);
+            if readers <= others {
+                return Ok(());
+            }
+            match deadline {
+                Some(deadline) => sleep_while(&self.readers, readers, deadline)?,
+                None => {
+                    let _ = self.readers.wait(readers);
+                }
+            }
+        }
+    }
+
+    fn read_unlock(&self) {
+        if self.readers.value.fetch_sub(1, Ordering::Release) <= 2 {
+            // Only the holder of `upgrader` ever waits on `readers`, for the
+            // count to drop to 0 (a writer) or 1 (an upgrade).
+            self.readers.wake(1);
+        }
+    }
+
+    fn write_unlock(&self) {
+        self.writer.value.store(0, Ordering::Release);
+        self.writer.wake(i32::MAX);
+        self.upgrader_unlock();
+    }
+
+    fn upgrader_unlock(&self) {
+        self.upgrader.value.store(0, Ordering::Release);
+        self.upgrader.wake(1);
+    }
+}
+
+/// Shared access to the number of an `RwSharedData`.
+#[must_use = "if unused the lock will immediately unlock"]
+pub struct RwReadGuard<'a> {
+    data: &'a RwSharedData,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Deref for RwReadGuard<'_> {
+    type Target = i64;
+
+    fn deref(&self) -> &i64 {
+        // SAFETY: no writer can hold the lock while we are a reader.
+        unsafe { &*self.data.number.as_ptr() }
+    }
+}
+
+impl Drop for RwReadGuard<'_> {
+    fn drop(&mut self) {
+        self.data.read_unlock();
+    }
+}
+
+/// Shared access to the number of an `RwSharedData` that can be upgraded to
+/// exclusive access.
+#[must_use = "if unused the lock will immediately unlock"]
+pub struct RwUpgradableGuard<'a> {
+    data: &'a RwSharedData,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<'a> RwUpgradableGuard<'a> {
+    /// Waits for the other readers to leave and takes the write lock. New
+    /// readers are turned away meanwhile, and no writer can get in first
+    /// because none can get past `upgrader` while we hold it.
+    pub fn upgrade(self) -> RwWriteGuard<'a> {
+        let data = self.data;
+        std::mem::forget(self);
+        data.writer.value.store(1, Ordering::SeqCst);
+        // Waits for everyone but us, then drops our own read slot.
+        let _ = data.wait_for_readers(1, None);
+        data.readers.value.fetch_sub(1, Ordering::SeqCst);
+        RwWriteGuard {
+            data,
+            _not_send: PhantomData,
+        }
+    }
+}
+
+impl Deref for RwUpgradableGuard<'_> {
+    type Target = i64;
+
+    fn deref(&self) -> &i64 {
+        // SAFETY: no writer can hold the lock while we are a reader.
+        unsafe { &*self.data.number.as_ptr() }
+    }
+}
+
+impl Drop for RwUpgradableGuard<'_> {
+    fn drop(&mut self) {
+        self.data.read_unlock();
+        self.data.upgrader_unlock();
+    }
+}
+
+/// Exclusive access to the number of an `RwSharedData`.
+#[must_use = "if unused the lock will immediately unlock"]
+pub struct RwWriteGuard<'a> {
+    data: &'a RwSharedData,
+    _not_send: PhantomData<*const ()>,
+}
+
+impl Deref for RwWriteGuard<'_> {
+    type Target = i64;
+
+    fn deref(&self) -> &i64 {
+        // SAFETY: the writer excludes both readers and other writers.
+        unsafe { &*self.data.number.as_ptr() }
+    }
+}
+
+impl DerefMut for RwWriteGuard<'_> {
+    fn deref_mut(&mut self) -> &mut i64 {
+        // SAFETY: see `Deref`.
+        unsafe { &mut *self.data.number.as_ptr() }
+    }
+}
+
+impl Drop for RwWriteGuard<'_> {
+    fn drop(&mut self) {
+        self.data.write_unlock();
+    }
+}
+
+/// A counting semaphore that can be embedded in a shared region, e.g. to
+/// let only K of N children into a section at once.
+///
+/// `count` holds the free permits. `wakeups` is bumped by every `release`,
+/// so a waiter that read it before finding no permits sleeps only until
+/// some permit may have come back.
+#[repr(C)]
+#[allow(dead_code)]
+pub struct SharedSemaphore {
+    pub count: AtomicI64,
+    pub wakeups: RawSync,
+}
+
+#[allow(dead_code)]
+impl SharedSemaphore {
+    pub fn new(permits: i64) -> Self {
+        Self {
+            count: AtomicI64::new(permits),
+            wakeups: RawSync::new(0),
+        }
+    }
+
+    /// Takes one permit, waiting up to `timeout` for one to be released.
+    pub fn acquire_timeout(&self, timeout: Duration) -> Result<(), SharedMemError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            // Read the sequence before the count so a release in between
+            // makes the futex wait return immediately.
+            let seq = self.wakeups.value.load(Ordering::Acquire);
+            let count = self.count.load(Ordering::Acquire);
+            if count > 0 {
+                if self
+                    .count
+                    .compare_exchange(count, count - 1, Ordering::Acquire, Ordering::Relaxed)
+                    .is_ok()
+                {
+                    return Ok(());
+                }
+                continue;
+            }
+            sleep_while(&self.wakeups, seq, deadline)?;
+        }
+    }
+
+    /// Returns a permit and wakes one waiter.
+    pub fn release(&self) {
+        self.count.fetch_add(1, Ordering::Release);
+        self.wakeups.value.fetch_add(1, Ordering::Release);
+        self.wakeups.wake(1);
+    }
+}
+
+/// A reusable rendezvous for a fixed number of threads or processes.
+///
+/// `generation` counts completed phases. Waiters sleep on it rather than on
+/// `arrived`, so a fast process re-entering `wait` cannot be mistaken for
+/// the end of the phase it just left. `arrived` holds the count of the
+/// current phase in its low 16 bits and that phase's number, truncated, in
+/// the high 16: the last arrival moves both on with one compare-and-swap,
+/// so nobody can be counted into a phase that is already over, and a
+/// timed-out participant can take back its arrival only in the phase it
+/// made it in.
+#[repr(C)]
+#[allow(dead_code)]
+pub struct SharedBarrier {
+    pub participants: u32,
+    pub arrived: AtomicU32,
+    pub generation: RawSync,
+}
+
+/// Bits of `SharedBarrier::arrived` holding the count.
+const BARRIER_COUNT_MASK: u32 = 0xFFFF;
+
+#[allow(dead_code)]
+impl SharedBarrier {
+    /// A barrier for `participants`, clamped to between 1 and 65535.
+    pub fn new(participants: u32) -> Self {
+        Self {
+            participants: participants.clamp(1, BARRIER_COUNT_MASK),
+            arrived: AtomicU32::new(0),
+            generation: RawSync::new(0),
+        }
+    }
+
+    /// Blocks until all participants have called `wait` in this phase.
+    /// Exactly one of them, the last to arrive, gets `true`.
+    pub fn wait(&self) -> bool {
+        let Some(phase) = self.arrive() else {
+            return true;
+        };
+        loop {
+            let generation = self.generation.value.load(Ordering::Acquire);
+            if phase_is_over(phase, generation) {
+                return false;
+            }
+            // Interruptions and stale values only mean re-checking.
+            let _ = self.generation.wait(generation);
+        }
+    }
+
+    /// Like `wait`, giving up after `timeout`. A participant that gives up
+    /// takes its arrival back, so the phase still needs someone else in its
+    /// place; if the phase ended before it could, it was not left behind
+    /// after all and gets `Ok(false)`.
+    pub fn wait_timeout(&self, timeout: Duration) -> Result<bool, SharedMemError> {
+        let deadline = Instant::now() + timeout;
+        let Some(phase) = self.arrive() else {
+            return Ok(true);
+        };
+        loop {
+            let generation = self.generation.value.load(Ordering::Acquire);
+            if phase_is_over(phase, generation) {
+                return Ok(false);
+            }
+            if let Err(e) = sleep_while(&self.generation, generation, deadline) {
+                return if self.leave(phase) { Err(e) } else { Ok(false) };
+            }
+        }
+    }
+
+    /// Counts this participant in. The last one ends the phase and gets
+    /// `None`; everyone else gets the number of the phase to wait out.
+    fn arrive(&self) -> Option<u32> {
+        let mut arrived = self.arrived.load(Ordering::Acquire);
+        loop {
+            let phase = arrived >> 16;
+            let count = (arrived & BARRIER_COUNT_MASK) + 1;
+            let last = count >= self.participants;
+            let next = if last {
+                ((phase + 1) & BARRIER_COUNT_MASK) << 16
+            } else {
+                arrived + 1
+            };
+            match self.arrived.compare_exchange_weak(
+                arrived,
+                next,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) if last => break,
+                Ok(_) => return Some(phase),
+                Err(actual) => arrived = actual,
+            }
+        }
+        self.generation.value.fetch_add(1, Ordering::Release);
+        self.generation.wake(i32::MAX);
+        None
+    }
+
+    /// Takes back an arrival in `phase`, unless that phase has already
+    /// ended. Returns whether it did.
+    fn leave(&self, phase: u32) -> bool {
+        let mut arrived = self.arrived.load(Ordering::Acquire);
+        loop {
+            if arrived >> 16 != phase {
+                return false;
+            }
+            match self.arrived.compare_exchange_weak(
+                arrived,
+                arrived - 1,
+                Ordering::AcqRel,
+                Ordering::Acquire,
+            ) {
+                Ok(_) => return true,
+                Err(actual) => arrived = actual,
+            }
+        }
+    }
+}
+
+/// Whether `generation` is past `phase`, a phase number as kept in the high
+/// bits of `SharedBarrier::arrived`. The bump can trail the next phase's
+/// first arrivals, so this looks for `generation` being ahead, not just
+/// different.
+fn phase_is_over(phase: u32, generation: u32) -> bool {
+    let ahead = (generation & BARRIER_COUNT_MASK).wrapping_sub(phase) & BARRIER_COUNT_MASK;
+    ahead != 0 && ahead < 0x8000
+}
+
+/// Sleeps while `futex` still holds `expected`, giving up at `deadline`.
+/// Wakeups, value changes and the futex's own timeout all return `Ok` so
+/// the caller re-checks its condition.
+fn sleep_while(futex: &RawSync, expected: u32, deadline: Instant) -> Result<(), SharedMemError> {
+    let remaining = deadline.saturating_duration_since(Instant::now());
+    if remaining.is_zero() {
+        return Err(SharedMemError::Timeout);
+    }
+    match futex.wait_for(expected, remaining) {
+        Ok(()) | Err(TimedWaitError::WrongValue) | Err(TimedWaitError::TimedOut) => Ok(()),
+        Err(TimedWaitError::Interrupted) => Err(SharedMemError::Interrupted),
     }
 }
@@ -0,0 +1,195 @@
+//! Fixed-capacity vector living in shared memory, for sharing a handful of
+//! values rather than a single number.
+//!
+//! Like `stack`, this file is included by the child with `#[path]`, so it
+//! only depends on `std` and `raw_sync`.
+
+#![allow(dead_code)]
+
+use crate::raw_sync::RawSync;
+use std::cell::UnsafeCell;
+use std::marker::PhantomData;
+use std::ops::Deref;
+use std::sync::atomic::{AtomicU32, Ordering};
+
+/// Element types a `SharedVec` may hold: plain numbers of the same width
+/// in the 64-bit parent and the 32-bit child, valid for any bit pattern.
+///
+/// The 8-byte types are only 4-aligned on i686, but the elements start 8
+/// bytes in, so they land at the same offsets on both sides as long as the
+/// vec itself is placed 8-aligned.
+///
+/// # Safety
+///
+/// Implementors must have the same size on every target and no padding,
+/// pointers or invalid bit patterns.
+pub unsafe trait FixedWidth: Copy {}
+
+unsafe impl FixedWidth for i8 {}
+unsafe impl FixedWidth for i16 {}
+unsafe impl FixedWidth for i32 {}
+unsafe impl FixedWidth for i64 {}
+unsafe impl FixedWidth for u8 {}
+unsafe impl FixedWidth for u16 {}
+unsafe impl FixedWidth for u32 {}
+unsafe impl FixedWidth for u64 {}
+unsafe impl FixedWidth for f32 {}
+unsafe impl FixedWidth for f64 {}
+
+/// Up to `N` values of `T`, pushed and popped at the end.
+///
+/// The elements are plain values, not atomics, so every access goes
+/// through `lock`, a futex word of the vec's own like `SharedStack`'s: a
+/// lock that lives next to the data cannot be mistaken for another
+/// region's. `len` is a `u32`, not `usize`, which is narrower in the
+/// 32-bit child, and is atomic so it can be read without the lock.
+#[repr(C)]
+pub struct SharedVec<T: FixedWidth, const N: usize> {
+    /// 1 while someone holds a `SharedVecGuard`.
+    pub lock: RawSync,
+    len: AtomicU32,
+    slots: UnsafeCell<[T; N]>,
+}
+
+// SAFETY: the slots are only touched through a guard, which holds `lock`.
+unsafe impl<T: FixedWidth, const N: usize> Sync for SharedVec<T, N> {}
+
+#[cfg(target_os = "linux")]
+const _: () = assert!(std::mem::size_of::<SharedVec<u32, 4>>() == 8 + 4 * 4);
+#[cfg(target_os = "linux")]
+const _: () = assert!(std::mem::offset_of!(SharedVec<i64, 4>, slots) == 8);
+
+impl<T: FixedWidth, const N: usize> SharedVec<T, N> {
+    /// An empty vec, its unused slots zeroed.
+    pub fn new() -> Self {
+        const { assert!(N <= u32::MAX as usize) };
+        Self {
+            lock: RawSync::new(0),
+            len: AtomicU32::new(0),
+            // SAFETY: `FixedWidth` types are valid for any bit pattern.
+            slots: UnsafeCell::new(unsafe { std::mem::zeroed() }),
+        }
+    }
+
+    /// Number of values, which may change as soon as it is read unless the
+    /// caller holds the lock.
+    pub fn len(&self) -> usize {
+        self.len.load(Ordering::Acquire) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    pub const fn capacity(&self) -> usize {
+        N
+    }
+
+    /// Takes the lock, sleeping on the futex while another holder has it.
+    /// Meant for short sections: there is no timeout, and a holder that
+    /// dies keeps the vec locked.
+    pub fn lock(&self) -> SharedVecGuard<'_, T, N> {
+        while self
+            .lock
+            .value
+            .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // Interruptions and stale values only mean trying again.
+            let _ = self.lock.wait(1);
+        }
+        SharedVecGuard {
+            vec: self,
+            _not_send: PhantomData,
+        }
+    }
+
+    /// Appends `value` under the lock, or hands it back if the vec is full.
+    pub fn push(&self, value: T) -> Result<(), T> {
+        self.lock().push(value)
+    }
+
+    /// Takes the last value under the lock, if any.
+    pub fn pop(&self) -> Option<T> {
+        self.lock().pop()
+    }
+
+    /// The value at `index` under the lock, if there is one.
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.lock().get(index)
+    }
+}
+
+impl<T: FixedWidth, const N: usize> Default for SharedVec<T, N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
+
+/// The lock on a `SharedVec`, released on drop. Dereferences to the values
+/// as a slice.
+pub struct SharedVecGuard<'a, T: FixedWidth, const N: usize> {
+    vec: &'a SharedVec<T, N>,
+    // The futex is released by whoever holds the guard, so keep it on the
+    // thread that took it, as with `SharedDataGuard`.
+    _not_send: PhantomData<*const ()>,
+}
+
+impl<T: FixedWidth, const N: usize> SharedVecGuard<'_, T, N> {
+    pub fn len(&self) -> usize {
+        self.vec.len.load(Ordering::Relaxed) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Appends `value`, or hands it back if the vec is full.
+    pub fn push(&mut self, value: T) -> Result<(), T> {
+        let len = self.len();
+        if len == N {
+            return Err(value);
+        }
+        // SAFETY: we hold the lock, and `&mut self` keeps the slice handed
+        // out by `as_slice` from being alive across this write.
+        let slots = unsafe { &mut *self.vec.slots.get() };
+        slots[len] = value;
+        self.vec.len.store(len as u32 + 1, Ordering::Release);
+        Ok(())
+    }
+
+    pub fn pop(&mut self) -> Option<T> {
+        let values = self.as_slice();
+        let value = *values.last()?;
+        let len = values.len() - 1;
+        self.vec.len.store(len as u32, Ordering::Release);
+        Some(value)
+    }
+
+    pub fn get(&self, index: usize) -> Option<T> {
+        self.as_slice().get(index).copied()
+    }
+
+    /// The values pushed so far, oldest first.
+    pub fn as_slice(&self) -> &[T] {
+        // SAFETY: we hold the lock, and only `&mut self` methods write.
+        // `min` guards against a peer that stored a bad length.
+        let slots = unsafe { &*self.vec.slots.get() };
+        &slots[..self.len().min(N)]
+    }
+}
+
+impl<T: FixedWidth, const N: usize> Deref for SharedVecGuard<'_, T, N> {
+    type Target = [T];
+
+    fn deref(&self) -> &[T] {
+        self.as_slice()
+    }
+}
+
+impl<T: FixedWidth, const N: usize> Drop for SharedVecGuard<'_, T, N> {
+    fn drop(&mut self) {
+        self.vec.lock.value.store(0, Ordering::Release);
+        self.vec.lock.wake(1);
+    }
+}
@@ -0,0 +1,143 @@
+//! Bounded LIFO stack living in shared memory, for handing out work where
+//! the most recently pushed item should be taken first.
+//!
+//! Like `ring`, this file is included by the child with `#[path]`, so it
+//! only depends on `std`, `raw_sync` and `ring`.
+
+#![allow(dead_code)]
+
+use crate::raw_sync::{RawSync, TimedWaitError};
+pub use crate::ring::Full;
+use crate::ring::wait_for_change;
+use std::sync::atomic::{AtomicI64, AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+/// A bounded stack of `i64` for any number of pushers and poppers.
+///
+/// Pushing and popping both move `top` and touch the slot under it, which
+/// cannot be done as one atomic step, so each takes the short internal
+/// `lock` first. `top` is a `u32`, not `usize`, which is narrower in the
+/// 32-bit child.
+///
+/// Pushers finding the stack full and poppers finding it empty sleep on
+/// `changes` rather than `top`: a push and a pop in quick succession leave
+/// `top` where it was, and a sleeper comparing against it would miss both.
+#[repr(C)]
+pub struct SharedStack<const N: usize> {
+    /// 1 while a push or pop is in progress.
+    pub lock: RawSync,
+    /// Number of values on the stack.
+    pub top: AtomicU32,
+    /// Bumped by every push and pop.
+    pub changes: RawSync,
+    slots: [AtomicI64; N],
+}
+
+#[cfg(target_os = "linux")]
+const _: () = assert!(std::mem::size_of::<SharedStack<4>>() == 16 + 4 * 8);
+
+impl<const N: usize> SharedStack<N> {
+    pub fn new() -> Self {
+        const { assert!(N > 0 && N <= u32::MAX as usize) };
+        Self {
+            lock: RawSync::new(0),
+            top: AtomicU32::new(0),
+            changes: RawSync::new(0),
+            slots: std::array::from_fn(|_| AtomicI64::new(0)),
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.top.load(Ordering::Acquire) as usize
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len() == 0
+    }
+
+    /// Puts `value` on top, or hands it back if the stack is full.
+    pub fn push(&self, value: i64) -> Result<(), Full> {
+        self.lock();
+        let top = self.top.load(Ordering::Relaxed);
+        if top as usize == N {
+            self.unlock();
+            return Err(Full(value));
+        }
+        self.slots[top as usize].store(value, Ordering::Relaxed);
+        self.top.store(top + 1, Ordering::Release);
+        self.unlock();
+        self.bump_changes();
+        Ok(())
+    }
+
+    /// Takes the most recently pushed value, if any.
+    pub fn pop(&self) -> Option<i64> {
+        self.lock();
+        let top = self.top.load(Ordering::Relaxed);
+        if top == 0 {
+            self.unlock();
+            return None;
+        }
+        let value = self.slots[top as usize - 1].load(Ordering::Relaxed);
+        self.top.store(top - 1, Ordering::Release);
+        self.unlock();
+        self.bump_changes();
+        Some(value)
+    }
+
+    /// Like `push`, but sleeps until a pop frees a slot.
+    pub fn push_blocking(&self, value: i64, timeout: Duration) -> Result<(), TimedWaitError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            // Read `changes` before trying so a pop in between wakes us at once.
+            let changes = self.changes.value.load(Ordering::Acquire);
+            if self.push(value).is_ok() {
+                return Ok(());
+            }
+            wait_for_change(&self.changes, changes, deadline)?;
+        }
+    }
+
+    /// Like `pop`, but sleeps until something is pushed.
+    pub fn pop_blocking(&self, timeout: Duration) -> Result<i64, TimedWaitError> {
+        let deadline = Instant::now() + timeout;
+
+        loop {
+            let changes = self.changes.value.load(Ordering::Acquire);
+            if let Some(value) = self.pop() {
+                return Ok(value);
+            }
+            wait_for_change(&self.changes, changes, deadline)?;
+        }
+    }
+
+    /// Held only for a load and two stores, so waiters rarely sleep.
+    fn lock(&self) {
+        while self
+            .lock
+            .value
+            .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
+            .is_err()
+        {
+            // Interruptions and stale values only mean trying again.
+            let _ = self.lock.wait(1);
+        }
+    }
+
+    fn unlock(&self) {
+        self.lock.value.store(0, Ordering::Release);
+        self.lock.wake(1);
+    }
+
+    fn bump_changes(&self) {
+        self.changes.value.fetch_add(1, Ordering::Release);
+        self.changes.wake(i32::MAX);
+    }
+}
+
+impl<const N: usize> Default for SharedStack<N> {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,118 @@
+//! Shared regions backed by a System V segment, for systems where POSIX
+//! shared memory is unavailable, such as containers without `/dev/shm`.
+//!
+//! The parent creates a private segment with `shmget(IPC_PRIVATE)` and
+//! hands children its ID as `sysv:<shmid>`; keys only matter for finding a
+//! segment by name, and a private one has none to collide with. Compared to
+//! a POSIX segment:
+//!
+//! - It needs no filesystem at all, only the kernel's SysV IPC, which
+//!   container runtimes leave enabled far more often than they mount
+//!   `/dev/shm`. Processes must share an IPC namespace, though.
+//! - Segments are limited by `kernel.shmmax` and `kernel.shmall` instead of
+//!   the size of `/dev/shm`; the defaults are far above what is needed here.
+//! - The ID is guessable and access is checked only against the mode bits,
+//!   much as for a named POSIX segment; unlike an anonymous memfd, other
+//!   processes of the same user can attach.
+//! - A segment outlives every process until removed. The creator marks it
+//!   for removal when dropped, after which Linux still lets attached
+//!   processes keep it and late ones attach; one left behind by a crash
+//!   shows up in `ipcs -m` and is removed with `ipcrm`.
+//!
+//! The child crate includes this file with `#[path]` to attach to the
+//! handle it was given.
+
+#![allow(dead_code)]
+
+use std::io;
+
+/// Prefix that marks an OS ID as a System V segment ID.
+pub const HANDLE_PREFIX: &str = "sysv:";
+
+/// The segment ID in a handle such as `sysv:32769`, or `None` for any
+/// other OS ID.
+pub fn parse_handle(os_id: &str) -> Option<libc::c_int> {
+    os_id.strip_prefix(HANDLE_PREFIX)?.parse().ok()
+}
+
+/// A System V segment attached to this process. Dropping it detaches, and
+/// the creator's copy also marks the segment for removal, which happens
+/// once nobody is attached any more.
+#[derive(Debug)]
+pub struct SysvMapping {
+    id: libc::c_int,
+    ptr: *mut u8,
+    len: usize,
+    owner: bool,
+}
+
+impl SysvMapping {
+    /// Creates a zeroed private segment of `len` bytes, readable and
+    /// writable by this user only.
+    pub fn create(len: usize) -> io::Result<Self> {
+        let id = unsafe { libc::shmget(libc::IPC_PRIVATE, len, libc::IPC_CREAT | 0o600) };
+        if id < 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let attached = Self::attach_id(id, len, true);
+        if attached.is_err() {
+            unsafe { libc::shmctl(id, libc::IPC_RMID, std::ptr::null_mut()) };
+        }
+        attached
+    }
+
+    /// Attaches to the segment `id` over its full size.
+    pub fn attach(id: libc::c_int) -> io::Result<Self> {
+        let mut stat = std::mem::MaybeUninit::<libc::shmid_ds>::uninit();
+        if unsafe { libc::shmctl(id, libc::IPC_STAT, stat.as_mut_ptr()) } != 0 {
+            return Err(io::Error::last_os_error());
+        }
+        let len = unsafe { stat.assume_init() }.shm_segsz;
+        Self::attach_id(id, len, false)
+    }
+
+    fn attach_id(id: libc::c_int, len: usize, owner: bool) -> io::Result<Self> {
+        let ptr = unsafe { libc::shmat(id, std::ptr::null(), 0) };
+        if ptr as isize == -1 {
+            return Err(io::Error::last_os_error());
+        }
+        Ok(SysvMapping {
+            id,
+            ptr: ptr as *mut u8,
+            len,
+            owner,
+        })
+    }
+
+    /// Start of the segment, page aligned.
+    pub fn as_ptr(&self) -> *mut u8 {
+        self.ptr
+    }
+
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// Whether this process created the segment (and so removes it).
+    pub fn is_owner(&self) -> bool {
+        self.owner
+    }
+
+    /// The handle other processes attach with, e.g. `sysv:32769`.
+    pub fn handle(&self) -> String {
+        format!("{}{}", HANDLE_PREFIX, self.id)
+    }
+}
+
+impl Drop for SysvMapping {
+    fn drop(&mut self) {
+        unsafe { libc::shmdt(self.ptr as *const libc::c_void) };
+        if self.owner {
+            unsafe { libc::shmctl(self.id, libc::IPC_RMID, std::ptr::null_mut()) };
+        }
+    }
+}
@@ -0,0 +1,49 @@
+//! One knob for every timeout the demo and the child wait with, for
+//! machines slower than the defaults were picked on, such as a loaded CI
+//! runner: `SHAREDMEM_TIMEOUT_SCALE=5` gives everything five times as long.
+//!
+//! Included by the child with `#[path]`, so both binaries read the variable
+//! the same way; it only depends on `std`.
+
+#![allow(dead_code)]
+
+use std::sync::OnceLock;
+use std::time::Duration;
+
+/// Environment variable holding the factor, a positive float. Unset means
+/// 1.0, which leaves every timeout as it is.
+pub const TIMEOUT_SCALE_VAR: &str = "SHAREDMEM_TIMEOUT_SCALE";
+
+/// `timeout` multiplied by the factor in `TIMEOUT_SCALE_VAR`. The variable
+/// is read once per process.
+pub fn scaled(timeout: Duration) -> Duration {
+    static SCALE: OnceLock<f64> = OnceLock::new();
+    let scale = *SCALE.get_or_init(|| {
+        let value = std::env::var(TIMEOUT_SCALE_VAR).ok();
+        parse_scale(value.as_deref()).unwrap_or_else(|e| {
+            eprintln!("Ignoring {}: {}", TIMEOUT_SCALE_VAR, e);
+            1.0
+        })
+    });
+    scale_by(timeout, scale)
+}
+
+/// The factor `value` of `TIMEOUT_SCALE_VAR` stands for, 1.0 if unset.
+pub fn parse_scale(value: Option<&str>) -> Result<f64, String> {
+    let Some(value) = value else {
+        return Ok(1.0);
+    };
+    let scale: f64 = value
+        .trim()
+        .parse()
+        .map_err(|e| format!("{:?} is not a number: {}", value, e))?;
+    if !scale.is_finite() || scale <= 0.0 {
+        return Err(format!("{:?} is not a positive factor", value));
+    }
+    Ok(scale)
+}
+
+/// `timeout * scale`, saturating instead of overflowing for huge factors.
+pub fn scale_by(timeout: Duration, scale: f64) -> Duration {
+    Duration::try_from_secs_f64(timeout.as_secs_f64() * scale).unwrap_or(Duration::MAX)
+}
@@ -0,0 +1,75 @@
+//! Pinning processes with `affinity::pin_to_cpu`, and the demo's
+//! `--child-cpu` built on it, checked against what the kernel reports.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::child_runnable;
+use sharedmem_multiarch::affinity::pin_to_cpu;
+use std::io::ErrorKind;
+use std::process::Command;
+
+/// `Cpus_allowed_list` from `/proc/<pid>/status`, e.g. "0-3" or "2".
+fn cpus_allowed(pid: u32) -> String {
+    let status = std::fs::read_to_string(format!("/proc/{}/status", pid)).unwrap();
+    status
+        .lines()
+        .find_map(|line| line.strip_prefix("Cpus_allowed_list:"))
+        .expect("no Cpus_allowed_list")
+        .trim()
+        .to_string()
+}
+
+/// Some CPU this process may run on, so pinning to it cannot fail.
+fn usable_cpu() -> usize {
+    let allowed = cpus_allowed(std::process::id());
+    let first = allowed.split([',', '-']).next().unwrap();
+    first.parse().unwrap()
+}
+
+#[test]
+fn pinned_process_is_allowed_only_that_cpu() {
+    let cpu = usable_cpu();
+    let mut sleeper = Command::new("sleep").arg("10").spawn().unwrap();
+    let pinned = pin_to_cpu(sleeper.id(), cpu);
+    let allowed = cpus_allowed(sleeper.id());
+    sleeper.kill().unwrap();
+    sleeper.wait().unwrap();
+
+    pinned.unwrap();
+    assert_eq!(allowed, cpu.to_string());
+}
+
+#[test]
+fn cpu_beyond_the_mask_is_refused() {
+    let error = pin_to_cpu(std::process::id(), libc::CPU_SETSIZE as usize).unwrap_err();
+    assert_eq!(error.kind(), ErrorKind::InvalidInput);
+}
+
+#[test]
+fn demo_pins_its_children() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping the pinned demo: the child cannot run here ({reason})");
+        return;
+    }
+
+    let cpu = usable_cpu();
+    let output = Command::new(env!("CARGO_BIN_EXE_sharedmem-multiarch"))
+        .env("SHAREDMEM_CHILD_CPU", cpu.to_string())
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "demo failed with {}\nstdout:\n{}\nstderr:\n{}",
+        output.status,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(
+        stdout.contains(&format!("Child 1 pinned to CPU {}\n", cpu)),
+        "no pinning reported in:\n{}",
+        stdout
+    );
+}
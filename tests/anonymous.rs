@@ -0,0 +1,61 @@
+//! Regions backed by an anonymous memfd, handed to the 32-bit child as an
+//! inherited `fd:<n>` descriptor instead of a name.
+
+#![cfg(target_os = "linux")]
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use sharedmem_multiarch::memfd::parse_handle;
+use sharedmem_multiarch::{OpenMode, SharedRegion};
+use std::collections::BTreeSet;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+fn dev_shm_entries() -> BTreeSet<PathBuf> {
+    std::fs::read_dir("/dev/shm")
+        .map(|dir| dir.map(|entry| entry.unwrap().path()).collect())
+        .unwrap_or_default()
+}
+
+#[test]
+fn anonymous_region_leaves_no_name_behind() {
+    let before = dev_shm_entries();
+    let shared_data = SharedRegion::builder()
+        .mode(OpenMode::Anonymous)
+        .build()
+        .unwrap();
+
+    let fd = parse_handle(shared_data.os_id()).expect("not an fd: handle");
+    let target = std::fs::read_link(format!("/proc/self/fd/{}", fd)).unwrap();
+    assert!(
+        target.to_string_lossy().starts_with("/memfd:"),
+        "descriptor points at {}",
+        target.display()
+    );
+    assert_eq!(dev_shm_entries(), before);
+}
+
+#[test]
+fn child_hands_the_number_back_through_an_inherited_memfd() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shared_data = SharedRegion::builder()
+        .mode(OpenMode::Anonymous)
+        .build()
+        .unwrap();
+    shared_data.set_number(100);
+
+    let child_exe = extract_child();
+    let status = Command::new(&child_exe)
+        .arg(shared_data.os_id())
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+
+    assert!(status.success(), "child failed with {}", status);
+    assert_eq!(shared_data.get_number(), 250);
+}
@@ -0,0 +1,74 @@
+//! `lock_async` waits for the lock without blocking the runtime thread, and
+//! recovers a lock left behind by a dead owner like the blocking calls do.
+
+#![cfg(feature = "async")]
+
+use sharedmem_multiarch::{OwnedSharedData, SharedMemError};
+use std::sync::atomic::{AtomicBool, AtomicU32, Ordering};
+use std::time::Duration;
+
+const HOLD: Duration = Duration::from_millis(200);
+
+#[test]
+fn waiting_leaves_the_runtime_free() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    let (held, lock_held) = std::sync::mpsc::channel();
+
+    std::thread::scope(|s| {
+        s.spawn(move || {
+            shared_data.lock().unwrap();
+            held.send(()).unwrap();
+            std::thread::sleep(HOLD);
+            shared_data.unlock();
+        });
+        lock_held.recv().unwrap();
+
+        let runtime = tokio::runtime::Builder::new_current_thread()
+            .enable_time()
+            .build()
+            .unwrap();
+        let ticks = AtomicU32::new(0);
+        let done = AtomicBool::new(false);
+        let number = runtime.block_on(async {
+            let ticker = async {
+                while !done.load(Ordering::Relaxed) {
+                    ticks.fetch_add(1, Ordering::Relaxed);
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            };
+            let waiter = async {
+                let guard = shared_data.lock_async().await.unwrap();
+                done.store(true, Ordering::Relaxed);
+                guard.get()
+            };
+            tokio::join!(ticker, waiter).1
+        });
+
+        assert_eq!(number, 100);
+        // The ticker ran on the same thread throughout the hold
+        assert!(ticks.load(Ordering::Relaxed) >= 5, "{:?}", ticks);
+    });
+}
+
+#[tokio::test(flavor = "current_thread")]
+async fn dead_owner_is_recovered() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let mut exited = std::process::Command::new("true").spawn().unwrap();
+    exited.wait().unwrap();
+    let dead_pid = exited.id() as i32;
+
+    shared_data.lock().unwrap();
+    // As the exited process would have left it
+    shared_data.owner_pid.store(dead_pid, Ordering::Relaxed);
+
+    match shared_data.lock_async().await {
+        Err(SharedMemError::RecoveredFromDeadOwner { owner_pid }) => {
+            assert_eq!(owner_pid, dead_pid)
+        }
+        Err(e) => panic!("expected RecoveredFromDeadOwner, got {}", e),
+        Ok(_) => panic!("took a lock that was still held"),
+    }
+    let guard = shared_data.lock_async().await.unwrap();
+    assert_eq!(guard.get(), 100);
+}
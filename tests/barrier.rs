@@ -0,0 +1,70 @@
+//! `SharedBarrier` holds every participant until the last one arrives, and
+//! can be crossed again right away for the next phase. One that gives up
+//! waiting is no longer counted.
+
+use sharedmem_multiarch::SharedMemError;
+use sharedmem_multiarch::shared::SharedBarrier;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+const PARTIES: u32 = 6;
+const PHASES: usize = 3;
+
+#[test]
+fn nobody_leaves_a_phase_early() {
+    let barrier = SharedBarrier::new(PARTIES);
+    let arrived: [AtomicU32; PHASES] = Default::default();
+    let leaders: [AtomicU32; PHASES] = Default::default();
+    // Counted rather than asserted in the threads: a panicking participant
+    // would leave the others waiting for it forever
+    let early = AtomicU32::new(0);
+
+    std::thread::scope(|s| {
+        for _ in 0..PARTIES {
+            s.spawn(|| {
+                for phase in 0..PHASES {
+                    arrived[phase].fetch_add(1, Ordering::SeqCst);
+                    if barrier.wait() {
+                        leaders[phase].fetch_add(1, Ordering::SeqCst);
+                    }
+                    if arrived[phase].load(Ordering::SeqCst) != PARTIES {
+                        early.fetch_add(1, Ordering::SeqCst);
+                    }
+                }
+            });
+        }
+    });
+
+    assert_eq!(early.load(Ordering::SeqCst), 0);
+    for phase in 0..PHASES {
+        assert_eq!(arrived[phase].load(Ordering::SeqCst), PARTIES);
+        assert_eq!(leaders[phase].load(Ordering::SeqCst), 1, "phase {}", phase);
+    }
+    assert_eq!(
+        barrier.generation.value.load(Ordering::SeqCst),
+        PHASES as u32
+    );
+}
+
+#[test]
+fn a_timed_out_participant_does_not_complete_the_phase_alone() {
+    let barrier = SharedBarrier::new(2);
+    for _ in 0..2 {
+        assert!(matches!(
+            barrier.wait_timeout(Duration::from_millis(50)),
+            Err(SharedMemError::Timeout)
+        ));
+    }
+    // Each gave its arrival back, and the phase never ended
+    assert_eq!(barrier.arrived.load(Ordering::SeqCst), 0);
+    assert_eq!(barrier.generation.value.load(Ordering::SeqCst), 0);
+
+    // The peer it was waiting for still gets through with it
+    let leaders = std::thread::scope(|s| {
+        let peer = s.spawn(|| barrier.wait_timeout(Duration::from_secs(10)));
+        let mine = barrier.wait_timeout(Duration::from_secs(10)).unwrap();
+        u32::from(mine) + u32::from(peer.join().unwrap().unwrap())
+    });
+    assert_eq!(leaders, 1);
+    assert_eq!(barrier.generation.value.load(Ordering::SeqCst), 1);
+}
@@ -0,0 +1,97 @@
+//! The diagnosis `build.rs` gives for a failed child build, fed with
+//! stderr as the common failures produce it.
+
+#[path = "../build_support/child_build_error.rs"]
+mod child_build_error;
+
+use child_build_error::{Diagnosis, diagnose};
+
+const GNU: &str = "i686-unknown-linux-gnu";
+
+/// Linking with rust-lld on a 64-bit Debian without `gcc-multilib`.
+const LLD_MISSING_LIBC: &str = "\
+error: linking with `cc` failed: exit status: 1
+  |
+  = note: rust-lld: error: unable to find library -lgcc_s
+          rust-lld: error: unable to find library -lc
+          rust-lld: error: cannot open crtn.o: No such file or directory
+          collect2: error: ld returned 1 exit status
+error: could not compile `child_process` (bin \"child_process\") due to 1 previous error";
+
+/// The same with GNU ld, which also warns about the 64-bit libraries it
+/// passed over.
+const LD_MISSING_CRT: &str = "\
+  = note: /usr/bin/ld: cannot find crt1.o: No such file or directory
+          /usr/bin/ld: cannot find crti.o: No such file or directory
+          /usr/bin/ld: skipping incompatible /usr/lib/gcc/x86_64-linux-gnu/12/libgcc.a when searching for -lgcc";
+
+const MISSING_STD: &str = "\
+error[E0463]: can't find crate for `std`
+  |
+  = note: the `i686-unknown-linux-gnu` target may not be installed";
+
+#[test]
+fn missing_libc_is_recognized_from_any_linker() {
+    assert_eq!(diagnose(LLD_MISSING_LIBC), Some(Diagnosis::MissingLibc));
+    assert_eq!(diagnose(LD_MISSING_CRT), Some(Diagnosis::MissingLibc));
+}
+
+#[test]
+fn missing_libc_message_lists_the_packages() {
+    let message = Diagnosis::MissingLibc.message(GNU);
+    assert!(message.starts_with("Failed to build child process: "));
+    assert!(message.contains(GNU), "{}", message);
+    for package in [
+        "apt install gcc-multilib",
+        "dnf install glibc-devel.i686 libgcc.i686",
+        "pacman -S lib32-glibc lib32-gcc-libs",
+        "SHAREDMEM_CHILD_BIN",
+    ] {
+        assert!(
+            message.contains(package),
+            "no {:?} in:\n{}",
+            package,
+            message
+        );
+    }
+    // Short enough to read at a glance, unlike the raw linker output
+    assert!(message.lines().count() <= 6, "{}", message);
+
+    let warnings = Diagnosis::MissingLibc.warnings(GNU);
+    assert!(
+        warnings
+            .iter()
+            .all(|line| { line.starts_with("cargo:warning=") && !line.contains('\n') })
+    );
+    assert!(warnings.iter().any(|line| line.contains("gcc-multilib")));
+}
+
+#[test]
+fn missing_std_points_at_rustup() {
+    assert_eq!(diagnose(MISSING_STD), Some(Diagnosis::MissingStd));
+    let message = Diagnosis::MissingStd.message(GNU);
+    assert!(
+        message.contains("rustup target add i686-unknown-linux-gnu"),
+        "{}",
+        message
+    );
+    assert!(!message.contains("apt install"), "{}", message);
+}
+
+/// An undefined symbol, from a link that did find a 32-bit libgcc after
+/// passing over the 64-bit one.
+const LD_UNDEFINED_SYMBOL: &str = "\
+  = note: /usr/bin/ld: skipping incompatible /usr/lib/gcc/x86_64-linux-gnu/12/libgcc.a when searching for -lgcc
+          /usr/bin/ld: child_process.o: in function `main':
+          main.rs:(.text+0x1c): undefined reference to `shm_open_wrapper'
+          collect2: error: ld returned 1 exit status";
+
+#[test]
+fn unknown_failures_are_left_alone() {
+    assert_eq!(diagnose(LD_UNDEFINED_SYMBOL), None);
+    let compile_error = "\
+error[E0308]: mismatched types
+ --> src/main.rs:10:5";
+    assert_eq!(diagnose(compile_error), None);
+    assert_eq!(diagnose(""), None);
+}
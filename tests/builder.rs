@@ -0,0 +1,62 @@
+//! `SharedRegion::builder` in the two modes unrelated programs meet
+//! through: one creates a named segment, the others open it.
+
+use sharedmem_multiarch::{OpenMode, SharedMemError, SharedRegion};
+
+fn region_name(test: &str) -> String {
+    format!("/sharedmem-builder-{}-{}", test, std::process::id())
+}
+
+#[test]
+fn opened_region_shares_the_created_one() {
+    let name = region_name("shared");
+    let created = SharedRegion::builder()
+        .os_id(&name)
+        .mode(OpenMode::Create)
+        .build()
+        .unwrap();
+    assert_eq!(created.os_id(), name);
+    assert!(created.is_owner());
+
+    let opened = SharedRegion::builder()
+        .os_id(&name)
+        .mode(OpenMode::Open)
+        .build()
+        .unwrap();
+    assert!(!opened.is_owner());
+    assert_eq!(opened.get_number(), 100);
+
+    created.set_number(5);
+    assert_eq!(opened.get_number(), 5);
+    opened.lock().unwrap();
+    assert!(created.is_locked());
+    opened.unlock();
+}
+
+#[test]
+fn create_refuses_a_taken_name() {
+    let name = region_name("taken");
+    let _created = SharedRegion::builder().os_id(&name).build().unwrap();
+    match SharedRegion::builder().os_id(&name).build() {
+        Err(SharedMemError::OpenFailed(_)) => {}
+        Err(e) => panic!("expected OpenFailed, got {}", e),
+        Ok(_) => panic!("created {} twice", name),
+    }
+}
+
+#[test]
+fn open_needs_an_existing_segment() {
+    let missing = SharedRegion::builder()
+        .os_id(region_name("missing"))
+        .mode(OpenMode::Open)
+        .build();
+    assert!(matches!(missing, Err(SharedMemError::OpenFailed(_))));
+
+    // Without an ID there is nothing to open
+    assert!(
+        SharedRegion::builder()
+            .mode(OpenMode::Open)
+            .build()
+            .is_err()
+    );
+}
@@ -0,0 +1,70 @@
+//! A region whose creator stores integers in the other byte order is
+//! refused as such, not misreported as a layout mismatch.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use shared_memory::{Shmem, ShmemConf};
+use sharedmem_multiarch::layout::BYTE_ORDER_MARK;
+use sharedmem_multiarch::{SharedData, SharedMemError};
+use std::process::Command;
+
+/// A region initialized like the parent's, with every header field
+/// byte-swapped as a creator of the other endianness would have stored it.
+fn region_with_flipped_header() -> Shmem {
+    let shmem = ShmemConf::new()
+        .size(std::mem::size_of::<SharedData>())
+        .create()
+        .unwrap();
+    let ptr = shmem.as_ptr() as *mut SharedData;
+    // SAFETY: the fresh mapping is page aligned, large enough and not yet
+    // seen by anyone else.
+    unsafe {
+        SharedData::init_in_place(ptr);
+        let header = &mut *std::ptr::addr_of_mut!((*ptr).header);
+        header.magic = header.magic.swap_bytes();
+        header.version = header.version.swap_bytes();
+        header.byte_order = header.byte_order.swap_bytes();
+        header.struct_size = header.struct_size.swap_bytes();
+        header.struct_align = header.struct_align.swap_bytes();
+    }
+    shmem
+}
+
+#[test]
+fn flipped_mark_is_detected() {
+    let shmem = region_with_flipped_header();
+    // SAFETY: the mapping is initialized and outlives the reference.
+    let data = unsafe { &*(shmem.as_ptr() as *const SharedData) };
+    assert_eq!(data.header.byte_order, BYTE_ORDER_MARK.swap_bytes());
+    assert!(!data.header.same_byte_order());
+}
+
+#[test]
+fn parent_refuses_a_region_with_the_other_byte_order() {
+    let shmem = region_with_flipped_header();
+    match sharedmem_multiarch::open_region(shmem.get_os_id()) {
+        Err(SharedMemError::EndianMismatch) => {}
+        Err(e) => panic!("expected EndianMismatch, got {}", e),
+        Ok(_) => panic!("a region with the other byte order was accepted"),
+    }
+}
+
+#[test]
+fn child_refuses_a_region_with_the_other_byte_order() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shmem = region_with_flipped_header();
+    let child_exe = extract_child();
+    let output = Command::new(&child_exe)
+        .arg(shmem.get_os_id())
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(18), "stderr: {}", stderr);
+    assert!(stderr.contains("other byte order"), "stderr: {}", stderr);
+}
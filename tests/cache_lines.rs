@@ -0,0 +1,40 @@
+//! The lock word and the number it protects sit on cache lines of their
+//! own in every mapped region, so spinning on one does not bounce the
+//! other between cores.
+
+use sharedmem_multiarch::layout::CACHE_LINE;
+use sharedmem_multiarch::{OpenMode, OwnedSharedData, SharedRegion};
+
+fn line_of<T>(field: &T) -> usize {
+    (field as *const T as usize) / CACHE_LINE
+}
+
+fn assert_own_lines(shared_data: &OwnedSharedData) {
+    let futex = &*shared_data.futex;
+    let number = &*shared_data.number;
+    assert_eq!(futex as *const _ as usize % CACHE_LINE, 0);
+    assert_eq!(number as *const _ as usize % CACHE_LINE, 0);
+    assert_ne!(line_of(futex), line_of(number));
+    // Nothing else shares the lock's line
+    assert_ne!(line_of(&shared_data.owner_pid), line_of(futex));
+    assert_ne!(line_of(&shared_data.number_generation), line_of(number));
+}
+
+#[test]
+fn futex_and_number_have_their_own_cache_lines() {
+    assert_own_lines(&OwnedSharedData::create().unwrap());
+    #[cfg(unix)]
+    assert_own_lines(
+        &SharedRegion::builder()
+            .mode(OpenMode::SysV)
+            .build()
+            .unwrap(),
+    );
+    #[cfg(target_os = "linux")]
+    assert_own_lines(
+        &SharedRegion::builder()
+            .mode(OpenMode::Anonymous)
+            .build()
+            .unwrap(),
+    );
+}
@@ -0,0 +1,55 @@
+//! `lock_cancelable` gives up soon after its cancel flag is set, long
+//! before its deadline.
+
+use sharedmem_multiarch::{OwnedSharedData, SharedData, SharedMemError};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+#[test]
+fn setting_the_flag_cancels_a_blocked_waiter() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data: &SharedData = &owned;
+    let cancel = AtomicBool::new(false);
+    shared_data.lock().unwrap();
+
+    let (result, noticed_after) = std::thread::scope(|scope| {
+        let waiter = scope.spawn(|| {
+            let result =
+                shared_data.lock_cancelable(Instant::now() + Duration::from_secs(30), &cancel);
+            (result, Instant::now())
+        });
+        std::thread::sleep(Duration::from_millis(100));
+        let canceled_at = Instant::now();
+        cancel.store(true, Ordering::Relaxed);
+        let (result, returned_at) = waiter.join().unwrap();
+        (result, returned_at.saturating_duration_since(canceled_at))
+    });
+    shared_data.unlock();
+
+    assert!(
+        matches!(result, Err(SharedMemError::Canceled)),
+        "{:?}",
+        result
+    );
+    assert!(
+        noticed_after < Duration::from_secs(1),
+        "{:?}",
+        noticed_after
+    );
+}
+
+#[test]
+fn a_flag_already_set_fails_at_once() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let cancel = AtomicBool::new(true);
+
+    let result = shared_data.lock_cancelable(Instant::now() + Duration::from_secs(30), &cancel);
+    assert!(matches!(result, Err(SharedMemError::Canceled)));
+    assert!(!shared_data.is_locked());
+
+    cancel.store(false, Ordering::Relaxed);
+    shared_data
+        .lock_cancelable(Instant::now() + Duration::from_secs(1), &cancel)
+        .unwrap();
+    shared_data.unlock();
+}
@@ -0,0 +1,103 @@
+//! `--check` validates the environment and reports on it without spawning
+//! a child, and fails when the child cannot be run.
+
+use sharedmem_multiarch::extract::{BinaryArch, binary_arch};
+use std::process::{Command, Output};
+
+/// Runs the parent with `--check` and `args`, under a child wrapper that
+/// leaves `marker` behind if a child is ever spawned.
+#[cfg(unix)]
+fn run_check(marker: &std::path::Path, args: &[&str]) -> Output {
+    use std::os::unix::fs::PermissionsExt;
+
+    let wrapper = marker.with_extension("sh");
+    std::fs::write(
+        &wrapper,
+        format!(
+            "#!/bin/sh\necho spawned >> '{}'\nexec \"$@\"\n",
+            marker.display()
+        ),
+    )
+    .unwrap();
+    std::fs::set_permissions(&wrapper, std::fs::Permissions::from_mode(0o755)).unwrap();
+
+    Command::new(env!("CARGO_BIN_EXE_sharedmem-multiarch"))
+        .arg("--check")
+        .args(args)
+        .env("SHAREDMEM_CHILD_WRAPPER", &wrapper)
+        .output()
+        .unwrap()
+}
+
+#[cfg(unix)]
+#[test]
+fn check_reports_success_without_spawning_a_child() {
+    let dir = tempfile::tempdir().unwrap();
+    let marker = dir.path().join("spawned");
+    let output = run_check(&marker, &[]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "check failed with {}\nstdout:\n{}\nstderr:\n{}",
+        output.status,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    for line in [
+        "Shared memory: ok, created /",
+        "Child binary: ok, ",
+        "Child target: ok, ",
+        "Preflight check passed, no child was spawned",
+    ] {
+        assert!(stdout.contains(line), "no {:?} in:\n{}", line, stdout);
+    }
+    assert!(!stdout.contains("Child Process Started"), "{}", stdout);
+    assert!(!marker.exists(), "a child was spawned:\n{}", stdout);
+}
+
+#[cfg(unix)]
+#[test]
+fn check_fails_for_a_child_that_is_not_executable() {
+    let dir = tempfile::tempdir().unwrap();
+    let marker = dir.path().join("spawned");
+    let not_a_child = dir.path().join("child_process");
+    std::fs::write(&not_a_child, "not an executable").unwrap();
+    let output = run_check(&marker, &["--child-runtime", not_a_child.to_str().unwrap()]);
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert_eq!(output.status.code(), Some(1), "stdout:\n{}", stdout);
+    assert!(stdout.contains("Shared memory: ok, "), "{}", stdout);
+    assert!(stdout.contains("Child binary: FAILED, "), "{}", stdout);
+    assert!(stdout.contains("Child target: FAILED, "), "{}", stdout);
+    assert!(
+        String::from_utf8_lossy(&output.stderr).contains("2 of 3 checks did not pass"),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(!marker.exists());
+}
+
+#[test]
+fn headers_name_the_architecture() {
+    let parent = std::fs::read(env!("CARGO_BIN_EXE_sharedmem-multiarch")).unwrap();
+    let arch = binary_arch(&parent).unwrap();
+    assert_eq!(arch.arch, std::env::consts::ARCH);
+    assert_eq!(arch.bits, usize::BITS);
+
+    assert_eq!(binary_arch(b"#!/bin/sh\n"), None);
+    assert_eq!(binary_arch(b"\x7fELF"), None);
+    assert_eq!(
+        BinaryArch::for_triple("i686-unknown-linux-musl"),
+        Some(BinaryArch {
+            format: "ELF",
+            bits: 32,
+            arch: "x86"
+        })
+    );
+    assert_eq!(
+        BinaryArch::for_triple("i686-pc-windows-gnu").map(|arch| arch.format),
+        Some("PE")
+    );
+    assert_eq!(BinaryArch::for_triple("riscv64gc-unknown-linux-gnu"), None);
+}
@@ -0,0 +1,35 @@
+//! The child times its update under the lock and leaves the duration in
+//! the region for the parent.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use sharedmem_multiarch::OwnedSharedData;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+#[test]
+fn child_reports_its_compute_time() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shared_data = OwnedSharedData::create().unwrap();
+    assert_eq!(shared_data.child_compute_time(), None);
+    let child_exe = extract_child();
+    let status = Command::new(&child_exe)
+        .arg(shared_data.os_id())
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    // The child simulates 500ms of work with the lock held
+    let took = shared_data.child_compute_time().unwrap();
+    assert!(
+        took >= Duration::from_millis(500) && took < Duration::from_secs(5),
+        "child reported {:?}",
+        took
+    );
+}
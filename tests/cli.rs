@@ -0,0 +1,56 @@
+//! The demo's command line refuses bad values up front, with the usage and
+//! exit code 2, before anything is created or spawned.
+
+use std::process::{Command, Output};
+
+fn demo(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_sharedmem-multiarch"))
+        .args(args)
+        .output()
+        .unwrap()
+}
+
+fn assert_refused(args: &[&str], reason: &str) {
+    let output = demo(args);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(2), "{:?}: {}", args, stderr);
+    assert!(stderr.contains(reason), "{:?}: {}", args, stderr);
+    assert!(stderr.contains("Usage: "), "{:?}: {}", args, stderr);
+    assert!(!String::from_utf8_lossy(&output.stdout).contains("Parent Process Started"));
+}
+
+#[test]
+fn bad_values_are_refused_with_the_usage() {
+    assert_refused(
+        &["--child-op", "n +"],
+        "invalid value 'n +' for '--child-op <CHILD_OP>'",
+    );
+    assert_refused(
+        &["--parent-op", "m * 2"],
+        "invalid value 'm * 2' for '--parent-op <PARENT_OP>'",
+    );
+    assert_refused(
+        &["--lock-timeout", "soon"],
+        "invalid value 'soon' for '--lock-timeout <LOCK_TIMEOUT>'",
+    );
+    assert_refused(
+        &["--child-count", "0"],
+        "invalid value '0' for '--child-count <CHILD_COUNT>'",
+    );
+    assert_refused(&["--sysv", "--anonymous"], "cannot be used with");
+}
+
+#[test]
+fn help_lists_the_options_and_succeeds() {
+    let output = demo(&["--help"]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for option in [
+        "--initial <INITIAL>",
+        "--lock-timeout <LOCK_TIMEOUT>",
+        "--child-op <CHILD_OP>",
+        "--parent-op <PARENT_OP>",
+    ] {
+        assert!(stdout.contains(option), "no {} in:\n{}", option, stdout);
+    }
+}
@@ -0,0 +1,23 @@
+//! Helpers shared by the tests that run the embedded child.
+
+use sharedmem_multiarch::ChildExecutable;
+use std::process::{Command, Stdio};
+
+/// A runnable copy of the child the build embedded.
+pub fn extract_child() -> ChildExecutable {
+    let child_binary = include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded"));
+    ChildExecutable::extract(child_binary).unwrap()
+}
+
+/// Starts the child with no arguments, which only prints its usage. Failing
+/// to start it at all means it was built for something this machine can't
+/// execute.
+pub fn child_runnable() -> Result<(), String> {
+    let child_exe = extract_child();
+    Command::new(&child_exe)
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .status()
+        .map(drop)
+        .map_err(|e| e.to_string())
+}
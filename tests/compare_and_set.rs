@@ -0,0 +1,43 @@
+//! `compare_and_set` lets exactly one of several racing writers through,
+//! without the lock.
+
+use sharedmem_multiarch::OwnedSharedData;
+use std::sync::Barrier;
+
+const ROUNDS: i64 = 200;
+
+#[test]
+fn exactly_one_racer_wins() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    let start = Barrier::new(2);
+
+    for round in 0..ROUNDS {
+        shared_data.set_number(round * 10);
+        let results: Vec<Result<i64, i64>> = std::thread::scope(|s| {
+            let racers: Vec<_> = [1, 2]
+                .into_iter()
+                .map(|id| {
+                    let start = &start;
+                    s.spawn(move || {
+                        start.wait();
+                        shared_data.compare_and_set(round * 10, round * 10 + id)
+                    })
+                })
+                .collect();
+            racers.into_iter().map(|r| r.join().unwrap()).collect()
+        });
+
+        let winners: Vec<_> = results.iter().filter(|r| r.is_ok()).collect();
+        assert_eq!(winners, [&Ok(round * 10)], "round {}: {:?}", round, results);
+        // The loser is told the value the winner stored
+        let number = shared_data.get_number();
+        assert!(
+            results.contains(&Err(number)),
+            "round {}: {:?}",
+            round,
+            results
+        );
+        assert_ne!(number, round * 10);
+    }
+}
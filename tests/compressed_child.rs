@@ -0,0 +1,41 @@
+//! Checks the child embedded compressed by `compress-child` decompresses to
+//! exactly the child build.rs built.
+//!
+//! Run with `cargo test --features compress-child --test compressed_child`.
+
+#![cfg(feature = "compress-child")]
+
+use sharedmem_multiarch::extract::{checksum, decompress_child};
+
+const BUILT: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded"));
+const COMPRESSED: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded.zst"));
+
+#[test]
+fn decompressed_child_matches_the_built_one() {
+    let decompressed = decompress_child(
+        COMPRESSED,
+        env!("SHAREDMEM_CHILD_LEN").parse().unwrap(),
+        env!("SHAREDMEM_CHILD_CHECKSUM").parse().unwrap(),
+    )
+    .unwrap();
+    assert!(COMPRESSED.len() < BUILT.len());
+    assert!(
+        decompressed == BUILT,
+        "decompressed child differs from the built one"
+    );
+}
+
+#[test]
+fn corrupt_child_is_rejected() {
+    let wrong_checksum = checksum(BUILT) ^ 1;
+    let err = decompress_child(COMPRESSED, BUILT.len(), wrong_checksum).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::InvalidData);
+    assert!(
+        decompress_child(
+            &COMPRESSED[..COMPRESSED.len() / 2],
+            BUILT.len(),
+            checksum(BUILT)
+        )
+        .is_err()
+    );
+}
@@ -0,0 +1,42 @@
+//! `lock_contended` counts the acquisitions that had to wait for another
+//! holder, and only those.
+
+use sharedmem_multiarch::OwnedSharedData;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn waiting_for_a_held_lock_counts_as_contended() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+
+    shared_data.lock().unwrap();
+    shared_data.unlock();
+    let uncontended = shared_data.stats();
+    assert_eq!(uncontended.acquisitions, 1);
+    assert_eq!(uncontended.contended, 0);
+    assert_eq!(uncontended.total_wait, Duration::ZERO);
+
+    let hold = Duration::from_millis(100);
+    shared_data.lock().unwrap();
+    std::thread::scope(|s| {
+        let waiter = s.spawn(|| {
+            shared_data.lock_timeout(TIMEOUT).unwrap();
+            shared_data.unlock();
+        });
+        // Far longer than the waiter takes to get to the lock
+        std::thread::sleep(hold);
+        assert!(!waiter.is_finished());
+        shared_data.unlock();
+    });
+
+    let stats = shared_data.stats();
+    assert_eq!(stats.acquisitions, 3);
+    assert_eq!(stats.contended, 1);
+    assert!(
+        stats.total_wait > Duration::ZERO && stats.total_wait <= TIMEOUT,
+        "waited {:?}",
+        stats.total_wait
+    );
+}
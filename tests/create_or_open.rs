@@ -0,0 +1,100 @@
+//! Two processes racing `SharedRegion::create_or_open` on one name: the
+//! one whose create wins initializes the region, the other attaches to it,
+//! and both end up looking at the same data.
+//!
+//! The racers are this test binary run again with `WORKER_VAR` set, so
+//! they need neither the child nor an example built.
+
+use sharedmem_multiarch::SharedRegion;
+use std::process::{Command, Output, Stdio};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+
+const WORKER_VAR: &str = "SHAREDMEM_CREATE_OR_OPEN_WORKER";
+const START_AT_VAR: &str = "SHAREDMEM_CREATE_OR_OPEN_START_AT_MS";
+const ROUNDS: u32 = 3;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn exactly_one_of_two_racers_initializes() {
+    if let Ok(name) = std::env::var(WORKER_VAR) {
+        return race(&name);
+    }
+
+    for round in 0..ROUNDS {
+        let name = format!("/sharedmem-create-or-open-{}-{}", std::process::id(), round);
+        // Both start at the same wall-clock moment, well after spawning
+        let start_at = now_millis() + 200;
+        let racers: Vec<_> = (0..2)
+            .map(|_| {
+                Command::new(std::env::current_exe().unwrap())
+                    .args(["--exact", "exactly_one_of_two_racers_initializes"])
+                    .args(["--nocapture", "--test-threads=1"])
+                    .env(WORKER_VAR, &name)
+                    .env(START_AT_VAR, start_at.to_string())
+                    .stdout(Stdio::piped())
+                    .stderr(Stdio::piped())
+                    .spawn()
+                    .unwrap()
+            })
+            .collect();
+        let reports: Vec<_> = racers
+            .into_iter()
+            .map(|racer| report(racer.wait_with_output().unwrap()))
+            .collect();
+
+        let owners = reports.iter().filter(|(owner, _)| *owner).count();
+        assert_eq!(owners, 1, "round {}: {:?}", round, reports);
+        // A second initialization would have reset the first increment
+        for (_, number) in &reports {
+            assert_eq!(*number, 102, "round {}: {:?}", round, reports);
+        }
+    }
+}
+
+/// One racer: attaches, adds 1 under the lock and waits for the other's 1,
+/// then reports whether it was the owner and what it saw.
+fn race(name: &str) {
+    let start_at: u128 = std::env::var(START_AT_VAR).unwrap().parse().unwrap();
+    while now_millis() < start_at {
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    let shared_data = SharedRegion::create_or_open(name, 0).unwrap();
+    shared_data
+        .lock_timeout_guard(TIMEOUT)
+        .unwrap()
+        .update(|n| n + 1);
+    shared_data.notify_change();
+    let number = shared_data.wait_until(|n| n == 102, TIMEOUT).unwrap();
+    println!("racer owner={} number={}", shared_data.is_owner(), number);
+}
+
+/// `(owner, number)` from a racer's report line.
+fn report(output: Output) -> (bool, i64) {
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "racer failed with {}: {}{}",
+        output.status,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    let line = stdout
+        .lines()
+        // The test harness prints its own name on the same line first
+        .find_map(|line| line.split_once("racer ").map(|(_, report)| report))
+        .unwrap_or_else(|| panic!("no report in: {}", stdout));
+    let mut fields = line
+        .split(' ')
+        .map(|field| field.split_once('=').unwrap().1);
+    let owner = fields.next().unwrap().parse().unwrap();
+    let number = fields.next().unwrap().parse().unwrap();
+    (owner, number)
+}
+
+fn now_millis() -> u128 {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .unwrap()
+        .as_millis()
+}
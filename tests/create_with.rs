@@ -0,0 +1,96 @@
+//! `SharedRegion::create_with` runs its initializer once, before the
+//! region is marked ready, so openers only ever see what it set.
+
+use sharedmem_multiarch::{OpenMode, SharedRegion};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+fn name(test: &str) -> String {
+    format!("/sharedmem-create-with-{}-{}", test, std::process::id())
+}
+
+#[test]
+fn opener_reads_the_initialized_number() {
+    let name = name("number");
+    let shared_data =
+        SharedRegion::create_with(&name, 4096, |data| data.set_initial_number(-4242)).unwrap();
+    assert!(shared_data.is_owner());
+    assert_eq!(shared_data.get_number(), -4242);
+    // The initializer's own write is not a change anyone has to notice
+    assert_eq!(shared_data.get_number_versioned(), (-4242, 0));
+
+    let opened = SharedRegion::builder()
+        .os_id(&name)
+        .mode(OpenMode::Open)
+        .build()
+        .unwrap();
+    assert_eq!(opened.get_number(), -4242);
+
+    // The initial number is what `reset` comes back to
+    opened.set_number(1);
+    opened.reset();
+    assert_eq!(shared_data.get_number(), -4242);
+}
+
+#[test]
+fn opener_waiting_during_init_never_sees_the_defaults() {
+    let name = name("waiting");
+    let opener = {
+        let name = name.clone();
+        std::thread::spawn(move || {
+            // Keep trying until the segment exists; the open itself then
+            // waits for the ready flag
+            let deadline = Instant::now() + Duration::from_secs(10);
+            loop {
+                let opened = SharedRegion::builder()
+                    .os_id(&name)
+                    .mode(OpenMode::Open)
+                    .build();
+                match opened {
+                    Ok(opened) => {
+                        return (opened.get_number(), opened.words[0].load(Ordering::Relaxed));
+                    }
+                    Err(e) if Instant::now() >= deadline => panic!("never opened: {}", e),
+                    Err(_) => std::thread::sleep(Duration::from_millis(1)),
+                }
+            }
+        })
+    };
+
+    let mut calls = 0;
+    let shared_data = SharedRegion::create_with(&name, 4096, |data| {
+        calls += 1;
+        data.set_initial_number(7);
+        // Long enough for the opener to be kept waiting on the ready flag
+        std::thread::sleep(Duration::from_millis(100));
+        data.words[0].store(99, Ordering::Relaxed);
+    })
+    .unwrap();
+    assert_eq!(calls, 1);
+
+    assert_eq!(opener.join().unwrap(), (7, 99));
+    drop(shared_data);
+}
+
+#[test]
+fn init_only_runs_for_the_creator() {
+    let name = name("create-or-open");
+    let creator = SharedRegion::builder()
+        .os_id(&name)
+        .mode(OpenMode::CreateOrOpen)
+        .build_with(|data| data.set_initial_number(5))
+        .unwrap();
+
+    let mut called = false;
+    let opener = SharedRegion::builder()
+        .os_id(&name)
+        .mode(OpenMode::CreateOrOpen)
+        .build_with(|data| {
+            called = true;
+            data.set_initial_number(6);
+        })
+        .unwrap();
+    assert!(!called);
+    assert!(creator.is_owner() && !opener.is_owner());
+    assert_eq!(opener.get_number(), 5);
+}
@@ -0,0 +1,56 @@
+//! A child that exits while holding the lock does not leave it held for
+//! good: once it is reaped, the next waiter that times out recovers it.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use sharedmem_multiarch::shared::LockState;
+use sharedmem_multiarch::{OwnedSharedData, SharedMemError};
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+#[test]
+fn parent_recovers_the_lock_from_a_child_that_exited_holding_it() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shared_data = OwnedSharedData::create().unwrap();
+    let marker_dir = tempfile::tempdir().unwrap();
+    let child_exe = extract_child();
+    let mut child = Command::new(&child_exe)
+        .arg(shared_data.os_id())
+        .env(
+            "SHAREDMEM_CHILD_CRASH_ONCE",
+            marker_dir.path().join("crashed"),
+        )
+        .stdout(Stdio::null())
+        .stderr(Stdio::null())
+        .spawn()
+        .unwrap();
+    let child_pid = child.id() as i32;
+    // Waiting reaps the child, so it is gone rather than a zombie
+    assert_eq!(child.wait().unwrap().code(), Some(1));
+
+    // It got as far as updating the number before it died
+    assert_eq!(shared_data.get_number(), (100 + 25) * 2);
+    assert_eq!(
+        shared_data.lock_state(),
+        LockState::Locked {
+            owner_pid: child_pid
+        }
+    );
+
+    match shared_data.lock_timeout(Duration::from_millis(100)) {
+        Err(SharedMemError::RecoveredFromDeadOwner { owner_pid }) => {
+            assert_eq!(owner_pid, child_pid)
+        }
+        other => panic!("expected a recovery, got {:?}", other),
+    }
+    assert_eq!(shared_data.lock_state(), LockState::Unlocked);
+
+    shared_data.lock_timeout(Duration::from_secs(1)).unwrap();
+    assert!(shared_data.owned_by_me());
+    shared_data.unlock();
+}
@@ -0,0 +1,60 @@
+//! `detect_deadlock` finds two threads each holding the lock the other
+//! waits for.
+
+#![cfg(target_os = "linux")]
+
+use sharedmem_multiarch::{OwnedSharedData, SharedData, SharedMemError, detect_deadlock};
+use std::sync::Barrier;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+/// How long each thread waits for the other's lock before backing off.
+const STUCK_FOR: Duration = Duration::from_secs(2);
+
+#[test]
+fn detects_a_two_lock_cycle() {
+    SharedData::set_deadlock_tracking(true);
+    let owned_a = OwnedSharedData::create().unwrap();
+    let owned_b = OwnedSharedData::create().unwrap();
+    // The mappings themselves cannot cross threads, but the data in them can
+    let (a, b): (&SharedData, &SharedData) = (&owned_a, &owned_b);
+    let barrier = Barrier::new(2);
+
+    let hold_then_wait = |held: &SharedData, wanted: &SharedData| {
+        held.lock().unwrap();
+        let tid = held.owner_tid.load(Ordering::Relaxed);
+        barrier.wait();
+        let result = wanted.lock_timeout(STUCK_FOR);
+        held.unlock();
+        (tid, result)
+    };
+
+    let (found, first, second) = std::thread::scope(|scope| {
+        let first = scope.spawn(|| hold_then_wait(a, b));
+        let second = scope.spawn(|| hold_then_wait(b, a));
+
+        let give_up = Instant::now() + STUCK_FOR;
+        let found = loop {
+            if let Some(cycle) = detect_deadlock(&[a, b]) {
+                break Some(cycle);
+            }
+            if Instant::now() >= give_up {
+                break None;
+            }
+            std::thread::sleep(Duration::from_millis(10));
+        };
+        (found, first.join().unwrap(), second.join().unwrap())
+    });
+
+    assert!(matches!(first.1, Err(SharedMemError::Timeout)));
+    assert!(matches!(second.1, Err(SharedMemError::Timeout)));
+    let mut cycle = found.expect("no cycle detected");
+    cycle.sort();
+    let mut expected = vec![first.0, second.0];
+    expected.sort();
+    assert_eq!(cycle, expected);
+    assert_ne!(first.0, second.0);
+
+    // Once both backed off, nothing is left to report
+    assert_eq!(detect_deadlock(&[a, b]), None);
+}
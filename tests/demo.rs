@@ -0,0 +1,266 @@
+//! Runs the demo binary end to end: the parent, the embedded 32-bit child
+//! and the handoff of the number between them.
+//!
+//! The child is built for another architecture, which not every machine
+//! that can build it can also run (no 32-bit loader or kernel support). In
+//! that case the test says so and passes rather than failing the suite.
+
+mod common;
+
+use common::child_runnable;
+use std::process::{Command, Output};
+
+#[test]
+fn demo_hands_the_number_to_the_child_and_back() {
+    assert_final_result(Command::new(env!("CARGO_BIN_EXE_sharedmem-multiarch")));
+}
+
+#[test]
+fn demo_runs_children_under_a_wrapper() {
+    let mut demo = Command::new(env!("CARGO_BIN_EXE_sharedmem-multiarch"));
+    demo.env("SHAREDMEM_CHILD_WRAPPER", "/usr/bin/env");
+    assert_final_result(demo);
+}
+
+#[cfg(unix)]
+#[test]
+fn demo_hands_the_number_over_through_sysv_shared_memory() {
+    let mut demo = Command::new(env!("CARGO_BIN_EXE_sharedmem-multiarch"));
+    demo.arg("--sysv");
+    if let Some(output) = assert_final_result(demo) {
+        let stdout = String::from_utf8_lossy(&output.stdout);
+        assert!(
+            stdout.contains("Shared memory created with OS ID: sysv:"),
+            "no System V segment in:\n{}",
+            stdout
+        );
+    }
+}
+
+/// Runs the demo with its default operations and checks it ends on the
+/// number they should produce, returning its output for further checks;
+/// `None` if it was skipped.
+fn assert_final_result(mut demo: Command) -> Option<Output> {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping the end-to-end demo: the child cannot run here ({reason})");
+        return None;
+    }
+
+    let output = demo.output().unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "demo failed with {}\nstdout:\n{}\nstderr:\n{}",
+        output.status,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    let expected = ((100 + 25) * 2) * 3 + 50;
+    assert!(
+        stdout.contains(&format!("Parent: Final result: {}\n", expected)),
+        "no final result of {} in:\n{}",
+        expected,
+        stdout
+    );
+    Some(output)
+}
+
+#[test]
+fn demo_terminates_a_child_that_never_exits() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping the hung child demo: the child cannot run here ({reason})");
+        return;
+    }
+
+    let start = std::time::Instant::now();
+    let output = Command::new(env!("CARGO_BIN_EXE_sharedmem-multiarch"))
+        .args(["--child-wait-timeout", "1"])
+        .env("SHAREDMEM_CHILD_HANG", "1")
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(
+        !output.status.success(),
+        "demo succeeded despite a hung child"
+    );
+    assert!(
+        stderr.contains("Child 1 did not exit within 1s and was terminated"),
+        "no timeout reported in:\n{}",
+        stderr
+    );
+    // Everything up to the wait takes about a second, the wait one more
+    // and SIGTERM ends the child at once
+    assert!(start.elapsed() < std::time::Duration::from_secs(10));
+}
+
+#[test]
+fn demo_retries_a_child_that_died_holding_the_lock() {
+    let marker_dir = tempfile::tempdir().unwrap();
+    let mut demo = Command::new(env!("CARGO_BIN_EXE_sharedmem-multiarch"));
+    demo.args(["--max-retries", "1"]).env(
+        "SHAREDMEM_CHILD_CRASH_ONCE",
+        marker_dir.path().join("crashed"),
+    );
+    // The retry starts over from 100, so the result is the usual one
+    let Some(output) = assert_final_result(demo) else {
+        return;
+    };
+
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    for line in [
+        "=== Retrying the children, attempt 2 of 2 ===",
+        "Parent: Recovered the lock from dead process",
+        "Parent: Number reset to checkpoint 100",
+    ] {
+        assert!(stdout.contains(line), "no {:?} in:\n{}", line, stdout);
+    }
+    assert!(marker_dir.path().join("crashed").exists());
+}
+
+#[test]
+fn children_apply_their_operations_in_turn() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping the multi-child demo: the child cannot run here ({reason})");
+        return;
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_sharedmem-multiarch"))
+        .args(["--initial", "5", "--child-count", "3"])
+        .args(["--child-op", "n * 2 + 1", "--parent-op", "n - 7"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "demo failed with {}\nstdout:\n{}\nstderr:\n{}",
+        output.status,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // 5 -> 11 -> 23 -> 47 through the children, then 40 in the parent; a
+    // skipped or repeated child, or the parent going first, ends elsewhere
+    assert!(
+        stdout.contains("Parent: Number after child processing: 47\n"),
+        "children did not end at 47 in:\n{}",
+        stdout
+    );
+    assert!(
+        stdout.contains("Parent: Final result: 40\n"),
+        "no final result of 40 in:\n{}",
+        stdout
+    );
+    assert_eq!(
+        stdout
+            .matches("=== Child process finished successfully ===")
+            .count(),
+        3
+    );
+}
+
+#[test]
+fn fan_out_gives_each_child_its_own_segment() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping the fan-out demo: the child cannot run here ({reason})");
+        return;
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_sharedmem-multiarch"))
+        .args(["--fan-out", "--child-count", "3"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "fan-out failed with {}\nstdout:\n{}\nstderr:\n{}",
+        output.status,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // Segment i starts at 100 + i - 1 and its child applies (n + 25) * 2
+    for (segment, initial) in (1..=3).zip(100..) {
+        let expected = (initial + 25) * 2;
+        assert!(
+            stdout.contains(&format!(
+                "Parent: Segment {} result: {} ",
+                segment, expected
+            )),
+            "segment {} did not end at {} in:\n{}",
+            segment,
+            expected,
+            stdout
+        );
+    }
+}
+
+/// `SHAREDMEM_CHILD_RUNTIME` points the demo at a child on disk, here a
+/// script announcing itself before running a copy of the built child.
+#[cfg(unix)]
+#[test]
+fn demo_runs_the_child_named_at_runtime() {
+    use std::os::unix::fs::PermissionsExt;
+
+    let dir = tempfile::tempdir().unwrap();
+    let child = dir.path().join("child_process");
+    std::fs::write(
+        &child,
+        include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded")),
+    )
+    .unwrap();
+    let runtime = dir.path().join("runtime_child");
+    std::fs::write(
+        &runtime,
+        format!(
+            "#!/bin/sh\necho \"runtime child running\" >&2\nexec '{}' \"$@\"\n",
+            child.display()
+        ),
+    )
+    .unwrap();
+    for path in [&child, &runtime] {
+        std::fs::set_permissions(path, std::fs::Permissions::from_mode(0o755)).unwrap();
+    }
+
+    let mut demo = Command::new(env!("CARGO_BIN_EXE_sharedmem-multiarch"));
+    demo.env("SHAREDMEM_CHILD_RUNTIME", &runtime);
+    if let Some(output) = assert_final_result(demo) {
+        let stderr = String::from_utf8_lossy(&output.stderr);
+        assert!(
+            stderr.contains("runtime child running"),
+            "the runtime child never ran:\n{}",
+            stderr
+        );
+    }
+}
+
+#[test]
+fn captured_child_output_ends_up_in_the_error() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping the captured output demo: the child cannot run here ({reason})");
+        return;
+    }
+
+    // The child crashes by itself once this marker can be created
+    let marker = std::env::temp_dir().join(format!("sharedmem-capture-{}", std::process::id()));
+    let _ = std::fs::remove_file(&marker);
+    let output = Command::new(env!("CARGO_BIN_EXE_sharedmem-multiarch"))
+        .arg("--capture-child-output")
+        .env("SHAREDMEM_CHILD_CRASH_ONCE", &marker)
+        .output()
+        .unwrap();
+    let _ = std::fs::remove_file(&marker);
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success(), "demo succeeded despite the crash");
+    let error = stderr
+        .split_once("Error: Child process failed")
+        .map(|(_, error)| error)
+        .unwrap_or_else(|| panic!("no failure reported in:\n{}", stderr));
+    assert!(
+        error.contains("child 1 ended with exit status: 1")
+            && error.contains("Child: Crashing with the lock held"),
+        "child's output missing from the error:\n{}",
+        error
+    );
+}
@@ -0,0 +1,35 @@
+//! Dropping the creator's `OwnedSharedData` removes the segment, while
+//! dropping an attached one leaves it to the creator.
+
+use sharedmem_multiarch::{OwnedSharedData, create_region, open_region};
+
+#[test]
+fn segment_is_gone_after_the_creator_drops() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let os_id = shared_data.os_id().to_string();
+    assert!(open_region(&os_id).is_ok());
+
+    drop(shared_data);
+    assert!(open_region(&os_id).is_err(), "{} still opens", os_id);
+    #[cfg(target_os = "linux")]
+    assert!(
+        !std::path::Path::new("/dev/shm")
+            .join(os_id.trim_start_matches('/'))
+            .exists()
+    );
+}
+
+#[test]
+fn an_opener_dropping_leaves_the_segment() {
+    let name = format!("/sharedmem-drop-opener-{}", std::process::id());
+    let creator = create_region(&name, 4096).unwrap();
+    let opener = open_region(&name).unwrap();
+    assert!(!opener.is_owner());
+    drop(opener);
+
+    let reopened = open_region(&name).unwrap();
+    assert_eq!(reopened.get_number(), creator.get_number());
+    drop(reopened);
+    drop(creator);
+    assert!(open_region(&name).is_err());
+}
@@ -0,0 +1,97 @@
+//! Each `SharedMemError` has its own exit code, which the parent can turn
+//! back into a description of how a child failed.
+
+use shared_memory::ShmemError;
+use sharedmem_multiarch::layout::{Header, LayoutMismatch};
+use sharedmem_multiarch::{OwnedSharedData, SharedMemError};
+use std::collections::HashSet;
+use std::error::Error;
+use std::time::Duration;
+
+fn every_error() -> Vec<SharedMemError> {
+    let header = Header::new::<u64>();
+    vec![
+        SharedMemError::Timeout,
+        SharedMemError::Interrupted,
+        SharedMemError::RecoveredFromDeadOwner { owner_pid: 42 },
+        SharedMemError::LayoutMismatch(LayoutMismatch {
+            expected_magic: header.magic,
+            found_magic: header.magic,
+            expected_version: header.version,
+            found_version: header.version - 1,
+            expected_size: header.struct_size,
+            found_size: header.struct_size,
+            expected_align: header.struct_align,
+            found_align: header.struct_align,
+        }),
+        SharedMemError::NotInitialized,
+        SharedMemError::OpenFailed(ShmemError::MapOpenFailed(2)),
+        SharedMemError::Stopped,
+        SharedMemError::SizeMismatch {
+            expected: 512,
+            actual: 8,
+        },
+        SharedMemError::EndianMismatch,
+        SharedMemError::ChildWait(std::io::Error::other("no such child")),
+        SharedMemError::Canceled,
+    ]
+}
+
+#[test]
+fn exit_codes_are_distinct_and_described() {
+    let errors = every_error();
+    let codes: HashSet<u8> = errors.iter().map(SharedMemError::exit_code).collect();
+    assert_eq!(codes.len(), errors.len(), "codes are shared: {:?}", codes);
+
+    for error in &errors {
+        let code = error.exit_code();
+        // Clear of 0, 1 and 101, which success, a plain error and a panic
+        // exit with
+        assert!(
+            !matches!(code, 0 | 1 | 101),
+            "{:?} exits with {}",
+            error,
+            code
+        );
+        assert!(
+            SharedMemError::describe_exit_code(code.into()).is_some(),
+            "no description for {} ({:?})",
+            code,
+            error
+        );
+        assert!(!error.to_string().is_empty(), "{:?}", error);
+    }
+    for code in [0, 1, 101] {
+        assert_eq!(SharedMemError::describe_exit_code(code), None);
+    }
+}
+
+#[test]
+fn wrapped_errors_are_their_source() {
+    for error in every_error() {
+        let wraps = matches!(
+            error,
+            SharedMemError::OpenFailed(_) | SharedMemError::ChildWait(_)
+        );
+        assert_eq!(error.source().is_some(), wraps, "{:?}", error);
+    }
+}
+
+#[test]
+fn failed_lock_reports_the_typed_error() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    shared_data.lock().unwrap();
+    let error = std::thread::scope(|s| {
+        s.spawn(|| shared_data.lock_timeout(Duration::from_millis(20)))
+            .join()
+            .unwrap()
+    })
+    .unwrap_err();
+    assert!(matches!(error, SharedMemError::Timeout), "{:?}", error);
+    assert_eq!(
+        SharedMemError::describe_exit_code(error.exit_code().into()),
+        Some("timed out")
+    );
+    shared_data.unlock();
+}
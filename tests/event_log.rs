@@ -0,0 +1,68 @@
+//! Events recorded under a lock by several threads come out of the dump
+//! in the order they happened.
+
+use sharedmem_multiarch::eventlog::SharedEventLog;
+use sharedmem_multiarch::{OwnedSharedData, SharedData};
+
+const ROUNDS: u32 = 100;
+
+#[test]
+fn dump_of_two_threads_is_chronological() {
+    let owned = OwnedSharedData::create().unwrap();
+    // The mapping itself cannot cross threads, but the data in it can
+    let shared_data: &SharedData = &owned;
+    let log = SharedEventLog::<{ 4 * ROUNDS as usize }>::new();
+
+    std::thread::scope(|scope| {
+        for thread in 0..2u32 {
+            let log = &log;
+            scope.spawn(move || {
+                for _ in 0..ROUNDS {
+                    let guard = shared_data.lock_guard().unwrap();
+                    // Thread 0 logs codes 10 and 20, thread 1 codes 11 and 21
+                    log.record(10 + thread);
+                    log.record(20 + thread);
+                    drop(guard);
+                }
+            });
+        }
+    });
+
+    let events = log.dump();
+    assert_eq!(log.recorded(), 4 * u64::from(ROUNDS));
+    assert_eq!(events.len(), 4 * ROUNDS as usize);
+    for (index, event) in events.iter().enumerate() {
+        assert_eq!(event.position, index as u64);
+        assert_eq!(event.pid, std::process::id() as i32);
+    }
+    // Recorded under the lock, each thread's pair is never split by the
+    // other's, and time never runs backwards
+    for pair in events.chunks(2) {
+        assert!((10..12).contains(&pair[0].code), "{:?}", pair);
+        assert_eq!(pair[1].code, pair[0].code + 10, "{:?}", pair);
+    }
+    for pair in events.windows(2) {
+        assert!(pair[0].timestamp_nanos <= pair[1].timestamp_nanos);
+    }
+    // Both threads got their turns
+    assert_eq!(
+        events.iter().filter(|event| event.code == 10).count(),
+        ROUNDS as usize
+    );
+    assert_eq!(
+        events.iter().filter(|event| event.code == 11).count(),
+        ROUNDS as usize
+    );
+}
+
+#[test]
+fn dump_keeps_only_the_last_lap() {
+    let log = SharedEventLog::<4>::new();
+    for code in 0..10 {
+        log.record(code);
+    }
+
+    let codes: Vec<u32> = log.dump().iter().map(|event| event.code).collect();
+    assert_eq!(codes, [6, 7, 8, 9]);
+    assert_eq!(log.recorded(), 10);
+}
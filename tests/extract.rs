@@ -0,0 +1,92 @@
+//! The memfd the child is extracted into on Linux: sealed once written,
+//! and still runnable. Extracting to files instead skips directories the
+//! child cannot be run from.
+
+#![cfg(target_os = "linux")]
+
+use sharedmem_multiarch::ChildExecutable;
+use std::io::Write;
+use std::os::fd::AsRawFd;
+use std::path::PathBuf;
+use std::process::{Command, Stdio};
+
+const CHILD: &[u8] = include_bytes!(concat!(env!("OUT_DIR"), "/child_process_embedded"));
+
+#[test]
+fn extracted_child_is_sealed_and_runs() {
+    let child_exe = ChildExecutable::extract(CHILD).unwrap();
+    assert!(
+        child_exe.path().starts_with("/proc/self/fd"),
+        "expected a memfd, got {}",
+        child_exe.path().display()
+    );
+
+    let file = std::fs::File::open(child_exe.path()).unwrap();
+    let seals = unsafe { libc::fcntl(file.as_raw_fd(), libc::F_GET_SEALS) };
+    let expected = libc::F_SEAL_WRITE | libc::F_SEAL_SHRINK | libc::F_SEAL_GROW | libc::F_SEAL_SEAL;
+    assert_eq!(seals & expected, expected, "seals {:#x}", seals);
+
+    // Even a writable descriptor cannot change the sealed contents
+    let mut writable = std::fs::OpenOptions::new()
+        .write(true)
+        .open(child_exe.path())
+        .unwrap();
+    let err = writable.write_all(b"tampered").unwrap_err();
+    assert_eq!(err.raw_os_error(), Some(libc::EPERM));
+    drop(writable);
+
+    // With no arguments the child prints its usage and fails, but it ran
+    match Command::new(&child_exe)
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .output()
+    {
+        Ok(output) => assert!(
+            String::from_utf8_lossy(&output.stderr).contains("Usage"),
+            "child did not run: {}",
+            String::from_utf8_lossy(&output.stderr)
+        ),
+        Err(e) => eprintln!("skipping the run: the child cannot run here ({e})"),
+    }
+}
+
+/// A writable directory on a `noexec` mount, such as `/dev/shm` often is,
+/// if this machine has one.
+fn noexec_dir() -> Option<PathBuf> {
+    let mounts = std::fs::read_to_string("/proc/mounts").ok()?;
+    mounts
+        .lines()
+        .filter_map(|line| {
+            let mut fields = line.split_whitespace();
+            let dir = fields.nth(1)?;
+            let options = fields.nth(1)?;
+            options
+                .split(',')
+                .any(|option| option == "noexec")
+                .then(|| PathBuf::from(dir))
+        })
+        .find(|dir| tempfile::tempfile_in(dir).is_ok())
+}
+
+#[test]
+fn extraction_falls_back_past_unusable_dirs() {
+    let good = tempfile::tempdir().unwrap();
+    // Stands in for a noexec temp dir where there is none to be had
+    let unusable = noexec_dir().unwrap_or_else(|| good.path().join("missing"));
+
+    let child_exe =
+        ChildExecutable::extract_to(CHILD, &[unusable.clone(), good.path().to_path_buf()]).unwrap();
+    assert!(
+        child_exe.path().starts_with(good.path()),
+        "extracted to {}",
+        child_exe.path().display()
+    );
+
+    let err = ChildExecutable::extract_to(CHILD, std::slice::from_ref(&unusable)).unwrap_err();
+    assert_eq!(err.kind(), std::io::ErrorKind::PermissionDenied);
+    assert!(
+        err.to_string().contains(&unusable.display().to_string()),
+        "{}",
+        err
+    );
+}
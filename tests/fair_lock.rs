@@ -0,0 +1,81 @@
+//! The fair lock serves contenders in the order they took their tickets,
+//! and a contender that gives up does not hold up those behind it.
+
+use sharedmem_multiarch::{OwnedSharedData, SharedData, SharedMemError};
+use std::sync::Mutex;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const CONTENDERS: u32 = 6;
+
+/// Waits until `tickets` tickets have been handed out, so contenders queue
+/// up in the order they are spawned.
+fn wait_for_tickets(shared_data: &SharedData, tickets: u32) {
+    while shared_data.next_ticket.load(Ordering::SeqCst) < tickets {
+        std::thread::yield_now();
+    }
+}
+
+#[test]
+fn contenders_are_served_in_ticket_order() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    let order = Mutex::new(Vec::new());
+
+    shared_data.lock_fair_timeout(TIMEOUT).unwrap();
+    std::thread::scope(|s| {
+        for contender in 0..CONTENDERS {
+            let order = &order;
+            s.spawn(move || {
+                shared_data.lock_fair_timeout(TIMEOUT).unwrap();
+                order.lock().unwrap().push(contender);
+                shared_data.unlock_fair();
+            });
+            wait_for_tickets(shared_data, contender + 2);
+        }
+        shared_data.unlock_fair();
+    });
+
+    assert_eq!(
+        order.into_inner().unwrap(),
+        (0..CONTENDERS).collect::<Vec<_>>()
+    );
+}
+
+#[test]
+fn an_abandoned_ticket_is_passed_on() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    let order = Mutex::new(Vec::new());
+
+    shared_data.lock_fair_timeout(TIMEOUT).unwrap();
+    std::thread::scope(|s| {
+        for contender in 0..3 {
+            let order = &order;
+            s.spawn(move || {
+                // The middle one gives up while the lock is still held
+                let timeout = if contender == 1 {
+                    Duration::from_millis(50)
+                } else {
+                    TIMEOUT
+                };
+                match shared_data.lock_fair_timeout(timeout) {
+                    Ok(()) => {
+                        order.lock().unwrap().push(contender);
+                        shared_data.unlock_fair();
+                    }
+                    Err(e) => {
+                        assert!(matches!(e, SharedMemError::Timeout), "{:?}", e);
+                        order.lock().unwrap().push(u32::MAX);
+                    }
+                }
+            });
+            wait_for_tickets(shared_data, contender + 2);
+        }
+        std::thread::sleep(Duration::from_millis(200));
+        shared_data.unlock_fair();
+    });
+
+    assert_eq!(order.into_inner().unwrap(), [u32::MAX, 0, 2]);
+}
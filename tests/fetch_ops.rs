@@ -0,0 +1,74 @@
+//! The lock-free read-modify-write operations on the number lose no update
+//! when threads race on them.
+
+use sharedmem_multiarch::OwnedSharedData;
+
+const THREADS: i64 = 8;
+const PER_THREAD: i64 = 10_000;
+
+#[test]
+fn concurrent_adds_and_subs_total_exactly() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    shared_data.set_number(0);
+    let (_, generation) = shared_data.get_number_versioned();
+
+    std::thread::scope(|s| {
+        for id in 0..THREADS {
+            s.spawn(move || {
+                for _ in 0..PER_THREAD {
+                    if id % 2 == 0 {
+                        shared_data.fetch_add(3);
+                    } else {
+                        shared_data.fetch_sub(1);
+                    }
+                }
+            });
+        }
+    });
+
+    let half = THREADS / 2;
+    assert_eq!(
+        shared_data.get_number(),
+        half * PER_THREAD * 3 - half * PER_THREAD
+    );
+    let (_, after) = shared_data.get_number_versioned();
+    assert_eq!(after - generation, (THREADS * PER_THREAD) as u64);
+}
+
+#[test]
+fn concurrent_max_and_min_keep_the_extremes() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+
+    shared_data.set_number(0);
+    std::thread::scope(|s| {
+        for id in 0..THREADS {
+            s.spawn(move || {
+                for i in 0..PER_THREAD {
+                    shared_data.fetch_max(i * THREADS + id);
+                }
+            });
+        }
+    });
+    assert_eq!(shared_data.get_number(), PER_THREAD * THREADS - 1);
+
+    std::thread::scope(|s| {
+        for id in 0..THREADS {
+            s.spawn(move || {
+                for i in 0..PER_THREAD {
+                    shared_data.fetch_min(-(i * THREADS + id));
+                }
+            });
+        }
+    });
+    assert_eq!(shared_data.get_number(), -(PER_THREAD * THREADS - 1));
+}
+
+#[test]
+fn add_wraps_on_overflow() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    shared_data.set_number(i64::MAX);
+    assert_eq!(shared_data.fetch_add(1), i64::MAX);
+    assert_eq!(shared_data.get_number(), i64::MIN);
+}
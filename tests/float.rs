@@ -0,0 +1,55 @@
+//! The shared float survives bit for bit and `fetch_add_float` loses no
+//! update under contention.
+
+use sharedmem_multiarch::{OwnedSharedData, SharedData};
+
+const THREADS: usize = 4;
+const ADDS: usize = 10_000;
+
+#[test]
+fn concurrent_adds_all_land() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data: &SharedData = &owned;
+    assert_eq!(shared_data.get_float(), 0.0);
+
+    std::thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for _ in 0..ADDS {
+                    shared_data.fetch_add_float(0.1);
+                }
+            });
+        }
+    });
+
+    // Rounding in each add depends on the order they landed in
+    let expected = 0.1 * (THREADS * ADDS) as f64;
+    let sum = shared_data.get_float();
+    assert!(
+        (sum - expected).abs() < 1e-6,
+        "sum {} expected {}",
+        sum,
+        expected
+    );
+}
+
+#[test]
+fn values_round_trip_bit_for_bit() {
+    let shared_data = OwnedSharedData::create().unwrap();
+
+    let nan_with_payload = f64::from_bits(0x7ff8_0000_dead_beef);
+    shared_data.set_float(nan_with_payload);
+    assert_eq!(
+        shared_data.get_float().to_bits(),
+        nan_with_payload.to_bits()
+    );
+
+    shared_data.set_float(-0.0);
+    assert!(shared_data.get_float().is_sign_negative());
+
+    assert_eq!(
+        shared_data.fetch_add_float(2.5).to_bits(),
+        (-0.0f64).to_bits()
+    );
+    assert_eq!(shared_data.get_float(), 2.5);
+}
@@ -0,0 +1,42 @@
+//! `force_reset_lock` unsticks a lock whose holder is alive but never
+//! going to release it, waking whoever was waiting.
+
+use sharedmem_multiarch::OwnedSharedData;
+use sharedmem_multiarch::shared::LockState;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+#[test]
+fn force_reset_lets_a_waiter_through() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    shared_data.lock().unwrap();
+    // As a hung holder in another process would leave it; PID 1 is alive,
+    // so the waiter has no dead owner to recover from
+    shared_data.owner_pid.store(1, Ordering::Relaxed);
+    assert_eq!(shared_data.lock_state(), LockState::Locked { owner_pid: 1 });
+
+    std::thread::scope(|s| {
+        let waiter = s.spawn(|| {
+            shared_data.lock_timeout(Duration::from_secs(10))?;
+            let state = shared_data.lock_state();
+            shared_data.unlock();
+            Ok::<_, sharedmem_multiarch::SharedMemError>(state)
+        });
+
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!waiter.is_finished(), "got a lock that was still held");
+        // SAFETY: the "holder" is this test, which no longer touches the
+        // protected data.
+        unsafe { shared_data.force_reset_lock() };
+
+        let state = waiter.join().unwrap().unwrap();
+        assert_eq!(
+            state,
+            LockState::Locked {
+                owner_pid: std::process::id() as i32
+            }
+        );
+    });
+    assert_eq!(shared_data.lock_state(), LockState::Unlocked);
+}
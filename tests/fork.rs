@@ -0,0 +1,64 @@
+//! A child forked by `fork_with_shared` works on the same `SharedData` as
+//! the parent, without a child binary or a named segment.
+
+#![cfg(unix)]
+
+use sharedmem_multiarch::fork_with_shared;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// Ends a forked child with `code`. The test harness runs other tests on
+/// other threads, so the child must not panic; see `fork_with_shared`.
+fn fail(code: i32) -> ! {
+    unsafe { libc::_exit(code) }
+}
+
+#[test]
+fn parent_sees_the_forked_childs_update() {
+    let mut child = unsafe {
+        fork_with_shared(|shared_data| {
+            let Ok(mut guard) = shared_data.lock_timeout_guard(TIMEOUT) else {
+                fail(1)
+            };
+            guard.update(|n| (n + 25) * 2);
+        })
+    }
+    .unwrap();
+
+    assert_eq!(child.wait().unwrap().code(), Some(0));
+    assert_eq!(child.get_number(), (100 + 25) * 2);
+    assert_eq!(child.stats().acquisitions, 1);
+}
+
+#[test]
+fn parent_and_child_take_turns() {
+    let mut child = unsafe {
+        fork_with_shared(|shared_data| {
+            for round in 0..5 {
+                if shared_data
+                    .wait_until(|n| n == 2 * round + 1, TIMEOUT)
+                    .is_err()
+                {
+                    fail(1);
+                }
+                let Ok(mut guard) = shared_data.lock_timeout_guard(TIMEOUT) else {
+                    fail(2)
+                };
+                guard.update(|n| n + 1);
+                drop(guard);
+                shared_data.notify_change();
+            }
+        })
+    }
+    .unwrap();
+
+    child.set_number(0);
+    for round in 0..5 {
+        child.lock_timeout_guard(TIMEOUT).unwrap().update(|n| n + 1);
+        child.notify_change();
+        child.wait_until(|n| n == 2 * round + 2, TIMEOUT).unwrap();
+    }
+    assert_eq!(child.wait().unwrap().code(), Some(0));
+    assert_eq!(child.get_number(), 10);
+}
@@ -0,0 +1,108 @@
+//! The counting operations of `FutexWord` that the locks are built from:
+//! bounds on `try_inc` and `try_dec`, and the wakeups paired with them.
+
+use sharedmem_multiarch::raw_sync::{TimedWaitError, WaitError};
+use sharedmem_multiarch::shared::FutexWord;
+use std::time::{Duration, Instant};
+
+const SHORT: Duration = Duration::from_millis(50);
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn counts_stay_within_their_bounds() {
+    let word = FutexWord::new(0);
+    assert!(!word.try_dec());
+    assert!(!word.dec_and_wake(1));
+    assert_eq!(word.load(), 0);
+
+    assert!(word.try_inc(2));
+    assert!(word.try_inc(2));
+    assert!(!word.try_inc(2));
+    assert_eq!(word.load(), 2);
+
+    assert!(word.try_dec());
+    assert!(word.dec_and_wake(1));
+    assert_eq!(word.load(), 0);
+
+    word.inc_and_wake(1);
+    assert_eq!(word.load(), 1);
+    word.store_and_wake(7, 1);
+    assert_eq!(word.load_relaxed(), 7);
+}
+
+#[test]
+fn waits_return_at_once_on_another_value() {
+    let word = FutexWord::new(1);
+    assert_eq!(word.wait_while(0), Err(WaitError::WrongValue));
+    assert_eq!(
+        word.wait_while_for(0, TIMEOUT),
+        Err(TimedWaitError::WrongValue)
+    );
+
+    let started = Instant::now();
+    assert_eq!(word.wait_while_for(1, SHORT), Err(TimedWaitError::TimedOut));
+    assert!(started.elapsed() >= SHORT);
+}
+
+/// Sleeps on `word` while it holds `value`, checking the count itself
+/// rather than the result so spurious wakeups are handled.
+fn wait_until_not(word: &FutexWord, value: u32) {
+    let deadline = Instant::now() + TIMEOUT;
+    while word.load() == value {
+        assert!(Instant::now() < deadline, "never woken");
+        let _ = word.wait_while_for(value, TIMEOUT);
+    }
+}
+
+/// Wakes until a waiter is actually found asleep, and returns how many
+/// were.
+fn wake_sleeper(word: &FutexWord) -> i32 {
+    let deadline = Instant::now() + TIMEOUT;
+    loop {
+        let woken = word.wake(i32::MAX);
+        if woken > 0 || Instant::now() >= deadline {
+            return woken;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+}
+
+#[test]
+fn giving_back_wakes_a_sleeper() {
+    // Release of a binary lock, as `dec_and_wake(1)`
+    let lock = FutexWord::new(1);
+    std::thread::scope(|s| {
+        let waiter = s.spawn(|| wait_until_not(&lock, 1));
+        std::thread::sleep(SHORT);
+        assert!(!waiter.is_finished());
+        assert!(lock.dec_and_wake(1));
+    });
+    assert_eq!(lock.load(), 0);
+
+    // A permit handed back, as `inc_and_wake(1)`
+    let permits = FutexWord::new(0);
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            wait_until_not(&permits, 0);
+            assert!(permits.try_dec());
+        });
+        std::thread::sleep(SHORT);
+        permits.inc_and_wake(1);
+    });
+    assert_eq!(permits.load(), 0);
+}
+
+#[test]
+fn wake_reports_the_sleepers_it_woke() {
+    let word = FutexWord::new(0);
+    assert_eq!(word.wake(i32::MAX), 0);
+
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            // Stays put until the store below, whatever wakes it before
+            wait_until_not(&word, 0);
+        });
+        assert_eq!(wake_sleeper(&word), 1);
+        word.store_and_wake(1, i32::MAX);
+    });
+}
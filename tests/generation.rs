@@ -0,0 +1,50 @@
+//! `number_generation` catches rewrites that leave the number where it was,
+//! which comparing values alone would miss.
+
+use sharedmem_multiarch::OwnedSharedData;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+fn same_value_rewrites_are_seen() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let (number, generation) = shared_data.get_number_versioned();
+    assert!(!shared_data.has_changed_since(generation));
+
+    // A -> A
+    shared_data.set_number(number);
+    assert!(shared_data.has_changed_since(generation));
+
+    // A -> B -> A
+    let (_, generation) = shared_data.get_number_versioned();
+    shared_data.set_number(number + 1);
+    shared_data.set_number(number);
+    let (after, later) = shared_data.get_number_versioned();
+    assert_eq!(after, number);
+    assert_eq!(later, generation + 2);
+}
+
+#[test]
+fn only_successful_rmws_bump_the_generation() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    shared_data.set_number(10);
+    let (_, generation) = shared_data.get_number_versioned();
+
+    assert_eq!(shared_data.compare_and_set(11, 12), Err(10));
+    assert!(!shared_data.has_changed_since(generation));
+
+    assert_eq!(shared_data.compare_and_set(10, 10), Ok(10));
+    assert!(shared_data.has_changed_since(generation));
+}
+
+#[test]
+fn writes_through_the_guard_are_not_counted() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let (_, generation) = shared_data.get_number_versioned();
+    {
+        let mut guard = shared_data.lock_timeout_guard(TIMEOUT).unwrap();
+        guard.update(|n| n + 1);
+    }
+    assert!(!shared_data.has_changed_since(generation));
+}
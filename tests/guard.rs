@@ -0,0 +1,56 @@
+//! `SharedDataGuard` gives access to the number while the lock is held and
+//! releases it when dropped, so no path out of a critical section can
+//! forget to.
+
+use sharedmem_multiarch::OwnedSharedData;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(5);
+
+#[test]
+fn guard_holds_the_lock_until_dropped() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let mut guard = shared_data.lock_timeout_guard(TIMEOUT).unwrap();
+    assert!(shared_data.owned_by_me());
+    assert!(!shared_data.try_lock());
+
+    assert_eq!(guard.get(), 100);
+    guard.update(|n| n + 1);
+    drop(guard);
+
+    assert!(!shared_data.is_locked());
+    assert_eq!(shared_data.get_number(), 101);
+}
+
+#[test]
+fn early_return_releases_the_lock() {
+    fn bump_unless_odd(shared_data: &OwnedSharedData) -> Result<(), i64> {
+        let mut guard = shared_data.lock_guard().unwrap();
+        if guard.get() % 2 == 1 {
+            return Err(guard.get());
+        }
+        guard.update(|n| n + 1);
+        Ok(())
+    }
+
+    let shared_data = OwnedSharedData::create().unwrap();
+    assert_eq!(bump_unless_odd(&shared_data), Ok(()));
+    assert_eq!(bump_unless_odd(&shared_data), Err(101));
+    assert!(!shared_data.is_locked());
+}
+
+#[test]
+fn lock_free_writes_during_the_hold_are_seen() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    let mut guard = shared_data.lock_timeout_guard(TIMEOUT).unwrap();
+
+    // `fetch_add` does not take the lock, so it lands while the guard is held
+    std::thread::scope(|s| {
+        s.spawn(|| shared_data.fetch_add(5));
+    });
+    assert_eq!(guard.get(), 105);
+    guard.update(|n| n * 2);
+    drop(guard);
+    assert_eq!(shared_data.get_number(), 210);
+}
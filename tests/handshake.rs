@@ -0,0 +1,58 @@
+//! A child spawned after `begin_handshake` reports in and then waits for
+//! `start_running`, leaving the number and the lock alone until then.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use sharedmem_multiarch::OwnedSharedData;
+use sharedmem_multiarch::layout::Phase;
+use sharedmem_multiarch::shared::LockState;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+#[test]
+fn child_waits_for_running() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shared_data = OwnedSharedData::create().unwrap();
+    assert_eq!(shared_data.phase(), Phase::Created);
+    shared_data.begin_handshake();
+    assert_eq!(shared_data.phase(), Phase::ParentReady);
+
+    let child_exe = extract_child();
+    let mut child = Command::new(&child_exe)
+        .arg(shared_data.os_id())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    shared_data
+        .wait_attached(1, Duration::from_secs(5))
+        .unwrap();
+    assert_eq!(shared_data.phase(), Phase::ChildAttached);
+
+    // Nothing holds the lock, so only the handshake keeps the child back
+    std::thread::sleep(Duration::from_millis(300));
+    assert!(child.try_wait().unwrap().is_none());
+    assert_eq!(shared_data.lock_state(), LockState::Unlocked);
+    assert_eq!(shared_data.stats().acquisitions, 0);
+    assert_eq!(shared_data.get_number(), 100);
+
+    shared_data.start_running();
+    assert!(child.wait().unwrap().success());
+    assert_eq!(shared_data.get_number(), (100 + 25) * 2);
+}
+
+#[test]
+fn wait_attached_times_out_without_children() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    shared_data.begin_handshake();
+    assert!(
+        shared_data
+            .wait_attached(1, Duration::from_millis(50))
+            .is_err()
+    );
+}
@@ -0,0 +1,82 @@
+//! The magic and layout version at the start of every region keep a peer
+//! from reading a region that is not ours, or ours but laid out by another
+//! build.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use shared_memory::{Shmem, ShmemConf};
+use sharedmem_multiarch::layout::{Header, LAYOUT_VERSION, MAGIC};
+use sharedmem_multiarch::{SharedData, SharedMemError};
+use std::process::Command;
+
+/// A region initialized like the parent's, with `stamp` applied to the
+/// header afterwards.
+fn region_with_header(stamp: impl FnOnce(&mut Header)) -> Shmem {
+    let shmem = ShmemConf::new()
+        .size(std::mem::size_of::<SharedData>())
+        .create()
+        .unwrap();
+    let ptr = shmem.as_ptr() as *mut SharedData;
+    // SAFETY: the fresh mapping is page aligned, large enough and not yet
+    // seen by anyone else.
+    unsafe {
+        SharedData::init_in_place(ptr);
+        stamp(&mut *std::ptr::addr_of_mut!((*ptr).header));
+    }
+    shmem
+}
+
+fn parent_error(shmem: &Shmem) -> String {
+    match sharedmem_multiarch::open_region(shmem.get_os_id()) {
+        Err(SharedMemError::LayoutMismatch(mismatch)) => mismatch.to_string(),
+        Err(e) => panic!("expected LayoutMismatch, got {}", e),
+        Ok(_) => panic!("a region with a foreign header was accepted"),
+    }
+}
+
+fn child_error(shmem: &Shmem) -> Option<String> {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping the child: it cannot run here ({reason})");
+        return None;
+    }
+    let child_exe = extract_child();
+    let output = Command::new(&child_exe)
+        .arg(shmem.get_os_id())
+        .output()
+        .unwrap();
+    let stderr = String::from_utf8_lossy(&output.stderr).into_owned();
+    assert_eq!(output.status.code(), Some(13), "stderr: {}", stderr);
+    Some(stderr)
+}
+
+#[test]
+fn foreign_magic_is_refused() {
+    let shmem = region_with_header(|header| header.magic = !MAGIC);
+    let expected = format!(
+        "magic mismatch: expected {:#010x} got {:#010x}",
+        MAGIC, !MAGIC
+    );
+
+    let error = parent_error(&shmem);
+    assert!(error.contains(&expected), "{}", error);
+    if let Some(stderr) = child_error(&shmem) {
+        assert!(stderr.contains(&expected), "stderr: {}", stderr);
+    }
+}
+
+#[test]
+fn older_layout_version_is_refused() {
+    let shmem = region_with_header(|header| header.version = LAYOUT_VERSION - 1);
+    let expected = format!(
+        "layout mismatch: expected v{} got v{}",
+        LAYOUT_VERSION,
+        LAYOUT_VERSION - 1
+    );
+
+    let error = parent_error(&shmem);
+    assert!(error.contains(&expected), "{}", error);
+    if let Some(stderr) = child_error(&shmem) {
+        assert!(stderr.contains(&expected), "stderr: {}", stderr);
+    }
+}
@@ -0,0 +1,48 @@
+//! `peer_alive` holds while the other side keeps beating and goes false
+//! once it stops.
+
+use sharedmem_multiarch::OwnedSharedData;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+const STALENESS: Duration = Duration::from_millis(200);
+
+#[test]
+fn peer_alive_goes_false_once_beating_stops() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    assert!(!shared_data.peer_alive(STALENESS), "alive before any beat");
+
+    let stop = AtomicBool::new(false);
+    let mut dead_while_beating = 0;
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            while !stop.load(Ordering::Relaxed) {
+                shared_data.beat();
+                std::thread::sleep(Duration::from_millis(10));
+            }
+        });
+
+        let spawned = Instant::now();
+        while !shared_data.peer_alive(STALENESS) && spawned.elapsed() < STALENESS * 5 {
+            std::thread::yield_now();
+        }
+        // Then alive for well past the staleness limit while it beats
+        let beating_until = Instant::now() + STALENESS * 2;
+        while Instant::now() < beating_until {
+            if !shared_data.peer_alive(STALENESS) {
+                dead_while_beating += 1;
+            }
+            std::thread::sleep(Duration::from_millis(20));
+        }
+        stop.store(true, Ordering::Relaxed);
+    });
+    assert_eq!(dead_while_beating, 0, "looked dead while beating");
+
+    let stopped = Instant::now();
+    while shared_data.peer_alive(STALENESS) {
+        assert!(stopped.elapsed() < STALENESS * 5, "still alive");
+        std::thread::sleep(Duration::from_millis(10));
+    }
+    assert!(stopped.elapsed() >= STALENESS / 2);
+}
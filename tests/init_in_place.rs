@@ -0,0 +1,39 @@
+//! `SharedData::open` on a region whose creator has not finished
+//! `init_in_place` waits for the ready flag instead of reading it half
+//! built.
+
+use shared_memory::ShmemConf;
+use sharedmem_multiarch::SharedData;
+use std::time::{Duration, Instant};
+
+#[test]
+fn open_before_init_waits_for_the_ready_flag() {
+    let shmem = ShmemConf::new()
+        .size(std::mem::size_of::<SharedData>())
+        .create()
+        .unwrap();
+    // Raw pointers are not `Send`; the mapping outlives both threads
+    let addr = shmem.as_ptr() as usize;
+
+    std::thread::scope(|s| {
+        let opener = s.spawn(|| {
+            // SAFETY: the mapping is large enough and outlives the scope.
+            let data = unsafe { SharedData::open(addr as *const SharedData) }.unwrap();
+            (Instant::now(), data.get_number(), data.is_locked())
+        });
+
+        // Give the opener time to find the fresh, all-zero region
+        std::thread::sleep(Duration::from_millis(100));
+        assert!(!opener.is_finished(), "opened a region nobody initialized");
+        let initialized_at = Instant::now();
+        // SAFETY: fresh, page aligned and large enough; the opener only
+        // reads `ready` until this sets it.
+        unsafe { SharedData::init_in_place(addr as *mut SharedData) };
+
+        let (opened_at, number, locked) = opener.join().unwrap();
+        assert!(opened_at >= initialized_at);
+        // Everything written before the flag is visible after it
+        assert_eq!(number, 100);
+        assert!(!locked);
+    });
+}
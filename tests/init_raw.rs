@@ -0,0 +1,47 @@
+//! `init_raw` writes the same fields `SharedData::new` builds, byte for
+//! byte, while leaving the padding between them as the segment had it.
+
+use sharedmem_multiarch::SharedData;
+use std::mem::{MaybeUninit, size_of};
+
+/// The bytes of a region filled with `fill` and then set up by `init`.
+fn region_bytes(fill: u8, init: impl FnOnce(*mut MaybeUninit<SharedData>)) -> Vec<u8> {
+    let mut region = Box::<SharedData>::new_uninit();
+    let ptr: *mut MaybeUninit<SharedData> = &mut *region;
+    // SAFETY: the box holds `size_of::<SharedData>()` writable bytes.
+    unsafe { ptr.cast::<u8>().write_bytes(fill, size_of::<SharedData>()) };
+    init(ptr);
+    // SAFETY: every byte was written by the fill, and `init` only
+    // overwrites them; nothing else uses the box.
+    unsafe { std::slice::from_raw_parts(ptr.cast::<u8>(), size_of::<SharedData>()) }.to_vec()
+}
+
+#[test]
+fn init_raw_matches_new_and_keeps_the_padding() {
+    // SAFETY: boxes are aligned for `SharedData`, large enough and private.
+    let zeroed = region_bytes(0x00, |ptr| unsafe { SharedData::init_raw(ptr) });
+    let filled = region_bytes(0xFF, |ptr| unsafe { SharedData::init_raw(ptr) });
+    let mut built = Box::<SharedData>::new_uninit();
+    let built_ptr = built.as_mut_ptr();
+    built.write(SharedData::new());
+
+    let mut padding = 0;
+    for offset in 0..size_of::<SharedData>() {
+        if zeroed[offset] != filled[offset] {
+            // Padding: never written, so it kept the fill
+            assert_eq!(
+                (zeroed[offset], filled[offset]),
+                (0x00, 0xFF),
+                "offset {}",
+                offset
+            );
+            padding += 1;
+            continue;
+        }
+        // SAFETY: a field byte, which `new` initialized; padding is skipped.
+        let byte = unsafe { built_ptr.cast::<u8>().add(offset).read() };
+        assert_eq!(byte, zeroed[offset], "field byte at offset {}", offset);
+    }
+    // The cache-line alignment of the futex and number leaves gaps
+    assert!(padding > 0);
+}
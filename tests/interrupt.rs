@@ -0,0 +1,113 @@
+//! Signals and the lock: `lock_interruptible` and `lock_timeout_no_retry`
+//! hand a signal back to the caller, while `lock_timeout` waits on.
+//!
+//! Handlers are installed without `SA_RESTART`, as the docs ask, so the
+//! futex wait really is interrupted rather than restarted by the kernel.
+
+#![cfg(target_os = "linux")]
+
+use sharedmem_multiarch::raw_sync::WaitError;
+use sharedmem_multiarch::{OwnedSharedData, SharedMemError};
+use std::thread::ScopedJoinHandle;
+use std::time::Duration;
+
+extern "C" fn ignore(_signal: libc::c_int) {}
+
+/// Installs a handler for `signal` that does nothing, so the signal
+/// interrupts a wait without killing the test.
+fn install_noop_handler(signal: libc::c_int) {
+    unsafe {
+        let mut action: libc::sigaction = std::mem::zeroed();
+        action.sa_sigaction = ignore as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        libc::sigemptyset(&mut action.sa_mask);
+        assert_eq!(libc::sigaction(signal, &action, std::ptr::null_mut()), 0);
+    }
+}
+
+/// Sends `signal` to the thread `id` until `thread` finishes. Once is not
+/// enough: a signal that lands while the thread is still spinning only runs
+/// the handler, and the wait it goes on to start is not interrupted.
+fn signal_until_finished<T>(
+    thread: &ScopedJoinHandle<'_, T>,
+    id: libc::pthread_t,
+    signal: libc::c_int,
+) {
+    while !thread.is_finished() {
+        unsafe { libc::pthread_kill(id, signal) };
+        std::thread::sleep(Duration::from_millis(20));
+    }
+}
+
+#[test]
+fn sigint_interrupts_lock_interruptible() {
+    install_noop_handler(libc::SIGINT);
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+
+    shared_data.lock().unwrap();
+    std::thread::scope(|s| {
+        let (id_tx, id_rx) = std::sync::mpsc::channel();
+        let waiter = s.spawn(move || {
+            id_tx.send(unsafe { libc::pthread_self() }).unwrap();
+            shared_data.lock_interruptible()
+        });
+        let id = id_rx.recv().unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        signal_until_finished(&waiter, id, libc::SIGINT);
+        assert_eq!(waiter.join().unwrap(), Err(WaitError::Interrupted));
+    });
+
+    // The waiter gave up without taking the lock; it is still ours
+    assert!(shared_data.owned_by_me());
+    shared_data.unlock();
+}
+
+#[test]
+fn sigusr1_interrupts_only_lock_timeout_no_retry() {
+    install_noop_handler(libc::SIGUSR1);
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    let timeout = Duration::from_secs(10);
+
+    shared_data.lock().unwrap();
+    std::thread::scope(|s| {
+        let (id_tx, id_rx) = std::sync::mpsc::channel();
+        let waiter = s.spawn(move || {
+            id_tx.send(unsafe { libc::pthread_self() }).unwrap();
+            shared_data.lock_timeout_no_retry(timeout)
+        });
+        let id = id_rx.recv().unwrap();
+        std::thread::sleep(Duration::from_millis(50));
+        signal_until_finished(&waiter, id, libc::SIGUSR1);
+        assert!(matches!(
+            waiter.join().unwrap(),
+            Err(SharedMemError::Interrupted)
+        ));
+    });
+    assert!(shared_data.owned_by_me());
+
+    std::thread::scope(|s| {
+        let (id_tx, id_rx) = std::sync::mpsc::channel();
+        let waiter = s.spawn(move || {
+            id_tx.send(unsafe { libc::pthread_self() }).unwrap();
+            let locked = shared_data.lock_timeout(timeout);
+            if locked.is_ok() {
+                shared_data.unlock();
+            }
+            locked
+        });
+        let id = id_rx.recv().unwrap();
+        for _ in 0..10 {
+            std::thread::sleep(Duration::from_millis(20));
+            unsafe { libc::pthread_kill(id, libc::SIGUSR1) };
+        }
+        // Every signal only sent it back to waiting for the lock
+        let still_waiting = !waiter.is_finished();
+        shared_data.unlock();
+        assert!(
+            still_waiting,
+            "lock_timeout returned while the lock was held"
+        );
+        assert!(waiter.join().unwrap().is_ok());
+    });
+}
@@ -0,0 +1,34 @@
+//! `is_locked` and `raw_futex_value` report whether the lock was held when
+//! looked at, whoever holds it.
+
+use sharedmem_multiarch::OwnedSharedData;
+use std::time::Duration;
+
+#[test]
+fn is_locked_follows_the_lock() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    assert!(!shared_data.is_locked());
+    assert_eq!(shared_data.raw_futex_value(), 0);
+
+    shared_data.lock().unwrap();
+    assert!(shared_data.is_locked());
+    assert_eq!(shared_data.raw_futex_value(), 1);
+    // Seen the same from another thread, which does not hold it
+    std::thread::scope(|s| {
+        let seen = s.spawn(|| (shared_data.is_locked(), shared_data.try_lock()));
+        assert_eq!(seen.join().unwrap(), (true, false));
+    });
+
+    shared_data.unlock();
+    assert!(!shared_data.is_locked());
+    assert_eq!(shared_data.raw_futex_value(), 0);
+
+    // And through a guard, released with it
+    let guard = shared_data
+        .lock_timeout_guard(Duration::from_secs(5))
+        .unwrap();
+    assert!(shared_data.is_locked());
+    drop(guard);
+    assert!(!shared_data.is_locked());
+}
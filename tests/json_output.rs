@@ -0,0 +1,63 @@
+//! Runs the demo with `--json` and checks the summary it ends on.
+//!
+//! Run with `cargo test --features json --test json_output`.
+
+#![cfg(feature = "json")]
+
+mod common;
+
+use common::child_runnable;
+use std::process::Command;
+
+#[test]
+fn json_summary_reports_the_results() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping the end-to-end demo: the child cannot run here ({reason})");
+        return;
+    }
+
+    let output = Command::new(env!("CARGO_BIN_EXE_sharedmem-multiarch"))
+        .args(["--json", "--child-count", "2"])
+        .output()
+        .unwrap();
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        output.status.success(),
+        "demo failed with {}\nstdout:\n{}\nstderr:\n{}",
+        output.status,
+        stdout,
+        String::from_utf8_lossy(&output.stderr)
+    );
+
+    // The progress lines before it, the children's included, stay as they are
+    let last_line = stdout.lines().last().unwrap_or_default();
+    let summary: serde_json::Value = serde_json::from_str(last_line)
+        .unwrap_or_else(|e| panic!("last line is not JSON ({}): {}", e, last_line));
+
+    assert_eq!(summary["initial"], 100);
+    assert_eq!(summary["child_count"], 2);
+    assert_eq!(summary["child_op"], "(n + 25) * 2");
+    // (100 + 25) * 2 = 250, then (250 + 25) * 2 = 550
+    assert_eq!(summary["child_result"], 550);
+    assert_eq!(summary["parent_op"], "n * 3 + 50");
+    assert_eq!(summary["parent_result"], 1700);
+    assert_eq!(summary["attempts"], 1);
+
+    let pids = summary["child_pids"].as_array().unwrap();
+    assert_eq!(pids.len(), 2);
+    assert!(
+        pids.iter()
+            .all(|pid| pid.as_u64().is_some_and(|pid| pid > 0))
+    );
+
+    let timings = &summary["timings_ms"];
+    for timing in ["children", "lock_wait", "total"] {
+        assert!(
+            timings[timing].as_f64().is_some_and(|ms| ms >= 0.0),
+            "bad {} timing in {}",
+            timing,
+            summary
+        );
+    }
+    assert!(timings["children"].as_f64() <= timings["total"].as_f64());
+}
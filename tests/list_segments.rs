@@ -0,0 +1,62 @@
+//! `list_segments` finds our segments by prefix and tells them apart from
+//! other programs' entries, and only ours can be unlinked through it.
+
+#![cfg(target_os = "linux")]
+
+use sharedmem_multiarch::layout::LAYOUT_VERSION;
+use sharedmem_multiarch::{SharedData, SharedRegion, list_segments};
+
+fn prefix(test: &str) -> String {
+    format!("/sharedmem-list-{}-{}-", test, std::process::id())
+}
+
+#[test]
+fn created_segment_is_listed_with_our_magic() {
+    let prefix = prefix("ours");
+    let name = format!("{}a", prefix);
+    let shared_data = SharedRegion::builder().os_id(&name).build().unwrap();
+
+    let segments = list_segments(&prefix);
+    assert_eq!(segments.len(), 1, "{:?}", segments);
+    let segment = &segments[0];
+    assert_eq!(segment.name, name);
+    assert_eq!(segment.name, shared_data.os_id());
+    assert!(segment.size >= std::mem::size_of::<SharedData>() as u64);
+    assert!(segment.has_magic);
+    assert_eq!(segment.layout_version, Some(LAYOUT_VERSION));
+
+    // The prefix works without the leading slash too
+    assert_eq!(list_segments(prefix.trim_start_matches('/')), segments);
+}
+
+#[test]
+fn only_segments_with_our_magic_are_unlinked() {
+    let prefix = prefix("cleanup");
+
+    // An orphan, as a creator that crashed would leave it
+    let orphan = format!("{}orphan", prefix);
+    std::mem::forget(SharedRegion::builder().os_id(&orphan).build().unwrap());
+    // Someone else's entry that happens to share the prefix
+    let foreign = format!("/dev/shm{}foreign", prefix);
+    std::fs::write(&foreign, b"not a SharedData header at all").unwrap();
+
+    let segments = list_segments(&prefix);
+    let names: Vec<_> = segments.iter().map(|s| s.name.as_str()).collect();
+    assert_eq!(
+        names,
+        [format!("{}foreign", prefix), orphan.clone()],
+        "{:?}",
+        segments
+    );
+    assert!(!segments[0].has_magic);
+    assert_eq!(segments[0].layout_version, None);
+
+    let error = segments[0].unlink().unwrap_err();
+    assert_eq!(error.kind(), std::io::ErrorKind::InvalidInput);
+    segments[1].unlink().unwrap();
+
+    let left = list_segments(&prefix);
+    assert_eq!(left.len(), 1);
+    assert!(!left[0].has_magic);
+    std::fs::remove_file(&foreign).unwrap();
+}
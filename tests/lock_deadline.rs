@@ -0,0 +1,57 @@
+//! `lock_deadline` with a deadline already gone fails at once with
+//! `Timeout` instead of waiting or taking the lock.
+
+use sharedmem_multiarch::shared::LockState;
+use sharedmem_multiarch::{OwnedSharedData, SharedMemError};
+use std::time::{Duration, Instant};
+
+#[test]
+fn deadline_in_the_past_times_out_immediately() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    let past = Instant::now() - Duration::from_millis(10);
+
+    // Even a free lock is not taken
+    let started = Instant::now();
+    assert!(matches!(
+        shared_data.lock_deadline(past),
+        Err(SharedMemError::Timeout)
+    ));
+    assert_eq!(shared_data.lock_state(), LockState::Unlocked);
+
+    // A held one is not waited for
+    shared_data.lock().unwrap();
+    std::thread::scope(|s| {
+        let result = s.spawn(|| shared_data.lock_deadline(past)).join().unwrap();
+        assert!(
+            matches!(result, Err(SharedMemError::Timeout)),
+            "{:?}",
+            result
+        );
+    });
+    assert!(started.elapsed() < Duration::from_secs(1));
+    shared_data.unlock();
+}
+
+#[test]
+fn future_deadline_is_waited_for() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    shared_data.lock().unwrap();
+
+    let wait = Duration::from_millis(100);
+    let started = Instant::now();
+    std::thread::scope(|s| {
+        let result = s
+            .spawn(|| shared_data.lock_deadline(started + wait))
+            .join()
+            .unwrap();
+        assert!(
+            matches!(result, Err(SharedMemError::Timeout)),
+            "{:?}",
+            result
+        );
+    });
+    assert!(started.elapsed() >= wait);
+    shared_data.unlock();
+}
@@ -0,0 +1,55 @@
+//! Hold times are measured by whoever releases the lock and add up in the
+//! shared counters, so `stats` covers the parent and the child alike.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use sharedmem_multiarch::OwnedSharedData;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+#[test]
+fn max_hold_covers_a_known_hold() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let hold = Duration::from_millis(200);
+
+    {
+        let _guard = shared_data.lock_guard().unwrap();
+        std::thread::sleep(hold);
+    }
+    // A short hold must not lower the maximum
+    shared_data.lock().unwrap();
+    shared_data.unlock();
+
+    let stats = shared_data.stats();
+    assert_eq!(stats.acquisitions, 2);
+    assert!(stats.max_hold >= hold, "max hold {:?}", stats.max_hold);
+    assert!(stats.total_hold >= stats.max_hold);
+    assert!(stats.average_hold().unwrap() >= hold / 2);
+}
+
+#[test]
+fn holds_by_the_child_are_counted() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shared_data = OwnedSharedData::create().unwrap();
+    assert_eq!(shared_data.stats().average_hold(), None);
+    let child_exe = extract_child();
+    // The child holds the lock for half a second after its update
+    let status = Command::new(&child_exe)
+        .arg(shared_data.os_id())
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    let stats = shared_data.stats();
+    assert!(
+        stats.max_hold >= Duration::from_millis(500),
+        "max hold {:?}",
+        stats.max_hold
+    );
+}
@@ -0,0 +1,137 @@
+//! `prometheus_metrics` produces well-formed exposition text with the
+//! expected metric names and the segment label.
+//!
+//! Run with `cargo test --features metrics --test metrics`.
+
+#![cfg(feature = "metrics")]
+
+use sharedmem_multiarch::OwnedSharedData;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const METRICS: [(&str, &str); 5] = [
+    ("sharedmem_lock_acquisitions_total", "counter"),
+    ("sharedmem_lock_contended_total", "counter"),
+    ("sharedmem_lock_wait_seconds_total", "counter"),
+    ("sharedmem_lock_hold_seconds_total", "counter"),
+    ("sharedmem_lock_hold_max_seconds", "gauge"),
+];
+
+#[test]
+fn metrics_parse_and_match_the_stats() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    for _ in 0..3 {
+        let guard = shared_data.lock_guard().unwrap();
+        std::thread::sleep(Duration::from_millis(2));
+        drop(guard);
+    }
+
+    let text = shared_data.prometheus_metrics(shared_data.os_id());
+    let samples = parse(&text);
+
+    assert_eq!(samples.len(), METRICS.len(), "{}", text);
+    for (name, kind) in METRICS {
+        let sample = &samples[name];
+        assert_eq!(sample.kind, kind, "{}", name);
+        assert_eq!(sample.labels, [("segment", shared_data.os_id().to_owned())]);
+    }
+    assert_eq!(samples["sharedmem_lock_acquisitions_total"].value, 3.0);
+    assert!(samples["sharedmem_lock_hold_seconds_total"].value >= 0.006);
+    assert!(samples["sharedmem_lock_hold_max_seconds"].value >= 0.002);
+}
+
+#[test]
+fn segment_label_is_escaped() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let segment = "odd \"name\" with \\ and\nnewline";
+
+    let samples = parse(&shared_data.prometheus_metrics(segment));
+    let labels = &samples["sharedmem_lock_contended_total"].labels;
+    assert_eq!(labels, &[("segment", segment.to_owned())]);
+}
+
+struct Sample {
+    kind: String,
+    labels: Vec<(&'static str, String)>,
+    value: f64,
+}
+
+/// The samples in `text` by metric name, panicking on anything that does
+/// not follow the text format: every sample must come after a `HELP` and
+/// a `TYPE` line for its name and appear only once, as we never emit
+/// several label sets.
+fn parse(text: &str) -> HashMap<String, Sample> {
+    assert!(text.ends_with('\n'), "no final newline: {:?}", text);
+    let mut help = HashMap::new();
+    let mut kinds = HashMap::new();
+    let mut samples = HashMap::new();
+
+    for line in text.lines() {
+        if let Some(rest) = line.strip_prefix("# HELP ") {
+            let (name, text) = rest.split_once(' ').expect("HELP without text");
+            assert!(valid_name(name), "bad name in {:?}", line);
+            assert!(help.insert(name, text).is_none(), "second HELP: {:?}", line);
+        } else if let Some(rest) = line.strip_prefix("# TYPE ") {
+            let (name, kind) = rest.split_once(' ').expect("TYPE without kind");
+            assert!(valid_name(name), "bad name in {:?}", line);
+            assert!(
+                ["counter", "gauge", "histogram", "summary", "untyped"].contains(&kind),
+                "bad type in {:?}",
+                line
+            );
+            assert!(
+                kinds.insert(name, kind).is_none(),
+                "second TYPE: {:?}",
+                line
+            );
+        } else {
+            assert!(!line.starts_with('#'), "unknown comment: {:?}", line);
+            let (name, rest) = line.split_once('{').expect("sample without labels");
+            let (labels, value) = rest.rsplit_once("} ").expect("unclosed labels");
+            assert!(valid_name(name), "bad name in {:?}", line);
+            assert!(help.contains_key(name), "no HELP before {:?}", line);
+            let sample = Sample {
+                kind: kinds.get(name).expect("no TYPE before sample").to_string(),
+                labels: parse_labels(labels),
+                value: value.parse().expect("bad value"),
+            };
+            assert!(
+                samples.insert(name.to_owned(), sample).is_none(),
+                "second sample: {:?}",
+                line
+            );
+        }
+    }
+    samples
+}
+
+/// `label="value",...`, only knowing the `segment` label.
+fn parse_labels(labels: &str) -> Vec<(&'static str, String)> {
+    let value = labels
+        .strip_prefix("segment=\"")
+        .and_then(|rest| rest.strip_suffix('"'))
+        .expect("not a single segment label");
+    let mut unescaped = String::new();
+    let mut chars = value.chars();
+    while let Some(c) = chars.next() {
+        match c {
+            '\\' => match chars.next() {
+                Some('\\') => unescaped.push('\\'),
+                Some('"') => unescaped.push('"'),
+                Some('n') => unescaped.push('\n'),
+                other => panic!("bad escape \\{:?} in {:?}", other, labels),
+            },
+            '"' => panic!("unescaped quote in {:?}", labels),
+            c => unescaped.push(c),
+        }
+    }
+    vec![("segment", unescaped)]
+}
+
+fn valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    chars
+        .next()
+        .is_some_and(|c| c.is_ascii_alphabetic() || c == '_' || c == ':')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_' || c == ':')
+}
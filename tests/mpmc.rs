@@ -0,0 +1,102 @@
+//! `SharedMpmcQueue` under several producers and consumers at once: every
+//! value comes out exactly once, and waiters that give up leave no trace.
+
+use sharedmem_multiarch::mpmc::SharedMpmcQueue;
+use sharedmem_multiarch::raw_sync::TimedWaitError;
+use std::sync::atomic::{AtomicUsize, Ordering};
+use std::time::Duration;
+
+const PRODUCERS: i64 = 4;
+const CONSUMERS: usize = 3;
+const PER_PRODUCER: i64 = 5_000;
+const TIMEOUT: Duration = Duration::from_secs(10);
+const SHORT: Duration = Duration::from_millis(50);
+
+#[test]
+fn consumers_dequeue_exactly_what_producers_enqueued() {
+    // Small, so producers and consumers both end up blocking
+    let queue = SharedMpmcQueue::<8>::new();
+    let total = (PRODUCERS * PER_PRODUCER) as usize;
+    let taken = AtomicUsize::new(0);
+
+    let dequeued: Vec<Vec<i64>> = std::thread::scope(|s| {
+        for producer in 0..PRODUCERS {
+            let queue = &queue;
+            s.spawn(move || {
+                for value in producer * PER_PRODUCER..(producer + 1) * PER_PRODUCER {
+                    queue.enqueue_timeout(value, TIMEOUT).unwrap();
+                }
+            });
+        }
+        let consumers: Vec<_> = (0..CONSUMERS)
+            .map(|_| {
+                s.spawn(|| {
+                    let mut got = Vec::new();
+                    while taken.load(Ordering::SeqCst) < total {
+                        // Short waits, so the last consumers notice the
+                        // end instead of sleeping through it
+                        match queue.dequeue_timeout(SHORT) {
+                            Ok(value) => {
+                                got.push(value);
+                                taken.fetch_add(1, Ordering::SeqCst);
+                            }
+                            Err(TimedWaitError::TimedOut) => {}
+                            Err(e) => panic!("dequeue failed: {:?}", e),
+                        }
+                    }
+                    got
+                })
+            })
+            .collect();
+        consumers.into_iter().map(|c| c.join().unwrap()).collect()
+    });
+
+    // Each consumer saw any one producer's values in the order enqueued
+    for got in &dequeued {
+        for producer in 0..PRODUCERS {
+            let range = producer * PER_PRODUCER..(producer + 1) * PER_PRODUCER;
+            let from_producer: Vec<_> = got.iter().filter(|v| range.contains(v)).collect();
+            assert!(
+                from_producer.is_sorted(),
+                "producer {} out of order",
+                producer
+            );
+        }
+    }
+    let mut all: Vec<i64> = dequeued.into_iter().flatten().collect();
+    all.sort_unstable();
+    assert!(all.iter().copied().eq(0..PRODUCERS * PER_PRODUCER));
+    assert!(queue.is_empty());
+    assert_eq!(queue.consumers_waiting.load(Ordering::SeqCst), 0);
+    assert_eq!(queue.producers_waiting.load(Ordering::SeqCst), 0);
+}
+
+#[test]
+fn waiters_that_time_out_deregister() {
+    let queue = SharedMpmcQueue::<2>::new();
+    assert_eq!(queue.dequeue_timeout(SHORT), Err(TimedWaitError::TimedOut));
+    assert_eq!(queue.consumers_waiting.load(Ordering::SeqCst), 0);
+
+    queue.try_enqueue(1).unwrap();
+    queue.try_enqueue(2).unwrap();
+    assert_eq!(
+        queue.enqueue_timeout(3, SHORT),
+        Err(TimedWaitError::TimedOut)
+    );
+    assert_eq!(queue.producers_waiting.load(Ordering::SeqCst), 0);
+    // The value that timed out was not enqueued after all
+    assert_eq!(queue.len(), 2);
+
+    // A waiter registered while it sleeps, and is woken by the other side
+    std::thread::scope(|s| {
+        let producer = s.spawn(|| queue.enqueue_timeout(3, TIMEOUT));
+        while queue.producers_waiting.load(Ordering::SeqCst) == 0 {
+            std::thread::yield_now();
+        }
+        assert_eq!(queue.try_dequeue(), Some(1));
+        assert_eq!(producer.join().unwrap(), Ok(()));
+    });
+    assert_eq!(queue.producers_waiting.load(Ordering::SeqCst), 0);
+    let rest: Vec<_> = std::iter::from_fn(|| queue.try_dequeue()).collect();
+    assert_eq!(rest, [2, 3]);
+}
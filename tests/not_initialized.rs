@@ -0,0 +1,68 @@
+//! A region nobody ran `init_in_place` on is refused with `NotInitialized`
+//! instead of being read as all zeros. Only `READY_SENTINEL` counts as
+//! ready, not any stray set bit.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use shared_memory::{Shmem, ShmemConf};
+use sharedmem_multiarch::timeouts::TIMEOUT_SCALE_VAR;
+use sharedmem_multiarch::{SharedData, SharedMemError};
+use std::process::Command;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+/// A zero-filled region of the right size, with `ready` set to `ready`.
+fn uninitialized_region(ready: u8) -> Shmem {
+    let shmem = ShmemConf::new()
+        .size(std::mem::size_of::<SharedData>())
+        .create()
+        .unwrap();
+    // SAFETY: the mapping is large enough; only the atomic flag is touched.
+    let data = unsafe { &*(shmem.as_ptr() as *const SharedData) };
+    data.ready.store(ready, Ordering::Release);
+    shmem
+}
+
+fn assert_not_initialized(ready: u8) {
+    let shmem = uninitialized_region(ready);
+    let started = Instant::now();
+    // SAFETY: the mapping is large enough and outlives the call.
+    match unsafe { SharedData::open(shmem.as_ptr() as *const SharedData) } {
+        Err(SharedMemError::NotInitialized) => {}
+        Err(e) => panic!("expected NotInitialized, got {}", e),
+        Ok(_) => panic!("opened a region with ready = {:#x}", ready),
+    }
+    // The creator is given its full grace period first
+    assert!(started.elapsed() >= Duration::from_secs(4));
+}
+
+#[test]
+fn zeroed_region_or_a_lone_set_bit_is_not_initialized() {
+    // Side by side, since each waits out the full grace period
+    std::thread::scope(|s| {
+        s.spawn(|| assert_not_initialized(0));
+        s.spawn(|| assert_not_initialized(1));
+    });
+}
+
+#[test]
+fn child_refuses_a_zeroed_region() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shmem = uninitialized_region(0);
+    let child_exe = extract_child();
+    let output = Command::new(&child_exe)
+        .arg(shmem.get_os_id())
+        // A tenth of the usual wait is plenty to see it give up
+        .env(TIMEOUT_SCALE_VAR, "0.1")
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(14), "stderr: {}", stderr);
+    assert!(stderr.contains("never marked ready"), "stderr: {}", stderr);
+}
@@ -0,0 +1,39 @@
+//! One `notify_all` wakes every `wait_until` caller, not just the first.
+
+use sharedmem_multiarch::{OwnedSharedData, SharedData};
+use std::sync::Barrier;
+use std::time::Duration;
+
+const WAITERS: usize = 4;
+
+#[test]
+fn notify_all_wakes_every_waiter() {
+    let owned = OwnedSharedData::create().unwrap();
+    // The mapping itself cannot cross threads, but the data in it can
+    let shared_data: &SharedData = &owned;
+    shared_data.set_number(0);
+    let barrier = Barrier::new(WAITERS + 1);
+
+    let woken: Vec<_> = std::thread::scope(|scope| {
+        let waiters: Vec<_> = (0..WAITERS)
+            .map(|_| {
+                scope.spawn(|| {
+                    barrier.wait();
+                    shared_data.wait_until(|n| n == 1, Duration::from_secs(5))
+                })
+            })
+            .collect();
+
+        barrier.wait();
+        // Give every waiter time to go to sleep on the futex
+        std::thread::sleep(Duration::from_millis(200));
+        shared_data.set_number(1);
+        shared_data.notify_all();
+
+        waiters.into_iter().map(|w| w.join().unwrap()).collect()
+    });
+
+    for result in woken {
+        assert_eq!(result.unwrap(), 1);
+    }
+}
@@ -0,0 +1,64 @@
+//! `lock_timeout_with` calls its callback once for every acquisition that
+//! finds the lock held, and never for one that does not.
+
+use sharedmem_multiarch::{OwnedSharedData, SharedData};
+use std::sync::mpsc;
+use std::time::Duration;
+
+const ROUNDS: u32 = 5;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn callback_fires_once_per_contended_acquisition() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data: &SharedData = &owned;
+    let mut calls = 0;
+
+    for _ in 0..ROUNDS {
+        let (locked_tx, locked_rx) = mpsc::channel();
+        std::thread::scope(|scope| {
+            scope.spawn(|| {
+                shared_data.lock().unwrap();
+                locked_tx.send(()).unwrap();
+                // Long enough for the main thread to spin out and sleep
+                std::thread::sleep(Duration::from_millis(50));
+                shared_data.unlock();
+            });
+
+            locked_rx.recv().unwrap();
+            shared_data
+                .lock_timeout_with(TIMEOUT, || calls += 1)
+                .unwrap();
+            shared_data.unlock();
+        });
+    }
+
+    assert_eq!(calls, ROUNDS);
+    assert_eq!(shared_data.stats().contended, ROUNDS as u64);
+}
+
+#[test]
+fn callback_is_not_called_for_a_free_lock() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let mut calls = 0;
+    for _ in 0..ROUNDS {
+        shared_data
+            .lock_timeout_with(TIMEOUT, || calls += 1)
+            .unwrap();
+        shared_data.unlock();
+    }
+    assert_eq!(calls, 0);
+}
+
+#[test]
+fn callback_runs_before_a_timeout() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    shared_data.lock().unwrap();
+
+    // Held by this very thread, so the wait can only time out
+    let mut calls = 0;
+    let result = shared_data.lock_timeout_with(Duration::from_millis(20), || calls += 1);
+    assert!(result.is_err());
+    assert_eq!(calls, 1);
+    shared_data.unlock();
+}
@@ -0,0 +1,72 @@
+//! The child keeps retrying a named segment that does not exist yet, as
+//! when it is spawned before the parent has finished creating it.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use sharedmem_multiarch::create_region;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+const OPEN_TIMEOUT_VAR: &str = "SHAREDMEM_CHILD_OPEN_TIMEOUT_MS";
+
+fn region_name(test: &str) -> String {
+    format!("/sharedmem-open-retry-{}-{}", test, std::process::id())
+}
+
+#[test]
+fn child_opens_a_segment_created_after_it_started() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let name = region_name("late");
+    let child_exe = extract_child();
+    let child = Command::new(&child_exe)
+        .arg(&name)
+        .env(OPEN_TIMEOUT_VAR, "5000")
+        .stdout(Stdio::null())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    std::thread::sleep(Duration::from_millis(300));
+    let shared_data = create_region(&name, 0).unwrap();
+    shared_data.set_number(100);
+
+    let output = child.wait_with_output().unwrap();
+    assert!(
+        output.status.success(),
+        "child failed with {}: {}",
+        output.status,
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // Alone and with defaults, the child applies (n + 25) * 2 once
+    assert_eq!(shared_data.get_number(), 250);
+}
+
+#[test]
+fn child_gives_up_on_a_segment_that_never_appears() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let child_exe = extract_child();
+    let output = Command::new(&child_exe)
+        .arg(region_name("never"))
+        .env(OPEN_TIMEOUT_VAR, "200")
+        // The message checked below is for the unscaled timeout
+        .env_remove("SHAREDMEM_TIMEOUT_SCALE")
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(15), "stderr: {}", stderr);
+    assert!(
+        stderr.contains("shared memory did not appear within 200ms"),
+        "stderr: {}",
+        stderr
+    );
+}
@@ -0,0 +1,56 @@
+//! `get_number_with` and `set_number_with` store and load the same number
+//! as the `SeqCst` pair, whatever valid ordering they are given.
+
+use sharedmem_multiarch::OwnedSharedData;
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+#[test]
+fn every_valid_ordering_round_trips() {
+    let shared_data = OwnedSharedData::create().unwrap();
+
+    shared_data.set_number(-3);
+    for load in [Ordering::Relaxed, Ordering::Acquire, Ordering::SeqCst] {
+        assert_eq!(shared_data.get_number_with(load), -3);
+    }
+
+    let mut value = 0;
+    for store in [Ordering::Relaxed, Ordering::Release, Ordering::SeqCst] {
+        for load in [Ordering::Relaxed, Ordering::Acquire, Ordering::SeqCst] {
+            value += 1;
+            let (_, generation) = shared_data.get_number_versioned();
+            shared_data.set_number_with(value, store);
+            assert_eq!(shared_data.get_number_with(load), value);
+            assert_eq!(shared_data.get_number(), value);
+            // Bumped as `set_number` does
+            assert!(shared_data.has_changed_since(generation));
+        }
+    }
+}
+
+#[test]
+fn release_store_publishes_earlier_writes_to_an_acquire_load() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    shared_data.set_number(0);
+
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            shared_data.set_status(7, "payload");
+            shared_data.set_number_with(1, Ordering::Release);
+        });
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while shared_data.get_number_with(Ordering::Acquire) != 1 {
+            assert!(Instant::now() < deadline, "the store never showed up");
+            std::hint::spin_loop();
+        }
+        assert_eq!(shared_data.read_status(), (7, "payload".to_string()));
+    });
+}
+
+#[test]
+#[should_panic]
+fn acquire_store_panics_like_any_atomic() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    shared_data.set_number_with(1, Ordering::Acquire);
+}
@@ -0,0 +1,68 @@
+//! `owned_by_me` is true exactly while this process holds the lock, not
+//! when another process does.
+
+use sharedmem_multiarch::OwnedSharedData;
+
+#[test]
+fn true_only_while_this_process_holds_the_lock() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    assert!(!shared_data.owned_by_me());
+
+    shared_data.lock().unwrap();
+    assert!(shared_data.owned_by_me());
+    shared_data.unlock();
+    assert!(!shared_data.owned_by_me());
+
+    {
+        let _guard = shared_data.lock_guard().unwrap();
+        assert!(shared_data.owned_by_me());
+    }
+    assert!(!shared_data.owned_by_me());
+
+    assert!(shared_data.try_lock());
+    assert!(shared_data.owned_by_me());
+    shared_data.unlock();
+    assert!(!shared_data.owned_by_me());
+}
+
+#[cfg(unix)]
+#[test]
+fn false_while_another_process_holds_the_lock() {
+    use std::time::Duration;
+
+    const TIMEOUT: Duration = Duration::from_secs(10);
+
+    fn fail(code: i32) -> ! {
+        unsafe { libc::_exit(code) }
+    }
+
+    // The child takes the lock, flags it in `number` and holds on until
+    // the parent flags back. It reports failures with its exit code, as
+    // it must not panic; see `fork_with_shared`.
+    let mut child = unsafe {
+        sharedmem_multiarch::fork_with_shared(|shared_data| {
+            if shared_data.lock_timeout(TIMEOUT).is_err() {
+                fail(1);
+            }
+            if !shared_data.owned_by_me() {
+                fail(2);
+            }
+            shared_data.set_number(1);
+            shared_data.notify_change();
+            if shared_data.wait_until(|n| n == 2, TIMEOUT).is_err() {
+                fail(3);
+            }
+            shared_data.unlock();
+        })
+    }
+    .unwrap();
+
+    child.wait_until(|n| n == 1, TIMEOUT).unwrap();
+    assert!(child.is_locked());
+    assert!(!child.owned_by_me());
+    child.set_number(2);
+    child.notify_change();
+
+    assert_eq!(child.wait().unwrap().code(), Some(0));
+    assert!(!child.is_locked());
+}
@@ -0,0 +1,53 @@
+//! The lock records the PID of whichever process holds it, the 32-bit
+//! child included, and `lock_state` reports it.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use sharedmem_multiarch::OwnedSharedData;
+use sharedmem_multiarch::shared::LockState;
+use std::process::{Command, Stdio};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+#[test]
+fn parent_sees_the_child_as_owner_while_it_holds_the_lock() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shared_data = OwnedSharedData::create().unwrap();
+    let child_exe = extract_child();
+    // The child holds the lock for about half a second after its update
+    let mut child = Command::new(&child_exe)
+        .arg(shared_data.os_id())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let child_pid = child.id() as i32;
+
+    let give_up = Instant::now() + Duration::from_secs(5);
+    let mut seen = LockState::Unlocked;
+    while Instant::now() < give_up {
+        seen = shared_data.lock_state();
+        // A holder that has not recorded itself yet shows up as PID 0
+        if let LockState::Locked { owner_pid } = seen
+            && owner_pid != 0
+        {
+            break;
+        }
+        std::thread::sleep(Duration::from_millis(1));
+    }
+
+    assert!(child.wait().unwrap().success());
+    assert_eq!(
+        seen,
+        LockState::Locked {
+            owner_pid: child_pid
+        }
+    );
+    // Cleared before the child released the futex
+    assert_eq!(shared_data.lock_state(), LockState::Unlocked);
+    assert_eq!(shared_data.owner_pid.load(Ordering::Relaxed), 0);
+}
@@ -0,0 +1,65 @@
+//! The builder's `permissions` and `owner` end up on the `/dev/shm` entry
+//! of the segment it creates.
+
+#![cfg(target_os = "linux")]
+
+use sharedmem_multiarch::{OpenMode, SharedRegion};
+use std::os::unix::fs::MetadataExt;
+
+fn region_name(test: &str) -> String {
+    format!("/sharedmem-permissions-{}-{}", test, std::process::id())
+}
+
+fn shm_metadata(name: &str) -> std::fs::Metadata {
+    std::fs::metadata(format!("/dev/shm{}", name)).unwrap()
+}
+
+#[test]
+fn created_segment_gets_the_requested_mode() {
+    for mode in [0o600, 0o640] {
+        let name = region_name(&format!("{:o}", mode));
+        let shared_data = SharedRegion::builder()
+            .os_id(&name)
+            .mode(OpenMode::Create)
+            .permissions(mode)
+            .build()
+            .unwrap();
+
+        assert_eq!(shm_metadata(&name).mode() & 0o777, mode);
+        drop(shared_data);
+    }
+}
+
+#[test]
+fn owner_can_be_set_to_ourselves() {
+    // Giving the segment to anyone else needs privileges tests should not
+    // rely on, but our own uid and gid are always allowed
+    let (uid, gid) = unsafe { (libc::geteuid(), libc::getegid()) };
+    let name = region_name("owner");
+    let _shared_data = SharedRegion::builder()
+        .os_id(&name)
+        .owner(Some(uid), Some(gid))
+        .build()
+        .unwrap();
+
+    let metadata = shm_metadata(&name);
+    assert_eq!((metadata.uid(), metadata.gid()), (uid, gid));
+}
+
+#[test]
+fn regions_opened_by_create_or_open_keep_their_mode() {
+    let name = region_name("kept");
+    let _created = SharedRegion::builder()
+        .os_id(&name)
+        .permissions(0o640)
+        .build()
+        .unwrap();
+
+    let _opened = SharedRegion::builder()
+        .os_id(&name)
+        .mode(OpenMode::CreateOrOpen)
+        .permissions(0o600)
+        .build()
+        .unwrap();
+    assert_eq!(shm_metadata(&name).mode() & 0o777, 0o640);
+}
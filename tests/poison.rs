@@ -0,0 +1,40 @@
+//! A panic while holding a lock guard poisons the data for the next
+//! `lock_guard`, which still hands the lock over.
+
+use sharedmem_multiarch::OwnedSharedData;
+
+#[test]
+fn panic_under_the_lock_poisons_the_next_acquirer() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+
+    // A clean release leaves nothing behind
+    drop(shared_data.lock_guard().unwrap());
+    assert!(!shared_data.is_poisoned());
+
+    let panicked = std::thread::scope(|s| {
+        s.spawn(|| {
+            let mut guard = shared_data.lock_guard().unwrap();
+            guard.set(7);
+            panic!("half way through an update");
+        })
+        .join()
+        .is_err()
+    });
+    assert!(panicked);
+    assert!(shared_data.is_poisoned());
+
+    let mut guard = match shared_data.lock_guard() {
+        Err(poisoned) => poisoned.into_inner(),
+        Ok(_) => panic!("lock_guard did not report the panic"),
+    };
+    // The lock was released and the write made before the panic kept
+    assert!(shared_data.owned_by_me());
+    assert_eq!(guard.get(), 7);
+    guard.set(8);
+    shared_data.clear_poison();
+    drop(guard);
+
+    assert_eq!(shared_data.lock_guard().unwrap().get(), 8);
+    assert!(!shared_data.is_poisoned());
+}
@@ -0,0 +1,59 @@
+//! `RawSync`, the wait/wake primitive under every lock, behaves the same on
+//! each backend, including across two mappings of one segment as separate
+//! processes would have them.
+
+use shared_memory::ShmemConf;
+use sharedmem_multiarch::raw_sync::{RawSync, TimedWaitError, WaitError};
+use std::sync::atomic::Ordering;
+use std::time::{Duration, Instant};
+
+const SHORT: Duration = Duration::from_millis(50);
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn waits_check_the_value_first() {
+    let sync = RawSync::new(1);
+    assert_eq!(sync.wait(0), Err(WaitError::WrongValue));
+    assert_eq!(sync.wait_for(0, TIMEOUT), Err(TimedWaitError::WrongValue));
+
+    let started = Instant::now();
+    assert_eq!(sync.wait_for(1, SHORT), Err(TimedWaitError::TimedOut));
+    assert!(started.elapsed() >= SHORT);
+}
+
+/// Sleeps on `sync` until its value leaves `value`.
+fn wait_until_not(sync: &RawSync, value: u32) {
+    let deadline = Instant::now() + TIMEOUT;
+    while sync.value.load(Ordering::Acquire) == value {
+        assert!(Instant::now() < deadline, "never woken");
+        let _ = sync.wait_for(value, TIMEOUT);
+    }
+}
+
+#[test]
+fn wake_through_another_mapping_of_the_segment() {
+    let shmem = ShmemConf::new()
+        .size(std::mem::size_of::<RawSync>())
+        .create()
+        .unwrap();
+    let other = ShmemConf::new().os_id(shmem.get_os_id()).open().unwrap();
+    assert_ne!(shmem.as_ptr(), other.as_ptr());
+    // SAFETY: the fresh mapping is large enough and aligned; both mappings
+    // outlive every use below.
+    let (sync, seen_elsewhere) = unsafe {
+        std::ptr::write(shmem.as_ptr() as *mut RawSync, RawSync::new(0));
+        (
+            &*(shmem.as_ptr() as *const RawSync),
+            &*(other.as_ptr() as *const RawSync),
+        )
+    };
+
+    std::thread::scope(|s| {
+        let waiter = s.spawn(|| wait_until_not(sync, 0));
+        std::thread::sleep(SHORT);
+        assert!(!waiter.is_finished());
+        seen_elsewhere.value.store(1, Ordering::Release);
+        seen_elsewhere.wake(i32::MAX);
+    });
+    assert_eq!(sync.value.load(Ordering::Acquire), 1);
+}
@@ -0,0 +1,63 @@
+//! An observer's read-only mapping sees the creator's writes but cannot
+//! write itself: the pages are mapped without `PROT_WRITE`, so a write
+//! forced through unsafe code faults.
+
+#![cfg(target_os = "linux")]
+
+use sharedmem_multiarch::{OwnedSharedData, SharedDataReadonly, SharedRegion};
+
+#[test]
+fn observer_sees_the_creators_writes() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let observer = SharedRegion::open_readonly(shared_data.os_id()).unwrap();
+    assert_eq!(observer.get_number(), 100);
+
+    shared_data.set_number(7);
+    shared_data.lock().unwrap();
+    assert_eq!(observer.get_number(), 7);
+    assert!(observer.is_locked());
+    shared_data.unlock();
+    assert!(!observer.is_locked());
+}
+
+#[test]
+fn observer_mapping_is_not_writable() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let observer = SharedRegion::open_readonly(shared_data.os_id()).unwrap();
+    let addr = (&*observer as *const SharedDataReadonly).expose_provenance();
+
+    // The kernel's view: `r--s` for a shared read-only mapping
+    let maps = std::fs::read_to_string("/proc/self/maps").unwrap();
+    let perms = maps
+        .lines()
+        .find_map(|line| {
+            let (range, rest) = line.split_once(' ')?;
+            let (start, end) = range.split_once('-')?;
+            let start = usize::from_str_radix(start, 16).ok()?;
+            let end = usize::from_str_radix(end, 16).ok()?;
+            (start..end).contains(&addr).then(|| rest[..4].to_string())
+        })
+        .expect("observer mapping not in /proc/self/maps");
+    assert_eq!(perms, "r--s");
+
+    // And a write forced through anyway faults. Done in a forked child,
+    // which touches nothing but the one byte before exiting.
+    // SAFETY: the child only performs the write and `_exit`, both fine
+    // after forking a multithreaded process.
+    let pid = unsafe { libc::fork() };
+    assert!(pid >= 0, "fork failed");
+    if pid == 0 {
+        unsafe {
+            std::ptr::write_volatile(std::ptr::with_exposed_provenance_mut::<u8>(addr), 0);
+            libc::_exit(0);
+        }
+    }
+    let mut status = 0;
+    assert_eq!(unsafe { libc::waitpid(pid, &mut status, 0) }, pid);
+    assert!(
+        libc::WIFSIGNALED(status) && libc::WTERMSIG(status) == libc::SIGSEGV,
+        "write through the read-only mapping did not fault (status {:#x})",
+        status
+    );
+    assert_eq!(shared_data.get_number(), 100);
+}
@@ -0,0 +1,49 @@
+//! Fields of a `SharedRecord` updated from different threads at once keep
+//! every update, and none spills into another field.
+
+use sharedmem_multiarch::record::{RecordSnapshot, SharedRecord};
+
+const BUMPS: u64 = 20_000;
+
+#[test]
+fn concurrent_updates_to_separate_fields() {
+    let record = SharedRecord::new();
+    assert_eq!(record.snapshot(), RecordSnapshot::default());
+
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            for _ in 0..BUMPS {
+                record.add_requests(1);
+            }
+        });
+        s.spawn(|| {
+            for i in 1..=BUMPS {
+                record.set_errors(i / 2);
+                record.set_last_value(-(i as i64));
+            }
+        });
+        // Snapshots taken meanwhile only ever see values that were stored
+        s.spawn(|| {
+            for _ in 0..1_000 {
+                let seen = record.snapshot();
+                assert!(seen.requests <= BUMPS, "{:?}", seen);
+                assert!(seen.errors <= BUMPS / 2, "{:?}", seen);
+                assert!(
+                    (-(BUMPS as i64)..=0).contains(&seen.last_value),
+                    "{:?}",
+                    seen
+                );
+            }
+        });
+    });
+
+    assert_eq!(
+        record.snapshot(),
+        RecordSnapshot {
+            requests: BUMPS,
+            errors: BUMPS / 2,
+            last_value: -(BUMPS as i64),
+        }
+    );
+    assert_eq!(record.add_errors(3), BUMPS / 2 + 3);
+}
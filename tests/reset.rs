@@ -0,0 +1,45 @@
+//! `reset` puts the number back where it started and zeroes the counters,
+//! leaving a region ready for another run.
+
+use sharedmem_multiarch::OwnedSharedData;
+use std::time::Duration;
+
+#[test]
+fn reset_restores_the_default_and_zeroes_the_stats() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    shared_data.set_number(7);
+    for _ in 0..3 {
+        shared_data.lock_guard().unwrap().update(|n| n + 1);
+    }
+    assert_eq!(shared_data.get_number(), 10);
+    assert_eq!(shared_data.stats().acquisitions, 3);
+
+    shared_data.reset();
+
+    assert_eq!(shared_data.get_number(), 100);
+    let (_, generation) = shared_data.get_number_versioned();
+    assert_eq!(generation, 0);
+    let stats = shared_data.stats();
+    assert_eq!(stats.acquisitions, 0);
+    assert_eq!(stats.contended, 0);
+    assert_eq!(stats.total_wait, Duration::ZERO);
+    assert_eq!(stats.total_hold, Duration::ZERO);
+    assert_eq!(stats.max_hold, Duration::ZERO);
+    assert!(!shared_data.is_locked());
+}
+
+#[test]
+fn reset_goes_back_to_a_custom_initial_number() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    shared_data.set_initial_number(-5);
+    assert_eq!(shared_data.get_number(), -5);
+
+    shared_data.set_number(42);
+    shared_data.reset();
+    assert_eq!(shared_data.get_number(), -5);
+
+    // The lock still works as usual afterwards
+    shared_data.lock_guard().unwrap().update(|n| n * 2);
+    assert_eq!(shared_data.get_number(), -10);
+    assert_eq!(shared_data.stats().acquisitions, 1);
+}
@@ -0,0 +1,85 @@
+//! `SharedRing` at its boundaries: exactly `N` values fit, an empty ring
+//! pops nothing, and order survives both slot and counter wraparound.
+
+use sharedmem_multiarch::raw_sync::TimedWaitError;
+use sharedmem_multiarch::ring::{Full, SharedRing};
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+#[test]
+fn full_and_empty_boundaries() {
+    let ring = SharedRing::<4>::new();
+    assert!(ring.is_empty());
+    assert_eq!(ring.pop(), None);
+
+    for value in 1..=4 {
+        ring.push(value).unwrap();
+    }
+    assert_eq!(ring.len(), 4);
+    // The rejected value comes back, and nothing was overwritten
+    assert_eq!(ring.push(5), Err(Full(5)));
+    assert_eq!(ring.len(), 4);
+
+    assert_eq!(ring.pop(), Some(1));
+    ring.push(5).unwrap();
+    assert_eq!(ring.push(6), Err(Full(6)));
+
+    for expected in 2..=5 {
+        assert_eq!(ring.pop(), Some(expected));
+    }
+    assert!(ring.is_empty());
+    assert_eq!(ring.pop(), None);
+}
+
+#[test]
+fn slots_wrap_around_in_order() {
+    let ring = SharedRing::<4>::new();
+    // Three at a time, so every lap starts at a different slot
+    let mut next_pop = 0;
+    for lap in 0..10 {
+        for value in lap * 3..lap * 3 + 3 {
+            ring.push(value).unwrap();
+        }
+        for _ in 0..3 {
+            assert_eq!(ring.pop(), Some(next_pop));
+            next_pop += 1;
+        }
+    }
+    assert!(ring.is_empty());
+}
+
+#[test]
+fn counters_wrap_past_u32_max() {
+    let ring = SharedRing::<4>::new();
+    // As if about four billion values had already gone through
+    let start = u32::MAX - 1;
+    ring.head.value.store(start, Ordering::Relaxed);
+    ring.tail.value.store(start, Ordering::Relaxed);
+    assert!(ring.is_empty());
+
+    for value in 0..4 {
+        ring.push(value).unwrap();
+    }
+    assert!(
+        ring.tail.value.load(Ordering::Relaxed) < start,
+        "tail did not wrap"
+    );
+    assert_eq!(ring.len(), 4);
+    assert_eq!(ring.push(4), Err(Full(4)));
+    for expected in 0..4 {
+        assert_eq!(ring.pop(), Some(expected));
+    }
+    assert!(ring.is_empty());
+    assert_eq!(ring.pop(), None);
+}
+
+#[test]
+fn blocking_calls_time_out_at_the_boundaries() {
+    let ring = SharedRing::<2>::new();
+    let short = Duration::from_millis(20);
+    assert_eq!(ring.pop_blocking(short), Err(TimedWaitError::TimedOut));
+    ring.push_blocking(1, short).unwrap();
+    ring.push_blocking(2, short).unwrap();
+    assert_eq!(ring.push_blocking(3, short), Err(TimedWaitError::TimedOut));
+    assert_eq!(ring.pop_blocking(short), Ok(1));
+}
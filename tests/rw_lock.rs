@@ -0,0 +1,129 @@
+//! `RwSharedData` lets readers in together but never while a writer is
+//! half way through an update.
+
+use sharedmem_multiarch::shared::RwSharedData;
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const READERS: usize = 4;
+const WRITES: i64 = 2_000;
+
+/// Writes odd then even under one write lock, so a reader that ever sees
+/// an odd number got in mid-update.
+fn write_in_two_steps(rw: &RwSharedData, round: i64) {
+    let mut guard = rw.write_lock_timeout(TIMEOUT).unwrap();
+    *guard = round * 2 - 1;
+    std::thread::yield_now();
+    *guard = round * 2;
+}
+
+#[test]
+fn readers_never_see_a_torn_write() {
+    let rw = RwSharedData::new(0);
+    let done = AtomicBool::new(false);
+    let reads = AtomicU64::new(0);
+
+    std::thread::scope(|s| {
+        for _ in 0..READERS {
+            s.spawn(|| {
+                let mut last = 0;
+                while !done.load(Ordering::Relaxed) {
+                    let guard = rw.read_lock_timeout(TIMEOUT).unwrap();
+                    let seen = *guard;
+                    // Still the same while the lock is held
+                    std::thread::yield_now();
+                    assert_eq!(*guard, seen, "changed under a read lock");
+                    drop(guard);
+                    assert_eq!(seen % 2, 0, "read {} mid-update", seen);
+                    assert!(seen >= last, "went back from {} to {}", last, seen);
+                    last = seen;
+                    reads.fetch_add(1, Ordering::Relaxed);
+                }
+            });
+        }
+        for round in 1..=WRITES {
+            write_in_two_steps(&rw, round);
+        }
+        done.store(true, Ordering::Relaxed);
+    });
+
+    assert_eq!(*rw.read_lock_timeout(TIMEOUT).unwrap(), WRITES * 2);
+    assert!(reads.load(Ordering::Relaxed) > 0);
+}
+
+#[test]
+fn upgradable_reader_shares_with_readers_but_not_writers() {
+    let rw = RwSharedData::new(1);
+    let short = Duration::from_millis(50);
+    let reader_left = AtomicBool::new(false);
+    let (entered, reader_in) = std::sync::mpsc::channel();
+
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            let guard = rw.read_lock_timeout(TIMEOUT).unwrap();
+            entered.send(*guard).unwrap();
+            std::thread::sleep(short * 2);
+            reader_left.store(true, Ordering::SeqCst);
+            drop(guard);
+        });
+        assert_eq!(reader_in.recv().unwrap(), 1);
+
+        let upgradable = rw.read_lock_upgradable_timeout(short).unwrap();
+        assert_eq!(*upgradable, 1);
+        assert!(rw.read_lock_timeout(short).is_ok());
+        // One upgradable reader at a time, and no writer next to it
+        assert!(rw.read_lock_upgradable_timeout(short).is_err());
+        assert!(rw.write_lock_timeout(short).is_err());
+
+        // The upgrade waits for the plain reader to leave
+        let mut guard = upgradable.upgrade();
+        assert!(reader_left.load(Ordering::SeqCst));
+        *guard = 2;
+    });
+    assert_eq!(*rw.read_lock_timeout(TIMEOUT).unwrap(), 2);
+}
+
+#[test]
+fn upgrades_are_never_torn() {
+    const UPGRADERS: usize = 3;
+    const ROUNDS: i64 = 500;
+
+    let rw = RwSharedData::new(0);
+    let done = AtomicBool::new(false);
+
+    std::thread::scope(|s| {
+        for _ in 0..READERS {
+            s.spawn(|| {
+                while !done.load(Ordering::Relaxed) {
+                    let seen = *rw.read_lock_timeout(TIMEOUT).unwrap();
+                    assert_eq!(seen % 2, 0, "read {} mid-upgrade", seen);
+                }
+            });
+        }
+        let upgraders: Vec<_> = (0..UPGRADERS)
+            .map(|_| {
+                s.spawn(|| {
+                    for _ in 0..ROUNDS {
+                        let guard = rw.read_lock_upgradable_timeout(TIMEOUT).unwrap();
+                        let seen = *guard;
+                        let mut guard = guard.upgrade();
+                        // Nobody wrote between the read and the upgrade
+                        *guard = seen + 1;
+                        std::thread::yield_now();
+                        *guard = seen + 2;
+                    }
+                })
+            })
+            .collect();
+        for upgrader in upgraders {
+            let _ = upgrader.join();
+        }
+        done.store(true, Ordering::Relaxed);
+    });
+
+    assert_eq!(
+        *rw.read_lock_timeout(TIMEOUT).unwrap(),
+        UPGRADERS as i64 * ROUNDS * 2
+    );
+}
@@ -0,0 +1,56 @@
+//! `SharedSemaphore` never lets more than its permits' worth of workers
+//! in at once.
+
+use sharedmem_multiarch::shared::SharedSemaphore;
+use std::sync::atomic::{AtomicU32, Ordering};
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+const WORKERS: u32 = 8;
+const PERMITS: u32 = 3;
+const ROUNDS: u32 = 50;
+
+#[test]
+fn concurrency_never_exceeds_the_permits() {
+    let semaphore = SharedSemaphore::new(PERMITS as i64);
+    let inside = AtomicU32::new(0);
+    let max_inside = AtomicU32::new(0);
+
+    std::thread::scope(|s| {
+        for _ in 0..WORKERS {
+            s.spawn(|| {
+                for _ in 0..ROUNDS {
+                    semaphore.acquire_timeout(TIMEOUT).unwrap();
+                    let now = inside.fetch_add(1, Ordering::SeqCst) + 1;
+                    max_inside.fetch_max(now, Ordering::SeqCst);
+                    std::thread::sleep(Duration::from_micros(200));
+                    inside.fetch_sub(1, Ordering::SeqCst);
+                    semaphore.release();
+                }
+            });
+        }
+    });
+
+    let max_inside = max_inside.into_inner();
+    assert!(
+        max_inside <= PERMITS,
+        "{} workers were inside at once",
+        max_inside
+    );
+    // Eight workers sleeping inside should have filled it at some point
+    assert!(max_inside > 1, "never more than one worker inside");
+    assert_eq!(semaphore.count.load(Ordering::SeqCst), PERMITS as i64);
+}
+
+#[test]
+fn acquire_times_out_without_a_free_permit() {
+    let semaphore = SharedSemaphore::new(1);
+    semaphore.acquire_timeout(TIMEOUT).unwrap();
+    assert!(
+        semaphore
+            .acquire_timeout(Duration::from_millis(20))
+            .is_err()
+    );
+    semaphore.release();
+    semaphore.acquire_timeout(TIMEOUT).unwrap();
+}
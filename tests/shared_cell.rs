@@ -0,0 +1,14 @@
+//! `SharedCell` holds any payload that fits the slot next to its futex.
+
+use sharedmem_multiarch::shared::SharedCell;
+
+#[test]
+fn payloads_up_to_the_slot_size_round_trip() {
+    let wide = SharedCell::new(0u64);
+    wide.set(u64::MAX - 1).unwrap();
+    assert_eq!(wide.get().unwrap(), u64::MAX - 1);
+
+    let bytes = SharedCell::new([0u8; 8]);
+    bytes.set(*b"sharedme").unwrap();
+    assert_eq!(&bytes.get().unwrap(), b"sharedme");
+}
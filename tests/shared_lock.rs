@@ -0,0 +1,111 @@
+//! Code written against `SharedLock` runs the same on the futex lock in
+//! `SharedData` and on a spinlock of our own placed in the user region.
+
+use sharedmem_multiarch::shared::LockState;
+use sharedmem_multiarch::{OwnedSharedData, SharedLock, SharedMemError, SharedRegion};
+use std::sync::atomic::{AtomicI64, AtomicU32, Ordering};
+use std::time::{Duration, Instant};
+
+const THREADS: usize = 4;
+const INCREMENTS: i64 = 500;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+/// A test-and-set spinlock: a zeroed word is unlocked, so it needs no
+/// initialization in a fresh segment.
+#[repr(C)]
+struct SpinLock {
+    word: AtomicU32,
+}
+
+impl SharedLock for SpinLock {
+    fn lock_timeout(&self, timeout: Duration) -> Result<(), SharedMemError> {
+        let deadline = Instant::now() + timeout;
+        while !self.try_lock() {
+            if Instant::now() >= deadline {
+                return Err(SharedMemError::Timeout);
+            }
+            // With fewer CPUs than threads the holder needs ours to run
+            std::thread::yield_now();
+        }
+        Ok(())
+    }
+
+    fn unlock(&self) {
+        self.word.store(0, Ordering::Release);
+    }
+
+    fn try_lock(&self) -> bool {
+        self.word
+            .compare_exchange(0, 1, Ordering::Acquire, Ordering::Relaxed)
+            .is_ok()
+    }
+
+    fn lock_state(&self) -> LockState {
+        match self.word.load(Ordering::Relaxed) {
+            0 => LockState::Unlocked,
+            _ => LockState::Locked { owner_pid: 0 },
+        }
+    }
+}
+
+/// Increments `counter` non-atomically under `lock` from several threads,
+/// so any lapse in mutual exclusion loses updates.
+fn hammer(lock: &(impl SharedLock + Sync), counter: &AtomicI64) {
+    std::thread::scope(|scope| {
+        for _ in 0..THREADS {
+            scope.spawn(|| {
+                for _ in 0..INCREMENTS {
+                    lock.lock_timeout(TIMEOUT).unwrap();
+                    let value = counter.load(Ordering::Relaxed);
+                    std::thread::yield_now();
+                    counter.store(value + 1, Ordering::Relaxed);
+                    lock.unlock();
+                }
+            });
+        }
+    });
+}
+
+#[test]
+fn futex_lock_through_the_trait() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let counter = AtomicI64::new(0);
+    hammer(&*shared_data, &counter);
+
+    assert_eq!(counter.load(Ordering::Relaxed), THREADS as i64 * INCREMENTS);
+    assert_eq!(
+        shared_data.stats().acquisitions,
+        THREADS as u64 * INCREMENTS as u64
+    );
+    assert_eq!(SharedLock::lock_state(&*shared_data), LockState::Unlocked);
+}
+
+#[test]
+fn spinlock_in_the_user_region_through_the_trait() {
+    let region = SharedRegion::builder()
+        // The spinlock's word, padding, then the counter
+        .user_bytes(16)
+        .build()
+        .unwrap();
+    let (ptr, len) = region.extra_space();
+    assert!(len >= 16);
+    // SAFETY: the region starts cache-line aligned and the segment is
+    // zeroed, which is an unlocked `SpinLock` and a 0 counter. Both stay
+    // mapped for as long as `region`.
+    let (spinlock, counter) = unsafe {
+        (
+            &*(ptr as *const SpinLock),
+            &*(ptr.add(8) as *const AtomicI64),
+        )
+    };
+
+    hammer(spinlock, counter);
+
+    assert_eq!(counter.load(Ordering::Relaxed), THREADS as i64 * INCREMENTS);
+    assert!(spinlock.try_lock());
+    assert_eq!(spinlock.lock_state(), LockState::Locked { owner_pid: 0 });
+    assert!(!spinlock.try_lock());
+    spinlock.unlock();
+    // The futex lock was never touched
+    assert_eq!(region.stats().acquisitions, 0);
+}
@@ -0,0 +1,92 @@
+//! A `SharedVec` filled by the 32-bit child reads back the same in the
+//! 64-bit parent, and refuses pushes beyond its capacity on both sides.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use shared_memory::ShmemConf;
+use sharedmem_multiarch::layout::VEC_CAPACITY;
+use sharedmem_multiarch::sharedvec::SharedVec;
+use std::process::{Command, Stdio};
+
+type Vec64 = SharedVec<i64, VEC_CAPACITY>;
+
+#[test]
+fn child_fills_the_vec_for_the_parent() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shmem = ShmemConf::new()
+        .size(std::mem::size_of::<Vec64>())
+        .create()
+        .unwrap();
+    let vec_ptr = shmem.as_ptr() as *mut Vec64;
+    // SAFETY: the fresh mapping is page aligned and large enough.
+    unsafe { vec_ptr.write(Vec64::new()) };
+    let vec = unsafe { &*vec_ptr };
+
+    let child_exe = extract_child();
+    let status = Command::new(&child_exe)
+        .args(["--fill-vec", shmem.get_os_id(), "5"])
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+
+    assert_eq!(vec.len(), 5);
+    assert_eq!(&*vec.lock(), &[0, 10, 20, 30, 40]);
+    assert_eq!(vec.get(4), Some(40));
+    assert_eq!(vec.get(5), None);
+    assert_eq!(vec.pop(), Some(40));
+    assert_eq!(vec.lock().as_slice(), &[0, 10, 20, 30]);
+}
+
+#[test]
+fn child_reports_a_full_vec() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shmem = ShmemConf::new()
+        .size(std::mem::size_of::<Vec64>())
+        .create()
+        .unwrap();
+    let vec_ptr = shmem.as_ptr() as *mut Vec64;
+    unsafe { vec_ptr.write(Vec64::new()) };
+
+    let child_exe = extract_child();
+    let output = Command::new(&child_exe)
+        .args(["--fill-vec", shmem.get_os_id()])
+        .arg((VEC_CAPACITY + 1).to_string())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(
+        String::from_utf8_lossy(&output.stderr)
+            .contains(&format!("Vec full before pushing {}", VEC_CAPACITY * 10)),
+        "stderr: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    // The guard was dropped on the way out, so the lock is free again
+    assert_eq!(unsafe { &*vec_ptr }.len(), VEC_CAPACITY);
+    assert!(unsafe { &*vec_ptr }.push(1).is_err());
+}
+
+#[test]
+fn push_and_pop_respect_capacity() {
+    let vec = SharedVec::<u32, 2>::new();
+    assert!(vec.is_empty());
+    assert_eq!(vec.pop(), None);
+    assert_eq!(vec.push(1), Ok(()));
+    assert_eq!(vec.push(2), Ok(()));
+    assert_eq!(vec.push(3), Err(3));
+    assert_eq!(vec.capacity(), 2);
+
+    let mut guard = vec.lock();
+    assert_eq!(guard.pop(), Some(2));
+    assert_eq!(guard.push(4), Ok(()));
+    assert_eq!(guard.as_slice(), &[1, 4]);
+}
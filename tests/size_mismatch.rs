@@ -0,0 +1,53 @@
+//! A segment too short for `SharedData` is refused on both sides before
+//! any of it is read.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use shared_memory::ShmemConf;
+use sharedmem_multiarch::{SharedData, SharedMemError};
+use std::process::Command;
+
+/// Far short of `SharedData`, but still a whole page once mapped.
+const TOO_SMALL: usize = 64;
+
+#[test]
+fn child_refuses_a_segment_that_is_too_small() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shmem = ShmemConf::new().size(TOO_SMALL).create().unwrap();
+    let child_exe = extract_child();
+    let output = Command::new(&child_exe)
+        .arg(shmem.get_os_id())
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(17), "stderr: {}", stderr);
+    assert!(
+        stderr.contains(&format!(
+            "shared memory region is {} bytes, expected at least {}",
+            TOO_SMALL,
+            std::mem::size_of::<SharedData>()
+        )),
+        "stderr: {}",
+        stderr
+    );
+}
+
+#[test]
+fn parent_refuses_a_segment_that_is_too_small() {
+    let shmem = ShmemConf::new().size(TOO_SMALL).create().unwrap();
+
+    match sharedmem_multiarch::open_region(shmem.get_os_id()) {
+        Err(SharedMemError::SizeMismatch { expected, actual }) => {
+            assert_eq!(expected, std::mem::size_of::<SharedData>());
+            assert_eq!(actual, TOO_SMALL);
+        }
+        Err(e) => panic!("expected SizeMismatch, got {}", e),
+        Ok(_) => panic!("a {}-byte segment was accepted", TOO_SMALL),
+    }
+}
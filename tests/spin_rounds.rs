@@ -0,0 +1,67 @@
+//! However many backoff rounds a waiter spins before sleeping on the
+//! futex, from none to more than the shift is capped at, contended locking
+//! stays correct and a waiter still gets the lock once it is released.
+//!
+//! The spin setting is per process, so everything runs in one test.
+
+use sharedmem_multiarch::shared::DEFAULT_SPIN_ROUNDS;
+use sharedmem_multiarch::{OwnedSharedData, SharedData};
+use std::time::{Duration, Instant};
+
+const THREADS: i64 = 4;
+const PER_THREAD: i64 = 2_000;
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+fn contended_increments(shared_data: &SharedData) {
+    shared_data.set_number(0);
+    std::thread::scope(|s| {
+        for _ in 0..THREADS {
+            s.spawn(|| {
+                for _ in 0..PER_THREAD {
+                    shared_data
+                        .lock_timeout_guard(TIMEOUT)
+                        .unwrap()
+                        .update(|n| n + 1);
+                }
+            });
+        }
+    });
+    assert_eq!(shared_data.get_number(), THREADS * PER_THREAD);
+}
+
+/// How long a waiter takes to get a lock held for `hold`.
+fn handoff_after(shared_data: &SharedData, hold: Duration) -> Duration {
+    shared_data.lock().unwrap();
+    std::thread::scope(|s| {
+        let waiter = s.spawn(|| {
+            let started = Instant::now();
+            shared_data.lock_timeout(TIMEOUT).unwrap();
+            shared_data.unlock();
+            started.elapsed()
+        });
+        std::thread::sleep(hold);
+        shared_data.unlock();
+        waiter.join().unwrap()
+    })
+}
+
+#[test]
+fn any_spin_setting_keeps_the_lock_correct() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    let hold = Duration::from_millis(50);
+
+    for rounds in [0, 1, DEFAULT_SPIN_ROUNDS, 40] {
+        SharedData::set_spin_rounds(rounds);
+        contended_increments(shared_data);
+        let waited = handoff_after(shared_data, hold);
+        assert!(
+            waited < TIMEOUT / 2,
+            "{} rounds: waited {:?} for a lock held {:?}",
+            rounds,
+            waited,
+            hold
+        );
+    }
+    SharedData::set_spin_rounds(DEFAULT_SPIN_ROUNDS);
+}
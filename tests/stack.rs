@@ -0,0 +1,114 @@
+//! `SharedStack` hands values back newest first, within its capacity, and
+//! the 32-bit child sees the same order through shared memory.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use shared_memory::{Shmem, ShmemConf};
+use sharedmem_multiarch::layout::STACK_CAPACITY;
+use sharedmem_multiarch::raw_sync::TimedWaitError;
+use sharedmem_multiarch::stack::{Full, SharedStack};
+use std::process::{Command, Output, Stdio};
+use std::time::Duration;
+
+type Stack = SharedStack<STACK_CAPACITY>;
+
+#[test]
+fn values_come_off_newest_first() {
+    let stack = SharedStack::<4>::new();
+    assert_eq!(stack.pop(), None);
+    for value in 1..=4 {
+        stack.push(value).unwrap();
+    }
+    assert_eq!(stack.push(5), Err(Full(5)));
+    assert_eq!(stack.len(), 4);
+
+    assert_eq!(stack.pop(), Some(4));
+    stack.push(6).unwrap();
+    let popped: Vec<_> = std::iter::from_fn(|| stack.pop()).collect();
+    assert_eq!(popped, [6, 3, 2, 1]);
+    assert!(stack.is_empty());
+}
+
+#[test]
+fn blocking_calls_time_out() {
+    let stack = SharedStack::<1>::new();
+    let short = Duration::from_millis(50);
+    assert_eq!(stack.pop_blocking(short), Err(TimedWaitError::TimedOut));
+    stack.push(1).unwrap();
+    assert_eq!(stack.push_blocking(2, short), Err(TimedWaitError::TimedOut));
+
+    // A pop from another thread frees the slot a blocked push waits for
+    std::thread::scope(|s| {
+        s.spawn(|| {
+            std::thread::sleep(short);
+            assert_eq!(stack.pop(), Some(1));
+        });
+        stack.push_blocking(2, Duration::from_secs(10)).unwrap();
+    });
+    assert_eq!(stack.pop(), Some(2));
+}
+
+/// A shared stack holding `values`, pushed in order.
+fn stack_holding(values: impl IntoIterator<Item = i64>) -> Shmem {
+    let shmem = ShmemConf::new()
+        .size(std::mem::size_of::<Stack>())
+        .create()
+        .unwrap();
+    // SAFETY: the fresh mapping is page aligned, large enough and not yet
+    // seen by anyone else.
+    unsafe { std::ptr::write(shmem.as_ptr() as *mut Stack, Stack::new()) };
+    for value in values {
+        stack_in(&shmem).push(value).unwrap();
+    }
+    shmem
+}
+
+fn stack_in(shmem: &Shmem) -> &Stack {
+    // SAFETY: initialized by `stack_holding` and mapped for as long as
+    // `shmem`.
+    unsafe { &*(shmem.as_ptr() as *const Stack) }
+}
+
+fn drain_in_child(shmem: &Shmem, count: usize) -> Output {
+    let child_exe = extract_child();
+    Command::new(&child_exe)
+        .arg("--stack")
+        .arg(shmem.get_os_id())
+        .arg(count.to_string())
+        .stdout(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn child_pops_what_the_parent_pushed_in_reverse() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shmem = stack_holding(1..=STACK_CAPACITY as i64);
+    let output = drain_in_child(&shmem, STACK_CAPACITY);
+    assert!(
+        output.status.success(),
+        "child failed: {}",
+        String::from_utf8_lossy(&output.stderr)
+    );
+    assert!(stack_in(&shmem).is_empty());
+}
+
+#[test]
+fn child_notices_values_out_of_order() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    // The child expects 3, 2, 1 and gets 3, 1
+    let shmem = stack_holding([2, 1, 3]);
+    let output = drain_in_child(&shmem, 3);
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(!output.status.success());
+    assert!(stderr.contains("Expected 2 got 1"), "stderr: {}", stderr);
+}
@@ -0,0 +1,63 @@
+//! The status a process leaves in the region: a message that exactly fills
+//! `STATUS_MESSAGE_LEN` comes back whole, a longer one is cut.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use sharedmem_multiarch::OwnedSharedData;
+use sharedmem_multiarch::layout::STATUS_MESSAGE_LEN;
+use std::process::{Command, Stdio};
+
+#[test]
+fn message_that_exactly_fills_the_buffer() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    assert_eq!(shared_data.read_status(), (0, String::new()));
+
+    let full = "f".repeat(STATUS_MESSAGE_LEN);
+    shared_data.set_status(3, &full);
+    assert_eq!(shared_data.read_status(), (3, full));
+}
+
+#[test]
+fn message_that_overflows_the_buffer_is_cut() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let long = "o".repeat(STATUS_MESSAGE_LEN + 10);
+    shared_data.set_status(-1, &long);
+    assert_eq!(
+        shared_data.read_status(),
+        (-1, long[..STATUS_MESSAGE_LEN].to_string())
+    );
+
+    // Cut at the last character that fits, not in the middle of one
+    let straddling = format!("{}é", "a".repeat(STATUS_MESSAGE_LEN - 1));
+    shared_data.set_status(1, &straddling);
+    assert_eq!(
+        shared_data.read_status(),
+        (1, "a".repeat(STATUS_MESSAGE_LEN - 1))
+    );
+
+    // A shorter message afterwards leaves nothing of the longer one
+    shared_data.set_status(0, "short");
+    assert_eq!(shared_data.read_status(), (0, "short".to_string()));
+}
+
+#[test]
+fn child_reports_its_result_on_exit() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shared_data = OwnedSharedData::create().unwrap();
+    let child_exe = extract_child();
+    let status = Command::new(&child_exe)
+        .arg(shared_data.os_id())
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+    assert!(status.success());
+    assert_eq!(
+        shared_data.read_status(),
+        (0, "child 1 of 1 produced 250".to_string())
+    );
+}
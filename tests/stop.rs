@@ -0,0 +1,117 @@
+//! A long-lived child keeps serving requests until the parent calls
+//! `request_stop`, then exits cleanly instead of being killed.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use sharedmem_multiarch::{OwnedSharedData, SharedMemError};
+use std::io::Read;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const REQUESTS: i64 = 3;
+
+#[test]
+fn stopping_leaves_the_number_alone() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    shared_data.set_number(42);
+    assert!(!shared_data.stop_requested());
+
+    shared_data.request_stop();
+    assert!(shared_data.stop_requested());
+    assert_eq!(shared_data.get_number(), 42);
+    // Only a wait that would otherwise go on is cut short
+    assert_eq!(
+        shared_data.wait_until(|n| n == 42, Duration::ZERO).unwrap(),
+        42
+    );
+    assert!(matches!(
+        shared_data.wait_until(|n| n == 43, Duration::from_secs(1)),
+        Err(SharedMemError::Stopped)
+    ));
+
+    // A reset starts a fresh run, which is not stopped
+    shared_data.reset();
+    assert!(!shared_data.stop_requested());
+    assert_eq!(
+        shared_data
+            .wait_until(|n| n == 100, Duration::ZERO)
+            .unwrap(),
+        100
+    );
+}
+
+#[test]
+fn dropping_the_owner_stops_without_touching_the_number() {
+    let owner = OwnedSharedData::create().unwrap();
+    let attached = sharedmem_multiarch::open_region(owner.os_id()).unwrap();
+    owner.set_number(7);
+
+    drop(owner);
+    assert!(attached.stop_requested());
+    assert_eq!(attached.get_number(), 7);
+}
+
+#[test]
+fn looping_child_exits_cleanly_when_asked_to_stop() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shared_data = OwnedSharedData::create().unwrap();
+    shared_data.set_number(0);
+    let child_exe = extract_child();
+    let mut child = Command::new(&child_exe)
+        .arg("--serve")
+        .arg(shared_data.os_id())
+        .stdout(Stdio::piped())
+        .spawn()
+        .unwrap();
+
+    // Each odd number is a request the child answers by adding one
+    for request in 0..REQUESTS {
+        shared_data.set_number(2 * request + 1);
+        shared_data.notify_all();
+        let answer = shared_data
+            .wait_until(|n| n == 2 * request + 2, Duration::from_secs(5))
+            .unwrap();
+        assert_eq!(answer, 2 * request + 2);
+    }
+
+    let asked = Instant::now();
+    shared_data.request_stop();
+    let status = loop {
+        if let Some(status) = child.try_wait().unwrap() {
+            break status;
+        }
+        assert!(
+            asked.elapsed() < Duration::from_secs(5),
+            "child did not stop"
+        );
+        std::thread::sleep(Duration::from_millis(10));
+    };
+
+    let mut stdout = String::new();
+    child
+        .stdout
+        .take()
+        .unwrap()
+        .read_to_string(&mut stdout)
+        .unwrap();
+    assert!(
+        status.success(),
+        "child failed with {}:\n{}",
+        status,
+        stdout
+    );
+    assert!(
+        stdout.contains(&format!(
+            "Stop requested after serving {} requests",
+            REQUESTS
+        )),
+        "no clean stop in:\n{}",
+        stdout
+    );
+}
@@ -0,0 +1,176 @@
+//! Many threads hammering one region with a random mix of operations,
+//! while a checker keeps confirming that the number is exactly what the
+//! writes add up to. Lost updates or a lock that lets two holders in show
+//! up as a mismatch.
+//!
+//! Each thread draws its operations from its own seeded generator, so a
+//! seed reproduces the same mix (though not the same interleaving). For
+//! a soak run, e.g.:
+//!
+//! `SHAREDMEM_STRESS_THREADS=16 SHAREDMEM_STRESS_MILLIS=60000 cargo test --test stress -- --nocapture`
+
+use sharedmem_multiarch::shared::LockState;
+use sharedmem_multiarch::{OwnedSharedData, SharedData};
+use std::sync::atomic::{AtomicBool, AtomicI64, AtomicU64, Ordering};
+use std::time::Duration;
+
+const THREADS_VAR: &str = "SHAREDMEM_STRESS_THREADS";
+const MILLIS_VAR: &str = "SHAREDMEM_STRESS_MILLIS";
+const SEED_VAR: &str = "SHAREDMEM_STRESS_SEED";
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn random_operations_keep_the_running_total() {
+    let threads = env_or(THREADS_VAR, 8);
+    let duration = Duration::from_millis(env_or(MILLIS_VAR, 500));
+    let seed = env_or(SEED_VAR, 0x5eed_cafe);
+    println!(
+        "stress: {} threads for {:?}, {}={:#x}",
+        threads, duration, SEED_VAR, seed
+    );
+
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data: &SharedData = &owned;
+    let initial = shared_data.get_number();
+    // The sum of every delta applied so far, only changed under the lock
+    // right after the number, so the two agree whenever the lock is free
+    let applied = AtomicI64::new(0);
+    let operations = AtomicU64::new(0);
+    let stop = AtomicBool::new(false);
+
+    let checks = std::thread::scope(|scope| {
+        for index in 0..threads {
+            let (applied, operations, stop) = (&applied, &operations, &stop);
+            let mut rng = XorShift::new(seed ^ (index + 1).wrapping_mul(0x9e37_79b9_7f4a_7c15));
+            scope.spawn(move || {
+                let mut done = 0;
+                while !stop.load(Ordering::Relaxed) {
+                    step(shared_data, applied, &mut rng);
+                    done += 1;
+                }
+                operations.fetch_add(done, Ordering::Relaxed);
+            });
+        }
+
+        let checker = scope.spawn(|| {
+            let mut checks = 0;
+            while !stop.load(Ordering::Relaxed) {
+                shared_data.lock_timeout(TIMEOUT).unwrap();
+                let (number, expected) = (
+                    shared_data.get_number(),
+                    initial + applied.load(Ordering::Relaxed),
+                );
+                shared_data.unlock();
+                assert_eq!(number, expected, "after {} checks", checks);
+                checks += 1;
+                std::thread::yield_now();
+            }
+            checks
+        });
+
+        std::thread::sleep(duration);
+        stop.store(true, Ordering::Relaxed);
+        checker.join().unwrap()
+    });
+
+    println!(
+        "stress: {} operations, {} mid-run checks",
+        operations.load(Ordering::Relaxed),
+        checks
+    );
+    assert_eq!(
+        shared_data.get_number(),
+        initial + applied.load(Ordering::Relaxed)
+    );
+    assert_eq!(shared_data.lock_state(), LockState::Unlocked);
+    assert!(checks > 0);
+}
+
+/// One random operation. Every write to the number happens under the lock
+/// and is followed by adding its delta to `applied`, whichever way the lock
+/// was taken and the number changed.
+fn step(shared_data: &SharedData, applied: &AtomicI64, rng: &mut XorShift) {
+    let delta = (rng.next() % 21) as i64 - 10;
+    match rng.next() % 6 {
+        0 => {
+            let mut guard = shared_data.lock_guard().unwrap();
+            guard.update(|n| n + delta);
+            applied.fetch_add(delta, Ordering::Relaxed);
+        }
+        1 => {
+            shared_data.lock().unwrap();
+            let number = shared_data.get_number();
+            maybe_yield(rng);
+            shared_data.set_number(number + delta);
+            applied.fetch_add(delta, Ordering::Relaxed);
+            shared_data.unlock();
+        }
+        2 => {
+            shared_data.lock_timeout(TIMEOUT).unwrap();
+            shared_data.fetch_add(delta);
+            applied.fetch_add(delta, Ordering::Relaxed);
+            shared_data.unlock();
+        }
+        3 => {
+            if shared_data.try_lock() {
+                let number = shared_data.get_number();
+                maybe_yield(rng);
+                shared_data.compare_and_set(number, number + delta).unwrap();
+                applied.fetch_add(delta, Ordering::Relaxed);
+                shared_data.unlock();
+            }
+        }
+        4 => {
+            if shared_data.try_lock_spin(100) {
+                shared_data.fetch_sub(-delta);
+                applied.fetch_add(delta, Ordering::Relaxed);
+                shared_data.unlock();
+            }
+        }
+        _ => {
+            // A reader outside the lock only has to see some value
+            let _ = shared_data.get_number();
+        }
+    }
+}
+
+/// Sometimes gives up the CPU in the middle of a read-modify-write, to
+/// widen the window a broken lock would let another writer into.
+fn maybe_yield(rng: &mut XorShift) {
+    if rng.next().is_multiple_of(4) {
+        std::thread::yield_now();
+    }
+}
+
+fn env_or(var: &str, default: u64) -> u64 {
+    match std::env::var(var) {
+        Ok(value) => {
+            let value = value.trim();
+            match value.strip_prefix("0x") {
+                Some(hex) => u64::from_str_radix(hex, 16),
+                None => value.parse(),
+            }
+            .unwrap_or_else(|e| panic!("{}={:?}: {}", var, value, e))
+        }
+        Err(_) => default,
+    }
+}
+
+/// xorshift64*, plenty for picking operations and no dependency.
+struct XorShift(u64);
+
+impl XorShift {
+    fn new(seed: u64) -> Self {
+        // Zero is the one state xorshift never leaves
+        Self(seed.max(1))
+    }
+
+    fn next(&mut self) -> u64 {
+        let mut x = self.0;
+        x ^= x >> 12;
+        x ^= x << 25;
+        x ^= x >> 27;
+        self.0 = x;
+        x.wrapping_mul(0x2545_f491_4f6c_dd1d)
+    }
+}
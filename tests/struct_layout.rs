@@ -0,0 +1,69 @@
+//! The header records the creator's `size_of` and `align_of` for
+//! `SharedData`, so peers built from different sources are caught at run
+//! time even if nobody bumped `LAYOUT_VERSION`.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use shared_memory::{Shmem, ShmemConf};
+use sharedmem_multiarch::{SharedData, SharedMemError};
+use std::process::Command;
+
+/// A region initialized like the parent's, except that the header claims
+/// `SharedData` is one cache line longer than it is.
+fn region_with_wrong_size() -> Shmem {
+    let shmem = ShmemConf::new()
+        .size(std::mem::size_of::<SharedData>())
+        .create()
+        .unwrap();
+    let ptr = shmem.as_ptr() as *mut SharedData;
+    // SAFETY: the fresh mapping is page aligned, large enough and not yet
+    // seen by anyone else.
+    unsafe {
+        SharedData::init_in_place(ptr);
+        std::ptr::addr_of_mut!((*ptr).header.struct_size)
+            .write(std::mem::size_of::<SharedData>() as u32 + 64);
+    }
+    shmem
+}
+
+#[test]
+fn parent_refuses_a_region_with_another_struct_size() {
+    let shmem = region_with_wrong_size();
+
+    match sharedmem_multiarch::open_region(shmem.get_os_id()) {
+        Err(SharedMemError::LayoutMismatch(mismatch)) => {
+            let size = std::mem::size_of::<SharedData>() as u32;
+            assert_eq!(mismatch.expected_size, size);
+            assert_eq!(mismatch.found_size, size + 64);
+            assert_eq!(mismatch.expected_align, mismatch.found_align);
+        }
+        Err(e) => panic!("expected LayoutMismatch, got {}", e),
+        Ok(_) => panic!("a region with the wrong size was accepted"),
+    }
+}
+
+#[test]
+fn child_refuses_a_region_with_another_struct_size() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shmem = region_with_wrong_size();
+    let child_exe = extract_child();
+    let output = Command::new(&child_exe)
+        .arg(shmem.get_os_id())
+        .output()
+        .unwrap();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(13), "stderr: {}", stderr);
+    let size = std::mem::size_of::<SharedData>();
+    assert!(
+        stderr.contains(&format!("expected {} bytes", size))
+            && stderr.contains(&format!("got {} aligned", size + 64)),
+        "stderr: {}",
+        stderr
+    );
+}
@@ -0,0 +1,52 @@
+//! Regions backed by System V shared memory instead of POSIX, handed to
+//! the 32-bit child by their `sysv:<shmid>` handle.
+
+#![cfg(unix)]
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use sharedmem_multiarch::{OpenMode, SharedRegion, open_region};
+use std::process::{Command, Stdio};
+
+#[test]
+fn child_hands_the_number_back_through_a_sysv_segment() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shared_data = SharedRegion::builder()
+        .mode(OpenMode::SysV)
+        .build()
+        .unwrap();
+    assert!(shared_data.os_id().starts_with("sysv:"));
+    shared_data.set_number(100);
+
+    let child_exe = extract_child();
+    let status = Command::new(&child_exe)
+        .arg(shared_data.os_id())
+        .stdout(Stdio::null())
+        .status()
+        .unwrap();
+
+    assert!(status.success(), "child failed with {}", status);
+    // Alone and with defaults, the child applies (n + 25) * 2 once
+    assert_eq!(shared_data.get_number(), 250);
+}
+
+#[test]
+fn sysv_handle_opens_the_same_segment() {
+    let created = SharedRegion::builder()
+        .mode(OpenMode::SysV)
+        .build()
+        .unwrap();
+    created.set_number(7);
+
+    let opened = open_region(created.os_id()).unwrap();
+    assert!(created.is_owner());
+    assert!(!opened.is_owner());
+    assert_eq!(opened.get_number(), 7);
+    opened.set_number(8);
+    assert_eq!(created.get_number(), 8);
+}
@@ -0,0 +1,55 @@
+//! `SHAREDMEM_TIMEOUT_SCALE` stretches the timeouts both binaries wait
+//! with, and values that are not a positive factor are rejected.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use sharedmem_multiarch::timeouts::{TIMEOUT_SCALE_VAR, parse_scale, scale_by};
+use std::process::Command;
+use std::time::{Duration, Instant};
+
+#[test]
+fn scale_parses_positive_factors_only() {
+    assert_eq!(parse_scale(None), Ok(1.0));
+    assert_eq!(parse_scale(Some("5")), Ok(5.0));
+    assert_eq!(parse_scale(Some(" 0.5 ")), Ok(0.5));
+    for bad in ["", "fast", "0", "-2", "inf", "NaN"] {
+        assert!(parse_scale(Some(bad)).is_err(), "{:?}", bad);
+    }
+
+    let second = Duration::from_secs(1);
+    assert_eq!(scale_by(second, 1.0), second);
+    assert_eq!(scale_by(second, 2.5), Duration::from_millis(2500));
+    assert_eq!(scale_by(Duration::MAX, 2.0), Duration::MAX);
+}
+
+#[test]
+fn child_waits_for_the_scaled_open_timeout() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let child_exe = extract_child();
+    let started = Instant::now();
+    let output = Command::new(&child_exe)
+        .arg(format!("/sharedmem-timeout-scale-{}", std::process::id()))
+        .env("SHAREDMEM_CHILD_OPEN_TIMEOUT_MS", "100")
+        .env(TIMEOUT_SCALE_VAR, "3")
+        .output()
+        .unwrap();
+    let took = started.elapsed();
+
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert_eq!(output.status.code(), Some(15), "stderr: {}", stderr);
+    assert!(
+        stderr.contains("shared memory did not appear within 300ms"),
+        "stderr: {}",
+        stderr
+    );
+    assert!(
+        took >= Duration::from_millis(300),
+        "gave up after {:?}",
+        took
+    );
+}
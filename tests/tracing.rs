@@ -0,0 +1,70 @@
+//! With the `tracing` feature, lock acquisition and release are wrapped in
+//! spans and report what happened as events.
+
+#![cfg(feature = "tracing")]
+
+use sharedmem_multiarch::{OwnedSharedData, SharedMemError};
+use std::io::Write;
+use std::sync::{Arc, Mutex};
+use std::time::Duration;
+
+/// Collects everything the subscriber formats.
+#[derive(Clone, Default)]
+struct Captured(Arc<Mutex<Vec<u8>>>);
+
+impl Write for Captured {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        self.0.lock().unwrap().extend_from_slice(buf);
+        Ok(buf.len())
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        Ok(())
+    }
+}
+
+/// What `f` traces on this thread, one event per line.
+fn traced(f: impl FnOnce()) -> String {
+    let captured = Captured::default();
+    let writer = captured.clone();
+    let subscriber = tracing_subscriber::fmt()
+        .with_max_level(tracing::Level::DEBUG)
+        .with_writer(move || writer.clone())
+        .finish();
+    tracing::subscriber::with_default(subscriber, f);
+    let bytes = captured.0.lock().unwrap().clone();
+    String::from_utf8(bytes).unwrap()
+}
+
+#[test]
+fn acquisition_and_release_are_traced() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let output = traced(|| {
+        shared_data.lock_timeout(Duration::from_secs(5)).unwrap();
+        shared_data.unlock();
+    });
+
+    let lines: Vec<_> = output.lines().collect();
+    let acquired = lines.iter().find(|line| line.contains("lock acquired"));
+    let acquired = acquired.unwrap_or_else(|| panic!("no acquisition in:\n{}", output));
+    assert!(acquired.contains("lock_timeout{pid="), "{}", acquired);
+    let released = lines.iter().find(|line| line.contains("lock released"));
+    let released = released.unwrap_or_else(|| panic!("no release in:\n{}", output));
+    assert!(released.contains("unlock{pid="), "{}", released);
+}
+
+#[test]
+fn contention_and_timeouts_are_traced() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    shared_data.lock().unwrap();
+    let output = traced(|| {
+        let result = shared_data.lock_timeout(Duration::from_millis(20));
+        assert!(matches!(result, Err(SharedMemError::Timeout)));
+    });
+    shared_data.unlock();
+
+    for event in ["lock contended", "lock timed out"] {
+        assert!(output.contains(event), "no {:?} in:\n{}", event, output);
+    }
+    assert!(!output.contains("lock acquired"), "{}", output);
+}
@@ -0,0 +1,78 @@
+//! `try_lock_spin` rides out short holds that make plain `try_lock` fail.
+
+use sharedmem_multiarch::{OwnedSharedData, SharedData};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::{Duration, Instant};
+
+const ATTEMPTS: u32 = 2000;
+
+#[test]
+fn spinning_succeeds_at_least_as_often_under_light_contention() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data: &SharedData = &owned;
+    let started = AtomicBool::new(false);
+    let stop = AtomicBool::new(false);
+
+    let (plain, spinning) = std::thread::scope(|scope| {
+        // Holds the lock about half the time, a few microseconds at once
+        scope.spawn(|| {
+            while !stop.load(Ordering::Relaxed) {
+                if shared_data.try_lock() {
+                    started.store(true, Ordering::Relaxed);
+                    busy_wait(Duration::from_micros(5));
+                    shared_data.unlock();
+                }
+                busy_wait(Duration::from_micros(5));
+            }
+        });
+
+        while !started.load(Ordering::Relaxed) {
+            std::thread::yield_now();
+        }
+        let plain = successes(shared_data, |data| data.try_lock());
+        let spinning = successes(shared_data, |data| data.try_lock_spin(100_000));
+        stop.store(true, Ordering::Relaxed);
+        (plain, spinning)
+    });
+    println!(
+        "try_lock: {}/{}, try_lock_spin: {}/{}",
+        plain, ATTEMPTS, spinning, ATTEMPTS
+    );
+
+    // With one CPU the holder never runs while we spin, so spinning
+    // cannot help and either rate is down to where preemption lands
+    if std::thread::available_parallelism().is_ok_and(|n| n.get() > 1) {
+        assert!(spinning >= plain);
+    }
+}
+
+#[test]
+fn gives_up_on_a_lock_that_stays_held() {
+    let owned = OwnedSharedData::create().unwrap();
+    assert!(owned.try_lock_spin(0));
+    // Held by this process now, so spinning cannot get it
+    assert!(!owned.try_lock_spin(1000));
+    owned.unlock();
+    assert!(owned.try_lock_spin(1000));
+    owned.unlock();
+}
+
+/// How many of `ATTEMPTS` calls of `attempt` took the lock, releasing it
+/// again after each success.
+fn successes(shared_data: &SharedData, attempt: impl Fn(&SharedData) -> bool) -> u32 {
+    let mut count = 0;
+    for _ in 0..ATTEMPTS {
+        if attempt(shared_data) {
+            count += 1;
+            shared_data.unlock();
+        }
+    }
+    count
+}
+
+fn busy_wait(duration: Duration) {
+    let until = Instant::now() + duration;
+    while Instant::now() < until {
+        std::hint::spin_loop();
+    }
+}
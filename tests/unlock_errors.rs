@@ -0,0 +1,48 @@
+//! Releasing a lock this process does not hold is refused: `try_unlock`
+//! says why, and `unlock` panics in debug builds.
+
+use sharedmem_multiarch::OwnedSharedData;
+use sharedmem_multiarch::shared::{LockState, UnlockError};
+use std::sync::atomic::Ordering;
+
+#[test]
+fn unlocking_a_lock_nobody_holds() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    assert_eq!(shared_data.try_unlock(), Err(UnlockError::NotLocked));
+
+    // A double unlock is the same mistake
+    shared_data.lock().unwrap();
+    assert_eq!(shared_data.try_unlock(), Ok(()));
+    assert_eq!(shared_data.try_unlock(), Err(UnlockError::NotLocked));
+    assert_eq!(shared_data.lock_state(), LockState::Unlocked);
+}
+
+#[test]
+fn unlocking_another_processes_lock() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    shared_data.lock().unwrap();
+    // As another process records itself on taking the lock; PID 1 is
+    // never this test
+    shared_data.owner_pid.store(1, Ordering::Relaxed);
+
+    assert_eq!(
+        shared_data.try_unlock(),
+        Err(UnlockError::WrongOwner { owner_pid: 1 })
+    );
+    // Refused, so the lock is still held for its owner
+    assert_eq!(shared_data.lock_state(), LockState::Locked { owner_pid: 1 });
+
+    shared_data
+        .owner_pid
+        .store(std::process::id() as i32, Ordering::Relaxed);
+    shared_data.unlock();
+}
+
+#[cfg(debug_assertions)]
+#[test]
+fn unlock_panics_in_debug_builds() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let panic = std::panic::catch_unwind(|| shared_data.unlock()).unwrap_err();
+    let message = panic.downcast_ref::<String>().unwrap();
+    assert!(message.contains("not held"), "{}", message);
+}
@@ -0,0 +1,75 @@
+//! Bytes asked for with `user_bytes` live just past `SharedData`, suitably
+//! aligned, and are shared with every mapping of the segment.
+
+use sharedmem_multiarch::layout::CACHE_LINE;
+use sharedmem_multiarch::{OwnedSharedData, SharedData, SharedRegion, open_region};
+use std::sync::atomic::{AtomicU64, Ordering};
+
+const USER_BYTES: usize = 100;
+
+fn region_name(test: &str) -> String {
+    format!("/sharedmem-user-region-{}-{}", test, std::process::id())
+}
+
+#[test]
+fn user_region_is_shared_with_another_mapping() {
+    let name = region_name("bytes");
+    let mut created = SharedRegion::builder()
+        .os_id(&name)
+        .user_bytes(USER_BYTES)
+        .build()
+        .unwrap();
+    let region = created.user_region_mut();
+    assert!(region.len() >= USER_BYTES);
+    for (i, byte) in region.iter_mut().take(USER_BYTES).enumerate() {
+        *byte = i as u8;
+    }
+
+    let opened = open_region(&name).unwrap();
+    let region = opened.user_region();
+    assert_eq!(region.len(), created.user_region().len());
+    assert!(
+        region
+            .iter()
+            .take(USER_BYTES)
+            .copied()
+            .eq(0..USER_BYTES as u8)
+    );
+}
+
+#[test]
+fn user_region_starts_aligned_past_shared_data() {
+    let name = region_name("atomics");
+    let created = SharedRegion::builder()
+        .os_id(&name)
+        .user_bytes(8)
+        .build()
+        .unwrap();
+    let start = created.user_region().as_ptr();
+    let data: &SharedData = &created;
+    assert_eq!(start as usize % CACHE_LINE, 0);
+    assert_eq!(
+        start as usize - data as *const SharedData as usize,
+        std::mem::size_of::<SharedData>()
+    );
+
+    // Our own atomic next to the crate's, reached from both mappings
+    let opened = open_region(&name).unwrap();
+    counter(&created).store(7, Ordering::Relaxed);
+    assert_eq!(counter(&opened).fetch_add(1, Ordering::Relaxed), 7);
+    assert_eq!(counter(&created).load(Ordering::Relaxed), 8);
+}
+
+#[test]
+fn without_user_bytes_the_region_is_empty() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    assert!(shared_data.user_region().is_empty());
+}
+
+fn counter(region: &OwnedSharedData) -> &AtomicU64 {
+    let (ptr, len) = region.extra_space();
+    assert!(len >= std::mem::size_of::<AtomicU64>());
+    // SAFETY: in bounds and aligned, as checked above, and mapped for as
+    // long as `region`.
+    unsafe { &*(ptr as *const AtomicU64) }
+}
@@ -0,0 +1,48 @@
+//! `SharedData::wait_and_read` reads the child's result only once the
+//! child has exited, and with the lock held.
+
+#![cfg(unix)]
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use sharedmem_multiarch::OwnedSharedData;
+use sharedmem_multiarch::shared::LockState;
+use std::process::{Command, Stdio};
+
+#[test]
+fn reads_under_the_lock_after_the_child_exits() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shared_data = OwnedSharedData::create().unwrap();
+    shared_data.set_number(100);
+    let child_exe = extract_child();
+    // Alone and with defaults, the child applies (n + 25) * 2 once
+    let mut child = Command::new(&child_exe)
+        .arg(shared_data.os_id())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+    let child_pid = child.id();
+
+    let (exit_status, (state, child_gone, number)) = shared_data
+        .wait_and_read(&mut child, |data| {
+            let child_gone = unsafe { libc::kill(child_pid as libc::pid_t, 0) } != 0;
+            (data.lock_state(), child_gone, data.get_number())
+        })
+        .unwrap();
+
+    assert!(exit_status.success(), "child failed with {}", exit_status);
+    assert_eq!(
+        state,
+        LockState::Locked {
+            owner_pid: std::process::id() as i32
+        }
+    );
+    assert!(child_gone, "the closure ran before the child was reaped");
+    assert_eq!(number, 250);
+    assert_eq!(shared_data.lock_state(), LockState::Unlocked);
+}
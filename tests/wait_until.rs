@@ -0,0 +1,66 @@
+//! `wait_until` wakes as soon as a writer notifies a change that satisfies
+//! it, rather than at its timeout.
+
+mod common;
+
+use common::{child_runnable, extract_child};
+use sharedmem_multiarch::OwnedSharedData;
+use std::process::{Command, Stdio};
+use std::time::{Duration, Instant};
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn returns_promptly_when_the_child_reaches_the_target() {
+    if let Err(reason) = child_runnable() {
+        eprintln!("skipping: the child cannot run here ({reason})");
+        return;
+    }
+
+    let shared_data = OwnedSharedData::create().unwrap();
+    let child_exe = extract_child();
+    let started = Instant::now();
+    // Alone and with defaults, the child sets 250 and notifies once it
+    // lets go of the lock, about half a second in
+    let mut child = Command::new(&child_exe)
+        .arg(shared_data.os_id())
+        .stdout(Stdio::null())
+        .spawn()
+        .unwrap();
+
+    let reached = shared_data.wait_until(|n| n == 250, TIMEOUT);
+    let took = started.elapsed();
+    assert!(child.wait().unwrap().success());
+    assert_eq!(reached.unwrap(), 250);
+    assert!(took < TIMEOUT / 2, "took {:?} to notice", took);
+}
+
+#[test]
+fn wakes_on_notify_not_on_timeout() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data = owned.get();
+    shared_data.set_number(0);
+
+    let (notified_at, woke_at) = std::thread::scope(|s| {
+        let writer = s.spawn(|| {
+            std::thread::sleep(Duration::from_millis(50));
+            // Values the predicate rejects only wake it to sleep again
+            shared_data.set_number(1);
+            shared_data.notify_all();
+            std::thread::sleep(Duration::from_millis(50));
+            shared_data.set_number(42);
+            shared_data.notify_all();
+            Instant::now()
+        });
+        assert_eq!(shared_data.wait_until(|n| n == 42, TIMEOUT).unwrap(), 42);
+        let woke_at = Instant::now();
+        (writer.join().unwrap(), woke_at)
+    });
+
+    let late = woke_at.saturating_duration_since(notified_at);
+    assert!(
+        late < Duration::from_secs(1),
+        "woke {:?} after notify",
+        late
+    );
+}
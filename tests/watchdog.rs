@@ -0,0 +1,53 @@
+//! The lock watchdog: it dumps the lock's state while a caller is stuck
+//! waiting, and stays quiet when the lock comes in time.
+
+use sharedmem_multiarch::{OwnedSharedData, SharedData};
+use std::sync::Mutex;
+use std::time::Duration;
+
+#[test]
+fn watchdog_dumps_a_held_lock() {
+    let owned = OwnedSharedData::create().unwrap();
+    let shared_data: &SharedData = &owned;
+    shared_data.set_number(42);
+    shared_data.lock().unwrap();
+
+    let dumps = Mutex::new(Vec::new());
+    std::thread::scope(|scope| {
+        let waiter = scope.spawn(|| {
+            shared_data
+                .lock_timeout_guard_watched(
+                    Duration::from_secs(5),
+                    Duration::from_millis(100),
+                    |dump| dumps.lock().unwrap().push(dump),
+                )
+                .map(drop)
+        });
+        std::thread::sleep(Duration::from_millis(500));
+        shared_data.unlock();
+        waiter.join().unwrap().unwrap();
+    });
+
+    let dumps = dumps.into_inner().unwrap();
+    assert_eq!(dumps.len(), 1);
+    let dump = dumps[0];
+    assert!(dump.waited >= Duration::from_millis(100));
+    assert_eq!(dump.futex_value, 1);
+    assert_eq!(dump.owner_pid, std::process::id() as i32);
+    assert_eq!(dump.owner_alive, Some(true));
+    assert_eq!(dump.number, 42);
+    assert!(dump.to_string().contains("(alive)"), "{}", dump);
+}
+
+#[test]
+fn watchdog_stays_quiet_for_a_free_lock() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let guard = shared_data
+        .lock_timeout_guard_watched(Duration::from_secs(1), Duration::from_millis(10), |dump| {
+            panic!("unexpected dump: {}", dump)
+        })
+        .unwrap();
+    drop(guard);
+    // Past the grace period, a watchdog still running would have fired
+    std::thread::sleep(Duration::from_millis(50));
+}
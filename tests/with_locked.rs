@@ -0,0 +1,60 @@
+//! `with_locked` updates `number` and `words` as one transaction and gives
+//! the lock back however the closure ends.
+
+use sharedmem_multiarch::OwnedSharedData;
+use sharedmem_multiarch::shared::LockState;
+use std::sync::atomic::Ordering;
+use std::time::Duration;
+
+const TIMEOUT: Duration = Duration::from_secs(10);
+
+#[test]
+fn updates_every_word_under_one_lock() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let sum = shared_data
+        .with_locked(
+            |view| {
+                view.number.store(1, Ordering::Relaxed);
+                for (i, word) in view.words.iter().enumerate() {
+                    word.store(i as i64 + 2, Ordering::Relaxed);
+                }
+                view.number.load(Ordering::Relaxed)
+                    + view
+                        .words
+                        .iter()
+                        .map(|word| word.load(Ordering::Relaxed))
+                        .sum::<i64>()
+            },
+            TIMEOUT,
+        )
+        .unwrap();
+
+    let words: Vec<i64> = shared_data
+        .words
+        .iter()
+        .map(|word| word.load(Ordering::SeqCst))
+        .collect();
+    assert_eq!(shared_data.get_number(), 1);
+    assert_eq!(words, (2..2 + words.len() as i64).collect::<Vec<_>>());
+    assert_eq!(sum, 1 + words.iter().sum::<i64>());
+    assert_eq!(shared_data.lock_state(), LockState::Unlocked);
+}
+
+#[test]
+fn lock_is_released_when_the_closure_panics() {
+    let shared_data = OwnedSharedData::create().unwrap();
+    let panicked = std::panic::catch_unwind(|| {
+        shared_data.with_locked(
+            |view| {
+                view.number.store(-1, Ordering::Relaxed);
+                panic!("half way through");
+            },
+            TIMEOUT,
+        )
+    });
+    assert!(panicked.is_err());
+
+    assert_eq!(shared_data.lock_state(), LockState::Unlocked);
+    shared_data.lock_timeout(Duration::from_secs(1)).unwrap();
+    shared_data.unlock();
+}